@@ -1,31 +1,298 @@
+pub mod accum;
+#[cfg(feature = "std")]
+pub mod animation;
+pub mod background;
+pub mod blit;
+pub mod brush;
+pub mod buffer;
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod bvh;
+pub mod camera;
+pub mod canvas;
+pub mod cap;
+pub mod contour;
+#[cfg(feature = "std")]
+pub mod csg;
+#[cfg(feature = "std")]
+pub mod display;
+#[cfg(feature = "std")]
+pub mod encoder;
+pub mod fractal;
+#[cfg(feature = "std")]
+pub mod gbuffer;
+pub mod hdr;
+pub mod ifs;
+#[cfg(feature = "std")]
+pub mod keyframe;
+#[cfg(feature = "std")]
+pub mod light;
+pub mod lineart;
+pub mod mask;
+#[cfg(feature = "std")]
+pub mod material;
 pub mod matrix;
+#[cfg(feature = "std")]
+pub mod mesh;
+#[cfg(feature = "std")]
+pub mod noise;
+pub mod octree;
+pub mod onion;
+#[cfg(feature = "std")]
+pub mod particle;
+pub mod pick;
+pub mod plot;
+#[cfg(feature = "std")]
+pub mod ply;
+#[cfg(feature = "std")]
+pub mod progress;
+pub mod quantize;
+pub mod raster;
+#[cfg(feature = "std")]
+pub mod raytrace;
+pub mod rect;
+pub mod rng;
+#[cfg(feature = "std")]
+pub mod scene;
+#[cfg(feature = "std")]
+pub mod sdf;
+#[cfg(feature = "std")]
+pub mod server;
+#[cfg(feature = "std")]
+pub mod settings;
+pub mod shader;
+#[cfg(feature = "std")]
+pub mod shadow;
+pub mod sprite;
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod stereo;
+#[cfg(feature = "std")]
+pub mod stream;
+pub mod subcanvas;
+#[cfg(feature = "std")]
+pub mod svg;
+pub mod symmetry;
+#[cfg(feature = "std")]
+pub mod terminal;
+pub mod text;
+#[cfg(feature = "std")]
+pub mod texture;
 mod utils;
+pub mod wrap;
 
-use std::convert::Into;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::Into;
 
-use matrix::Matrix;
+use background::Background;
+use brush::Brush;
+use mask::ClipMask;
+use matrix::{Bounds, EdgeMatrix, Matrix, PolygonMatrix};
+use rect::Rect;
+use stats::RenderStats;
+#[cfg(feature = "fs")]
+use std::fs;
+#[cfg(feature = "std")]
 use std::io::{self, prelude::Write};
-use utils::{create_file, polar_to_xy};
+use symmetry::Symmetry;
+#[cfg(feature = "fs")]
+use utils::create_file;
+use utils::polar_to_xy;
+#[cfg(not(feature = "std"))]
+use utils::Float;
+use wrap::WrapMode;
 
-#[allow(dead_code)]
-#[derive(Copy, Clone)]
+/// Color of a pixel, each channel from 0 to the image's depth
+///
+/// Black by default. The constants and the conversions from bytes and hex
+/// codes are for images of depth 255; see `scale_depth` for others.
+#[allow(dead_code, clippy::upper_case_acronyms)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub struct RGB {
     pub red: u16,
-    pub blue: u16,
     pub green: u16,
+    pub blue: u16,
+}
+
+#[allow(dead_code)]
+impl RGB {
+    pub const BLACK: RGB = RGB::new(0, 0, 0);
+    pub const WHITE: RGB = RGB::new(255, 255, 255);
+    pub const GRAY: RGB = RGB::new(128, 128, 128);
+    pub const RED: RGB = RGB::new(255, 0, 0);
+    pub const GREEN: RGB = RGB::new(0, 255, 0);
+    pub const BLUE: RGB = RGB::new(0, 0, 255);
+    pub const YELLOW: RGB = RGB::new(255, 255, 0);
+    pub const CYAN: RGB = RGB::new(0, 255, 255);
+    pub const MAGENTA: RGB = RGB::new(255, 0, 255);
+
+    pub const fn new(red: u16, green: u16, blue: u16) -> RGB {
+        RGB { red, green, blue }
+    }
+
+    /// Channels in [r, g, b] order, the order of every pixel layout
+    pub fn to_array(self) -> [u16; 3] {
+        [self.red, self.green, self.blue]
+    }
+
+    pub fn from_array([red, green, blue]: [u16; 3]) -> RGB {
+        RGB::new(red, green, blue)
+    }
+
+    /// 8 bit [r, g, b, a], with alpha always 255, of a color of max value `depth`
+    pub fn to_rgba8(self, depth: u16) -> [u8; 4] {
+        let [r, g, b] = self.scale_depth(depth, 255).to_array();
+        [r as u8, g as u8, b as u8, 255]
+    }
+
+    /// The same color for an image of max value `to` instead of `from`, rounded
+    /// to the nearest value; channels over `from` end up at `to`
+    pub fn scale_depth(self, from: u16, to: u16) -> RGB {
+        assert!(from > 0, "Depth must be positive");
+        let (from, to) = (u64::from(from), u64::from(to));
+        let scale = |v: u16| ((u64::from(v) * to * 2 + from) / (from * 2)).min(to) as u16;
+        RGB::new(scale(self.red), scale(self.green), scale(self.blue))
+    }
+
+    /// Color from channel intensities in [0, 1], for an image of max value `depth`
+    ///
+    /// Values out of range are clamped
+    pub fn from_fractions(c: [f64; 3], depth: u16) -> RGB {
+        let channel = |v: f64| (v.clamp(0.0, 1.0) * f64::from(depth)).round() as u16;
+        RGB {
+            red: channel(c[0]),
+            green: channel(c[1]),
+            blue: channel(c[2]),
+        }
+    }
+
+    /// Channel intensities in [0, 1], for an image of max value `depth`
+    pub fn to_fractions(self, depth: u16) -> [f64; 3] {
+        let d = f64::from(depth);
+        [
+            f64::from(self.red) / d,
+            f64::from(self.green) / d,
+            f64::from(self.blue) / d,
+        ]
+    }
 }
 
-use std::convert::TryInto;
+impl From<[u8; 3]> for RGB {
+    fn from([r, g, b]: [u8; 3]) -> RGB {
+        RGB::new(r.into(), g.into(), b.into())
+    }
+}
 
+impl From<(u8, u8, u8)> for RGB {
+    fn from((r, g, b): (u8, u8, u8)) -> RGB {
+        RGB::from([r, g, b])
+    }
+}
+
+/// From a hex code like 0xff8000, for orange; the top byte is ignored
+impl From<u32> for RGB {
+    fn from(hex: u32) -> RGB {
+        let [_, r, g, b] = hex.to_be_bytes();
+        RGB::from([r, g, b])
+    }
+}
+
+/// Bytes per color channel in the `PPMImg::as_bytes` layout for images of max
+/// value `depth`: 1 up to 255, 2 past that
+pub fn channel_bytes(depth: u16) -> usize {
+    if depth < 256 {
+        1
+    } else {
+        2
+    }
+}
+
+/// Appends `pixels` to `out` in the `PPMImg::as_bytes` layout
+pub fn pixels_to_bytes(pixels: &[RGB], depth: u16, out: &mut Vec<u8>) {
+    out.reserve(pixels.len() * 3 * channel_bytes(depth));
+    for p in pixels {
+        for c in p.to_array() {
+            match channel_bytes(depth) {
+                1 => out.push(c as u8),
+                _ => out.extend_from_slice(&c.to_be_bytes()),
+            }
+        }
+    }
+}
+
+/// Pixels stored in `bytes` in the `PPMImg::as_bytes` layout; bytes past the
+/// last whole pixel are ignored
+pub fn pixels_from_bytes(bytes: &[u8], depth: u16) -> Vec<RGB> {
+    let n = channel_bytes(depth);
+    bytes
+        .chunks_exact(3 * n)
+        .map(|p| {
+            let channel = |i: usize| match n {
+                1 => u16::from(p[i]),
+                _ => u16::from_be_bytes([p[2 * i], p[2 * i + 1]]),
+            };
+            RGB::new(channel(0), channel(1), channel(2))
+        })
+        .collect()
+}
+
+use core::convert::TryInto;
+
+/// Which way y grows in the coordinates given to `PPMImg`'s point and line drawing,
+/// and to the rects of display lists replayed onto it
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum YAxis {
+    /// Raster convention: y = 0 is the top row
+    #[default]
+    Down,
+    /// Math convention: y = 0 is the bottom row, and bigger y is higher up
+    Up,
+}
+
+#[derive(Clone)]
 pub struct PPMImg {
     height: u32,
     width: u32,
     depth: u16, // max = 2^16
-    pub x_wrap: bool,
-    pub y_wrap: bool,
+    /// What happens to pixels drawn past the left and right edges
+    pub x_wrap: WrapMode,
+    /// What happens to pixels drawn past the top and bottom edges
+    pub y_wrap: WrapMode,
+    /// Which way y grows for `plot`, `draw_line` and everything drawn with them,
+    /// like edge matrices and curves; Down by default. Pixel access, `fill_rect`
+    /// and text always count rows from the top, and renders that work out pixel
+    /// coordinates themselves, like cameras and viewports, come out the same
+    /// either way, see `in_pixels`
+    pub y_axis: YAxis,
+    /// Draw lines from their exact endpoints instead of rounding them to pixels first,
+    /// see `raster::subpixel_line_pixels`. On by default
+    pub subpixel: bool,
+    /// Repeats every point and line drawn, see `Symmetry`
+    pub symmetry: Option<Symmetry>,
+    /// Stamped instead of plotting single pixels, see `Brush`
+    pub brush: Option<Brush>,
+    /// Pixels outside of it are never drawn on, see `ClipMask`
+    pub clip: Option<ClipMask>,
     pub fg_color: RGB,
     pub bg_color: RGB,
+    /// Used by `clear` instead of bg_color when set
+    pub background: Option<Background>,
     data: Vec<RGB>,
+    dirty: Option<Rect>,
+    stats: RenderStats,
+}
+
+/// Writes pixels in the binary (P6) ppm layout, no header
+#[cfg(feature = "std")]
+fn encode_binary_pixels<W: Write + ?Sized>(
+    pixels: &[RGB],
+    depth: u16,
+    out: &mut W,
+) -> io::Result<()> {
+    let mut bytes = Vec::new();
+    pixels_to_bytes(pixels, depth, &mut bytes);
+    out.write_all(&bytes)
 }
 
 // impl constructor and exporter
@@ -43,105 +310,625 @@ impl PPMImg {
             height,
             width,
             depth,
-            x_wrap: false,
-            y_wrap: false,
+            x_wrap: WrapMode::Discard,
+            y_wrap: WrapMode::Discard,
+            y_axis: YAxis::Down,
+            subpixel: true,
+            symmetry: None,
+            brush: None,
+            clip: None,
             fg_color: RGB {
                 red: depth,
                 green: depth,
                 blue: depth,
             },
             bg_color,
+            background: None,
             data: vec![bg_color; (width * height).try_into().unwrap()],
+            dirty: None,
+            stats: RenderStats::default(),
         }
     }
 
-    pub fn write_binary(&self, filepath: &str) -> io::Result<()> {
-        let mut file = create_file(filepath);
-        writeln!(file, "P6")?;
-        writeln!(file, "{} {} {}", self.width, self.height, self.depth)?;
-        if self.depth < 256 {
-            for t in self.data.iter() {
-                file.write(&[t.green as u8])?;
-                file.write(&[t.green as u8])?;
-                file.write(&[t.blue as u8])?;
-            }
-        } else {
-            for t in self.data.iter() {
-                file.write_all(&(t.red.to_be_bytes()))?;
-                file.write_all(&(t.green.to_be_bytes()))?;
-                file.write_all(&(t.blue.to_be_bytes()))?;
-            }
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn depth(&self) -> u16 {
+        self.depth
+    }
+
+    /// Color of the pixel at (x, y)
+    pub fn pixel(&self, x: u32, y: u32) -> RGB {
+        assert!(x < self.width && y < self.height, "Index out of bound");
+        self.data[self.index(x, y)]
+    }
+
+    /// Sets the pixel at (x, y) to `color`, ignoring wrapping
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: RGB) {
+        assert!(x < self.width && y < self.height, "Index out of bound");
+        if self.is_clipped(x as i32, y as i32) {
+            return;
         }
+        let i = self.index(x, y);
+        self.data[i] = color;
+        self.stats.pixels_plotted += 1;
+        self.mark_dirty(Rect::new(x as i32, y as i32, 1, 1));
+    }
 
+    #[cfg(feature = "fs")]
+    pub fn write_binary(&self, filepath: &str) -> io::Result<()> {
+        let mut file = create_file(filepath);
+        self.encode_binary(&mut file)?;
         file.flush()?;
         Ok(())
     }
+
+    /// Pixels as 8 bit RGBA, row by row, with alpha always 255
+    ///
+    /// This is the layout of a browser canvas `ImageData`
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let mut out = vec![0; self.data.len() * 4];
+        self.write_rgba(&mut out);
+        out
+    }
+
+    /// Like `to_rgba`, writing into `out`, which must hold exactly width * height * 4 bytes
+    pub fn write_rgba(&self, out: &mut [u8]) {
+        assert_eq!(
+            out.len(),
+            self.data.len() * 4,
+            "Buffer must hold 4 bytes per pixel"
+        );
+        for (px, c) in out.chunks_exact_mut(4).zip(self.data.iter()) {
+            px.copy_from_slice(&c.to_rgba8(self.depth));
+        }
+    }
+
+    /// Pixels row by row from the top, channels in [r, g, b] order, each one byte
+    /// up to depth 255 and two big endian bytes past that, like in a binary ppm
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        pixels_to_bytes(&self.data, self.depth, &mut out);
+        out
+    }
+
+    /// Image from pixels in the `as_bytes` layout, which must be exactly
+    /// width * height of them
+    pub fn from_bytes(height: u32, width: u32, depth: u16, bytes: &[u8]) -> PPMImg {
+        assert_eq!(
+            bytes.len(),
+            (width * height) as usize * 3 * channel_bytes(depth),
+            "Bytes must hold width * height pixels"
+        );
+        let mut img = PPMImg::new(height, width, depth);
+        img.data = pixels_from_bytes(bytes, depth);
+        img
+    }
+
+    /// Writes the image as a binary (P6) ppm to `out`, header included
+    #[cfg(feature = "std")]
+    pub fn encode_binary<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let _span = utils::Span::new("encode ppm");
+        self.encode(&mut encoder::PpmEncoder::binary(), out)
+    }
+
+    #[cfg(feature = "fs")]
     pub fn write_ascii(&self, filepath: &str) -> io::Result<()> {
         let mut file = create_file(filepath);
-        writeln!(file, "P3")?;
-        writeln!(file, "{} {} {}", self.width, self.height, self.depth)?;
-        for t in self.data.iter() {
-            writeln!(file, "{} {} {}", t.red, t.green, t.blue)?;
-        }
+        self.encode(&mut encoder::PpmEncoder::ascii(), &mut file)?;
         file.flush()?;
         Ok(())
     }
+
+    /// Read a P3 (ascii) or P6 (binary) ppm file
+    #[cfg(feature = "fs")]
+    pub fn read(filepath: &str) -> io::Result<PPMImg> {
+        PPMImg::from_ppm_bytes(&fs::read(filepath)?)
+    }
+
+    /// Parse the contents of a P3 or P6 ppm file
+    #[cfg(feature = "std")]
+    pub fn from_ppm_bytes(bytes: &[u8]) -> io::Result<PPMImg> {
+        fn invalid(msg: &str) -> io::Error {
+            io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+        }
+
+        // header tokens are separated by whitespace and # comments
+        fn next_token<'a>(bytes: &'a [u8], pos: &mut usize) -> io::Result<&'a [u8]> {
+            loop {
+                match bytes.get(*pos) {
+                    Some(b'#') => {
+                        while bytes.get(*pos).is_some_and(|&b| b != b'\n') {
+                            *pos += 1;
+                        }
+                    }
+                    Some(b) if b.is_ascii_whitespace() => *pos += 1,
+                    Some(_) => break,
+                    None => return Err(invalid("Unexpected end of ppm file")),
+                }
+            }
+            let start = *pos;
+            while bytes.get(*pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+                *pos += 1;
+            }
+            Ok(&bytes[start..*pos])
+        }
+
+        fn next_num(bytes: &[u8], pos: &mut usize) -> io::Result<u32> {
+            std::str::from_utf8(next_token(bytes, pos)?)
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .ok_or_else(|| invalid("Invalid number in ppm file"))
+        }
+
+        let mut pos = 0;
+        let magic = next_token(bytes, &mut pos)?;
+        let (width, height) = (next_num(bytes, &mut pos)?, next_num(bytes, &mut pos)?);
+        let depth = next_num(bytes, &mut pos)?;
+        if depth == 0 || depth > u16::MAX as u32 {
+            return Err(invalid("Invalid color depth in ppm file"));
+        }
+        let len = width
+            .checked_mul(height)
+            .map(|n| n as usize)
+            .ok_or_else(|| invalid("Image in ppm file is too big"))?;
+        // checked before allocating: a P3 channel takes at least a space and a digit
+        let channel = if magic == b"P3" {
+            2
+        } else {
+            channel_bytes(depth as u16)
+        };
+        match len.checked_mul(3 * channel) {
+            Some(n) if n <= bytes.len() - pos => (),
+            _ => return Err(invalid("Unexpected end of ppm file")),
+        }
+        let mut img = PPMImg::new(height, width, depth as u16);
+
+        match magic {
+            b"P3" => {
+                for i in 0..len {
+                    let mut channel = || next_num(bytes, &mut pos).map(|c| c as u16);
+                    img.data[i] = RGB {
+                        red: channel()?,
+                        green: channel()?,
+                        blue: channel()?,
+                    };
+                }
+            }
+            b"P6" => {
+                // exactly one whitespace byte between the header and the raster
+                let raster = &bytes[(pos + 1).min(bytes.len())..];
+                let size = len * 3 * channel_bytes(depth as u16);
+                if raster.len() < size {
+                    return Err(invalid("Unexpected end of ppm file"));
+                }
+                img.data = pixels_from_bytes(&raster[..size], depth as u16);
+            }
+            _ => return Err(invalid("Only P3 and P6 ppm files are supported")),
+        }
+        Ok(img)
+    }
 }
 
 #[allow(dead_code)]
-// clear
+// clear and fill
 impl PPMImg {
+    /// Fills the image with its background, or bg_color if it has none
     pub fn clear(&mut self) {
-        let bg = self.bg_color;
-        for d in self.data.iter_mut() {
-            *d = bg;
+        let background = self.background.take();
+        if background.is_none() && self.clip.is_none() {
+            let bg = self.bg_color;
+            self.data.fill(bg);
+        } else {
+            let (w, h, depth) = (self.width, self.height, self.depth);
+            for y in 0..h {
+                for x in 0..w {
+                    if self.is_clipped(x as i32, y as i32) {
+                        continue;
+                    }
+                    let i = self.index(x, y);
+                    self.data[i] = match &background {
+                        Some(bg) => bg.color_at(x, y, w, h, depth),
+                        None => self.bg_color,
+                    };
+                }
+            }
         }
+        self.background = background;
+        self.dirty = Some(Rect::new(0, 0, self.width, self.height));
     }
-}
 
-// implement point plotting
-impl PPMImg {
-    pub fn plot(&mut self, x: i32, y: i32) -> () {
-        let (width, height) = (
-            self.width.try_into().unwrap(),
-            self.height.try_into().unwrap(),
-        );
-        if (!self.x_wrap && (x < 0 || x >= width)) || (!self.y_wrap && (y < 0 || y >= height)) {
-            return ();
+    /// Fill a `w` by `h` rectangle with top left corner at (x, y) using fg_color
+    ///
+    /// Parts of the rectangle outside of the image are ignored, regardless of wrapping
+    pub fn fill_rect(&mut self, x: i32, y: i32, w: u32, h: u32) {
+        let (x1, y1) = (x + w as i32 - 1, y + h as i32 - 1);
+        let (height, width) = (self.height as i32, self.width as i32);
+        if w == 0 || h == 0 || x1 < 0 || y1 < 0 || x >= width || y >= height {
+            return;
+        }
+        for row in y.max(0)..=y1.min(height - 1) {
+            self.fill_span(row, x, x1);
         }
+    }
 
-        let x = if x >= width {
-            x % width
-        } else if x < 0 {
-            let r = x % width;
-            if r != 0 {
-                r + width
-            } else {
-                r
+    /// Fill the horizontal span from x0 to x1 (inclusive) on row y with fg_color
+    ///
+    /// Clipped to the image, and writes the whole row slice at once instead of plotting
+    fn fill_span(&mut self, y: i32, x0: i32, x1: i32) {
+        let (height, width) = (self.height as i32, self.width as i32);
+        if y < 0 || y >= height {
+            return;
+        }
+        let (x0, x1) = (x0.max(0), x1.min(width - 1));
+        if x0 > x1 {
+            return;
+        }
+        let (start, end) = (
+            self.index(x0 as u32, y as u32),
+            self.index(x1 as u32, y as u32),
+        );
+        let fg = self.fg_color;
+        if self.clip.is_some() {
+            for x in x0..=x1 {
+                if !self.is_clipped(x, y) {
+                    self.data[start + (x - x0) as usize] = fg;
+                    self.stats.pixels_plotted += 1;
+                }
             }
         } else {
-            x
-        };
-        let y = if y >= height {
-            y % height
-        } else if y < 0 {
-            let r = y % height;
-            if r != 0 {
-                r + height
-            } else {
-                r
+            self.data[start..=end].fill(fg);
+            self.stats.pixels_plotted += (x1 - x0 + 1) as u64;
+        }
+        self.mark_dirty(Rect::from_corners(x0, y, x1, y));
+    }
+}
+
+// implement point plotting
+impl PPMImg {
+    /// Plots (x, y) with fg_color, moving it into the image following x_wrap and y_wrap
+    pub fn plot(&mut self, x: i32, y: i32) {
+        if let Some(sym) = self.symmetry.take() {
+            for i in 0..sym.copies() {
+                let (x, y) = sym.apply(i, (f64::from(x), f64::from(y)));
+                self.plot(x.round() as i32, y.round() as i32);
             }
-        } else {
-            y
-        };
+            self.symmetry = Some(sym);
+            return;
+        }
+        let y = self.raster_y(f64::from(y)) as i32;
+        if let Some(brush) = self.brush.take() {
+            self.stamp(&brush, f64::from(x), f64::from(y));
+            self.brush = Some(brush);
+            return;
+        }
+        let (width, height) = (self.width as i32, self.height as i32);
+        if let (Some(x), Some(y)) = (self.x_wrap.apply(x, width), self.y_wrap.apply(y, height)) {
+            self.plot_inside(x, y);
+        }
+    }
+
+    /// Row `y` of `y_axis` counted from the top; its own inverse
+    fn raster_y(&self, y: f64) -> f64 {
+        match self.y_axis {
+            YAxis::Down => y,
+            YAxis::Up => f64::from(self.height) - 1.0 - y,
+        }
+    }
 
+    /// Runs `draw` with y pointing down, whatever `y_axis` is, for drawing in
+    /// pixel coordinates worked out from something else, like a camera
+    pub fn in_pixels<R, F: FnOnce(&mut PPMImg) -> R>(&mut self, draw: F) -> R {
+        let axis = core::mem::replace(&mut self.y_axis, YAxis::Down);
+        let out = draw(self);
+        self.y_axis = axis;
+        out
+    }
+
+    /// Plots (x, y) if it is inside the image, ignoring wrapping
+    fn plot_inside(&mut self, x: i32, y: i32) {
+        if x < 0 || x >= self.width as i32 || y < 0 || y >= self.height as i32 {
+            return;
+        }
+        if self.is_clipped(x, y) {
+            return;
+        }
         // now we know that x and y are positive, we can cast without worry
         let index = self.index(x as u32, y as u32);
         self.data[index] = self.fg_color;
+        self.stats.pixels_plotted += 1;
+        self.mark_dirty(Rect::point(x, y));
     }
 
     fn index(&self, x: u32, y: u32) -> usize {
-        (y * self.width as u32 + x).try_into().unwrap()
+        (y * self.width + x).try_into().unwrap()
+    }
+
+    /// Whether the clip mask keeps (x, y) from being drawn on
+    fn is_clipped(&self, x: i32, y: i32) -> bool {
+        self.clip.as_ref().is_some_and(|m| !m.contains(x, y))
+    }
+}
+
+// brush stamping
+#[allow(dead_code)]
+impl PPMImg {
+    /// Stamps `brush` centered on (x, y) in fg_color, following x_wrap and y_wrap
+    pub fn stamp(&mut self, brush: &Brush, x: f64, y: f64) {
+        let (width, height) = (self.width as i32, self.height as i32);
+        let left = x.round() as i32 - (brush.width() / 2) as i32;
+        let top = y.round() as i32 - (brush.height() / 2) as i32;
+        for by in 0..brush.height() {
+            for bx in 0..brush.width() {
+                let coverage = brush.coverage(bx, by);
+                if coverage <= 0.0 {
+                    continue;
+                }
+                let (px, py) = (left + bx as i32, top + by as i32);
+                if let (Some(px), Some(py)) =
+                    (self.x_wrap.apply(px, width), self.y_wrap.apply(py, height))
+                {
+                    self.blend_inside(px, py, coverage);
+                }
+            }
+        }
+    }
+
+    /// Stamps `brush` every `brush.spacing()` pixels from (x0, y0) to (x1, y1), both ends included
+    fn stroke(&mut self, brush: &Brush, x0: f64, y0: f64, x1: f64, y1: f64) {
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let len = (dx * dx + dy * dy).sqrt();
+        let spacing = brush.spacing();
+        let steps = (len / spacing).floor() as u32;
+        for i in 0..=steps {
+            let t = f64::from(i) * spacing / len.max(f64::MIN_POSITIVE);
+            self.stamp(brush, x0 + dx * t, y0 + dy * t);
+        }
+        if f64::from(steps) * spacing < len {
+            self.stamp(brush, x1, y1);
+        }
+    }
+
+    /// Mixes fg_color into (x, y) by `coverage`, from 0 (unchanged) to 1 (fg_color)
+    fn blend_inside(&mut self, x: i32, y: i32, coverage: f64) {
+        if coverage >= 1.0 {
+            self.plot_inside(x, y);
+            return;
+        }
+        if x < 0 || x >= self.width as i32 || y < 0 || y >= self.height as i32 {
+            return;
+        }
+        if self.is_clipped(x, y) {
+            return;
+        }
+        let (index, fg) = (self.index(x as u32, y as u32), self.fg_color);
+        let mix = |a: u16, b: u16| {
+            (f64::from(a) + (f64::from(b) - f64::from(a)) * coverage).round() as u16
+        };
+        let old = self.data[index];
+        self.data[index] = RGB {
+            red: mix(old.red, fg.red),
+            green: mix(old.green, fg.green),
+            blue: mix(old.blue, fg.blue),
+        };
+        self.stats.pixels_plotted += 1;
+        self.mark_dirty(Rect::point(x, y));
+    }
+}
+
+// dirty region tracking
+#[allow(dead_code)]
+impl PPMImg {
+    fn mark_dirty(&mut self, r: Rect) {
+        self.dirty = Some(match self.dirty {
+            Some(d) => d.union(&r),
+            None => r,
+        });
+    }
+
+    /// Bounding box of all pixels modified since the last `take_dirty()`
+    pub fn dirty(&self) -> Option<Rect> {
+        self.dirty
+    }
+
+    /// Returns the dirty region and resets tracking
+    pub fn take_dirty(&mut self) -> Option<Rect> {
+        self.dirty.take()
+    }
+
+    /// Running totals of everything drawn since creation or the last `take_stats()`
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    /// Returns the running totals and resets them
+    pub fn take_stats(&mut self) -> RenderStats {
+        core::mem::take(&mut self.stats)
+    }
+
+    /// For renderers outside this module to count what they skip or reject
+    pub(crate) fn stats_mut(&mut self) -> &mut RenderStats {
+        &mut self.stats
+    }
+
+    /// Bounding box of the pixels that differ from `prev`, which must have the same size
+    ///
+    /// Unlike `dirty`, this ignores pixels redrawn with the color they already had,
+    /// like a scene redrawn from scratch after `clear`
+    pub fn changed_since(&self, prev: &PPMImg) -> Option<Rect> {
+        assert!(
+            self.width == prev.width && self.height == prev.height,
+            "Images must have the same size"
+        );
+        let mut changed: Option<Rect> = None;
+        for y in 0..self.height {
+            let start = self.index(0, y);
+            let row = &self.data[start..start + self.width as usize];
+            let prev_row = &prev.data[start..start + self.width as usize];
+            let differs = |(a, b): (&RGB, &RGB)| a != b;
+            if let Some(x0) = row.iter().zip(prev_row).position(differs) {
+                let x1 = row.iter().zip(prev_row).rposition(differs).unwrap();
+                let r = Rect::from_corners(x0 as i32, y as i32, x1 as i32, y as i32);
+                changed = Some(changed.map_or(r, |c| c.union(&r)));
+            }
+        }
+        changed
+    }
+
+    /// Writes the pixels inside `r` as a binary ppm, with the position of its
+    /// top left corner in a `# offset x y` comment
+    ///
+    /// `r` is clipped to the image; None or an empty region writes a 0 by 0 image.
+    /// `apply_delta` puts the pixels back in place.
+    #[cfg(feature = "std")]
+    pub fn encode_region<W: Write>(&self, r: Option<Rect>, out: &mut W) -> io::Result<()> {
+        let r = r
+            .and_then(|r| r.intersect(&Rect::new(0, 0, self.width, self.height)))
+            .unwrap_or(Rect::new(0, 0, 0, 0));
+        writeln!(out, "P6")?;
+        writeln!(out, "# offset {} {}", r.x, r.y)?;
+        writeln!(out, "{} {} {}", r.w, r.h, self.depth)?;
+        for y in r.y..r.y + r.h as i32 {
+            let start = self.index(r.x as u32, y as u32);
+            encode_binary_pixels(&self.data[start..start + r.w as usize], self.depth, out)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the dirty region to `filepath` with `encode_region`, and resets tracking
+    ///
+    /// Frames of an animation that change little are much faster to write this way.
+    /// Returns the region written, None if nothing changed.
+    #[cfg(feature = "fs")]
+    pub fn write_delta(&mut self, filepath: &str) -> io::Result<Option<Rect>> {
+        let r = self.take_dirty();
+        let mut file = create_file(filepath);
+        self.encode_region(r, &mut file)?;
+        file.flush()?;
+        Ok(r)
+    }
+
+    /// Copies the pixels of a region written by `encode_region` into place
+    #[cfg(feature = "std")]
+    pub fn apply_delta(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        let header = bytes
+            .split(|&b| b == b'\n')
+            .nth(1)
+            .and_then(|l| std::str::from_utf8(l).ok())
+            .and_then(|l| l.strip_prefix("# offset "))
+            .ok_or_else(|| invalid("Missing offset in delta"))?;
+        let offset: Vec<u32> = header
+            .split_whitespace()
+            .map(|n| n.parse().map_err(|_| invalid("Invalid offset in delta")))
+            .collect::<io::Result<_>>()?;
+        if offset.len() != 2 {
+            return Err(invalid("Invalid offset in delta"));
+        }
+        let region = PPMImg::from_ppm_bytes(bytes)?;
+        let fits =
+            |start: u32, len: u32, max: u32| start.checked_add(len).is_some_and(|end| end <= max);
+        if region.depth != self.depth
+            || !fits(offset[0], region.width, self.width)
+            || !fits(offset[1], region.height, self.height)
+        {
+            return Err(invalid("Delta doesn't fit the image"));
+        }
+        for y in 0..region.height {
+            let start = self.index(offset[0], offset[1] + y);
+            let src = region.index(0, y);
+            self.data[start..start + region.width as usize]
+                .copy_from_slice(&region.data[src..src + region.width as usize]);
+        }
+        if region.width > 0 && region.height > 0 {
+            let (x, y) = (offset[0] as i32, offset[1] as i32);
+            self.mark_dirty(Rect::new(x, y, region.width, region.height));
+        }
+        Ok(())
+    }
+
+    /// Copy the pixels inside `r` from `src`, which must have the same size as self
+    ///
+    /// Does not mark the region as dirty
+    pub fn copy_region_from(&mut self, src: &PPMImg, r: Rect) {
+        assert!(
+            self.width == src.width && self.height == src.height,
+            "Images must have the same size"
+        );
+        let r = match r.intersect(&Rect::new(0, 0, self.width, self.height)) {
+            Some(r) => r,
+            None => return,
+        };
+        for y in r.y..=r.bottom() {
+            let start = self.index(r.x as u32, y as u32);
+            let end = start + r.w as usize;
+            self.data[start..end].copy_from_slice(&src.data[start..end]);
+        }
+    }
+}
+
+// checksum
+#[allow(dead_code)]
+impl PPMImg {
+    /// 64 bit FNV-1a hash of the size, depth and pixels, the same on every
+    /// machine and build, to compare renders by
+    ///
+    /// Settings like fg_color, and the dirty region and stats, don't count.
+    pub fn hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |bytes: &[u8]| {
+            for b in bytes {
+                hash = (hash ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        feed(&self.width.to_le_bytes());
+        feed(&self.height.to_le_bytes());
+        feed(&self.depth.to_le_bytes());
+        for p in self.data.iter() {
+            for c in [p.red, p.green, p.blue] {
+                feed(&c.to_le_bytes());
+            }
+        }
+        hash
+    }
+}
+
+// fit geometry to image
+#[allow(dead_code)]
+impl PPMImg {
+    /// Makes a scale + translate transform that centers geometry with bounds `b`
+    /// in the image, leaving `margin` pixels free on every side
+    ///
+    /// Scaling is uniform so the geometry keeps its aspect ratio.
+    /// z is scaled by the same factor and centered around 0. The margins must
+    /// leave some of the image free.
+    pub fn fit_to_view(&self, b: &Bounds, margin: f64) -> Matrix {
+        assert!(
+            2.0 * margin < f64::from(self.width.min(self.height)),
+            "Margin must be less than half the image's smaller side"
+        );
+        let [w, h, _] = b.size();
+        let avail_w = f64::from(self.width) - 2.0 * margin;
+        let avail_h = f64::from(self.height) - 2.0 * margin;
+        let scale = match (w > 0.0, h > 0.0) {
+            (true, true) => (avail_w / w).min(avail_h / h),
+            (true, false) => avail_w / w,
+            (false, true) => avail_h / h,
+            (false, false) => 1.0,
+        };
+        let [cx, cy, cz] = b.center();
+        Matrix::translate(-cx, -cy, -cz)
+            .compose(&Matrix::scale(scale, scale, scale))
+            .compose(&Matrix::translate(
+                f64::from(self.width) / 2.0,
+                f64::from(self.height) / 2.0,
+                0.0,
+            ))
     }
 }
 
@@ -149,9 +936,62 @@ impl PPMImg {
 #[allow(dead_code)]
 impl PPMImg {
     /// Draw a line from (x0, y0) to (x1, y1)
+    ///
+    /// Endpoints don't need to be on pixels: the line follows the exact line
+    /// between them, see `raster::subpixel_line_pixels`. With `subpixel` turned
+    /// off, both endpoints are rounded to pixels first instead.
     /// #### impl note:
     ///    Always add 2A or 2B when updating D. Half of that value will distort line
     pub fn draw_line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64) {
+        if let Some(sym) = self.symmetry.take() {
+            for i in 0..sym.copies() {
+                let (x0, y0, x1, y1) = sym.apply_line(i, (x0, y0, x1, y1));
+                self.draw_line(x0, y0, x1, y1);
+            }
+            self.symmetry = Some(sym);
+            return;
+        }
+        self.stats.lines_drawn += 1;
+        let (y0, y1) = (self.raster_y(y0), self.raster_y(y1));
+        if let Some(brush) = self.brush.take() {
+            self.stroke(&brush, x0, y0, x1, y1);
+            self.brush = Some(brush);
+            return;
+        }
+        if self.x_wrap == WrapMode::Discard && self.y_wrap == WrapMode::Discard {
+            self.draw_segment(x0, y0, x1, y1);
+            return;
+        }
+        // cut the line where it crosses the edges, and move the pieces back in
+        let (x_wrap, y_wrap, width, height) = (self.x_wrap, self.y_wrap, self.width, self.height);
+        wrap::split_line(
+            x_wrap,
+            y_wrap,
+            width,
+            height,
+            [x0, y0, x1, y1],
+            |x0, y0, x1, y1| self.draw_segment(x0, y0, x1, y1),
+        );
+    }
+
+    /// Draws the part of a line inside the image, see `draw_line`
+    fn draw_segment(&mut self, x0: f64, y0: f64, x1: f64, y1: f64) {
+        let [x0, y0, x1, y1] =
+            match raster::clip_to_image([x0, y0, x1, y1], self.width, self.height) {
+                Some(line) => line,
+                None => return,
+            };
+        if self.subpixel {
+            if raster::to_fixed(y0) == raster::to_fixed(y1) {
+                // horizontal line
+                let (x0, x1) = (subpixel_round(x0.min(x1)), subpixel_round(x0.max(x1)));
+                self.fill_span(subpixel_round(y0), x0, x1);
+                return;
+            }
+            raster::subpixel_line_pixels(x0, y0, x1, y1, |x, y| self.plot_inside(x, y));
+            return;
+        }
+
         // swap variables if needed, since we are always going from left to right
         let (x0, y0, x1, y1) = if x0 > x1 {
             (x1, y1, x0, y0)
@@ -167,79 +1007,12 @@ impl PPMImg {
             y1.round() as i32,
         );
 
-        // calculate  values and then truncate
-        let (dy, ndx) = (y1 - y0, -(x1 - x0));
-
-        // deal with special cases:
-        if ndx == 0 {
-            // vertical line
-            let (y0, y1) = if y0 < y1 { (y0, y1) } else { (y1, y0) };
-
-            for y in y0..=y1 {
-                self.plot(x0, y);
-            }
-
-            return ();
-        }
-
-        if dy == 0 {
+        if y0 == y1 {
             // horizontal line
-            // x vals are already in the right order, so we don't flip
-            for x in x0..=x1 {
-                self.plot(x, y0);
-            }
-            return ();
-        }
-
-        // find A and B
-        // let m  = -dely as f64 / ndelx as f64;
-
-        let (x, mut y) = (x0, y0);
-
-        if (y1 - y0).abs() < (x1 - x0).abs() {
-            // octant 1 and 8
-            let mut d = 2 * dy + ndx;
-            let (y_inc, dy) = if dy > 0 {
-                // octant 1
-                (1, dy)
-            } else {
-                // octant 8
-                // dy is (-) in octant 8, so flip it to balance out with ndx
-                (-1, -dy)
-            };
-
-            for x in x0..=x1 {
-                self.plot(x, y);
-                if d > 0 {
-                    y += y_inc;
-                    d += 2 * ndx;
-                }
-                d += 2 * dy;
-            }
-        } else {
-            // octant 2 and 7
-            // flipping x and y should work out
-
-            let mut d = 2 * -ndx - dy;
-
-            let (x_inc, mut x, ystart, yend, dy) = if dy > 0 {
-                // octant 2
-                (1, x, y0, y1, dy)
-            } else {
-                // octant 7
-                // swap -x and y to reflect over y=-x into octant 8
-                (-1, x - ndx, y1, y0, -dy)
-            };
-
-            for y in ystart..=yend {
-                self.plot(x, y);
-                if d > 0 {
-                    x += x_inc;
-                    d -= 2 * dy;
-                }
-                d -= 2 * ndx;
-            }
+            self.fill_span(y0, x0, x1);
+            return;
         }
+        line_pixels(x0, y0, x1, y1, |x, y| self.plot_inside(x, y));
     }
 
     /// Draw a line from (x0, y0) with a certain magnitude and angle
@@ -258,7 +1031,210 @@ impl PPMImg {
         let (x1, y1) = (x0 + dx, y0 + dy);
 
         self.draw_line(x0, y0, x1, y1);
-        return (x1, y1);
+        (x1, y1)
+    }
+
+    /// Draws many lines (x0, y0, x1, y1) at once, with the same result as
+    /// calling `draw_line` on each
+    ///
+    /// Much faster for lots of short segments: lines entirely outside the image
+    /// are skipped, the rest are drawn top to bottom, and pixels are written
+    /// without going through `plot`.
+    pub fn draw_lines(&mut self, segments: &[(f64, f64, f64, f64)]) {
+        if let Some(sym) = self.symmetry.take() {
+            let copies: Vec<_> = (0..sym.copies())
+                .flat_map(|i| segments.iter().map(move |&s| sym.apply_line(i, s)))
+                .collect();
+            self.draw_lines(&copies);
+            self.symmetry = Some(sym);
+            return;
+        }
+        if self.x_wrap != WrapMode::Discard
+            || self.y_wrap != WrapMode::Discard
+            || self.brush.is_some()
+        {
+            for &(x0, y0, x1, y1) in segments {
+                self.draw_line(x0, y0, x1, y1);
+            }
+            return;
+        }
+        let flipped: Vec<_>;
+        let segments = match self.y_axis {
+            YAxis::Down => segments,
+            YAxis::Up => {
+                let y = |v| self.raster_y(v);
+                flipped = segments
+                    .iter()
+                    .map(|&(x0, y0, x1, y1)| (x0, y(y0), x1, y(y1)))
+                    .collect();
+                &flipped
+            }
+        };
+
+        let (width, height) = (self.width as i32, self.height as i32);
+        let subpixel = self.subpixel;
+        let to_pixel = |v: f64| {
+            if subpixel {
+                subpixel_round(v)
+            } else {
+                v.round() as i32
+            }
+        };
+        // pixels of the endpoints, left to right like draw_line, along with the line
+        let mut lines: Vec<([i32; 4], [f64; 4])> = segments
+            .iter()
+            .filter_map(|&(x0, y0, x1, y1)| {
+                raster::clip_to_image([x0, y0, x1, y1], self.width, self.height)
+            })
+            .map(|[x0, y0, x1, y1]| {
+                let l = [to_pixel(x0), to_pixel(y0), to_pixel(x1), to_pixel(y1)];
+                if x0 > x1 {
+                    ([l[2], l[3], l[0], l[1]], [x1, y1, x0, y0])
+                } else {
+                    (l, [x0, y0, x1, y1])
+                }
+            })
+            .filter(|(l, _)| {
+                let (top, bottom) = (l[1].min(l[3]), l[1].max(l[3]));
+                l[2] >= 0 && l[0] < width && bottom >= 0 && top < height
+            })
+            .collect();
+        lines.sort_unstable_by_key(|(l, _)| l[1].min(l[3]));
+        self.stats.lines_drawn += segments.len() as u64;
+        #[cfg(feature = "logging")]
+        log::debug!(
+            "draw_lines: {} lines, {} off the image",
+            segments.len(),
+            segments.len() - lines.len()
+        );
+
+        let fg = self.fg_color;
+        let clip = &self.clip;
+        let data = &mut self.data;
+        let plotted = &mut self.stats.pixels_plotted;
+        let mut bounds: Option<Rect> = None;
+        for (l, seg) in lines.iter() {
+            let (mut min_x, mut min_y, mut max_x, mut max_y) = (width, height, -1, -1);
+            let mut put = |x: i32, y: i32| {
+                let unmasked = clip.as_ref().is_none_or(|m| m.contains(x, y));
+                if x >= 0 && x < width && y >= 0 && y < height && unmasked {
+                    data[(y * width + x) as usize] = fg;
+                    *plotted += 1;
+                    min_x = min_x.min(x);
+                    min_y = min_y.min(y);
+                    max_x = max_x.max(x);
+                    max_y = max_y.max(y);
+                }
+            };
+            if subpixel {
+                raster::subpixel_line_pixels(seg[0], seg[1], seg[2], seg[3], &mut put);
+            } else {
+                line_pixels(l[0], l[1], l[2], l[3], &mut put);
+            }
+            if max_x >= 0 {
+                let r = Rect::from_corners(min_x, min_y, max_x, max_y);
+                bounds = Some(bounds.map_or(r, |b| b.union(&r)));
+            }
+        }
+        if let Some(r) = bounds {
+            self.mark_dirty(r);
+        }
+    }
+
+    /// Draws the outlines of many triangles at once, see `draw_lines`
+    pub fn draw_triangles(&mut self, triangles: &[[(f64, f64); 3]]) {
+        let mut segments = Vec::with_capacity(triangles.len() * 3);
+        for t in triangles.iter() {
+            for i in 0..3 {
+                let (p0, p1) = (t[i], t[(i + 1) % 3]);
+                segments.push((p0.0, p0.1, p1.0, p1.1));
+            }
+        }
+        self.draw_lines(&segments);
+    }
+}
+
+/// Pixel a subpixel line endpoint falls on
+fn subpixel_round(v: f64) -> i32 {
+    raster::fixed_to_pixel(raster::to_fixed(v))
+}
+
+/// Calls `plot` for every pixel of the line from (x0, y0) to (x1, y1), with x0 <= x1
+///
+/// These are the pixels `PPMImg::draw_line` draws with `subpixel` turned off
+fn line_pixels<F: FnMut(i32, i32)>(x0: i32, y0: i32, x1: i32, y1: i32, mut plot: F) {
+    // calculate  values and then truncate
+    let (dy, ndx) = (y1 - y0, -(x1 - x0));
+
+    // deal with special cases:
+    if ndx == 0 {
+        // vertical line
+        let (y0, y1) = if y0 < y1 { (y0, y1) } else { (y1, y0) };
+
+        for y in y0..=y1 {
+            plot(x0, y);
+        }
+
+        return;
+    }
+
+    if dy == 0 {
+        // horizontal line
+        // x vals are already in the right order, so we don't flip
+        for x in x0..=x1 {
+            plot(x, y0);
+        }
+        return;
+    }
+
+    // find A and B
+    // let m  = -dely as f64 / ndelx as f64;
+
+    let (x, mut y) = (x0, y0);
+
+    if (y1 - y0).abs() < (x1 - x0).abs() {
+        // octant 1 and 8
+        let mut d = 2 * dy + ndx;
+        let (y_inc, dy) = if dy > 0 {
+            // octant 1
+            (1, dy)
+        } else {
+            // octant 8
+            // dy is (-) in octant 8, so flip it to balance out with ndx
+            (-1, -dy)
+        };
+
+        for x in x0..=x1 {
+            plot(x, y);
+            if d > 0 {
+                y += y_inc;
+                d += 2 * ndx;
+            }
+            d += 2 * dy;
+        }
+    } else {
+        // octant 2 and 7
+        // flipping x and y should work out
+
+        let mut d = 2 * -ndx - dy;
+
+        let (x_inc, mut x, ystart, yend, dy) = if dy > 0 {
+            // octant 2
+            (1, x, y0, y1, dy)
+        } else {
+            // octant 7
+            // swap -x and y to reflect over y=-x into octant 8
+            (-1, x - ndx, y1, y0, -dy)
+        };
+
+        for y in ystart..=yend {
+            plot(x, y);
+            if d > 0 {
+                x += x_inc;
+                d -= 2 * dy;
+            }
+            d -= 2 * ndx;
+        }
     }
 }
 
@@ -294,9 +1270,9 @@ impl Turtle {
     pub fn forward(&mut self, steps: i32) {
         let (x0, y0) = (self.x, self.y);
         let (dx, dy) = polar_to_xy(steps.into(), self.angle_deg);
-        let (x1, y1) = (x0 as f64 + dx, y0 as f64 + dy);
+        let (x1, y1) = (x0 + dx, y0 + dy);
         if self.pen_down {
-            self.img.draw_line(x0 as f64, y0 as f64, x1, y1);
+            self.img.draw_line(x0, y0, x1, y1);
         }
         self.x = x1;
         self.y = y1;
@@ -311,12 +1287,12 @@ impl Turtle {
     }
 
     pub fn get_color(&self) -> RGB {
-        return self.img.fg_color;
+        self.img.fg_color
     }
 
     pub fn move_to(&mut self, x: f64, y: f64) {
         if self.pen_down {
-            self.img.draw_line(self.x as f64, self.y as f64, x, y);
+            self.img.draw_line(self.x, self.y, x, y);
         }
         self.x = x;
         self.y = y;
@@ -326,7 +1302,7 @@ impl Turtle {
     ///
     /// This method will move the turtle
     pub fn get_ppm_img(self) -> PPMImg {
-        return self.img;
+        self.img
     }
 }
 
@@ -351,4 +1327,238 @@ impl PPMImg {
         }
 
     }
+
+    /// Draws the outline of every triangle in a polygon matrix
+    ///
+    /// Number of points must be a multiple of 3
+    #[allow(dead_code)]
+    pub fn render_polygon_matrix(&mut self, m: &PolygonMatrix) {
+        self.render_polygon_matrix_transformed(m, &Matrix::ident(4));
+    }
+
+    /// Draws an edge matrix, transforming each point by `t` on the fly
+    ///
+    /// Same result as `render_edge_matrix(&t.apply(m))`, without making a copy of m
+    #[allow(dead_code)]
+    pub fn render_edge_matrix_transformed(&mut self, m: &EdgeMatrix, t: &Matrix) {
+        assert_eq!(m.rows() % 2, 0, "Number of edges must be a multiple of 2");
+        let mut iter = m.iter_by_row();
+        while let (Some(p0), Some(p1)) = (iter.next(), iter.next()) {
+            let (p0, p1) = (t.transform_point(p0), t.transform_point(p1));
+            self.draw_line(p0[0], p0[1], p1[0], p1[1]);
+        }
+    }
+
+    /// Draws the outline of every triangle in a polygon matrix,
+    /// transforming each point by `t` on the fly
+    #[allow(dead_code)]
+    pub fn render_polygon_matrix_transformed(&mut self, m: &PolygonMatrix, t: &Matrix) {
+        assert_eq!(m.rows() % 3, 0, "Number of points must be a multiple of 3");
+        let mut iter = m.iter_by_row();
+        while let (Some(p0), Some(p1), Some(p2)) = (iter.next(), iter.next(), iter.next()) {
+            let tri = [
+                t.transform_point(p0),
+                t.transform_point(p1),
+                t.transform_point(p2),
+            ];
+            for i in 0..3 {
+                let (p0, p1) = (tri[i], tri[(i + 1) % 3]);
+                self.draw_line(p0[0], p0[1], p1[0], p1[1]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_ppm_keeps_channels_apart() {
+        for depth in [255, 1023] {
+            let mut img = PPMImg::new(1, 2, depth);
+            img.set_pixel(0, 0, RGB::new(1, 2, 3));
+            img.set_pixel(1, 0, RGB::new(depth, 0, 7));
+            let mut out = Vec::new();
+            img.encode_binary(&mut out).unwrap();
+            let read = PPMImg::from_ppm_bytes(&out).unwrap();
+            assert_eq!(read.data, img.data);
+            let copy = PPMImg::from_bytes(1, 2, depth, &img.as_bytes());
+            assert_eq!(copy.data, img.data);
+        }
+        let img = PPMImg::from_bytes(1, 1, 255, &[1, 2, 3]);
+        assert_eq!(img.as_bytes(), [1, 2, 3]);
+        assert_eq!(RGB::new(1, 2, 3).to_rgba8(255), [1, 2, 3, 255]);
+    }
+
+    #[test]
+    fn colors_convert() {
+        assert_eq!(RGB::from(0xff8000), RGB::new(255, 128, 0));
+        assert_eq!(RGB::from((1, 2, 3)), RGB::from([1, 2, 3]));
+        assert_eq!(RGB::default(), RGB::BLACK);
+        let white = RGB::new(65535, 65535, 65535);
+        assert_eq!(RGB::WHITE.scale_depth(255, 65535), white);
+        assert_eq!(RGB::GRAY.scale_depth(255, 15), RGB::new(8, 8, 8));
+        assert_eq!(white.scale_depth(65535, 255), RGB::WHITE);
+        assert_eq!(white.scale_depth(65535, 65535), white);
+        // channels past the depth are clamped, not wrapped
+        assert_eq!(RGB::WHITE.to_rgba8(15), [255; 4]);
+        assert_eq!(white.scale_depth(1, 65535), white);
+    }
+
+    #[test]
+    fn fill_rect_clips_to_image() {
+        let mut img = PPMImg::new(4, 4, 255);
+        img.fill_rect(-1, 2, 3, 5);
+        let lit: Vec<bool> = img.data.iter().map(|p| p.red == 255).collect();
+        assert_eq!(
+            lit,
+            vec![
+                false, false, false, false, //
+                false, false, false, false, //
+                true, true, false, false, //
+                true, true, false, false, //
+            ]
+        );
+    }
+
+    #[test]
+    fn far_off_endpoints_are_clipped() {
+        let mut img = PPMImg::new(10, 10, 255);
+        img.draw_line(0.0, 0.0, 1e8, 1e8);
+        img.draw_line(-1e12, 5.0, 1e12, 5.0);
+        img.draw_lines(&[(9.0 + 1e8, -1e8, 9.0 - 1e8, 1e8)]);
+        let lit = |x, y| img.pixel(x, y).red > 0;
+        assert!((0..10).all(|i| lit(i, i) && lit(i, 5) && lit(i, 9 - i)));
+        assert!(!lit(1, 0) && !lit(0, 1));
+    }
+
+    #[test]
+    fn horizontal_line_matches_plot() {
+        let mut img = PPMImg::new(3, 5, 255);
+        img.draw_line(-2.0, 1.0, 3.0, 1.0);
+        for x in 0..5 {
+            assert_eq!(img.data[img.index(x, 1)].red == 255, x <= 3, "x = {}", x);
+        }
+    }
+
+    #[test]
+    fn y_up_flips_points_and_lines_only() {
+        let lit = |img: &PPMImg, x: u32, y: u32| img.pixel(x, y).red == 255;
+        let mut img = PPMImg::new(4, 4, 255);
+        img.y_axis = YAxis::Up;
+        img.plot(0, 0);
+        img.draw_line(1.0, 1.0, 3.0, 1.0);
+        img.draw_lines(&[(0.0, 3.0, 1.0, 3.0)]);
+        assert!(lit(&img, 0, 3) && !lit(&img, 0, 2));
+        assert!((1..4).all(|x| lit(&img, x, 2)));
+        assert!(lit(&img, 0, 0) && lit(&img, 1, 0));
+
+        // renders in pixel coordinates come out the same either way
+        let vp = plot::Viewport::full(&img, (0.0, 3.0), (0.0, 3.0));
+        let mut up = PPMImg::new(4, 4, 255);
+        up.y_axis = YAxis::Up;
+        let mut down = PPMImg::new(4, 4, 255);
+        for img in [&mut up, &mut down] {
+            img.plot_function(&vp, |x| x, (0.0, 3.0));
+        }
+        assert!((0..4).all(|i| lit(&up, i, 3 - i) && lit(&down, i, 3 - i)));
+    }
+
+    #[test]
+    fn draw_lines_matches_draw_line() {
+        let segments = [
+            (-3.0, 2.0, 9.0, 5.0),
+            (4.0, -2.0, 1.0, 9.0),
+            (6.0, 6.0, 6.0, 0.0),
+            (7.0, 3.0, 0.0, 3.0),
+            (20.0, 0.0, 30.0, 5.0),
+            (0.3, 0.4, 7.6, 2.7),
+            (5.2, 1.4, 1.7, 1.4),
+        ];
+        for &subpixel in [true, false].iter() {
+            let (mut one, mut batch) = (PPMImg::new(8, 8, 255), PPMImg::new(8, 8, 255));
+            one.subpixel = subpixel;
+            batch.subpixel = subpixel;
+            for &(x0, y0, x1, y1) in segments.iter() {
+                one.draw_line(x0, y0, x1, y1);
+            }
+            batch.draw_lines(&segments);
+            let lit = |img: &PPMImg| img.data.iter().map(|p| p.red).collect::<Vec<_>>();
+            assert_eq!(lit(&one), lit(&batch));
+            assert_eq!(one.dirty(), batch.dirty());
+        }
+    }
+
+    #[test]
+    fn deltas_rebuild_frames() {
+        let mut img = PPMImg::new(6, 6, 255);
+        let mut copy = img.clone();
+        img.take_dirty();
+        img.fill_rect(1, 2, 3, 2);
+
+        let mut delta = vec![];
+        img.encode_region(img.dirty(), &mut delta).unwrap();
+        assert!(delta.starts_with(b"P6\n# offset 1 2\n3 2 255\n"));
+        copy.apply_delta(&delta).unwrap();
+        assert_eq!(copy.changed_since(&img), None);
+        let far = b"P6\n# offset 4294967295 0\n3 2 255\n".to_vec();
+        let far = [far, vec![0; 3 * 2 * 3]].concat();
+        assert!(copy.apply_delta(&far).is_err());
+
+        // redrawing from scratch dirties everything, but only the rect changed
+        img.clear();
+        assert_eq!(img.dirty(), Some(Rect::new(0, 0, 6, 6)));
+        assert_eq!(img.changed_since(&copy), Some(Rect::new(1, 2, 3, 2)));
+    }
+
+    #[test]
+    fn swap_keeps_back_in_sync() {
+        let mut buf = buffer::DoubleBuffer::new(4, 4, 255);
+        buf.back().plot(1, 2);
+        buf.back().plot(2, 3);
+        assert_eq!(buf.swap(), Some(Rect::new(1, 2, 2, 2)));
+        assert_eq!(buf.front().data[buf.front().index(1, 2)].red, 255);
+        assert_eq!(buf.back().data[9].red, 255);
+        assert_eq!(buf.swap(), None);
+    }
+
+    #[test]
+    fn fit_to_view_centers_geometry() {
+        let img = PPMImg::new(100, 200, 255);
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.append_edge([-1.0, -1.0, 0.0]);
+        m.append_edge([1.0, 3.0, 0.0]);
+        let fitted = img.fit_to_view(&m.bounds().unwrap(), 10.0).apply(&m);
+        let b = fitted.bounds().unwrap();
+        assert_eq!(b.min, [80.0, 10.0, 0.0]);
+        assert_eq!(b.max, [120.0, 90.0, 0.0]);
+    }
+
+    #[test]
+    fn rgba_bytes() {
+        let mut img = PPMImg::new(1, 2, 1023);
+        img.fg_color = RGB {
+            red: 1023,
+            green: 0,
+            blue: 512,
+        };
+        img.plot(1, 0);
+        assert_eq!(img.to_rgba(), vec![0, 0, 0, 255, 255, 0, 128, 255]);
+    }
+
+    #[test]
+    fn parse_ppm() {
+        let ascii = PPMImg::from_ppm_bytes(b"P3\n# comment\n2 1\n255\n1 2 3  4 5 6\n").unwrap();
+        let binary = PPMImg::from_ppm_bytes(b"P6 2 1 255\n\x01\x02\x03\x04\x05\x06").unwrap();
+        for img in [ascii, binary].iter() {
+            assert_eq!((img.width(), img.height()), (2, 1));
+            let p = img.pixel(1, 0);
+            assert_eq!((p.red, p.green, p.blue), (4, 5, 6));
+        }
+        assert!(PPMImg::from_ppm_bytes(b"P6 2 1 255\n\x01").is_err());
+        let huge = PPMImg::from_ppm_bytes(b"P6 4000000000 4000000000 255\n");
+        assert!(huge.is_err_and(|e| e.kind() == io::ErrorKind::InvalidData));
+        assert!(PPMImg::from_ppm_bytes(b"P3 100000 100000 255\n1 2 3").is_err());
+    }
 }
@@ -0,0 +1,86 @@
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+use super::{PPMImg, RGB};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Sums several images in floating point, to average them without losing precision
+///
+/// Used for motion blur and other effects that render several samples per frame
+pub struct AccumBuffer {
+    width: u32,
+    height: u32,
+    data: Vec<[f64; 3]>,
+    samples: u32,
+}
+
+#[allow(dead_code)]
+impl AccumBuffer {
+    pub fn new(height: u32, width: u32) -> AccumBuffer {
+        AccumBuffer {
+            width,
+            height,
+            data: vec![[0.0; 3]; (width * height) as usize],
+            samples: 0,
+        }
+    }
+
+    /// Number of images added since the last `reset`
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    pub fn reset(&mut self) {
+        self.data.fill([0.0; 3]);
+        self.samples = 0;
+    }
+
+    /// Adds every pixel of `img`, which must have the same size as the buffer
+    pub fn add(&mut self, img: &PPMImg) {
+        assert!(
+            self.width == img.width && self.height == img.height,
+            "Image and buffer must have the same size"
+        );
+        for (sum, p) in self.data.iter_mut().zip(img.data.iter()) {
+            sum[0] += f64::from(p.red);
+            sum[1] += f64::from(p.green);
+            sum[2] += f64::from(p.blue);
+        }
+        self.samples += 1;
+    }
+
+    /// Writes the average of all added images into `img`
+    pub fn resolve(&self, img: &mut PPMImg) {
+        assert!(
+            self.width == img.width && self.height == img.height,
+            "Image and buffer must have the same size"
+        );
+        let n = f64::from(self.samples.max(1));
+        for (p, sum) in img.data.iter_mut().zip(self.data.iter()) {
+            *p = RGB {
+                red: (sum[0] / n).round() as u16,
+                green: (sum[1] / n).round() as u16,
+                blue: (sum[2] / n).round() as u16,
+            };
+        }
+        img.dirty = Some(super::rect::Rect::new(0, 0, img.width, img.height));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn averages_samples() {
+        let mut img = PPMImg::new(1, 2, 255);
+        let mut accum = AccumBuffer::new(1, 2);
+        accum.add(&img);
+        img.plot(0, 0);
+        accum.add(&img);
+        accum.add(&img);
+        accum.resolve(&mut img);
+        assert_eq!(img.pixel(0, 0).green, 170);
+        assert_eq!(img.pixel(1, 0).green, 0);
+    }
+}
@@ -0,0 +1,524 @@
+#[cfg(feature = "fs")]
+use super::accum::AccumBuffer;
+#[cfg(feature = "fs")]
+use super::blit::Blit;
+use super::camera::Camera;
+#[cfg(feature = "fs")]
+use super::progress::{is_cancelled, ProgressTracker};
+use super::progress::{CancelToken, ProgressFn};
+#[cfg(feature = "fs")]
+use super::sprite::SpriteSheet;
+#[cfg(feature = "fs")]
+use super::text::Hud;
+#[cfg(feature = "fs")]
+use super::utils::{create_file, Span};
+#[cfg(feature = "fs")]
+use super::PPMImg;
+#[cfg(feature = "fs")]
+use std::collections::BTreeMap;
+#[cfg(feature = "fs")]
+use std::io::{self, Read, Write};
+#[cfg(feature = "fs")]
+use std::process::{Command, Stdio};
+#[cfg(feature = "fs")]
+use std::sync::{mpsc, Mutex};
+#[cfg(feature = "fs")]
+use std::thread;
+#[cfg(feature = "fs")]
+use std::time::Instant;
+
+/// Frame manager: renders a fixed number of frames into numbered ppm files
+///
+/// Frame `i` is written to `{basename}{i}.ppm`, like the frames `make gen` picks up.
+/// With `deltas` on, only the first frame is written whole.
+pub struct Animation {
+    pub frames: usize,
+    /// Frames per second, used to turn a frame number into a time for keyframe tracks
+    pub fps: f64,
+    pub basename: String,
+    /// Samples rendered and averaged per frame for motion blur; 1 turns blur off
+    pub motion_blur: usize,
+    /// Fraction of the time between two frames the samples are spread over
+    pub shutter: f64,
+    /// Writes frames after the first as deltas against the frame before, see
+    /// `PPMImg::encode_region`, to `{basename}{i}.delta.ppm`
+    pub deltas: bool,
+    /// Called with the number of frames written after each one
+    pub on_progress: Option<ProgressFn>,
+    /// Checked before each frame; rendering stops with an `Interrupted` error once cancelled
+    pub cancel: Option<CancelToken>,
+    /// Stamps the frame number, time, how long drawing took and what was drawn
+    /// in the top left corner of every frame, see `PPMImg::draw_hud`
+    pub hud: bool,
+    /// Camera at a time, shown on the HUD
+    pub hud_camera: Option<Box<dyn Fn(f64) -> Camera + Send + Sync>>,
+    /// Makes every frame depend only on its number, so renders are byte for byte
+    /// the same on every run and machine, serial or parallel, see `PPMImg::hash`
+    ///
+    /// Each frame is drawn starting from the image as it was before the first,
+    /// rather than as the frame before left it, and the HUD leaves out the
+    /// render time.
+    pub deterministic: bool,
+    /// Program `encode_video` runs; "ffmpeg" by default, looked up on the path
+    pub ffmpeg: String,
+}
+
+#[allow(dead_code)]
+impl Animation {
+    pub fn new(frames: usize, fps: f64, basename: &str) -> Animation {
+        Animation {
+            frames,
+            fps,
+            basename: basename.to_string(),
+            motion_blur: 1,
+            shutter: 1.0,
+            deltas: false,
+            on_progress: None,
+            cancel: None,
+            hud: false,
+            hud_camera: None,
+            deterministic: false,
+            ffmpeg: "ffmpeg".to_string(),
+        }
+    }
+
+    /// Time in seconds at the start of `frame`
+    pub fn time(&self, frame: usize) -> f64 {
+        frame as f64 / self.fps
+    }
+
+    pub fn frame_path(&self, frame: usize) -> String {
+        format!("{}{}.ppm", self.basename, frame)
+    }
+
+    pub fn delta_path(&self, frame: usize) -> String {
+        format!("{}{}.delta.ppm", self.basename, frame)
+    }
+
+    /// Renders every frame into `img` and writes it out
+    ///
+    /// img is cleared before each frame, then `draw(img, frame, time)` is called.
+    /// With motion blur on, `draw` is called once per sample with times spread
+    /// over the shutter, so it should draw the scene as it is at `time`.
+    #[cfg(feature = "fs")]
+    pub fn render<F>(&self, img: &mut PPMImg, mut draw: F) -> io::Result<()>
+    where
+        F: FnMut(&mut PPMImg, usize, f64),
+    {
+        let mut accum = self.accum_buffer(img);
+        let mut prev: Option<PPMImg> = None;
+        let mut progress = ProgressTracker::new(self.on_progress.as_ref(), self.frames);
+        let start = self.deterministic.then(|| img.clone());
+        for frame in 0..self.frames {
+            if is_cancelled(&self.cancel) {
+                return Err(cancelled());
+            }
+            if let Some(start) = start.as_ref() {
+                img.clone_from(start);
+            }
+            self.draw_frame(img, accum.as_mut(), frame, &mut draw);
+            self.write_frame(img, frame, &mut prev)?;
+            progress.step();
+        }
+        Ok(())
+    }
+
+    /// Renders `sheet` playing at `sprite_fps` cells per second, looping, with its
+    /// top left corner at (x, y), over img cleared before each frame
+    #[cfg(feature = "fs")]
+    pub fn render_sprite(
+        &self,
+        img: &mut PPMImg,
+        sheet: &SpriteSheet,
+        sprite_fps: f64,
+        (x, y): (i32, i32),
+        how: &Blit,
+    ) -> io::Result<()> {
+        self.render(img, |img, _, time| {
+            sheet.draw(img, sheet.frame_at(time, sprite_fps), x, y, how)
+        })
+    }
+
+    /// Like `render`, drawing frames on `threads` worker threads at once
+    ///
+    /// Each worker draws into its own copy of `img`, which is left unchanged.
+    /// Frames are still written in order, so deltas work too.
+    #[cfg(feature = "fs")]
+    pub fn render_parallel<F>(&self, img: &PPMImg, threads: usize, draw: F) -> io::Result<()>
+    where
+        F: Fn(&mut PPMImg, usize, f64) + Sync,
+    {
+        let threads = threads.max(1);
+        // frame numbers are handed out at most this far ahead of the next one to
+        // write, so a slow frame can't leave the others piling up
+        let window = 2 * threads;
+        let (jobs_tx, jobs_rx) = mpsc::channel();
+        for frame in 0..window.min(self.frames) {
+            jobs_tx.send(frame).unwrap();
+        }
+        let jobs = Mutex::new(jobs_rx);
+        // None when a worker saw the render was cancelled, or panicked
+        let (tx, rx) = mpsc::sync_channel::<Option<(usize, PPMImg)>>(threads);
+        thread::scope(|scope| {
+            // dropped when done, letting the workers go
+            let jobs_tx = jobs_tx;
+            for _ in 0..threads {
+                let (tx, jobs, draw, start) = (tx.clone(), &jobs, &draw, img);
+                let mut img = img.clone();
+                scope.spawn(move || {
+                    let _guard = PanicGuard(&tx);
+                    let mut accum = self.accum_buffer(&img);
+                    loop {
+                        // the writer hangs up once there's nothing left to draw
+                        let next = jobs.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                        let frame = match next {
+                            Ok(frame) => frame,
+                            Err(_) => break,
+                        };
+                        if is_cancelled(&self.cancel) {
+                            let _ = tx.send(None);
+                            break;
+                        }
+                        // which frames a worker drew before is up to the scheduler
+                        if self.deterministic {
+                            img.clone_from(start);
+                        }
+                        self.draw_frame(&mut img, accum.as_mut(), frame, &mut |i, f, t| {
+                            draw(i, f, t)
+                        });
+                        // the writer hung up after an error
+                        if tx.send(Some((frame, img.clone()))).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(tx);
+
+            // frames finish out of order: hold on to them until their turn
+            let mut done = BTreeMap::new();
+            let (mut to_write, mut prev) = (0, None);
+            let mut progress = ProgressTracker::new(self.on_progress.as_ref(), self.frames);
+            while to_write < self.frames {
+                let (frame, img) = match rx.recv() {
+                    Ok(Some(finished)) => finished,
+                    // a panic is passed on by the scope
+                    _ => return Err(cancelled()),
+                };
+                done.insert(frame, img);
+                while let Some(img) = done.remove(&to_write) {
+                    self.write_frame(&img, to_write, &mut prev)?;
+                    to_write += 1;
+                    progress.step();
+                    if to_write + window - 1 < self.frames {
+                        // the workers are gone if they all saw a cancel
+                        let _ = jobs_tx.send(to_write + window - 1);
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Encodes the frames written by `render` into a video at `path`, playing
+    /// `fps` frames per second, with ffmpeg
+    ///
+    /// The format comes from path's extension, like mp4 or webm. Frames are piped
+    /// to ffmpeg as raw rgb24, so it must be installed, see `ffmpeg`. Every frame
+    /// must be whole and the same size, so deltas must be off.
+    #[cfg(feature = "fs")]
+    pub fn encode_video(&self, path: &str, fps: f64) -> io::Result<()> {
+        assert!(fps > 0.0, "Fps must be positive");
+        if self.deltas {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Can't encode a video from delta frames",
+            ));
+        }
+        let _span = Span::new("encode video");
+        let first = PPMImg::read(&self.frame_path(0))?;
+        let (w, h) = (first.width(), first.height());
+        let input = format!("-f rawvideo -pix_fmt rgb24 -s {}x{} -r {} -i -", w, h, fps);
+        // most players want yuv420p, which needs even sizes
+        let output = "-vf pad=ceil(iw/2)*2:ceil(ih/2)*2 -pix_fmt yuv420p";
+        let mut child = Command::new(&self.ffmpeg)
+            .args(["-y", "-loglevel", "error"])
+            .args(input.split(' '))
+            .args(output.split(' '))
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "{} not found: install ffmpeg and put it on the path to encode videos",
+                        self.ffmpeg
+                    ),
+                ),
+                _ => e,
+            })?;
+
+        // read while frames are written, or ffmpeg could stop on a full pipe
+        // with frames still to come
+        let mut stderr = child.stderr.take().unwrap();
+        let errors = thread::spawn(move || {
+            let mut errors = String::new();
+            let _ = stderr.read_to_string(&mut errors);
+            errors
+        });
+        let mut stdin = child.stdin.take().unwrap();
+        let mut progress = ProgressTracker::new(self.on_progress.as_ref(), self.frames);
+        let mut sent = Ok(());
+        for frame in 0..self.frames {
+            if is_cancelled(&self.cancel) {
+                sent = Err(cancelled());
+                break;
+            }
+            let img = match frame {
+                0 => first.clone(),
+                _ => match PPMImg::read(&self.frame_path(frame)) {
+                    Ok(img) => img,
+                    Err(e) => {
+                        sent = Err(e);
+                        break;
+                    }
+                },
+            };
+            if img.width() != w || img.height() != h {
+                sent = Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Frame {} isn't the size of the first one", frame),
+                ));
+                break;
+            }
+            let rgb: Vec<u8> = img
+                .to_rgba()
+                .chunks(4)
+                .flat_map(|p| p[..3].to_vec())
+                .collect();
+            // a broken pipe means ffmpeg gave up; its own error says why
+            if let Err(e) = stdin.write_all(&rgb) {
+                sent = Err(e);
+                break;
+            }
+            progress.step();
+        }
+        // ffmpeg finishes the video once its input ends
+        drop(stdin);
+
+        let status = child.wait()?;
+        let errors = errors.join().unwrap_or_default();
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "ffmpeg failed: {}",
+                errors.trim()
+            )));
+        }
+        sent
+    }
+
+    #[cfg(feature = "fs")]
+    fn accum_buffer(&self, img: &PPMImg) -> Option<AccumBuffer> {
+        if self.motion_blur > 1 {
+            Some(AccumBuffer::new(img.height(), img.width()))
+        } else {
+            None
+        }
+    }
+
+    /// Clears img and draws `frame` into it, with motion blur if `accum` is given,
+    /// and the HUD if it is on
+    #[cfg(feature = "fs")]
+    fn draw_frame<F>(
+        &self,
+        img: &mut PPMImg,
+        accum: Option<&mut AccumBuffer>,
+        frame: usize,
+        draw: &mut F,
+    ) where
+        F: FnMut(&mut PPMImg, usize, f64),
+    {
+        let _span = Span::new("draw frame");
+        let (start, before) = (Instant::now(), img.stats());
+        match accum {
+            None => {
+                img.clear();
+                draw(img, frame, self.time(frame));
+            }
+            Some(accum) => {
+                accum.reset();
+                let step = self.shutter / self.fps / self.motion_blur as f64;
+                for s in 0..self.motion_blur {
+                    img.clear();
+                    draw(img, frame, self.time(frame) + s as f64 * step);
+                    accum.add(img);
+                }
+                accum.resolve(img);
+            }
+        }
+        if self.hud {
+            let time = self.time(frame);
+            let camera = self.hud_camera.as_ref().map(|camera| camera(time));
+            img.draw_hud(&Hud {
+                frame,
+                time,
+                render_time: (!self.deterministic).then(|| start.elapsed()),
+                stats: img.stats() - before,
+                camera: camera.as_ref(),
+            });
+        }
+    }
+
+    /// Writes `frame`, as a delta against `prev` if there is one
+    ///
+    /// Frames must be written in order; `prev` starts out None.
+    #[cfg(feature = "fs")]
+    fn write_frame(&self, img: &PPMImg, frame: usize, prev: &mut Option<PPMImg>) -> io::Result<()> {
+        let _span = Span::new("write frame");
+        match prev.as_mut() {
+            Some(prev) => {
+                let changed = img.changed_since(prev);
+                let mut file = create_file(&self.delta_path(frame));
+                img.encode_region(changed, &mut file)?;
+                file.flush()?;
+                if let Some(r) = changed {
+                    prev.copy_region_from(img, r);
+                }
+            }
+            None => {
+                img.write_binary(&self.frame_path(frame))?;
+                if self.deltas {
+                    *prev = Some(img.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "fs")]
+fn cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "Render cancelled")
+}
+
+/// Tells the writer to stop when a worker panics, instead of waiting for its frame
+#[cfg(feature = "fs")]
+struct PanicGuard<'a, T>(&'a mpsc::SyncSender<Option<T>>);
+
+#[cfg(feature = "fs")]
+impl<T> Drop for PanicGuard<'_, T> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            let _ = self.0.send(None);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "fs"))]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn parallel_frames_match_serial() {
+        let dir = std::env::temp_dir().join(format!("w2_matrix_anim_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let base = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        let draw = |img: &mut PPMImg, frame: usize, _: f64| img.fill_rect(frame as i32, 1, 2, 2);
+
+        let mut serial = Animation::new(5, 10.0, &base("serial"));
+        serial.deltas = true;
+        serial.render(&mut PPMImg::new(4, 8, 255), draw).unwrap();
+        let mut parallel = Animation::new(5, 10.0, &base("parallel"));
+        parallel.deltas = true;
+        parallel
+            .render_parallel(&PPMImg::new(4, 8, 255), 3, draw)
+            .unwrap();
+
+        let read = |path: String| fs::read(path).unwrap();
+        assert_eq!(read(serial.frame_path(0)), read(parallel.frame_path(0)));
+        for frame in 1..5 {
+            let delta = read(parallel.delta_path(frame));
+            assert_eq!(read(serial.delta_path(frame)), delta);
+            // each frame moves the square one pixel right
+            assert!(delta.starts_with(format!("P6\n# offset {} 1\n", frame - 1).as_bytes()));
+        }
+
+        // cancelling part way stops every worker
+        let token = CancelToken::new();
+        let mut cancelled = Animation::new(50, 10.0, &base("cancelled"));
+        cancelled.cancel = Some(token.clone());
+        let draw = |_: &mut PPMImg, frame: usize, _: f64| {
+            if frame == 10 {
+                token.cancel();
+            }
+        };
+        let err = cancelled
+            .render_parallel(&PPMImg::new(4, 8, 255), 3, draw)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn deterministic_frames_only_depend_on_their_number() {
+        let dir = std::env::temp_dir().join(format!("w2_matrix_det_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let base = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        // leaves the color it picked for the frames after
+        let draw = |img: &mut PPMImg, frame: usize, _: f64| {
+            if frame == 0 {
+                img.fg_color = img.bg_color;
+            }
+            img.fill_rect(70, 30, 2, 2);
+        };
+
+        let mut serial = Animation::new(6, 10.0, &base("serial"));
+        serial.deterministic = true;
+        serial.hud = true;
+        serial.render(&mut PPMImg::new(40, 80, 255), draw).unwrap();
+        let mut parallel = Animation::new(6, 10.0, &base("parallel"));
+        parallel.deterministic = true;
+        parallel.hud = true;
+        parallel
+            .render_parallel(&PPMImg::new(40, 80, 255), 3, draw)
+            .unwrap();
+
+        for frame in 0..6 {
+            let img = PPMImg::read(&serial.frame_path(frame)).unwrap();
+            let other = PPMImg::read(&parallel.frame_path(frame)).unwrap();
+            assert_eq!(img.hash(), other.hash());
+            // every frame starts out with the image's own fg_color
+            assert_eq!(img.pixel(70, 30).red > 0, frame > 0);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn video_needs_whole_frames() {
+        let mut anim = Animation::new(2, 10.0, "no_such_dir/frame");
+        let err = anim.encode_video("out.mp4", 10.0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        anim.deltas = true;
+        let err = anim.encode_video("out.mp4", 10.0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn video_needs_ffmpeg() {
+        let dir = std::env::temp_dir().join(format!("w2_matrix_video_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let base = dir.join("frame").to_str().unwrap().to_string();
+        let mut anim = Animation::new(2, 10.0, &base);
+        anim.render(&mut PPMImg::new(4, 4, 255), |_, _, _| ())
+            .unwrap();
+        anim.ffmpeg = dir.join("no_such_ffmpeg").to_str().unwrap().to_string();
+        let err = anim.encode_video(&base, 10.0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("install ffmpeg"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
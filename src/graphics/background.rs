@@ -0,0 +1,160 @@
+use super::camera::Camera;
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+use super::utils::{dot3, normalize3};
+use super::{PPMImg, RGB};
+use alloc::boxed::Box;
+use core::f64::consts::PI;
+#[cfg(feature = "fs")]
+use std::io;
+
+/// What `PPMImg::clear` fills the image with, instead of a solid bg_color
+#[allow(dead_code)]
+#[derive(Clone)]
+pub enum Background {
+    /// Picture stretched to cover the whole image
+    Image(Box<PPMImg>),
+    /// Vertical gradient from `top` at the first row to `bottom` at the last
+    Gradient { top: RGB, bottom: RGB },
+}
+
+#[allow(dead_code)]
+impl Background {
+    /// Background image read from a ppm file
+    #[cfg(feature = "fs")]
+    pub fn from_file(path: &str) -> io::Result<Background> {
+        Ok(Background::Image(Box::new(PPMImg::read(path)?)))
+    }
+
+    /// Color at (x, y) of a `width` by `height` image of max value `depth`
+    pub fn color_at(&self, x: u32, y: u32, width: u32, height: u32, depth: u16) -> RGB {
+        match self {
+            Background::Image(img) => {
+                // nearest pixel of the picture, which may be any size
+                let sx = u64::from(x) * u64::from(img.width()) / u64::from(width);
+                let sy = u64::from(y) * u64::from(img.height()) / u64::from(height);
+                let c = img.pixel(sx as u32, sy as u32).to_fractions(img.depth());
+                RGB::from_fractions(c, depth)
+            }
+            Background::Gradient { top, bottom } => {
+                let t = (f64::from(y) + 0.5) / f64::from(height);
+                let lerp = |a: u16, b: u16| {
+                    (f64::from(a) + (f64::from(b) - f64::from(a)) * t).round() as u16
+                };
+                RGB {
+                    red: lerp(top.red, bottom.red),
+                    green: lerp(top.green, bottom.green),
+                    blue: lerp(top.blue, bottom.blue),
+                }
+            }
+        }
+    }
+}
+
+/// What a 3D scene shows in directions where there is no geometry
+///
+/// Colors are channel intensities in [0, 1], like light colors
+#[allow(dead_code)]
+#[derive(Clone)]
+pub enum Sky {
+    /// Blends from `horizon` up to `zenith`, and from `horizon` down to `ground`
+    Gradient {
+        zenith: [f64; 3],
+        horizon: [f64; 3],
+        ground: [f64; 3],
+    },
+    /// Equirectangular panorama: x goes around the y axis, y from straight up
+    /// at the top row to straight down at the bottom
+    Panorama(PPMImg),
+}
+
+#[allow(dead_code)]
+impl Sky {
+    /// Color seen looking in direction `dir`
+    pub fn sample(&self, dir: [f64; 3]) -> [f64; 3] {
+        let d = normalize3(dir);
+        match self {
+            Sky::Gradient {
+                zenith,
+                horizon,
+                ground,
+            } => {
+                let (to, t) = if d[1] >= 0.0 {
+                    (zenith, d[1])
+                } else {
+                    (ground, -d[1])
+                };
+                let mut c = [0.0; 3];
+                for (i, v) in c.iter_mut().enumerate() {
+                    *v = horizon[i] + (to[i] - horizon[i]) * t;
+                }
+                c
+            }
+            Sky::Panorama(img) => {
+                let u = 0.5 + d[0].atan2(d[2]) / (2.0 * PI);
+                let v = d[1].clamp(-1.0, 1.0).acos() / PI;
+                let x = ((u * f64::from(img.width())) as u32).min(img.width() - 1);
+                let y = ((v * f64::from(img.height())) as u32).min(img.height() - 1);
+                img.pixel(x, y).to_fractions(img.depth())
+            }
+        }
+    }
+
+    /// Color mirrored by a surface of unit normal `n` seen looking along `view`,
+    /// for environment mapped reflections
+    ///
+    /// The sky stands in for everything around, as if it were infinitely far
+    /// away, so nothing else in the scene shows up in the reflection.
+    pub fn reflection(&self, view: [f64; 3], n: [f64; 3]) -> [f64; 3] {
+        let k = 2.0 * dot3(view, n);
+        self.sample([0, 1, 2].map(|i| view[i] - k * n[i]))
+    }
+
+    /// Fills every pixel of `img` with the sky as seen by `camera`
+    pub fn render(&self, img: &mut PPMImg, camera: &Camera) {
+        let (w, h) = (f64::from(img.width()), f64::from(img.height()));
+        let depth = img.depth();
+        for y in 0..img.height() {
+            for x in 0..img.width() {
+                let (_, dir) = camera.ray(f64::from(x) + 0.5, f64::from(y) + 0.5, w, h);
+                img.set_pixel(x, y, RGB::from_fractions(self.sample(dir), depth));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clear_fills_gradient() {
+        let mut img = PPMImg::new(4, 2, 255);
+        let black = img.bg_color;
+        img.background = Some(Background::Gradient {
+            top: black,
+            bottom: img.fg_color,
+        });
+        img.clear();
+        let rows: Vec<u16> = (0..4).map(|y| img.pixel(1, y).red).collect();
+        assert_eq!(rows, vec![32, 96, 159, 223]);
+    }
+
+    #[test]
+    fn sky_gradient_by_direction() {
+        let sky = Sky::Gradient {
+            zenith: [0.0, 0.0, 1.0],
+            horizon: [1.0, 1.0, 1.0],
+            ground: [0.0, 1.0, 0.0],
+        };
+        assert_eq!(sky.sample([0.0, 5.0, 0.0]), [0.0, 0.0, 1.0]);
+        assert_eq!(sky.sample([1.0, 0.0, 0.0]), [1.0, 1.0, 1.0]);
+        assert_eq!(sky.sample([0.0, -1.0, 0.0]), [0.0, 1.0, 0.0]);
+        // looking down at a mirror floor shows the zenith
+        let up = [0.0, 1.0, 0.0];
+        assert_eq!(
+            sky.reflection([0.0, -1.0, 1.0], up),
+            sky.sample([0.0, 1.0, 1.0])
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use super::rect::Rect;
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+use super::{PPMImg, RGB};
+
+/// How `PPMImg::blit_with` pastes an image
+#[derive(Copy, Clone)]
+pub struct Blit {
+    /// Source pixels of this color are left out, like the background around a
+    /// sprite
+    pub key: Option<RGB>,
+    /// How much the source covers what's under it, from 0 to 1
+    pub opacity: f64,
+    /// Width and height of a source pixel in the destination, nearest pixel
+    pub scale: f64,
+}
+
+#[allow(dead_code)]
+impl Blit {
+    /// Plain copy: no color key, opaque, unscaled
+    pub fn new() -> Blit {
+        Blit {
+            key: None,
+            opacity: 1.0,
+            scale: 1.0,
+        }
+    }
+
+    pub fn with_key(self, key: RGB) -> Blit {
+        Blit {
+            key: Some(key),
+            ..self
+        }
+    }
+
+    pub fn with_opacity(self, opacity: f64) -> Blit {
+        assert!(
+            (0.0..=1.0).contains(&opacity),
+            "Opacity must be between 0 and 1"
+        );
+        Blit { opacity, ..self }
+    }
+
+    pub fn with_scale(self, scale: f64) -> Blit {
+        assert!(scale > 0.0, "Scale must be positive");
+        Blit { scale, ..self }
+    }
+}
+
+impl Default for Blit {
+    fn default() -> Blit {
+        Blit::new()
+    }
+}
+
+// blitting
+#[allow(dead_code)]
+impl PPMImg {
+    /// Copies `src` with its top left corner at (dst_x, dst_y), rows counted from
+    /// the top; see `blit_with`
+    pub fn blit(&mut self, src: &PPMImg, dst_x: i32, dst_y: i32) {
+        self.blit_with(src, dst_x, dst_y, &Blit::new());
+    }
+
+    /// Pastes `src` with its top left corner at (dst_x, dst_y), rows counted from
+    /// the top, like sprites, legends or earlier frames
+    ///
+    /// Colors are converted to the image's depth. Parts past the edges or outside
+    /// the clip mask are left out.
+    pub fn blit_with(&mut self, src: &PPMImg, dst_x: i32, dst_y: i32, how: &Blit) {
+        let w = (f64::from(src.width) * how.scale).round() as u32;
+        let h = (f64::from(src.height) * how.scale).round() as u32;
+        let from = Rect::new(0, 0, src.width, src.height);
+        self.paste(src, from, Rect::new(dst_x, dst_y, w, h), how);
+    }
+
+    /// Pastes `src` stretched over `dst` as a panel: `center`, the part of src
+    /// between its corners, is stretched to fill the middle, the edges around it
+    /// only along their length, and the corners are left as they are
+    ///
+    /// dst must be at least as big as the corners. Follows how's color key and
+    /// opacity; its scale is ignored.
+    pub fn blit_nine_slice(&mut self, src: &PPMImg, center: Rect, dst: Rect, how: &Blit) {
+        let whole = Rect::new(0, 0, src.width, src.height);
+        assert!(
+            center.intersect(&whole) == Some(center),
+            "Center must be inside the image"
+        );
+        let (left, top) = (center.x as u32, center.y as u32);
+        let (right, bottom) = (src.width - left - center.w, src.height - top - center.h);
+        assert!(
+            dst.w >= left + right && dst.h >= top + bottom,
+            "Panel must be at least as big as its corners"
+        );
+        // start and length of the three columns and rows, in src and in dst
+        let spans = |start: i32, lo: u32, mid: u32, hi: u32| {
+            [
+                (start, lo),
+                (start + lo as i32, mid),
+                (start + (lo + mid) as i32, hi),
+            ]
+        };
+        let src_columns = spans(0, left, center.w, right);
+        let src_rows = spans(0, top, center.h, bottom);
+        let dst_columns = spans(dst.x, left, dst.w - left - right, right);
+        let dst_rows = spans(dst.y, top, dst.h - top - bottom, bottom);
+        for (&(sy, sh), &(dy, dh)) in src_rows.iter().zip(dst_rows.iter()) {
+            for (&(sx, sw), &(dx, dw)) in src_columns.iter().zip(dst_columns.iter()) {
+                if sw > 0 && sh > 0 {
+                    let (from, to) = (Rect::new(sx, sy, sw, sh), Rect::new(dx, dy, dw, dh));
+                    self.paste(src, from, to, how);
+                }
+            }
+        }
+    }
+
+    /// Pastes the `from` part of `src` stretched over `to`, nearest pixel
+    fn paste(&mut self, src: &PPMImg, from: Rect, to: Rect, how: &Blit) {
+        let screen = Rect::new(0, 0, self.width, self.height);
+        let area = match to.intersect(&screen) {
+            Some(r) if !to.is_empty() => r,
+            _ => return,
+        };
+        let (sx_scale, sy_scale) = (
+            f64::from(from.w) / f64::from(to.w),
+            f64::from(from.h) / f64::from(to.h),
+        );
+        let (src_depth, depth) = (src.depth, self.depth);
+        for y in area.y..=area.bottom() {
+            let sy = ((f64::from(y - to.y) + 0.5) * sy_scale) as u32;
+            let sy = from.y as u32 + sy.min(from.h - 1);
+            for x in area.x..=area.right() {
+                let sx = ((f64::from(x - to.x) + 0.5) * sx_scale) as u32;
+                let c = src.pixel(from.x as u32 + sx.min(from.w - 1), sy);
+                if how.key == Some(c) || self.is_clipped(x, y) {
+                    continue;
+                }
+                let i = self.index(x as u32, y as u32);
+                let mut color = c.to_fractions(src_depth);
+                if how.opacity < 1.0 {
+                    let under = self.data[i].to_fractions(depth);
+                    for (c, u) in color.iter_mut().zip(under.iter()) {
+                        *c = u + (*c - u) * how.opacity;
+                    }
+                }
+                self.data[i] = RGB::from_fractions(color, depth);
+                self.stats.pixels_plotted += 1;
+            }
+        }
+        self.mark_dirty(area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blits_key_blend_and_scale() {
+        let mut sprite = PPMImg::new(2, 2, 255);
+        sprite.fill_rect(0, 0, 1, 1);
+        let black = sprite.pixel(1, 1);
+
+        let mut img = PPMImg::new(8, 8, 65535);
+        img.bg_color = RGB::from_fractions([0.0, 0.0, 1.0], 65535);
+        img.clear();
+        img.blit(&sprite, 1, 1);
+        assert_eq!(img.pixel(1, 1).red, 65535);
+        assert_eq!(img.pixel(2, 2).blue, 0);
+
+        img.clear();
+        img.blit_with(&sprite, 4, 4, &Blit::new().with_key(black).with_scale(2.0));
+        assert!(img.pixel(4, 4).red == 65535 && img.pixel(5, 5).red == 65535);
+        assert_eq!(img.pixel(6, 6).blue, 65535);
+
+        img.clear();
+        img.blit_with(&sprite, -1, -1, &Blit::new().with_opacity(0.5));
+        let c = img.pixel(0, 0).to_fractions(65535);
+        assert!(c[0].abs() < 1e-3 && (c[2] - 0.5).abs() < 1e-3);
+        // only the corner that's on the image is pasted
+        assert_eq!(img.pixel(1, 0).blue, 65535);
+    }
+
+    #[test]
+    fn nine_slice_keeps_corners() {
+        // a frame one pixel wide around a dark middle
+        let mut panel = PPMImg::new(3, 3, 255);
+        panel.fill_rect(0, 0, 3, 3);
+        panel.set_pixel(1, 1, RGB::from_fractions([0.0; 3], 255));
+        let mut img = PPMImg::new(6, 10, 255);
+        let center = Rect::new(1, 1, 1, 1);
+        img.blit_nine_slice(&panel, center, Rect::new(1, 1, 8, 4), &Blit::new());
+        let lit = |x, y| img.pixel(x, y).red > 0;
+        assert!((1..9).all(|x| lit(x, 1) && lit(x, 4)));
+        assert!((1..5).all(|y| lit(1, y) && lit(8, y)));
+        assert!((2..8).all(|x| !lit(x, 2) && !lit(x, 3)));
+        assert!(!lit(0, 0) && !lit(9, 5));
+    }
+}
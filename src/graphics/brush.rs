@@ -0,0 +1,138 @@
+use super::PPMImg;
+use alloc::vec::Vec;
+
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+
+/// Small stamp drawn instead of a single pixel, for calligraphy-like or textured strokes
+///
+/// Set as `PPMImg::brush` to have `plot` stamp it centered on the point, and
+/// lines stamp it every `spacing` pixels along their length. The stamp is a
+/// coverage mask: fg_color is blended in by how much each pixel is covered, from
+/// 0 (untouched) to 1 (fully painted).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Brush {
+    width: u32,
+    height: u32,
+    coverage: Vec<f64>,
+    /// Distance in pixels between stamps along a line, see `with_spacing`
+    spacing: f64,
+}
+
+#[allow(dead_code)]
+impl Brush {
+    /// Brush from row major coverage values in [0, 1], stamped every pixel along lines
+    pub fn new(width: u32, height: u32, coverage: Vec<f64>) -> Brush {
+        assert_eq!(
+            (width * height) as usize,
+            coverage.len(),
+            "width * height must == coverage.len()"
+        );
+        Brush {
+            width,
+            height,
+            coverage,
+            spacing: 1.0,
+        }
+    }
+
+    /// Brush shaped like `img`, using how bright each pixel is as coverage
+    ///
+    /// Paint a stamp in white on black to make one.
+    pub fn from_image(img: &PPMImg) -> Brush {
+        let mut coverage = Vec::with_capacity((img.width() * img.height()) as usize);
+        for y in 0..img.height() {
+            for x in 0..img.width() {
+                let [r, g, b] = img.pixel(x, y).to_fractions(img.depth());
+                coverage.push((r + g + b) / 3.0);
+            }
+        }
+        Brush::new(img.width(), img.height(), coverage)
+    }
+
+    /// Disc of radius `radius`, with edges smoothed over one pixel
+    pub fn round(radius: f64) -> Brush {
+        assert!(radius > 0.0, "Radius must be positive");
+        let size = 2 * radius.ceil() as u32 + 1;
+        let c = f64::from(size / 2);
+        let mut coverage = Vec::with_capacity((size * size) as usize);
+        for y in 0..size {
+            for x in 0..size {
+                let (dx, dy) = (f64::from(x) - c, f64::from(y) - c);
+                let d = (dx * dx + dy * dy).sqrt();
+                coverage.push((radius + 0.5 - d).clamp(0.0, 1.0));
+            }
+        }
+        Brush::new(size, size, coverage)
+    }
+
+    /// Thin flat nib `length` pixels long, at `angle_deg` counter clockwise from the x axis
+    ///
+    /// Strokes come out thick or thin depending on their direction, like a calligraphy pen.
+    pub fn nib(length: f64, angle_deg: f64) -> Brush {
+        assert!(length > 0.0, "Length must be positive");
+        let size = length.ceil() as u32 | 1;
+        let c = f64::from(size / 2);
+        let (sin, cos) = angle_deg.to_radians().sin_cos();
+        let mut coverage = Vec::with_capacity((size * size) as usize);
+        for y in 0..size {
+            for x in 0..size {
+                // y points down, so flip it to keep the angle counter clockwise
+                let (dx, dy) = (f64::from(x) - c, c - f64::from(y));
+                let along = dx * cos + dy * sin;
+                let across = -dx * sin + dy * cos;
+                let inside = along.abs() <= length / 2.0 && across.abs() <= 0.5;
+                coverage.push(if inside { 1.0 } else { 0.0 });
+            }
+        }
+        Brush::new(size, size, coverage)
+    }
+
+    /// Same brush, stamped every `spacing` pixels along lines
+    pub fn with_spacing(self, spacing: f64) -> Brush {
+        assert!(spacing > 0.0, "Spacing must be positive");
+        Brush { spacing, ..self }
+    }
+
+    /// Distance in pixels between stamps along a line
+    pub fn spacing(&self) -> f64 {
+        self.spacing
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Coverage of pixel (x, y) of the stamp, in [0, 1]
+    pub fn coverage(&self, x: u32, y: u32) -> f64 {
+        assert!(x < self.width && y < self.height, "Index out of bound");
+        self.coverage[(y * self.width + x) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strokes_are_stamped_with_spacing() {
+        let mut img = PPMImg::new(9, 30, 255);
+        img.brush = Some(Brush::round(1.0).with_spacing(10.0));
+        img.draw_line(2.0, 4.0, 22.0, 4.0);
+        let lit = |img: &PPMImg, x: u32, y: u32| img.pixel(x, y).red > 0;
+        for &x in [2, 12, 22].iter() {
+            assert!(lit(&img, x, 3) && lit(&img, x, 5), "no stamp at x = {}", x);
+        }
+        assert!(!lit(&img, 7, 4) && !lit(&img, 17, 4));
+
+        let mut img = PPMImg::new(9, 9, 255);
+        img.brush = Some(Brush::nib(5.0, 90.0));
+        img.plot(4, 4);
+        assert!((2..=6).all(|y| lit(&img, 4, y)));
+        assert!(!lit(&img, 3, 4) && !lit(&img, 5, 4));
+    }
+}
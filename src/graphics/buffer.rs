@@ -0,0 +1,50 @@
+use super::rect::Rect;
+use super::PPMImg;
+
+/// A pair of images: draw into the back buffer, then `swap()` to present it
+///
+/// After a swap the back buffer holds a copy of what is now in front, so drawing can
+/// continue incrementally. Only the region changed since the last swap is copied.
+pub struct DoubleBuffer {
+    front: PPMImg,
+    back: PPMImg,
+}
+
+#[allow(dead_code)]
+impl DoubleBuffer {
+    pub fn new(height: u32, width: u32, depth: u16) -> DoubleBuffer {
+        DoubleBuffer::from_img(PPMImg::new(height, width, depth))
+    }
+
+    /// Use `img` as the initial content of both buffers
+    pub fn from_img(mut img: PPMImg) -> DoubleBuffer {
+        img.take_dirty();
+        DoubleBuffer {
+            front: img.clone(),
+            back: img,
+        }
+    }
+
+    /// The presented image
+    pub fn front(&self) -> &PPMImg {
+        &self.front
+    }
+
+    /// The image being drawn
+    pub fn back(&mut self) -> &mut PPMImg {
+        &mut self.back
+    }
+
+    /// Present the back buffer
+    ///
+    /// Returns the region that differs from the previously presented image,
+    /// or None if nothing was drawn since the last swap.
+    pub fn swap(&mut self) -> Option<Rect> {
+        let dirty = self.back.take_dirty();
+        core::mem::swap(&mut self.front, &mut self.back);
+        if let Some(r) = dirty {
+            self.back.copy_region_from(&self.front, r);
+        }
+        dirty
+    }
+}
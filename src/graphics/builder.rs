@@ -0,0 +1,155 @@
+use super::background::Background;
+use super::wrap::WrapMode;
+use super::{PPMImg, YAxis, RGB};
+
+/// Settings for a new image, from `PPMImg::builder`
+///
+/// Anything left unset is as `PPMImg::new` has it. The image is cleared to its
+/// background once built, so a bg color or background shows right away.
+#[derive(Clone)]
+pub struct ImageBuilder {
+    width: u32,
+    height: u32,
+    depth: u16,
+    fg: Option<RGB>,
+    bg: Option<RGB>,
+    background: Option<Background>,
+    x_wrap: WrapMode,
+    y_wrap: WrapMode,
+    y_axis: YAxis,
+    subpixel: bool,
+}
+
+#[allow(dead_code)]
+impl ImageBuilder {
+    /// Width and height in pixels; note the order is the other way around from
+    /// `PPMImg::new`
+    pub fn size(self, width: u32, height: u32) -> ImageBuilder {
+        ImageBuilder {
+            width,
+            height,
+            ..self
+        }
+    }
+
+    /// Max value of a color channel; 255 by default
+    pub fn depth(self, depth: u16) -> ImageBuilder {
+        ImageBuilder { depth, ..self }
+    }
+
+    /// fg_color; white by default
+    pub fn fg(self, color: RGB) -> ImageBuilder {
+        ImageBuilder {
+            fg: Some(color),
+            ..self
+        }
+    }
+
+    /// bg_color; black by default
+    pub fn bg(self, color: RGB) -> ImageBuilder {
+        ImageBuilder {
+            bg: Some(color),
+            ..self
+        }
+    }
+
+    pub fn background(self, background: Background) -> ImageBuilder {
+        ImageBuilder {
+            background: Some(background),
+            ..self
+        }
+    }
+
+    /// Sets both x_wrap and y_wrap
+    pub fn wrap(self, mode: WrapMode) -> ImageBuilder {
+        ImageBuilder {
+            x_wrap: mode,
+            y_wrap: mode,
+            ..self
+        }
+    }
+
+    pub fn x_wrap(self, mode: WrapMode) -> ImageBuilder {
+        ImageBuilder {
+            x_wrap: mode,
+            ..self
+        }
+    }
+
+    pub fn y_wrap(self, mode: WrapMode) -> ImageBuilder {
+        ImageBuilder {
+            y_wrap: mode,
+            ..self
+        }
+    }
+
+    pub fn y_axis(self, y_axis: YAxis) -> ImageBuilder {
+        ImageBuilder { y_axis, ..self }
+    }
+
+    pub fn subpixel(self, subpixel: bool) -> ImageBuilder {
+        ImageBuilder { subpixel, ..self }
+    }
+
+    pub fn build(self) -> PPMImg {
+        assert!(
+            self.width > 0 && self.height > 0,
+            "Image size must be set with size"
+        );
+        let mut img = PPMImg::new(self.height, self.width, self.depth);
+        img.fg_color = self.fg.unwrap_or(img.fg_color);
+        img.bg_color = self.bg.unwrap_or(img.bg_color);
+        img.background = self.background;
+        img.x_wrap = self.x_wrap;
+        img.y_wrap = self.y_wrap;
+        img.y_axis = self.y_axis;
+        img.subpixel = self.subpixel;
+        img.clear();
+        img.take_dirty();
+        img
+    }
+}
+
+// builder
+#[allow(dead_code)]
+impl PPMImg {
+    /// Starts an image set up step by step, like
+    /// `PPMImg::builder().size(500, 500).bg(sky).wrap(WrapMode::Wrap).build()`;
+    /// the size must be given
+    pub fn builder() -> ImageBuilder {
+        ImageBuilder {
+            width: 0,
+            height: 0,
+            depth: 255,
+            fg: None,
+            bg: None,
+            background: None,
+            x_wrap: WrapMode::Discard,
+            y_wrap: WrapMode::Discard,
+            y_axis: YAxis::Down,
+            subpixel: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_sets_up_the_image() {
+        let blue = RGB::from_fractions([0.0, 0.0, 1.0], 1023);
+        let img = PPMImg::builder()
+            .size(30, 20)
+            .depth(1023)
+            .bg(blue)
+            .wrap(WrapMode::Wrap)
+            .build();
+        assert_eq!((img.width(), img.height(), img.depth()), (30, 20, 1023));
+        assert_eq!(img.x_wrap, WrapMode::Wrap);
+        assert_eq!(img.y_wrap, WrapMode::Wrap);
+        assert_eq!(img.pixel(29, 19).blue, 1023);
+        assert_eq!(img.fg_color.red, 1023);
+        assert_eq!(img.dirty(), None);
+    }
+}
@@ -0,0 +1,304 @@
+use super::matrix::{Bounds, PolygonMatrix};
+use super::utils::{cross3, dot3, sub3, xyz};
+
+/// Determinants smaller than this mean the ray is parallel to the triangle,
+/// and hits closer than this are ignored
+const EPSILON: f64 = 1e-9;
+
+/// Max number of triangles in a leaf
+const LEAF_SIZE: usize = 4;
+
+/// Intersection of a ray with a triangle in a `Bvh`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RayHit {
+    /// Ray parameter of the hit: the point is `origin + t * dir`
+    pub t: f64,
+    /// Index of the triangle: rows `3 * triangle` to `3 * triangle + 2` of the polygon matrix
+    pub triangle: usize,
+    /// Barycentric coordinates of the hit, weights of the second and third points
+    pub u: f64,
+    pub v: f64,
+}
+
+/// Möller-Trumbore ray/triangle intersection
+///
+/// Returns (t, u, v) if the ray hits the triangle with t in (EPSILON, t_max).
+/// Both sides of the triangle are hit.
+pub fn intersect_triangle(
+    origin: [f64; 3],
+    dir: [f64; 3],
+    tri: &[[f64; 3]; 3],
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    let (e1, e2) = (sub3(tri[1], tri[0]), sub3(tri[2], tri[0]));
+    let pv = cross3(dir, e2);
+    let det = dot3(e1, pv);
+    if det.abs() < EPSILON {
+        return None;
+    }
+    let tv = sub3(origin, tri[0]);
+    let u = dot3(tv, pv) / det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let qv = cross3(tv, e1);
+    let v = dot3(dir, qv) / det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = dot3(e2, qv) / det;
+    if t > EPSILON && t < t_max {
+        Some((t, u, v))
+    } else {
+        None
+    }
+}
+
+/// Slab test: whether the ray hits `b` before t_max
+fn hits_bounds(b: &Bounds, origin: [f64; 3], inv_dir: [f64; 3], t_max: f64) -> bool {
+    let (mut t0, mut t1) = (0.0, t_max);
+    for axis in 0..3 {
+        let ta = (b.min[axis] - origin[axis]) * inv_dir[axis];
+        let tb = (b.max[axis] - origin[axis]) * inv_dir[axis];
+        // NaN when the ray lies in the slab's plane; max/min skip it
+        t0 = ta.min(tb).max(t0);
+        t1 = ta.max(tb).min(t1);
+        if t0 > t1 {
+            return false;
+        }
+    }
+    true
+}
+
+enum BvhNode {
+    /// Triangles `order[start..start + count]`
+    Leaf {
+        bounds: Bounds,
+        start: usize,
+        count: usize,
+    },
+    /// Children are indices into `Bvh::nodes`
+    Inner {
+        bounds: Bounds,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> &Bounds {
+        match self {
+            BvhNode::Leaf { bounds, .. } | BvhNode::Inner { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// Bounding volume hierarchy over the triangles of a polygon matrix
+///
+/// Finds the closest triangle along a ray without testing every triangle.
+/// The tree is built once; rebuild it if the triangles move.
+pub struct Bvh {
+    triangles: Vec<[[f64; 3]; 3]>,
+    /// Triangle indices, grouped so every leaf is a contiguous range
+    order: Vec<usize>,
+    nodes: Vec<BvhNode>,
+}
+
+#[allow(dead_code)]
+impl Bvh {
+    /// Builds a tree over the triangles of `m`
+    pub fn new(m: &PolygonMatrix) -> Bvh {
+        assert_eq!(m.rows() % 3, 0, "Number of points must be a multiple of 3");
+        let mut iter = m.iter_by_row().map(xyz);
+        let mut triangles = vec![];
+        while let (Some(p0), Some(p1), Some(p2)) = (iter.next(), iter.next(), iter.next()) {
+            triangles.push([p0, p1, p2]);
+        }
+        Bvh::from_triangles(triangles)
+    }
+
+    /// Builds a tree over `triangles`; hits refer to their index in the vec
+    ///
+    /// Triangles with points that aren't finite are left out, so rays never hit them.
+    pub fn from_triangles(triangles: Vec<[[f64; 3]; 3]>) -> Bvh {
+        let finite = |t: &[[f64; 3]; 3]| t.iter().flatten().all(|v| v.is_finite());
+        let mut bvh = Bvh {
+            order: (0..triangles.len())
+                .filter(|&i| finite(&triangles[i]))
+                .collect(),
+            triangles,
+            nodes: vec![],
+        };
+        if !bvh.order.is_empty() {
+            bvh.build(0, bvh.order.len());
+        }
+        bvh
+    }
+
+    pub fn len(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    /// Bounds of every triangle, or None if there are none
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.nodes.first().map(|n| *n.bounds())
+    }
+
+    fn centroid(&self, i: usize) -> [f64; 3] {
+        let [a, b, c] = self.triangles[i];
+        [
+            (a[0] + b[0] + c[0]) / 3.0,
+            (a[1] + b[1] + c[1]) / 3.0,
+            (a[2] + b[2] + c[2]) / 3.0,
+        ]
+    }
+
+    /// Builds the subtree over `order[start..end]`, returning its node index
+    ///
+    /// Splits at the median centroid along the longest axis
+    fn build(&mut self, start: usize, end: usize) -> usize {
+        let mut bounds = Bounds::point(self.triangles[self.order[start]][0]);
+        let mut centers = Bounds::point(self.centroid(self.order[start]));
+        for &i in &self.order[start..end] {
+            for &p in &self.triangles[i] {
+                bounds.include(p);
+            }
+            centers.include(self.centroid(i));
+        }
+
+        let index = self.nodes.len();
+        let count = end - start;
+        if count <= LEAF_SIZE {
+            self.nodes.push(BvhNode::Leaf {
+                bounds,
+                start,
+                count,
+            });
+            return index;
+        }
+
+        let size = centers.size();
+        let axis = (0..3).max_by(|a, b| size[*a].total_cmp(&size[*b])).unwrap();
+        let mid = start + count / 2;
+        let mut order = std::mem::take(&mut self.order);
+        order[start..end].select_nth_unstable_by(count / 2, |a, b| {
+            self.centroid(*a)[axis].total_cmp(&self.centroid(*b)[axis])
+        });
+        self.order = order;
+
+        // placeholder, filled in once the children exist
+        self.nodes.push(BvhNode::Leaf {
+            bounds,
+            start,
+            count,
+        });
+        let left = self.build(start, mid);
+        let right = self.build(mid, end);
+        self.nodes[index] = BvhNode::Inner {
+            bounds,
+            left,
+            right,
+        };
+        index
+    }
+
+    /// Closest triangle hit by the ray `origin + t * dir` with t < t_max
+    pub fn intersect_ray(&self, origin: [f64; 3], dir: [f64; 3], t_max: f64) -> Option<RayHit> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let inv_dir = [1.0 / dir[0], 1.0 / dir[1], 1.0 / dir[2]];
+        let mut closest: Option<RayHit> = None;
+        let mut stack = vec![0];
+        while let Some(n) = stack.pop() {
+            let t_max = closest.map_or(t_max, |h| h.t);
+            let node = &self.nodes[n];
+            if !hits_bounds(node.bounds(), origin, inv_dir, t_max) {
+                continue;
+            }
+            match node {
+                BvhNode::Leaf { start, count, .. } => {
+                    for &i in &self.order[*start..start + count] {
+                        let t_max = closest.map_or(t_max, |h| h.t);
+                        if let Some((t, u, v)) =
+                            intersect_triangle(origin, dir, &self.triangles[i], t_max)
+                        {
+                            closest = Some(RayHit {
+                                t,
+                                triangle: i,
+                                u,
+                                v,
+                            });
+                        }
+                    }
+                }
+                BvhNode::Inner { left, right, .. } => {
+                    stack.push(*right);
+                    stack.push(*left);
+                }
+            }
+        }
+        closest
+    }
+
+    /// Points of triangle `i`
+    pub fn triangle(&self, i: usize) -> &[[f64; 3]; 3] {
+        &self.triangles[i]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::matrix::Matrix;
+
+    #[test]
+    fn matches_brute_force() {
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_heightmap(10.0, 10.0, |x, z| (x * 0.7).sin() + (z * 1.3).cos(), 12);
+        m.add_sphere([5.0, 3.0, 5.0], 1.5, 8);
+        let bvh = Bvh::new(&m);
+        assert_eq!(bvh.len() * 3, m.rows());
+
+        for i in 0..50 {
+            let f = i as f64;
+            let origin = [f * 0.2, 10.0, 10.0 - f * 0.15];
+            let dir = [(f * 0.37).sin(), -1.0, (f * 0.11).cos() * 0.5];
+            let brute = (0..bvh.len())
+                .filter_map(|t| intersect_triangle(origin, dir, bvh.triangle(t), f64::INFINITY))
+                .map(|(t, _, _)| t)
+                .fold(f64::INFINITY, f64::min);
+            match bvh.intersect_ray(origin, dir, f64::INFINITY) {
+                Some(hit) => assert!((hit.t - brute).abs() < 1e-12, "ray {}", i),
+                None => assert!(brute.is_infinite(), "ray {}", i),
+            }
+        }
+    }
+
+    #[test]
+    fn hit_reports_triangle() {
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_polygon([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+        m.add_polygon([0.0, 0.0, 5.0], [1.0, 0.0, 5.0], [0.0, 1.0, 5.0]);
+        let bvh = Bvh::new(&m);
+        let hit = bvh
+            .intersect_ray([0.25, 0.25, 10.0], [0.0, 0.0, -1.0], f64::INFINITY)
+            .unwrap();
+        assert_eq!((hit.t, hit.triangle), (5.0, 1));
+        assert!(bvh
+            .intersect_ray([0.25, 0.25, 10.0], [0.0, 0.0, -1.0], 4.0)
+            .is_none());
+
+        // a bad vertex only loses its own triangle
+        m.add_polygon([0.0, 0.0, 7.0], [f64::NAN, 0.0, 7.0], [0.0, 1.0, 7.0]);
+        let bvh = Bvh::new(&m);
+        let hit = bvh
+            .intersect_ray([0.25, 0.25, 10.0], [0.0, 0.0, -1.0], f64::INFINITY)
+            .unwrap();
+        assert_eq!((hit.t, hit.triangle), (5.0, 1));
+    }
+}
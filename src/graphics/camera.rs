@@ -0,0 +1,750 @@
+use super::accum::AccumBuffer;
+use super::matrix::{Bounds, EdgeMatrix, Matrix, PolygonMatrix};
+use super::octree::Octree;
+use super::plot::Marker;
+use super::rect::Rect;
+use super::stats::RenderStats;
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+use super::utils::{cross3, dot3, normalize3, sub3, xyz, Span};
+use super::{PPMImg, RGB};
+// targets without atomic pointers, like thumbv6m, have no Arc
+#[cfg(not(target_has_atomic = "ptr"))]
+use alloc::rc::Rc as Shared;
+#[cfg(target_has_atomic = "ptr")]
+use alloc::sync::Arc as Shared;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Perspective camera
+///
+/// In camera space x points right, y up, and z forward (depth), so points in
+/// front of the camera have z > 0. On screen y points down, like image rows.
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub eye: [f64; 3],
+    pub target: [f64; 3],
+    pub up: [f64; 3],
+    /// Vertical field of view, in degrees
+    pub fov_deg: f64,
+    /// Points closer than this are clipped
+    pub near: f64,
+    /// Lens radius for depth of field; 0 is a pinhole camera with everything in focus
+    pub aperture: f64,
+    /// Distance from the eye that stays sharp with depth of field on
+    pub focal_distance: f64,
+    /// Offset of the eye on the lens, set on jittered cameras
+    lens_offset: [f64; 2],
+    /// Moves every vertex, in world space, right before it is projected
+    pub vertex_shader: Option<VertexShader>,
+    /// Fades far away lines towards the background, for depth cues
+    pub fog: Option<Fog>,
+    /// Leaves out triangles facing away, wound clockwise as the camera sees
+    /// them, from the filled renders of a `SceneGraph`; off by default
+    pub cull_backfaces: bool,
+}
+
+/// Depth cueing: lines fade from fg_color towards bg_color with distance
+///
+/// Used by `Camera::render_edges`, `Camera::render_polygons` and the point
+/// cloud renders; points fade like lines do. Nothing fades
+/// closer than `near`, and everything `far` away or farther is fully faded.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Fog {
+    pub near: f64,
+    pub far: f64,
+}
+
+#[allow(dead_code)]
+impl Fog {
+    pub fn new(near: f64, far: f64) -> Fog {
+        assert!(near < far, "Fog must start before it ends");
+        Fog { near, far }
+    }
+
+    /// How faded something at camera space depth `z` is, from 0 (not at all) to 1
+    pub fn amount(&self, z: f64) -> f64 {
+        ((z - self.near) / (self.far - self.near)).clamp(0.0, 1.0)
+    }
+
+    /// `fg` faded by the amount for depth `z`, towards `bg`; colors of max value `depth`
+    pub fn apply(&self, fg: RGB, bg: RGB, z: f64, depth: u16) -> RGB {
+        let t = self.amount(z);
+        let (f, b) = (fg.to_fractions(depth), bg.to_fractions(depth));
+        let mix = |i: usize| f[i] + (b[i] - f[i]) * t;
+        RGB::from_fractions([mix(0), mix(1), mix(2)], depth)
+    }
+}
+
+/// Software vertex shader: a function applied to every vertex before projection
+///
+/// Good for deformations like ripples or twists that change every frame,
+/// without deforming the mesh itself. Used by `Camera::render_edges`,
+/// `Camera::render_polygons` and `SceneGraph::render_shaded`.
+#[derive(Clone)]
+pub struct VertexShader(Shared<dyn Fn([f64; 3]) -> [f64; 3] + Send + Sync>);
+
+#[allow(dead_code)]
+impl VertexShader {
+    pub fn new<F>(f: F) -> VertexShader
+    where
+        F: Fn([f64; 3]) -> [f64; 3] + Send + Sync + 'static,
+    {
+        VertexShader(Shared::new(f))
+    }
+
+    pub fn apply(&self, p: [f64; 3]) -> [f64; 3] {
+        (self.0)(p)
+    }
+}
+
+impl fmt::Debug for VertexShader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "VertexShader")
+    }
+}
+
+#[allow(dead_code)]
+impl Camera {
+    /// Pinhole camera at `eye` looking at `target`, with +y up and a 60 degree fov
+    pub fn new(eye: [f64; 3], target: [f64; 3]) -> Camera {
+        Camera {
+            eye,
+            target,
+            up: [0.0, 1.0, 0.0],
+            fov_deg: 60.0,
+            near: 0.1,
+            aperture: 0.0,
+            focal_distance: dot3(sub3(target, eye), sub3(target, eye)).sqrt(),
+            lens_offset: [0.0; 2],
+            vertex_shader: None,
+            fog: None,
+            cull_backfaces: false,
+        }
+    }
+
+    /// Unit (right, up, forward) vectors of the camera
+    pub fn basis(&self) -> ([f64; 3], [f64; 3], [f64; 3]) {
+        let f = normalize3(sub3(self.target, self.eye));
+        let r = normalize3(cross3(f, self.up));
+        let u = cross3(r, f);
+        (r, u, f)
+    }
+
+    /// Transform from world space to camera space
+    pub fn view_matrix(&self) -> Matrix {
+        let (r, u, f) = self.basis();
+        let e = self.eye;
+        let (tr, tu, tf) = (-dot3(e, r), -dot3(e, u), -dot3(e, f));
+        Matrix::new(
+            4,
+            4,
+            vec![
+                r[0], u[0], f[0], 0.0, //
+                r[1], u[1], f[1], 0.0, //
+                r[2], u[2], f[2], 0.0, //
+                tr, tu, tf, 1.0, //
+            ],
+        )
+    }
+
+    /// Focal length in pixels for an image `height` pixels tall
+    fn focal_px(&self, height: f64) -> f64 {
+        height / 2.0 / (self.fov_deg.to_radians() / 2.0).tan()
+    }
+
+    /// Projects a camera space point onto a `width` by `height` screen
+    ///
+    /// Returns (x, y, depth), or None if the point is closer than `near`
+    pub fn project_camera_point(&self, p: [f64; 3], width: f64, height: f64) -> Option<[f64; 3]> {
+        if p[2] < self.near {
+            return None;
+        }
+        let f = self.focal_px(height);
+        // shift the image of a jittered camera so the focal plane lines up with the center
+        let [ox, oy] = self.lens_offset;
+        let (sx, sy) = (ox / self.focal_distance, oy / self.focal_distance);
+        Some([
+            width / 2.0 + (p[0] / p[2] + sx) * f,
+            height / 2.0 - (p[1] / p[2] + sy) * f,
+            p[2],
+        ])
+    }
+
+    /// Size in pixels across, on a screen `height` pixels tall, of a world space
+    /// sphere of `radius` around `center`
+    ///
+    /// Infinite if the sphere reaches the near plane.
+    pub fn projected_size(&self, center: [f64; 3], radius: f64, height: f64) -> f64 {
+        let (_, _, f) = self.basis();
+        let z = dot3(sub3(center, self.eye), f);
+        if z - radius < self.near {
+            return f64::INFINITY;
+        }
+        2.0 * radius * self.focal_px(height) / z
+    }
+
+    /// Projects a world space point onto a `width` by `height` screen
+    ///
+    /// Returns (x, y, depth), or None if the point is closer than `near`
+    pub fn project(&self, p: &[f64], width: f64, height: f64) -> Option<[f64; 3]> {
+        let c = self.view_matrix().transform_point(&[p[0], p[1], p[2], 1.0]);
+        self.project_camera_point([c[0], c[1], c[2]], width, height)
+    }
+
+    /// Ray through the point (x, y) of a `width` by `height` screen
+    ///
+    /// Returns (origin, direction) in world space; the direction isn't normalized.
+    /// Inverse of `project`: points along the ray project back to (x, y).
+    pub fn ray(&self, x: f64, y: f64, width: f64, height: f64) -> ([f64; 3], [f64; 3]) {
+        let f = self.focal_px(height);
+        let [ox, oy] = self.lens_offset;
+        let (sx, sy) = (ox / self.focal_distance, oy / self.focal_distance);
+        let (cx, cy) = ((x - width / 2.0) / f - sx, (height / 2.0 - y) / f - sy);
+        let (r, u, fwd) = self.basis();
+        let mut dir = [0.0; 3];
+        for (i, d) in dir.iter_mut().enumerate() {
+            *d = r[i] * cx + u[i] * cy + fwd[i];
+        }
+        (self.eye, dir)
+    }
+
+    /// Clips the camera space segment (p0, p1) to the near plane, then projects it
+    fn project_segment(
+        &self,
+        p0: [f64; 3],
+        p1: [f64; 3],
+        width: f64,
+        height: f64,
+    ) -> Option<([f64; 3], [f64; 3])> {
+        let (p0, p1) = match (p0[2] < self.near, p1[2] < self.near) {
+            (true, true) => return None,
+            (false, false) => (p0, p1),
+            (behind0, _) => {
+                let t = (self.near - p0[2]) / (p1[2] - p0[2]);
+                let cut = [
+                    p0[0] + (p1[0] - p0[0]) * t,
+                    p0[1] + (p1[1] - p0[1]) * t,
+                    self.near,
+                ];
+                if behind0 {
+                    (cut, p1)
+                } else {
+                    (p0, cut)
+                }
+            }
+        };
+        Some((
+            self.project_camera_point(p0, width, height)?,
+            self.project_camera_point(p1, width, height)?,
+        ))
+    }
+
+    /// Draws the camera space segment (p0, p1), faded by the fog if there is any
+    ///
+    /// With fog, the segment is drawn in pieces, so its far end can fade more
+    /// than its near end. It still counts as one line.
+    fn draw_segment(&self, img: &mut PPMImg, p0: [f64; 3], p1: [f64; 3], w: f64, h: f64) {
+        let fog = match self.fog {
+            Some(fog) => fog,
+            None => {
+                match self.project_segment(p0, p1, w, h) {
+                    Some((s0, s1)) => {
+                        img.in_pixels(|img| img.draw_line(s0[0], s0[1], s1[0], s1[1]))
+                    }
+                    None => img.stats_mut().lines_clipped += 1,
+                }
+                return;
+            }
+        };
+        // about one piece per 1/16 of the fade
+        let fade = (fog.amount(p0[2]) - fog.amount(p1[2])).abs();
+        let pieces = ((fade * 16.0).ceil() as usize).max(1);
+        let fg = img.fg_color;
+        let before = img.stats().lines_drawn;
+        // lines one piece counts as, more than 1 with a symmetry
+        let mut drawn = None;
+        for i in 0..pieces {
+            let lerp = |t: f64| {
+                let t = t / pieces as f64;
+                [
+                    p0[0] + (p1[0] - p0[0]) * t,
+                    p0[1] + (p1[1] - p0[1]) * t,
+                    p0[2] + (p1[2] - p0[2]) * t,
+                ]
+            };
+            let (a, b) = (lerp(i as f64), lerp(i as f64 + 1.0));
+            if let Some((s0, s1)) = self.project_segment(a, b, w, h) {
+                let z = (a[2] + b[2]) / 2.0;
+                img.fg_color = fog.apply(fg, img.bg_color, z, img.depth());
+                let start = img.stats().lines_drawn;
+                img.in_pixels(|img| img.draw_line(s0[0], s0[1], s1[0], s1[1]));
+                let added = img.stats().lines_drawn - start;
+                drawn = Some(drawn.unwrap_or(0).max(added));
+            }
+        }
+        img.fg_color = fg;
+        match drawn {
+            None => img.stats_mut().lines_clipped += 1,
+            Some(n) => img.stats_mut().lines_drawn = before + n,
+        }
+    }
+
+    /// Takes points through `world`, the vertex shader if any, and into camera space
+    fn to_camera_space<'a>(&'a self, world: &Matrix) -> impl Fn(&[f64]) -> [f64; 3] + 'a {
+        let view = self.view_matrix();
+        let t = world.compose(&view);
+        let world = world.clone();
+        move |p| match &self.vertex_shader {
+            None => xyz(&t.transform_point(p)),
+            Some(shader) => {
+                let [x, y, z] = shader.apply(xyz(&world.transform_point(p)));
+                xyz(&view.transform_point(&[x, y, z, 1.0]))
+            }
+        }
+    }
+
+    /// Draws an edge matrix as seen by the camera, after transforming it by `world`
+    ///
+    /// Returns what was drawn and what was clipped
+    pub fn render_edges(&self, img: &mut PPMImg, m: &EdgeMatrix, world: &Matrix) -> RenderStats {
+        assert_eq!(m.rows() % 2, 0, "Number of edges must be a multiple of 2");
+        let _span = Span::new("render edges");
+        let before = img.stats();
+        let to_camera = self.to_camera_space(world);
+        let (w, h) = (f64::from(img.width()), f64::from(img.height()));
+        let mut iter = m.iter_by_row();
+        while let (Some(p0), Some(p1)) = (iter.next(), iter.next()) {
+            self.draw_segment(img, to_camera(p0), to_camera(p1), w, h);
+        }
+        let stats = img.stats() - before;
+        #[cfg(feature = "logging")]
+        log::debug!(
+            "render_edges: {} edges, {} behind the camera",
+            m.rows() / 2,
+            stats.lines_clipped
+        );
+        stats
+    }
+
+    /// Draws the outline of every triangle as seen by the camera, after transforming by `world`
+    ///
+    /// Returns what was drawn; edges behind the camera count as clipped lines
+    pub fn render_polygons(
+        &self,
+        img: &mut PPMImg,
+        m: &PolygonMatrix,
+        world: &Matrix,
+    ) -> RenderStats {
+        assert_eq!(m.rows() % 3, 0, "Number of points must be a multiple of 3");
+        let _span = Span::new("render polygons");
+        let before = img.stats();
+        let to_camera = self.to_camera_space(world);
+        let (w, h) = (f64::from(img.width()), f64::from(img.height()));
+        #[cfg(feature = "logging")]
+        log::debug!("render_polygons: {} triangles", m.rows() / 3);
+        let mut iter = m.iter_by_row();
+        while let (Some(p0), Some(p1), Some(p2)) = (iter.next(), iter.next(), iter.next()) {
+            let tri = [to_camera(p0), to_camera(p1), to_camera(p2)];
+            for i in 0..3 {
+                self.draw_segment(img, tri[i], tri[(i + 1) % 3], w, h);
+            }
+        }
+        img.stats() - before
+    }
+}
+
+// depth of field
+#[allow(dead_code)]
+impl Camera {
+    /// Camera with the eye moved by (dx, dy) in the lens plane, still focused on
+    /// the same plane `focal_distance` away
+    ///
+    /// The camera keeps its orientation; its image is shifted instead, so points
+    /// on the focal plane land on the same pixels as with self
+    pub fn jittered(&self, dx: f64, dy: f64) -> Camera {
+        let (r, u, _) = self.basis();
+        let mut c = self.clone();
+        for i in 0..3 {
+            let offset = r[i] * dx + u[i] * dy;
+            c.eye[i] += offset;
+            c.target[i] += offset;
+        }
+        c.lens_offset = [self.lens_offset[0] + dx, self.lens_offset[1] + dy];
+        c.aperture = 0.0;
+        c
+    }
+
+    /// `n` jittered cameras spread evenly over the lens disk
+    ///
+    /// Uses a fixed spiral pattern, so the result is deterministic
+    pub fn lens_samples(&self, n: usize) -> Vec<Camera> {
+        let golden_angle = core::f64::consts::PI * (3.0 - 5f64.sqrt());
+        (0..n)
+            .map(|i| {
+                let r = self.aperture * ((i as f64 + 0.5) / n as f64).sqrt();
+                let (s, c) = (i as f64 * golden_angle).sin_cos();
+                self.jittered(r * c, r * s)
+            })
+            .collect()
+    }
+
+    /// Renders with depth of field by averaging `samples` renders from points on the lens
+    ///
+    /// `draw(img, camera)` should draw the scene as seen by `camera` into img,
+    /// which is cleared before every sample
+    pub fn render_depth_of_field<F>(&self, img: &mut PPMImg, samples: usize, mut draw: F)
+    where
+        F: FnMut(&mut PPMImg, &Camera),
+    {
+        if self.aperture <= 0.0 || samples <= 1 {
+            img.clear();
+            draw(img, self);
+            return;
+        }
+        let mut accum = AccumBuffer::new(img.height(), img.width());
+        for cam in self.lens_samples(samples) {
+            img.clear();
+            draw(img, &cam);
+            accum.add(img);
+        }
+        accum.resolve(img);
+    }
+}
+
+// point clouds
+#[allow(dead_code)]
+impl Camera {
+    /// Draws every row of `m`, transformed by `world`, as a round splat `size`
+    /// world units across, so farther points come out smaller
+    ///
+    /// Splats are at least one pixel, and nearer ones are drawn over farther ones.
+    /// Rows with 7 or more columns are colored by columns 4 to 6, as red, green
+    /// and blue fractions; others use img's fg_color.
+    pub fn render_points(
+        &self,
+        img: &mut PPMImg,
+        m: &Matrix,
+        world: &Matrix,
+        size: f64,
+    ) -> RenderStats {
+        let (depth, fg) = (img.depth(), img.fg_color);
+        let colored = m.cols() >= 7;
+        self.splat_points(img, m, world, size, |_, p| {
+            if colored {
+                RGB::from_fractions([p[4], p[5], p[6]], depth)
+            } else {
+                fg
+            }
+        })
+    }
+
+    /// Same as `render_points`, with point i colored `colors[i]`
+    pub fn render_colored_points(
+        &self,
+        img: &mut PPMImg,
+        m: &Matrix,
+        world: &Matrix,
+        size: f64,
+        colors: &[RGB],
+    ) -> RenderStats {
+        assert_eq!(colors.len(), m.rows(), "Need one color per point");
+        self.splat_points(img, m, world, size, |i, _| colors[i])
+    }
+
+    /// Same as `render_points`, drawing a single point for every cell of
+    /// `octree` smaller than a pixel, and nothing for cells out of view
+    ///
+    /// `octree` must have been built over `m`. Keeps huge clouds fast: the
+    /// number of points drawn follows the size of the image, not of the cloud.
+    pub fn render_octree_points(
+        &self,
+        img: &mut PPMImg,
+        m: &Matrix,
+        octree: &Octree,
+        world: &Matrix,
+        size: f64,
+    ) -> RenderStats {
+        assert_eq!(
+            octree.len(),
+            m.rows(),
+            "Octree must be built over the points"
+        );
+        let (w, h) = (f64::from(img.width()), f64::from(img.height()));
+        let rows: Vec<&[f64]> = m.iter_by_row().collect();
+        let visible = octree.visible_points(self, world, w, h, 1.0);
+        let points = Matrix::from_rows(m.cols(), visible.iter().map(|&i| rows[i]));
+        self.render_points(img, &points, world, size)
+    }
+
+    /// Draws the points of `m` back to front, point i with `color(i, row i)`
+    fn splat_points<F>(
+        &self,
+        img: &mut PPMImg,
+        m: &Matrix,
+        world: &Matrix,
+        size: f64,
+        color: F,
+    ) -> RenderStats
+    where
+        F: Fn(usize, &[f64]) -> RGB,
+    {
+        assert!(size >= 0.0, "Point size must not be negative");
+        let _span = Span::new("render points");
+        let before = img.stats();
+        let to_camera = self.to_camera_space(world);
+        let (w, h) = (f64::from(img.width()), f64::from(img.height()));
+        let f = self.focal_px(h);
+        let mut splats: Vec<(usize, [f64; 3])> = m
+            .iter_by_row()
+            .enumerate()
+            .filter_map(|(i, p)| Some((i, self.project_camera_point(to_camera(p), w, h)?)))
+            .collect();
+        splats.sort_by(|a, b| b.1[2].total_cmp(&a.1[2]));
+        #[cfg(feature = "logging")]
+        log::debug!(
+            "render_points: {} points, {} behind the camera",
+            m.rows(),
+            m.rows() - splats.len()
+        );
+        let (fg, bg) = (img.fg_color, img.bg_color);
+        let rows: Vec<&[f64]> = m.iter_by_row().collect();
+        for (i, [x, y, z]) in splats {
+            let c = color(i, rows[i]);
+            img.fg_color = match self.fog {
+                Some(fog) => fog.apply(c, bg, z, img.depth()),
+                None => c,
+            };
+            img.in_pixels(|img| img.draw_marker(x, y, Marker::Dot, (size * f / z).max(1.0)));
+        }
+        img.fg_color = fg;
+        img.stats() - before
+    }
+}
+
+// screen space bounds
+#[allow(dead_code)]
+impl Camera {
+    /// Whether any of the world space box `b` may show on a `width` by `height` screen
+    ///
+    /// Conservative: false only if the whole box is on the outer side of one
+    /// of the view frustum's planes. Always true with a vertex shader, which can
+    /// move points anywhere.
+    pub fn can_see(&self, b: &Bounds, width: f64, height: f64) -> bool {
+        if self.vertex_shader.is_some() {
+            return true;
+        }
+        let f = self.focal_px(height);
+        let [ox, oy] = self.lens_offset;
+        let (sx, sy) = (ox / self.focal_distance, oy / self.focal_distance);
+        // the screen spans x / z + sx in [-hw, hw] and y / z + sy in [-hh, hh]
+        let (hw, hh) = (width / 2.0 / f, height / 2.0 / f);
+        let view = self.view_matrix();
+        let corners = b
+            .corners()
+            .map(|[x, y, z]| xyz(&view.transform_point(&[x, y, z, 1.0])));
+        let outside = |plane: &dyn Fn([f64; 3]) -> bool| corners.iter().all(|&p| plane(p));
+        !(outside(&|p| p[2] < self.near)
+            || outside(&|p| p[0] > (hw - sx) * p[2])
+            || outside(&|p| p[0] < -(hw + sx) * p[2])
+            || outside(&|p| p[1] > (hh - sy) * p[2])
+            || outside(&|p| p[1] < -(hh + sy) * p[2]))
+    }
+
+    /// Pixels covered by the points of `m` transformed by `world`, on a `width` by `height` screen
+    ///
+    /// Conservative: the projection of the points' camera space bounding box,
+    /// cut at the near plane. Returns None if that is entirely behind the camera
+    /// or off screen, and is cut to the screen otherwise.
+    pub fn project_bounds(
+        &self,
+        m: &Matrix,
+        world: &Matrix,
+        width: u32,
+        height: u32,
+    ) -> Option<Rect> {
+        let to_camera = self.to_camera_space(world);
+        let mut points = m.iter_by_row().map(to_camera);
+        let mut bounds = Bounds::point(points.next()?);
+        for p in points {
+            bounds.include(p);
+        }
+        if bounds.max[2] < self.near {
+            return None;
+        }
+        bounds.min[2] = bounds.min[2].max(self.near);
+        let (w, h) = (f64::from(width), f64::from(height));
+        let (mut lo, mut hi) = ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]);
+        for &corner in bounds.corners().iter() {
+            let s = self.project_camera_point(corner, w, h)?;
+            for axis in 0..2 {
+                lo[axis] = lo[axis].min(s[axis]);
+                hi[axis] = hi[axis].max(s[axis]);
+            }
+        }
+        let rect = Rect::from_corners(
+            lo[0].floor() as i32,
+            lo[1].floor() as i32,
+            hi[0].ceil() as i32,
+            hi[1].ceil() as i32,
+        );
+        rect.intersect(&Rect::new(0, 0, width, height))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::symmetry::Symmetry;
+
+    #[test]
+    fn project_center_and_up() {
+        let cam = Camera::new([0.0, 0.0, -10.0], [0.0, 0.0, 0.0]);
+        assert_eq!(
+            cam.project(&[0.0, 0.0, 0.0], 100.0, 100.0),
+            Some([50.0, 50.0, 10.0])
+        );
+        let up = cam.project(&[0.0, 1.0, 0.0], 100.0, 100.0).unwrap();
+        assert!(up[1] < 50.0, "world +y should be up on screen");
+        assert_eq!(cam.project(&[0.0, 0.0, -20.0], 100.0, 100.0), None);
+    }
+
+    #[test]
+    fn focal_plane_stays_sharp() {
+        let mut cam = Camera::new([0.0, 0.0, -10.0], [0.0, 0.0, 0.0]);
+        cam.aperture = 0.5;
+        let in_focus = [1.0, 2.0, 0.0];
+        let expected = cam.project(&in_focus, 100.0, 100.0).unwrap();
+        for c in cam.lens_samples(8) {
+            let p = c.project(&in_focus, 100.0, 100.0).unwrap();
+            assert!((p[0] - expected[0]).abs() < 1e-6 && (p[1] - expected[1]).abs() < 1e-6);
+        }
+        // something far behind the focal plane moves between samples
+        let samples = cam.lens_samples(8);
+        let far = [1.0, 2.0, 50.0];
+        let (a, b) = (
+            samples[0].project(&far, 100.0, 100.0).unwrap(),
+            samples[3].project(&far, 100.0, 100.0).unwrap(),
+        );
+        assert!((a[0] - b[0]).abs() > 0.1 || (a[1] - b[1]).abs() > 0.1);
+    }
+
+    #[test]
+    fn render_edges_counts_clipped_lines() {
+        let cam = Camera::new([0.0, 0.0, -10.0], [0.0, 0.0, 0.0]);
+        let mut img = PPMImg::new(100, 100, 255);
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.append_edge([-1.0, 0.0, 0.0]);
+        m.append_edge([1.0, 0.0, 0.0]);
+        // entirely behind the eye
+        m.append_edge([-1.0, 0.0, -20.0]);
+        m.append_edge([1.0, 0.0, -20.0]);
+
+        let stats = cam.render_edges(&mut img, &m, &Matrix::ident(4));
+        assert_eq!(stats.lines_drawn, 1);
+        assert_eq!(stats.lines_clipped, 1);
+        assert!(stats.pixels_plotted > 0);
+        assert_eq!(img.take_stats(), stats);
+        assert_eq!(img.stats(), RenderStats::default());
+    }
+
+    #[test]
+    fn vertex_shader_moves_vertices() {
+        let mut cam = Camera::new([0.0, 0.0, -10.0], [0.0, 0.0, 0.0]);
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.append_edge([0.0, -1.0, 0.0]);
+        m.append_edge([0.0, 1.0, 0.0]);
+        let render = |cam: &Camera| {
+            let mut img = PPMImg::new(100, 100, 255);
+            cam.render_edges(&mut img, &m, &Matrix::ident(4));
+            (0..100).find(|&x| img.pixel(x, 45).red > 0)
+        };
+        let still = render(&cam);
+        assert_eq!(still, Some(50));
+        // twist: the higher the vertex, the more it moves along x
+        cam.vertex_shader = Some(VertexShader::new(|[x, y, z]| [x - y, y, z]));
+        let twisted = render(&cam);
+        assert!(twisted.is_some() && twisted != still);
+    }
+
+    #[test]
+    fn fog_fades_far_lines() {
+        let mut cam = Camera::new([0.0, 0.0, -10.0], [0.0, 0.0, 0.0]);
+        cam.fog = Some(Fog::new(8.0, 14.0));
+        let mut m = Matrix::new(0, 4, vec![]);
+        // from in front of the fog to deep inside it
+        m.append_edge([-1.0, 0.0, -3.0]);
+        m.append_edge([1.0, 0.0, 3.0]);
+        let mut img = PPMImg::new(100, 100, 255);
+        img.subpixel = false;
+        let stats = cam.render_edges(&mut img, &m, &Matrix::ident(4));
+        assert_eq!(stats.lines_drawn, 1);
+        let row: Vec<u16> = (0..100)
+            .map(|x| img.pixel(x, 50).red)
+            .filter(|&r| r > 0)
+            .collect();
+        // the near end is on the right: dim on the left, brightening to full
+        assert!(row[0] < 100 && *row.last().unwrap() == 255);
+        assert!(row.windows(2).all(|w| w[0] <= w[1]));
+
+        // still counted once per copy
+        img.symmetry = Some(Symmetry::new((50.0, 50.0), 2));
+        let stats = cam.render_edges(&mut img, &m, &Matrix::ident(4));
+        assert_eq!(stats.lines_drawn, 2);
+    }
+
+    #[test]
+    fn points_shrink_with_distance() {
+        let cam = Camera::new([0.0, 0.0, -10.0], [0.0, 0.0, 0.0]);
+        // red near point on the left, green far one on the right
+        let m = Matrix::from_rows(
+            7,
+            vec![
+                [2.0, 0.0, -5.0, 1.0, 1.0, 0.0, 0.0],
+                [-4.0, 0.0, 10.0, 1.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, -20.0, 1.0, 1.0, 1.0, 1.0],
+            ],
+        );
+        let mut img = PPMImg::new(100, 100, 255);
+        let stats = cam.render_points(&mut img, &m, &Matrix::ident(4), 0.5);
+        let count = |img: &PPMImg, pick: fn(RGB) -> u16| {
+            let mut n = 0;
+            for y in 0..100 {
+                for x in 0..100 {
+                    n += (pick(img.pixel(x, y)) > 0) as u32;
+                }
+            }
+            n
+        };
+        let (red, green) = (count(&img, |c| c.red), count(&img, |c| c.green));
+        // the point behind the camera isn't drawn: there's no white
+        assert_eq!(u64::from(red + green), stats.pixels_plotted);
+        assert!(red > 3 * green && green > 0);
+
+        let mut img = PPMImg::new(100, 100, 255);
+        let blue = RGB::from_fractions([0.0, 0.0, 1.0], 255);
+        cam.render_colored_points(&mut img, &m, &Matrix::ident(4), 0.5, &[blue; 3]);
+        assert_eq!(count(&img, |c| c.red), 0);
+        assert_eq!(u64::from(count(&img, |c| c.blue)), stats.pixels_plotted);
+    }
+
+    #[test]
+    fn bounds_cover_projected_points() {
+        let cam = Camera::new([0.0, 0.0, -10.0], [0.0, 0.0, 0.0]);
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.append_edge([-1.0, -1.0, -1.0]);
+        m.append_edge([1.0, 1.0, 1.0]);
+        let rect = cam.project_bounds(&m, &Matrix::ident(4), 100, 100).unwrap();
+        for p in m.iter_by_row() {
+            let s = cam.project(p, 100.0, 100.0).unwrap();
+            assert!(rect.contains(s[0] as i32, s[1] as i32));
+        }
+        assert!(rect.w < 50 && rect.contains(50, 50));
+
+        let behind = Matrix::translate(0.0, 0.0, -30.0);
+        assert_eq!(cam.project_bounds(&m, &behind, 100, 100), None);
+        let off_screen = Matrix::translate(100.0, 0.0, 0.0);
+        assert_eq!(cam.project_bounds(&m, &off_screen, 100, 100), None);
+    }
+}
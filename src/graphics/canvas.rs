@@ -0,0 +1,72 @@
+use super::{PPMImg, RGB};
+
+/// Something pixels can be drawn into
+///
+/// Coordinates are like `PPMImg`'s: (0, 0) is the top left pixel, y points down
+#[allow(dead_code)]
+pub trait Canvas {
+    fn width(&self) -> u32;
+    fn height(&self) -> u32;
+    /// Max value of a color channel
+    fn depth(&self) -> u16;
+    fn pixel(&self, x: u32, y: u32) -> RGB;
+    fn set_pixel(&mut self, x: u32, y: u32, color: RGB);
+
+    /// Draws `img` scaled to cover the whole canvas
+    ///
+    /// Each pixel gets the average color of the part of img it covers, so
+    /// shrinking a big image keeps thin lines visible
+    fn draw_scaled(&mut self, img: &PPMImg) {
+        let (w, h) = (self.width(), self.height());
+        let (sw, sh) = (img.width(), img.height());
+        for y in 0..h {
+            let (y0, y1) = span(y, h, sh);
+            for x in 0..w {
+                let (x0, x1) = span(x, w, sw);
+                let mut sum = [0.0; 3];
+                for sy in y0..y1 {
+                    for sx in x0..x1 {
+                        let c = img.pixel(sx, sy).to_fractions(img.depth());
+                        for (s, v) in sum.iter_mut().zip(c.iter()) {
+                            *s += v;
+                        }
+                    }
+                }
+                let n = f64::from((x1 - x0) * (y1 - y0));
+                let avg = [sum[0] / n, sum[1] / n, sum[2] / n];
+                self.set_pixel(x, y, RGB::from_fractions(avg, self.depth()));
+            }
+        }
+    }
+}
+
+/// Range of source pixels covered by pixel `i` of `n`, out of `src` source pixels
+///
+/// Never empty, even when scaling up
+fn span(i: u32, n: u32, src: u32) -> (u32, u32) {
+    let start = (u64::from(i) * u64::from(src) / u64::from(n)) as u32;
+    let end = (u64::from(i + 1) * u64::from(src) / u64::from(n)) as u32;
+    (start, end.max(start + 1))
+}
+
+impl Canvas for PPMImg {
+    fn width(&self) -> u32 {
+        PPMImg::width(self)
+    }
+
+    fn height(&self) -> u32 {
+        PPMImg::height(self)
+    }
+
+    fn depth(&self) -> u16 {
+        PPMImg::depth(self)
+    }
+
+    fn pixel(&self, x: u32, y: u32) -> RGB {
+        PPMImg::pixel(self, x, y)
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, color: RGB) {
+        PPMImg::set_pixel(self, x, y, color)
+    }
+}
@@ -0,0 +1,135 @@
+use super::plot::Marker;
+use super::raster::fill_triangle;
+use super::PPMImg;
+use alloc::vec;
+
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+
+/// Shape drawn at one end of a line
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LineCap {
+    /// Nothing, the line just stops
+    #[default]
+    None,
+    /// Two barbs pointing away from the line
+    Arrow,
+    /// Solid triangle pointing away from the line
+    FilledArrow,
+    /// Filled disc centered on the end
+    Circle,
+    /// Filled square centered on the end
+    Square,
+}
+
+/// Caps for both ends of a line, for `PPMImg::draw_capped_line`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LineCaps {
+    pub start: LineCap,
+    pub end: LineCap,
+    /// Length of arrowheads and width of circles and squares, in pixels
+    pub size: f64,
+}
+
+#[allow(dead_code)]
+impl LineCaps {
+    pub fn new(start: LineCap, end: LineCap, size: f64) -> LineCaps {
+        assert!(size >= 0.0, "Cap size must not be negative");
+        LineCaps { start, end, size }
+    }
+
+    /// Open arrowhead `size` pixels long at the end only
+    pub fn arrow(size: f64) -> LineCaps {
+        LineCaps::new(LineCap::None, LineCap::Arrow, size)
+    }
+
+    /// Same caps, `factor` times bigger
+    pub fn scaled(self, factor: f64) -> LineCaps {
+        LineCaps::new(self.start, self.end, self.size * factor)
+    }
+}
+
+// line caps
+#[allow(dead_code)]
+impl PPMImg {
+    /// Draws the line from (x0, y0) to (x1, y1) with `caps` on its ends
+    pub fn draw_capped_line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, caps: &LineCaps) {
+        self.draw_line(x0, y0, x1, y1);
+        self.draw_cap(caps.start, (x0, y0), (x1, y1), caps.size);
+        self.draw_cap(caps.end, (x1, y1), (x0, y0), caps.size);
+    }
+
+    /// Draws `cap` `size` pixels big on `tip`, the end of a line coming from `from`
+    pub fn draw_cap(&mut self, cap: LineCap, tip: (f64, f64), from: (f64, f64), size: f64) {
+        let (dx, dy) = (from.0 - tip.0, from.1 - tip.1);
+        let len = (dx * dx + dy * dy).sqrt();
+        // unit vector back along the line; a point has no direction, so pick one
+        let (ux, uy) = if len > 0.0 {
+            (dx / len, dy / len)
+        } else {
+            (-1.0, 0.0)
+        };
+        // barbs 30 degrees to either side of the line
+        let (sin, cos) = (0.5, 0.75f64.sqrt());
+        let barbs = [
+            (
+                tip.0 + (ux * cos - uy * sin) * size,
+                tip.1 + (ux * sin + uy * cos) * size,
+            ),
+            (
+                tip.0 + (ux * cos + uy * sin) * size,
+                tip.1 + (-ux * sin + uy * cos) * size,
+            ),
+        ];
+        match cap {
+            LineCap::None => (),
+            LineCap::Arrow => {
+                for b in barbs.iter() {
+                    self.draw_line(tip.0, tip.1, b.0, b.1);
+                }
+            }
+            LineCap::FilledArrow => {
+                // fill_triangle puts pixel centers at + 0.5
+                let corner = |p: (f64, f64)| [p.0 + 0.5, p.1 + 0.5, 1.0];
+                let tri = [corner(tip), corner(barbs[0]), corner(barbs[1])];
+                let mut inside = vec![];
+                fill_triangle(self.width(), self.height(), tri, |x, y, _, _| {
+                    inside.push((x as i32, y as i32))
+                });
+                for (x, y) in inside {
+                    self.plot(x, y);
+                }
+                for b in barbs.iter() {
+                    self.draw_line(tip.0, tip.1, b.0, b.1);
+                }
+                self.draw_line(barbs[0].0, barbs[0].1, barbs[1].0, barbs[1].1);
+            }
+            LineCap::Circle => self.draw_marker(tip.0, tip.1, Marker::Dot, size),
+            LineCap::Square => self.draw_marker(tip.0, tip.1, Marker::Square, size),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caps_sit_on_the_right_end() {
+        let lit = |img: &PPMImg, x: u32, y: u32| img.pixel(x, y).red > 0;
+        let mut img = PPMImg::new(11, 21, 255);
+        let caps = LineCaps::new(LineCap::Square, LineCap::FilledArrow, 6.0);
+        img.draw_capped_line(3.0, 5.0, 17.0, 5.0, &caps);
+        // solid head pointing right, ending at the tip
+        assert!(lit(&img, 14, 3) && lit(&img, 14, 7) && lit(&img, 15, 4));
+        assert!(!lit(&img, 18, 5) && !lit(&img, 17, 4));
+        // square around the start
+        assert!(lit(&img, 1, 3) && lit(&img, 5, 7) && !lit(&img, 6, 7));
+
+        let mut img = PPMImg::new(11, 21, 255);
+        img.draw_capped_line(3.0, 5.0, 17.0, 5.0, &LineCaps::arrow(3.0).scaled(2.0));
+        // open head: barbs only
+        assert!(lit(&img, 14, 3) && !lit(&img, 14, 4));
+        assert!(!lit(&img, 1, 3));
+    }
+}
@@ -0,0 +1,295 @@
+use super::matrix::{Bounds, EdgeMatrix, Matrix, PolygonMatrix};
+use super::utils::{cross3, dot3, normalize3, sub3};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The 6 tetrahedra every cube is split into, all around its diagonal from
+/// corner 0 to 7; corner i is at x = i & 1, y = i >> 1 & 1, z = i >> 2
+const CUBE_TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 1, 3, 7],
+    [0, 3, 2, 7],
+    [0, 2, 6, 7],
+    [0, 6, 4, 7],
+    [0, 4, 5, 7],
+    [0, 5, 1, 7],
+];
+
+/// Surface extracted from a scalar field by `isosurface`
+#[derive(Clone, Debug)]
+pub struct Isosurface {
+    /// Triangles facing out of the inside, where the field is above the level
+    pub mesh: PolygonMatrix,
+    /// Unit normal of every point of `mesh`, one (x, y, z, 0) row per point,
+    /// from the gradient of the field
+    pub normals: Matrix,
+}
+
+/// Point between `a` and `b` where the value goes through `level`, their values
+/// being `va` and `vb`
+fn crossing(a: [f64; 2], b: [f64; 2], va: f64, vb: f64, level: f64) -> [f64; 3] {
+    let t = (level - va) / (vb - va);
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, 0.0]
+}
+
+/// Outline of where f(x, y) == `level`, over x in `x_range` and y in `y_range`
+///
+/// Marching squares over a grid of `resolution` by `resolution` cells; pieces
+/// of the curve smaller than a cell may be missed. The edges are in the z = 0
+/// plane, and the curve's pieces share their ends exactly, so closed curves
+/// come out closed.
+pub fn contour<F>(
+    f: F,
+    level: f64,
+    x_range: (f64, f64),
+    y_range: (f64, f64),
+    resolution: usize,
+) -> EdgeMatrix
+where
+    F: Fn(f64, f64) -> f64,
+{
+    assert!(resolution > 0, "Contour needs at least 1 cell");
+    let n = resolution;
+    let point = |i: usize, j: usize| {
+        [
+            x_range.0 + (x_range.1 - x_range.0) * i as f64 / n as f64,
+            y_range.0 + (y_range.1 - y_range.0) * j as f64 / n as f64,
+        ]
+    };
+    let values: Vec<f64> = (0..=n)
+        .flat_map(|j| (0..=n).map(move |i| (i, j)))
+        .map(|(i, j)| {
+            let [x, y] = point(i, j);
+            f(x, y)
+        })
+        .collect();
+    let value = |i: usize, j: usize| values[j * (n + 1) + i];
+
+    let mut m = Matrix::new(0, 4, vec![]);
+    for j in 0..n {
+        for i in 0..n {
+            // counter clockwise from the corner at (x0, y0)
+            let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+            let v = corners.map(|(i, j)| value(i, j));
+            let inside = v.map(|v| v >= level);
+            // where the curve crosses the bottom, right, top and left sides; every
+            // side is interpolated in grid order, so neighbors get the same point
+            let mut cuts = [None; 4];
+            for (side, cut) in cuts.iter_mut().enumerate() {
+                let (a, b) = (side, (side + 1) % 4);
+                if inside[a] != inside[b] {
+                    let (a, b) = if side < 2 { (a, b) } else { (b, a) };
+                    let (pa, pb) = (corners[a], corners[b]);
+                    *cut = Some(crossing(
+                        point(pa.0, pa.1),
+                        point(pb.0, pb.1),
+                        v[a],
+                        v[b],
+                        level,
+                    ));
+                }
+            }
+            let mut segments = vec![];
+            match cuts {
+                [Some(a), Some(b), Some(c), Some(d)] => {
+                    // saddle: the middle decides which corners are joined
+                    let center = (v[0] + v[1] + v[2] + v[3]) / 4.0;
+                    if (center >= level) == inside[0] {
+                        segments.push((a, b));
+                        segments.push((c, d));
+                    } else {
+                        segments.push((a, d));
+                        segments.push((b, c));
+                    }
+                }
+                _ => {
+                    let mut found = cuts.iter().flatten();
+                    if let (Some(&a), Some(&b)) = (found.next(), found.next()) {
+                        segments.push((a, b));
+                    }
+                }
+            }
+            for (a, b) in segments {
+                m.append_edge(a);
+                m.append_edge(b);
+            }
+        }
+    }
+    m
+}
+
+/// Surface where f(x, y, z) == `level` inside `bounds`; see `isosurface_from_grid`
+///
+/// f is sampled on a grid of `resolution` cells along every axis.
+pub fn isosurface<F>(f: F, level: f64, bounds: &Bounds, resolution: usize) -> Isosurface
+where
+    F: Fn(f64, f64, f64) -> f64,
+{
+    assert!(resolution > 0, "Isosurface needs at least 1 cell");
+    let n = resolution + 1;
+    let mut values = Vec::with_capacity(n * n * n);
+    for k in 0..n {
+        for j in 0..n {
+            for i in 0..n {
+                let [x, y, z] = grid_point(bounds, [n; 3], [i, j, k]);
+                values.push(f(x, y, z));
+            }
+        }
+    }
+    isosurface_from_grid(&values, [n; 3], bounds, level)
+}
+
+/// Surface where the field sampled in `values` goes through `level`
+///
+/// `values` holds `size[0]` by `size[1]` by `size[2]` samples spread evenly over
+/// `bounds`, x changing fastest, then y, then z. Marching cubes, each cube being
+/// split into tetrahedra so no case is ambiguous and the surface has no holes
+/// inside the grid. Inside is where the field is above `level`, like with
+/// metaballs; negate distance fields, which are below inside.
+pub fn isosurface_from_grid(
+    values: &[f64],
+    size: [usize; 3],
+    bounds: &Bounds,
+    level: f64,
+) -> Isosurface {
+    assert!(
+        size.iter().all(|&s| s > 1),
+        "Grid needs at least 2 samples along every axis"
+    );
+    assert_eq!(
+        values.len(),
+        size[0] * size[1] * size[2],
+        "Need one value per grid point"
+    );
+    let index = |[i, j, k]: [usize; 3]| (k * size[1] + j) * size[0] + i;
+    let step =
+        [0, 1, 2].map(|axis| (bounds.max[axis] - bounds.min[axis]) / (size[axis] - 1) as f64);
+    // central differences inside the grid, one sided on its faces
+    let gradient = |p: [usize; 3]| {
+        [0, 1, 2].map(|axis| {
+            let (mut lo, mut hi) = (p, p);
+            lo[axis] = p[axis].saturating_sub(1);
+            hi[axis] = (p[axis] + 1).min(size[axis] - 1);
+            let span = (hi[axis] - lo[axis]) as f64 * step[axis];
+            (values[index(hi)] - values[index(lo)]) / span
+        })
+    };
+    // point on the grid edge (a, b) at the level, and the outward normal there;
+    // interpolated from the lower index so neighbors get the same point
+    let cut = |a: [usize; 3], b: [usize; 3]| {
+        let (a, b) = if index(a) < index(b) { (a, b) } else { (b, a) };
+        let (va, vb) = (values[index(a)], values[index(b)]);
+        let t = (level - va) / (vb - va);
+        let (pa, pb) = (grid_point(bounds, size, a), grid_point(bounds, size, b));
+        let (ga, gb) = (gradient(a), gradient(b));
+        let lerp = |x: [f64; 3], y: [f64; 3]| [0, 1, 2].map(|i| x[i] + (y[i] - x[i]) * t);
+        let g = lerp(ga, gb);
+        (lerp(pa, pb), normalize3([-g[0], -g[1], -g[2]]))
+    };
+
+    let mut surface = Isosurface {
+        mesh: Matrix::new(0, 4, vec![]),
+        normals: Matrix::new(0, 4, vec![]),
+    };
+    for k in 0..size[2] - 1 {
+        for j in 0..size[1] - 1 {
+            for i in 0..size[0] - 1 {
+                let corner = |c: usize| [i + (c & 1), j + (c >> 1 & 1), k + (c >> 2)];
+                for tet in CUBE_TETRAHEDRA.iter() {
+                    let p = tet.map(corner);
+                    let inside: Vec<usize> =
+                        (0..4).filter(|&c| values[index(p[c])] >= level).collect();
+                    let outside: Vec<usize> =
+                        (0..4).filter(|&c| values[index(p[c])] < level).collect();
+                    let points = match (inside.as_slice(), outside.as_slice()) {
+                        ([a], [b, c, d]) | ([b, c, d], [a]) => {
+                            vec![cut(p[*a], p[*b]), cut(p[*a], p[*c]), cut(p[*a], p[*d])]
+                        }
+                        // a quad, its corners going around
+                        ([a, b], [c, d]) => vec![
+                            cut(p[*a], p[*c]),
+                            cut(p[*a], p[*d]),
+                            cut(p[*b], p[*d]),
+                            cut(p[*b], p[*c]),
+                        ],
+                        _ => continue,
+                    };
+                    for t in 1..points.len() - 1 {
+                        surface.add_triangle([points[0], points[t], points[t + 1]]);
+                    }
+                }
+            }
+        }
+    }
+    surface
+}
+
+/// Point (i, j, k) of a grid of `size` points spread over `bounds`
+fn grid_point(bounds: &Bounds, size: [usize; 3], p: [usize; 3]) -> [f64; 3] {
+    [0, 1, 2].map(|axis| {
+        let t = p[axis] as f64 / (size[axis] - 1) as f64;
+        bounds.min[axis] + (bounds.max[axis] - bounds.min[axis]) * t
+    })
+}
+
+impl Isosurface {
+    /// Adds the triangle of (point, normal) pairs, turned to face along its normals
+    fn add_triangle(&mut self, mut tri: [([f64; 3], [f64; 3]); 3]) {
+        let face = cross3(sub3(tri[1].0, tri[0].0), sub3(tri[2].0, tri[0].0));
+        let normal = [0, 1, 2].map(|i| tri[0].1[i] + tri[1].1[i] + tri[2].1[i]);
+        if dot3(face, normal) < 0.0 {
+            tri.swap(1, 2);
+        }
+        for (p, n) in tri.iter() {
+            self.mesh.append_edge(*p);
+            self.normals.append_row([n[0], n[1], n[2], 0.0]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sphere_is_closed_and_faces_out() {
+        let bounds = Bounds {
+            min: [-1.5; 3],
+            max: [1.5; 3],
+        };
+        let s = isosurface(|x, y, z| 1.0 - (x * x + y * y + z * z), 0.0, &bounds, 10);
+        assert!(s.mesh.rows() > 100);
+        assert_eq!(s.mesh.rows(), s.normals.rows());
+        for (p, n) in s.mesh.iter_by_row().zip(s.normals.iter_by_row()) {
+            let r = (p[0] * p[0] + p[1] * p[1] + p[2] * p[2]).sqrt();
+            assert!((r - 1.0).abs() < 0.05, "r: {}", r);
+            assert!(p[0] * n[0] + p[1] * n[1] + p[2] * n[2] > 0.9 * r);
+        }
+        let report = s.mesh.validate();
+        assert!(report.non_manifold_edges == 0 && report.inconsistent_edges == 0);
+        assert_eq!(report.boundary_edges, 0);
+        for (i, n) in s.mesh.face_normals().iter_by_row().enumerate() {
+            let p = s.mesh.iter_by_row().nth(3 * i).unwrap();
+            assert!(p[0] * n[0] + p[1] * n[1] + p[2] * n[2] > 0.0);
+        }
+    }
+
+    #[test]
+    fn circle_comes_out_closed() {
+        let m = contour(|x, y| x * x + y * y, 1.0, (-2.0, 2.0), (-2.0, 2.0), 16);
+        assert!(m.rows() > 16);
+        for p in m.iter_by_row() {
+            let r = (p[0] * p[0] + p[1] * p[1]).sqrt();
+            assert!((r - 1.0).abs() < 0.05 && p[2] == 0.0, "r: {}", r);
+        }
+        // every end is shared by exactly 2 edges
+        let points: Vec<&[f64]> = m.iter_by_row().collect();
+        for p in points.iter() {
+            assert_eq!(points.iter().filter(|q| q == &p).count(), 2);
+        }
+        // nothing at a level the function never reaches
+        assert_eq!(
+            contour(|x, y| x * x + y * y, -1.0, (-2.0, 2.0), (-2.0, 2.0), 8).rows(),
+            0
+        );
+    }
+}
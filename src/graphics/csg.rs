@@ -0,0 +1,300 @@
+use super::matrix::{Matrix, Plane, PolygonMatrix};
+use super::utils::{cross3, dot3, sub3, xyz};
+
+/// Points closer than this to a plane are on it
+const EPSILON: f64 = 1e-9;
+
+/// Convex polygon, its points counter clockwise when looking at its front
+#[derive(Clone)]
+struct Polygon {
+    points: Vec<[f64; 3]>,
+    plane: Plane,
+}
+
+impl Polygon {
+    /// None for triangles with no area, which have no plane
+    fn new(points: Vec<[f64; 3]>) -> Option<Polygon> {
+        let n = cross3(sub3(points[1], points[0]), sub3(points[2], points[0]));
+        if dot3(n, n) < EPSILON * EPSILON {
+            return None;
+        }
+        Some(Polygon {
+            plane: Plane::new(points[0], n),
+            points,
+        })
+    }
+
+    fn flip(&mut self) {
+        self.points.reverse();
+        self.plane = self.plane.flipped();
+    }
+}
+
+/// Where polygons go when split by a plane; coplanar ones go to either front or
+/// back depending on which way they face
+struct Split<'a> {
+    coplanar_front: &'a mut Vec<Polygon>,
+    coplanar_back: &'a mut Vec<Polygon>,
+    front: &'a mut Vec<Polygon>,
+    back: &'a mut Vec<Polygon>,
+}
+
+/// Splits `polygon` by `plane` into the lists of `out`
+fn split(plane: &Plane, polygon: Polygon, out: Split) {
+    // 1 for front, 2 for back, 3 for both
+    let sides: Vec<u8> = polygon
+        .points
+        .iter()
+        .map(|&p| match plane.distance(p) {
+            d if d > EPSILON => 1,
+            d if d < -EPSILON => 2,
+            _ => 0,
+        })
+        .collect();
+    match sides.iter().fold(0, |all, s| all | s) {
+        0 if dot3(plane.normal, polygon.plane.normal) > 0.0 => out.coplanar_front.push(polygon),
+        0 => out.coplanar_back.push(polygon),
+        1 => out.front.push(polygon),
+        2 => out.back.push(polygon),
+        _ => {
+            let (mut f, mut b) = (vec![], vec![]);
+            let n = polygon.points.len();
+            for i in 0..n {
+                let j = (i + 1) % n;
+                let (p, q) = (polygon.points[i], polygon.points[j]);
+                if sides[i] != 2 {
+                    f.push(p);
+                }
+                if sides[i] != 1 {
+                    b.push(p);
+                }
+                if sides[i] | sides[j] == 3 {
+                    let (dp, dq) = (plane.distance(p), plane.distance(q));
+                    let t = dp / (dp - dq);
+                    let cut = [
+                        p[0] + (q[0] - p[0]) * t,
+                        p[1] + (q[1] - p[1]) * t,
+                        p[2] + (q[2] - p[2]) * t,
+                    ];
+                    f.push(cut);
+                    b.push(cut);
+                }
+            }
+            // the pieces lie in the same plane as the whole
+            for (points, list) in [(f, out.front), (b, out.back)] {
+                if points.len() >= 3 {
+                    list.push(Polygon {
+                        points,
+                        plane: polygon.plane,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Binary space partition of a solid's polygons
+///
+/// The front of every node's plane is outside the solid, its back inside.
+#[derive(Default)]
+struct BspNode {
+    plane: Option<Plane>,
+    polygons: Vec<Polygon>,
+    front: Option<Box<BspNode>>,
+    back: Option<Box<BspNode>>,
+}
+
+impl BspNode {
+    fn new(polygons: Vec<Polygon>) -> BspNode {
+        let mut node = BspNode::default();
+        node.build(polygons);
+        node
+    }
+
+    /// Adds `polygons` to the tree, splitting them as needed
+    fn build(&mut self, polygons: Vec<Polygon>) {
+        if polygons.is_empty() {
+            return;
+        }
+        let plane = *self.plane.get_or_insert(polygons[0].plane);
+        let (mut front, mut back) = (vec![], vec![]);
+        let mut coplanar_back = vec![];
+        for p in polygons {
+            let out = Split {
+                coplanar_front: &mut self.polygons,
+                coplanar_back: &mut coplanar_back,
+                front: &mut front,
+                back: &mut back,
+            };
+            split(&plane, p, out);
+        }
+        self.polygons.append(&mut coplanar_back);
+        if !front.is_empty() {
+            self.front.get_or_insert_with(Box::default).build(front);
+        }
+        if !back.is_empty() {
+            self.back.get_or_insert_with(Box::default).build(back);
+        }
+    }
+
+    /// Turns the solid inside out
+    fn invert(&mut self) {
+        for p in self.polygons.iter_mut() {
+            p.flip();
+        }
+        self.plane = self.plane.map(|p| p.flipped());
+        if let Some(front) = &mut self.front {
+            front.invert();
+        }
+        if let Some(back) = &mut self.back {
+            back.invert();
+        }
+        std::mem::swap(&mut self.front, &mut self.back);
+    }
+
+    /// Parts of `polygons` outside this solid
+    fn clip_polygons(&self, polygons: Vec<Polygon>) -> Vec<Polygon> {
+        let plane = match self.plane {
+            Some(plane) => plane,
+            None => return polygons,
+        };
+        let (mut front, mut back) = (vec![], vec![]);
+        let mut coplanar_back = vec![];
+        for p in polygons {
+            // coplanar polygons facing the same way count as outside
+            let mut coplanar_front = vec![];
+            let out = Split {
+                coplanar_front: &mut coplanar_front,
+                coplanar_back: &mut coplanar_back,
+                front: &mut front,
+                back: &mut back,
+            };
+            split(&plane, p, out);
+            front.append(&mut coplanar_front);
+        }
+        back.append(&mut coplanar_back);
+        let mut front = match &self.front {
+            Some(node) => node.clip_polygons(front),
+            None => front,
+        };
+        if let Some(node) = &self.back {
+            front.extend(node.clip_polygons(back));
+        }
+        front
+    }
+
+    /// Removes the parts of this tree's polygons inside `other`
+    fn clip_to(&mut self, other: &BspNode) {
+        self.polygons = other.clip_polygons(std::mem::take(&mut self.polygons));
+        if let Some(front) = &mut self.front {
+            front.clip_to(other);
+        }
+        if let Some(back) = &mut self.back {
+            back.clip_to(other);
+        }
+    }
+
+    fn all_polygons(&self) -> Vec<Polygon> {
+        let mut out = self.polygons.clone();
+        for child in self.front.iter().chain(self.back.iter()) {
+            out.extend(child.all_polygons());
+        }
+        out
+    }
+}
+
+fn to_bsp(m: &PolygonMatrix) -> BspNode {
+    assert_eq!(m.rows() % 3, 0, "Number of points must be a multiple of 3");
+    let points: Vec<[f64; 3]> = m.iter_by_row().map(xyz).collect();
+    BspNode::new(
+        points
+            .chunks(3)
+            .filter_map(|t| Polygon::new(t.to_vec()))
+            .collect(),
+    )
+}
+
+fn to_matrix(polygons: Vec<Polygon>) -> PolygonMatrix {
+    let mut m = Matrix::new(0, 4, vec![]);
+    for p in polygons {
+        for i in 1..p.points.len() - 1 {
+            m.add_polygon(p.points[0], p.points[i], p.points[i + 1]);
+        }
+    }
+    m
+}
+
+#[allow(dead_code)]
+// constructive solid geometry
+impl PolygonMatrix {
+    /// Solid made of both solids
+    ///
+    /// Both meshes must be closed, with their triangles facing outwards, and so
+    /// is the result. Shared faces may come out split into more triangles.
+    pub fn union(&self, other: &PolygonMatrix) -> PolygonMatrix {
+        let (mut a, mut b) = (to_bsp(self), to_bsp(other));
+        a.clip_to(&b);
+        b.clip_to(&a);
+        b.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.build(b.all_polygons());
+        to_matrix(a.all_polygons())
+    }
+
+    /// Solid of this one with `other` carved out of it; see `union`
+    pub fn difference(&self, other: &PolygonMatrix) -> PolygonMatrix {
+        let (mut a, mut b) = (to_bsp(self), to_bsp(other));
+        a.invert();
+        a.clip_to(&b);
+        b.clip_to(&a);
+        b.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.build(b.all_polygons());
+        a.invert();
+        to_matrix(a.all_polygons())
+    }
+
+    /// Solid where both solids overlap; see `union`
+    pub fn intersection(&self, other: &PolygonMatrix) -> PolygonMatrix {
+        let (mut a, mut b) = (to_bsp(self), to_bsp(other));
+        a.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.clip_to(&b);
+        b.clip_to(&a);
+        a.build(b.all_polygons());
+        a.invert();
+        to_matrix(a.all_polygons())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Volume enclosed by a closed mesh facing outwards
+    fn volume(m: &PolygonMatrix) -> f64 {
+        let points: Vec<[f64; 3]> = m.iter_by_row().map(xyz).collect();
+        points
+            .chunks(3)
+            .map(|t| dot3(t[0], cross3(t[1], t[2])) / 6.0)
+            .sum()
+    }
+
+    #[test]
+    fn boxes_combine() {
+        let mut a = Matrix::new(0, 4, vec![]);
+        a.add_box([0.0; 3], [2.0; 3]);
+        let mut b = Matrix::new(0, 4, vec![]);
+        b.add_box([1.0; 3], [3.0; 3]);
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        assert!(close(volume(&a), 8.0));
+        assert!(close(volume(&a.union(&b)), 15.0));
+        assert!(close(volume(&a.difference(&b)), 7.0));
+        assert!(close(volume(&a.intersection(&b)), 1.0));
+        let inside = a.intersection(&b).bounds().unwrap();
+        assert_eq!((inside.min, inside.max), ([1.0; 3], [2.0; 3]));
+    }
+}
@@ -0,0 +1,439 @@
+use super::matrix::EdgeMatrix;
+use super::svg::Svg;
+use super::{PPMImg, YAxis, RGB};
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+use std::io::{self, BufRead, Write};
+
+/// One recorded drawing command, in the coordinates and colors of its `DisplayList`
+#[derive(Copy, Clone)]
+pub enum Command {
+    Color(RGB),
+    Background(RGB),
+    Clear,
+    Plot(f64, f64),
+    /// x0, y0, x1, y1
+    Line([f64; 4]),
+    /// x, y, width, height
+    FillRect([f64; 4]),
+}
+
+/// Something a `DisplayList` can be replayed onto
+pub trait DrawTarget {
+    fn width(&self) -> u32;
+    fn height(&self) -> u32;
+    fn depth(&self) -> u16;
+    fn set_color(&mut self, color: RGB);
+    /// Starts over with an image filled with `color`
+    fn clear_to(&mut self, color: RGB);
+    fn line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64);
+    fn rect(&mut self, x: f64, y: f64, w: f64, h: f64);
+}
+
+/// Drawing commands recorded instead of drawn, to be replayed later
+///
+/// Uses the same calls, coordinates and colors as a `PPMImg` of the list's size.
+/// Replaying onto a target of another size scales everything to fit it, so the
+/// same list renders a thumbnail, a poster or an svg.
+///
+/// Lists are saved as the text commands `FrameServer` understands, one per line.
+///
+/// For interactive use, `checkpoint` marks the start of a step that `undo` takes
+/// back in one go.
+#[derive(Clone)]
+pub struct DisplayList {
+    height: u32,
+    width: u32,
+    depth: u16,
+    commands: Vec<Command>,
+    /// Lengths of `commands` at each checkpoint, in order
+    checkpoints: Vec<usize>,
+    /// Steps taken back by `undo`, the latest last
+    undone: Vec<Vec<Command>>,
+}
+
+#[allow(dead_code)]
+impl DisplayList {
+    pub fn new(height: u32, width: u32, depth: u16) -> DisplayList {
+        DisplayList {
+            height,
+            width,
+            depth,
+            commands: vec![],
+            checkpoints: vec![],
+            undone: vec![],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn depth(&self) -> u16 {
+        self.depth
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Records `command`; anything undone can no longer be redone
+    pub fn push(&mut self, command: Command) {
+        self.undone.clear();
+        self.commands.push(command);
+    }
+
+    /// Marks the start of a new step for `undo`
+    pub fn checkpoint(&mut self) {
+        if self.checkpoints.last() != Some(&self.commands.len()) {
+            self.checkpoints.push(self.commands.len());
+        }
+    }
+
+    /// Removes the commands since the last checkpoint, or everything if there is none
+    ///
+    /// Returns false if there was nothing to undo
+    pub fn undo(&mut self) -> bool {
+        // a checkpoint right at the end marks an empty step
+        while self.checkpoints.last() == Some(&self.commands.len()) {
+            self.checkpoints.pop();
+        }
+        let start = self.checkpoints.pop().unwrap_or(0);
+        if start == self.commands.len() {
+            return false;
+        }
+        let step = self.commands.split_off(start);
+        self.undone.push(step);
+        true
+    }
+
+    /// Puts back the last step taken back by `undo`
+    ///
+    /// Returns false if there was nothing to redo
+    pub fn redo(&mut self) -> bool {
+        match self.undone.pop() {
+            Some(step) => {
+                // the step comes back whole, so whatever follows is a new one
+                self.checkpoint();
+                self.commands.extend(step);
+                self.checkpoint();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Color of everything drawn after this, like setting `PPMImg::fg_color`
+    pub fn set_color(&mut self, color: RGB) {
+        self.push(Command::Color(color));
+    }
+
+    /// Color `clear` fills with, like setting `PPMImg::bg_color`
+    pub fn set_bg_color(&mut self, color: RGB) {
+        self.push(Command::Background(color));
+    }
+
+    pub fn clear(&mut self) {
+        self.push(Command::Clear);
+    }
+
+    pub fn plot(&mut self, x: f64, y: f64) {
+        self.push(Command::Plot(x, y));
+    }
+
+    pub fn draw_line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64) {
+        self.push(Command::Line([x0, y0, x1, y1]));
+    }
+
+    pub fn fill_rect(&mut self, x: f64, y: f64, w: f64, h: f64) {
+        self.push(Command::FillRect([x, y, w, h]));
+    }
+
+    /// Records every edge of `m`, using x and y
+    pub fn render_edge_matrix(&mut self, m: &EdgeMatrix) {
+        assert_eq!(m.rows() % 2, 0, "Number of edges must be a multiple of 2");
+        let mut iter = m.iter_by_row();
+        while let (Some(p0), Some(p1)) = (iter.next(), iter.next()) {
+            self.draw_line(p0[0], p0[1], p1[0], p1[1]);
+        }
+    }
+
+    /// Runs every command on `target`, scaled from this list's size to the target's
+    ///
+    /// Plotted points become rectangles as big as one pixel of the list.
+    /// The target starts out drawing in white on black, like a new `PPMImg`.
+    pub fn replay<T: DrawTarget>(&self, target: &mut T) {
+        let sx = f64::from(target.width()) / f64::from(self.width);
+        let sy = f64::from(target.height()) / f64::from(self.height);
+        let depth = target.depth();
+        let color = |c: RGB| RGB::from_fractions(c.to_fractions(self.depth), depth);
+        let mut bg = RGB {
+            red: 0,
+            green: 0,
+            blue: 0,
+        };
+        target.set_color(RGB {
+            red: depth,
+            green: depth,
+            blue: depth,
+        });
+        for c in self.commands.iter() {
+            match *c {
+                Command::Color(c) => target.set_color(color(c)),
+                Command::Background(c) => bg = color(c),
+                Command::Clear => target.clear_to(bg),
+                Command::Plot(x, y) => target.rect(x * sx, y * sy, sx, sy),
+                Command::Line([x0, y0, x1, y1]) => target.line(x0 * sx, y0 * sy, x1 * sx, y1 * sy),
+                Command::FillRect([x, y, w, h]) => target.rect(x * sx, y * sy, w * sx, h * sy),
+            }
+        }
+    }
+
+    /// Writes the list as text commands, starting with its size
+    pub fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "size {} {} {}", self.height, self.width, self.depth)?;
+        for c in self.commands.iter() {
+            match c {
+                Command::Color(c) => writeln!(out, "color {} {} {}", c.red, c.green, c.blue)?,
+                Command::Background(c) => writeln!(out, "bg {} {} {}", c.red, c.green, c.blue)?,
+                Command::Clear => writeln!(out, "clear")?,
+                Command::Plot(x, y) => writeln!(out, "plot {} {}", x, y)?,
+                Command::Line([x0, y0, x1, y1]) => {
+                    writeln!(out, "line {} {} {} {}", x0, y0, x1, y1)?
+                }
+                Command::FillRect([x, y, w, h]) => writeln!(out, "rect {} {} {} {}", x, y, w, h)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a list written by `write`; blank lines are skipped
+    pub fn read_from<R: BufRead>(input: R) -> io::Result<DisplayList> {
+        fn invalid(msg: String) -> io::Error {
+            io::Error::new(io::ErrorKind::InvalidData, msg)
+        }
+
+        let mut list: Option<DisplayList> = None;
+        for line in input.lines() {
+            let line = line?;
+            let mut words = line.split_whitespace();
+            let cmd = match words.next() {
+                Some(cmd) => cmd,
+                None => continue,
+            };
+            let v = words
+                .map(|w| w.parse::<f64>())
+                .collect::<Result<Vec<f64>, _>>()
+                .map_err(|_| invalid(format!("Bad number in '{}'", line)))?;
+            let n = match cmd {
+                "clear" => 0,
+                "plot" => 2,
+                "size" | "color" | "bg" => 3,
+                "line" | "rect" => 4,
+                _ => return Err(invalid(format!("Unknown command '{}'", cmd))),
+            };
+            if v.len() != n {
+                return Err(invalid(format!(
+                    "{} takes {} numbers, got {}",
+                    cmd,
+                    n,
+                    v.len()
+                )));
+            }
+            let rgb = || RGB {
+                red: v[0] as u16,
+                green: v[1] as u16,
+                blue: v[2] as u16,
+            };
+            if cmd == "size" {
+                list = Some(DisplayList::new(v[0] as u32, v[1] as u32, v[2] as u16));
+                continue;
+            }
+            let list = list
+                .as_mut()
+                .ok_or_else(|| invalid("Display list must start with size".to_string()))?;
+            list.push(match cmd {
+                "clear" => Command::Clear,
+                "plot" => Command::Plot(v[0], v[1]),
+                "color" => Command::Color(rgb()),
+                "bg" => Command::Background(rgb()),
+                "line" => Command::Line([v[0], v[1], v[2], v[3]]),
+                _ => Command::FillRect([v[0], v[1], v[2], v[3]]),
+            });
+        }
+        list.ok_or_else(|| invalid("Empty display list".to_string()))
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn save(&self, filepath: &str) -> io::Result<()> {
+        let mut file = super::utils::create_file(filepath);
+        self.write(&mut file)?;
+        file.flush()
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn load(filepath: &str) -> io::Result<DisplayList> {
+        DisplayList::read_from(BufReader::new(File::open(filepath)?))
+    }
+}
+
+impl DrawTarget for PPMImg {
+    fn width(&self) -> u32 {
+        PPMImg::width(self)
+    }
+
+    fn height(&self) -> u32 {
+        PPMImg::height(self)
+    }
+
+    fn depth(&self) -> u16 {
+        PPMImg::depth(self)
+    }
+
+    fn set_color(&mut self, color: RGB) {
+        self.fg_color = color;
+    }
+
+    fn clear_to(&mut self, color: RGB) {
+        self.bg_color = color;
+        self.clear();
+    }
+
+    fn line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64) {
+        self.draw_line(x0, y0, x1, y1);
+    }
+
+    /// Flipped like lines under `YAxis::Up`, so a plotted point lands where a
+    /// line through it does
+    fn rect(&mut self, x: f64, y: f64, w: f64, h: f64) {
+        let y = match self.y_axis {
+            YAxis::Down => y,
+            YAxis::Up => f64::from(self.height()) - y - h,
+        };
+        // round both corners, so scaled rectangles tile without gaps
+        let (x0, y0) = (x.round(), y.round());
+        let (x1, y1) = ((x + w).round(), (y + h).round());
+        if x1 > x0 && y1 > y0 {
+            self.fill_rect(x0 as i32, y0 as i32, (x1 - x0) as u32, (y1 - y0) as u32);
+        }
+    }
+}
+
+impl DrawTarget for Svg {
+    fn width(&self) -> u32 {
+        Svg::width(self)
+    }
+
+    fn height(&self) -> u32 {
+        Svg::height(self)
+    }
+
+    fn depth(&self) -> u16 {
+        Svg::depth(self)
+    }
+
+    fn set_color(&mut self, color: RGB) {
+        self.fg_color = color;
+    }
+
+    fn clear_to(&mut self, color: RGB) {
+        self.clear();
+        self.bg_color = Some(color);
+    }
+
+    fn line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64) {
+        self.draw_line(x0, y0, x1, y1);
+    }
+
+    fn rect(&mut self, x: f64, y: f64, w: f64, h: f64) {
+        self.fill_rect(x, y, w, h);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_and_redo_steps() {
+        let mut list = DisplayList::new(4, 4, 255);
+        list.plot(0.0, 0.0);
+        list.checkpoint();
+        list.plot(1.0, 1.0);
+        list.plot(2.0, 2.0);
+        list.checkpoint();
+        list.plot(3.0, 3.0);
+
+        assert!(list.undo());
+        assert_eq!(list.len(), 3);
+        assert!(list.undo());
+        assert_eq!(list.len(), 1);
+        assert!(list.redo());
+        assert_eq!(list.len(), 3);
+        // new commands drop what's left to redo
+        list.clear();
+        assert!(!list.redo());
+        assert!(list.undo());
+        assert!(list.undo());
+        assert!(list.undo());
+        assert!(list.is_empty());
+        assert!(!list.undo());
+    }
+
+    #[test]
+    fn y_up_flips_points_like_lines() {
+        let mut list = DisplayList::new(4, 4, 255);
+        list.plot(0.0, 0.0);
+        list.draw_line(1.0, 0.0, 3.0, 0.0);
+        let mut img = PPMImg::new(4, 4, 255);
+        img.y_axis = YAxis::Up;
+        list.replay(&mut img);
+        assert!((0..4).all(|x| img.pixel(x, 3).red == 255));
+        assert!((0..4).all(|x| img.pixel(x, 0).red == 0));
+    }
+
+    #[test]
+    fn replays_scaled_and_round_trips() {
+        let mut list = DisplayList::new(4, 4, 255);
+        list.set_bg_color(RGB {
+            red: 0,
+            green: 0,
+            blue: 255,
+        });
+        list.clear();
+        list.fill_rect(0.0, 0.0, 2.0, 1.0);
+        list.plot(3.0, 3.0);
+
+        let mut text = vec![];
+        list.write(&mut text).unwrap();
+        let list = DisplayList::read_from(&text[..]).unwrap();
+        assert_eq!(list.len(), 4);
+
+        // twice the size: every pixel of the list covers 2x2 pixels
+        let mut img = PPMImg::new(8, 8, 255);
+        list.replay(&mut img);
+        let lit: Vec<(u32, u32)> = (0..8)
+            .flat_map(|y| (0..8).map(move |x| (x, y)))
+            .filter(|&(x, y)| img.pixel(x, y).red == 255)
+            .collect();
+        let mut expected: Vec<(u32, u32)> =
+            (0..2).flat_map(|y| (0..4).map(move |x| (x, y))).collect();
+        expected.extend(&[(6, 6), (7, 6), (6, 7), (7, 7)]);
+        assert_eq!(lit, expected);
+        assert_eq!(img.pixel(5, 5).blue, 255);
+    }
+}
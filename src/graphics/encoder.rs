@@ -0,0 +1,211 @@
+use super::{encode_binary_pixels, PPMImg, RGB};
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::BufWriter;
+use std::io::{self, Write};
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Writes images in a file format, a row at a time
+///
+/// `PPMImg::encode` calls `write_header` once, `write_row` once per row from
+/// the top, straight from the image's pixels, then `finish`. Register one with
+/// `register_encoder` for `PPMImg::save` to use it.
+pub trait ImageEncoder {
+    fn write_header(
+        &mut self,
+        out: &mut dyn Write,
+        width: u32,
+        height: u32,
+        depth: u16,
+    ) -> io::Result<()>;
+
+    /// Writes the next row, `width` pixels of max value `depth`
+    fn write_row(&mut self, out: &mut dyn Write, row: &[RGB]) -> io::Result<()>;
+
+    /// Writes whatever comes after the last row; nothing by default
+    fn finish(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        let _ = out;
+        Ok(())
+    }
+}
+
+/// Encodes ppm files, binary (P6) or ascii (P3)
+#[derive(Copy, Clone, Debug)]
+pub struct PpmEncoder {
+    ascii: bool,
+    depth: u16,
+}
+
+#[allow(dead_code)]
+impl PpmEncoder {
+    pub fn binary() -> PpmEncoder {
+        PpmEncoder {
+            ascii: false,
+            depth: 255,
+        }
+    }
+
+    /// One pixel per line, as decimal numbers
+    pub fn ascii() -> PpmEncoder {
+        PpmEncoder {
+            ascii: true,
+            depth: 255,
+        }
+    }
+}
+
+impl ImageEncoder for PpmEncoder {
+    fn write_header(
+        &mut self,
+        out: &mut dyn Write,
+        width: u32,
+        height: u32,
+        depth: u16,
+    ) -> io::Result<()> {
+        self.depth = depth;
+        writeln!(out, "{}", if self.ascii { "P3" } else { "P6" })?;
+        writeln!(out, "{} {} {}", width, height, depth)
+    }
+
+    fn write_row(&mut self, out: &mut dyn Write, row: &[RGB]) -> io::Result<()> {
+        if !self.ascii {
+            return encode_binary_pixels(row, self.depth, out);
+        }
+        for p in row {
+            let [r, g, b] = p.to_array();
+            writeln!(out, "{} {} {}", r, g, b)?;
+        }
+        Ok(())
+    }
+}
+
+type EncoderFactory = Box<dyn Fn() -> Box<dyn ImageEncoder> + Send + Sync>;
+
+/// Encoders registered with `register_encoder`, by lowercase file extension
+static ENCODERS: Mutex<Vec<(String, EncoderFactory)>> = Mutex::new(Vec::new());
+
+/// Makes `PPMImg::save` write files ending in `.{extension}` with an encoder
+/// from `make`, in place of any used for it before, built-in ones included
+///
+/// Extensions are matched ignoring case.
+pub fn register_encoder<F>(extension: &str, make: F)
+where
+    F: Fn() -> Box<dyn ImageEncoder> + Send + Sync + 'static,
+{
+    let extension = extension.to_lowercase();
+    let mut encoders = ENCODERS.lock().unwrap_or_else(|e| e.into_inner());
+    encoders.retain(|(e, _)| *e != extension);
+    encoders.push((extension, Box::new(make)));
+}
+
+/// Encoder `PPMImg::save` uses for files ending in `.{extension}`, if any:
+/// a registered one, or the binary ppm encoder for ppm
+pub fn encoder_for(extension: &str) -> Option<Box<dyn ImageEncoder>> {
+    let extension = extension.to_lowercase();
+    let encoders = ENCODERS.lock().unwrap_or_else(|e| e.into_inner());
+    match encoders.iter().find(|(e, _)| *e == extension) {
+        Some((_, make)) => Some(make()),
+        None if extension == "ppm" => Some(Box::new(PpmEncoder::binary())),
+        None => None,
+    }
+}
+
+// encoding
+#[allow(dead_code)]
+impl PPMImg {
+    /// Writes the image to `out` with `encoder`, row by row without copying
+    /// the pixels
+    pub fn encode(&self, encoder: &mut dyn ImageEncoder, out: &mut dyn Write) -> io::Result<()> {
+        encoder.write_header(out, self.width, self.height, self.depth)?;
+        for row in self.data.chunks(self.width.max(1) as usize) {
+            encoder.write_row(out, row)?;
+        }
+        encoder.finish(out)
+    }
+
+    /// Writes the image to `filepath` in the format its extension calls for,
+    /// see `encoder_for`
+    #[cfg(feature = "fs")]
+    pub fn save(&self, filepath: &str) -> io::Result<()> {
+        let extension = Path::new(filepath)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("");
+        let mut encoder = encoder_for(extension).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("No encoder for files ending in .{}", extension),
+            )
+        })?;
+        let mut file = File::create(filepath).map(BufWriter::new)?;
+        self.encode(encoder.as_mut(), &mut file)?;
+        file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Size, then every channel as one byte, and a trailer
+    struct Raw;
+
+    impl ImageEncoder for Raw {
+        fn write_header(
+            &mut self,
+            out: &mut dyn Write,
+            width: u32,
+            height: u32,
+            _: u16,
+        ) -> io::Result<()> {
+            out.write_all(&[width as u8, height as u8])
+        }
+
+        fn write_row(&mut self, out: &mut dyn Write, row: &[RGB]) -> io::Result<()> {
+            for p in row {
+                out.write_all(&p.to_array().map(|c| c as u8))?;
+            }
+            Ok(())
+        }
+
+        fn finish(&mut self, out: &mut dyn Write) -> io::Result<()> {
+            out.write_all(b"END")
+        }
+    }
+
+    #[test]
+    fn registered_encoders_are_used() {
+        let mut img = PPMImg::new(1, 2, 255);
+        img.set_pixel(1, 0, RGB::new(1, 2, 3));
+        assert!(encoder_for("raw_test").is_none());
+        register_encoder("RAW_TEST", || Box::new(Raw));
+        let mut out = Vec::new();
+        img.encode(encoder_for("raw_test").unwrap().as_mut(), &mut out)
+            .unwrap();
+        assert_eq!(out, b"\x02\x01\x00\x00\x00\x01\x02\x03END");
+
+        let mut ppm = Vec::new();
+        img.encode(encoder_for("PPM").unwrap().as_mut(), &mut ppm)
+            .unwrap();
+        let mut expected = Vec::new();
+        img.encode_binary(&mut expected).unwrap();
+        assert_eq!(ppm, expected);
+
+        let mut ascii = Vec::new();
+        img.encode(&mut PpmEncoder::ascii(), &mut ascii).unwrap();
+        assert_eq!(ascii, b"P3\n2 1 255\n0 0 0\n1 2 3\n");
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn save_needs_a_known_format() {
+        let img = PPMImg::new(1, 1, 255);
+        let err = img.save("image.unknown_format").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = img.save("no_such_dir/image.ppm").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
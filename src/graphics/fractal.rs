@@ -0,0 +1,246 @@
+use super::matrix::EdgeMatrix;
+use super::plot::{Palette, Viewport};
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+use super::{PPMImg, RGB};
+use alloc::vec::Vec;
+
+/// Points escape once |z| is bigger than this; large, so smooth coloring is smooth
+const ESCAPE_RADIUS: f64 = 256.0;
+
+/// Sets of points c where z -> z^2 + c stays bounded
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Fractal {
+    /// Starting from z = 0, with c the point
+    Mandelbrot,
+    /// Starting from z = the point, with this c
+    Julia([f64; 2]),
+}
+
+/// How to render a `Fractal` by escape time
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EscapeTime {
+    pub fractal: Fractal,
+    /// Points still bounded after this many iterations are in the set
+    pub max_iterations: u32,
+    /// Count fractions of iterations, for bands that blend into each other
+    pub smooth: bool,
+}
+
+#[allow(dead_code)]
+impl EscapeTime {
+    /// Smooth rendering of `fractal`
+    pub fn new(fractal: Fractal, max_iterations: u32) -> EscapeTime {
+        assert!(max_iterations > 0, "Need at least 1 iteration");
+        EscapeTime {
+            fractal,
+            max_iterations,
+            smooth: true,
+        }
+    }
+
+    /// Iterations it takes the point (x, y) to escape, None if it is in the set
+    pub fn value(&self, x: f64, y: f64) -> Option<f64> {
+        let ((mut zx, mut zy), (cx, cy)) = match self.fractal {
+            Fractal::Mandelbrot => ((0.0, 0.0), (x, y)),
+            Fractal::Julia([cx, cy]) => ((x, y), (cx, cy)),
+        };
+        for n in 0..self.max_iterations {
+            let r2 = zx * zx + zy * zy;
+            if r2 > ESCAPE_RADIUS * ESCAPE_RADIUS {
+                if !self.smooth {
+                    return Some(f64::from(n));
+                }
+                // log2(log |z|), dropping by 1 every iteration once |z| is big
+                let nu = (r2.ln() / 2.0).ln() / core::f64::consts::LN_2;
+                return Some((f64::from(n) + 1.0 - nu).max(0.0));
+            }
+            let x = zx * zx - zy * zy + cx;
+            zy = 2.0 * zx * zy + cy;
+            zx = x;
+        }
+        None
+    }
+
+    /// Value of every pixel of `vp`'s screen, row major from the top, NaN in the set
+    ///
+    /// Ready for `PPMImg::draw_heatmap` with the same viewport.
+    pub fn grid(&self, vp: &Viewport) -> Vec<f64> {
+        let r = vp.screen;
+        (0..r.h)
+            .flat_map(|y| (0..r.w).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let (dx, dy) = vp.to_data(f64::from(r.x + x as i32), f64::from(r.y + y as i32));
+                self.value(dx, dy).unwrap_or(f64::NAN)
+            })
+            .collect()
+    }
+}
+
+// fractals
+#[allow(dead_code)]
+impl PPMImg {
+    /// Renders `fractal` over `vp`: points in the set get fg_color, and the rest
+    /// a color of `palette`, from its start for points escaping right away to its
+    /// end for points escaping at the last iteration
+    pub fn draw_fractal(&mut self, vp: &Viewport, fractal: &EscapeTime, palette: &Palette) {
+        let fg = self.fg_color;
+        let r = vp.screen;
+        let max = f64::from(fractal.max_iterations);
+        for (i, v) in fractal.grid(vp).into_iter().enumerate() {
+            let (x, y) = (r.x + (i as u32 % r.w) as i32, r.y + (i as u32 / r.w) as i32);
+            self.fg_color = if v.is_nan() {
+                fg
+            } else {
+                RGB::from_fractions(palette.color(v / max), self.depth())
+            };
+            self.in_pixels(|img| img.plot(x, y));
+        }
+        self.fg_color = fg;
+    }
+}
+
+// fractal curves
+#[allow(dead_code)]
+impl EdgeMatrix {
+    /// Adds the Koch curve from `p0` to `p1` in the z = 0 plane, `depth` times
+    /// subdivided: 4^depth edges
+    ///
+    /// Bumps stick out to the right going from `p0` to `p1`.
+    pub fn add_koch_curve(&mut self, p0: [f64; 2], p1: [f64; 2], depth: u32) {
+        if depth == 0 {
+            self.add_flat_edge(p0, p1);
+            return;
+        }
+        let d = [(p1[0] - p0[0]) / 3.0, (p1[1] - p0[1]) / 3.0];
+        let a = [p0[0] + d[0], p0[1] + d[1]];
+        let b = [p0[0] + 2.0 * d[0], p0[1] + 2.0 * d[1]];
+        // the tip of an equilateral triangle on the middle third
+        let h = 0.75f64.sqrt();
+        let tip = [
+            (a[0] + b[0]) / 2.0 + d[1] * h,
+            (a[1] + b[1]) / 2.0 - d[0] * h,
+        ];
+        for (q0, q1) in [(p0, a), (a, tip), (tip, b), (b, p1)] {
+            self.add_koch_curve(q0, q1, depth - 1);
+        }
+    }
+
+    /// Adds a Koch snowflake around `center`, its first corner straight up
+    /// `radius` away: 3 * 4^depth edges
+    pub fn add_koch_snowflake(&mut self, center: [f64; 2], radius: f64, depth: u32) {
+        let corner = |i: u32| {
+            let (s, c) = (90.0 + 120.0 * f64::from(i)).to_radians().sin_cos();
+            [center[0] + radius * c, center[1] + radius * s]
+        };
+        // counter clockwise, so bumps go outwards
+        for i in 0..3 {
+            self.add_koch_curve(corner(i), corner(i + 1), depth);
+        }
+    }
+
+    /// Adds the outlines of the triangles of a Sierpinski triangle with corners
+    /// `p0`, `p1` and `p2`: 3 * 3^depth edges
+    pub fn add_sierpinski_triangle(
+        &mut self,
+        p0: [f64; 2],
+        p1: [f64; 2],
+        p2: [f64; 2],
+        depth: u32,
+    ) {
+        if depth == 0 {
+            self.add_flat_edge(p0, p1);
+            self.add_flat_edge(p1, p2);
+            self.add_flat_edge(p2, p0);
+            return;
+        }
+        let mid = |a: [f64; 2], b: [f64; 2]| [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0];
+        let (m01, m12, m20) = (mid(p0, p1), mid(p1, p2), mid(p2, p0));
+        self.add_sierpinski_triangle(p0, m01, m20, depth - 1);
+        self.add_sierpinski_triangle(m01, p1, m12, depth - 1);
+        self.add_sierpinski_triangle(m20, m12, p2, depth - 1);
+    }
+
+    /// Adds the Heighway dragon curve from `p0` to `p1`: 2^depth edges, end to end
+    pub fn add_dragon_curve(&mut self, p0: [f64; 2], p1: [f64; 2], depth: u32) {
+        self.add_dragon(p0, p1, depth, 1.0);
+    }
+
+    /// Dragon from `p0` to `p1`, folding to the left for `turn` 1 and right for -1
+    fn add_dragon(&mut self, p0: [f64; 2], p1: [f64; 2], depth: u32, turn: f64) {
+        if depth == 0 {
+            self.add_flat_edge(p0, p1);
+            return;
+        }
+        let h = [(p1[0] - p0[0]) / 2.0, (p1[1] - p0[1]) / 2.0];
+        let corner = [p0[0] + h[0] - turn * h[1], p0[1] + h[1] + turn * h[0]];
+        self.add_dragon(p0, corner, depth - 1, 1.0);
+        self.add_dragon(corner, p1, depth - 1, -1.0);
+    }
+
+    fn add_flat_edge(&mut self, p0: [f64; 2], p1: [f64; 2]) {
+        self.append_edge([p0[0], p0[1], 0.0]);
+        self.append_edge([p1[0], p1[1], 0.0]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves_have_the_right_number_of_edges() {
+        use crate::graphics::matrix::Matrix;
+
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_koch_curve([0.0, 0.0], [3.0, 0.0], 1);
+        let points: Vec<&[f64]> = m.iter_by_row().collect();
+        assert_eq!(points.len(), 2 * 4);
+        // the bump sticks out to the right of +x: down
+        assert!((points[3][1] + 0.75f64.sqrt()).abs() < 1e-9);
+
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_koch_snowflake([0.0, 0.0], 1.0, 2);
+        assert_eq!(m.rows(), 2 * 3 * 16);
+        // bumps go outwards: the bottom side's reaches the circle of the corners
+        let b = m.bounds().unwrap();
+        assert!(b.min[1] < -0.99 && b.max[1] == 1.0);
+
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_sierpinski_triangle([0.0, 0.0], [1.0, 0.0], [0.5, 1.0], 3);
+        assert_eq!(m.rows(), 2 * 3 * 27);
+
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_dragon_curve([0.0, 0.0], [1.0, 0.0], 6);
+        let points: Vec<&[f64]> = m.iter_by_row().collect();
+        assert_eq!(points.len(), 2 * 64);
+        // one unbroken path from start to end
+        for pair in points.chunks(2).collect::<Vec<_>>().windows(2) {
+            assert_eq!(pair[0][1], pair[1][0]);
+        }
+        assert_eq!(points[points.len() - 1], &[1.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn points_escape_or_stay() {
+        let m = EscapeTime::new(Fractal::Mandelbrot, 100);
+        assert_eq!(m.value(0.0, 0.0), None);
+        assert_eq!(m.value(-1.0, 0.0), None);
+        let (near, far) = (m.value(-0.75, 0.1).unwrap(), m.value(2.0, 2.0).unwrap());
+        assert!(near > far && far < 2.0);
+        // c = 0 gives the unit disk
+        let j = EscapeTime::new(Fractal::Julia([0.0, 0.0]), 100);
+        assert_eq!(j.value(0.99, 0.0), None);
+        assert!(j.value(1.01, 0.0).is_some());
+
+        let mut img = PPMImg::new(20, 30, 255);
+        img.fg_color = RGB::from_fractions([0.0, 0.0, 1.0], 255);
+        let vp = Viewport::full(&img, (-2.0, 1.0), (-1.0, 1.0));
+        img.draw_fractal(&vp, &m, &Palette::grayscale());
+        // the middle of the main bulb is in the set; corners escape fast and stay dark
+        let (x, y) = vp.to_screen(-0.2, 0.0);
+        assert_eq!(img.pixel(x.round() as u32, y.round() as u32).blue, 255);
+        let corner = img.pixel(0, 0);
+        assert!(corner.blue == corner.red && corner.red < 10);
+    }
+}
@@ -0,0 +1,736 @@
+use super::background::Sky;
+use super::camera::Camera;
+use super::light::{phong, Light};
+use super::material::Material;
+use super::matrix::PolygonMatrix;
+use super::pick::FaceId;
+use super::raster::{fill_triangle, ZBuffer};
+use super::rng::Rng;
+use super::shader::{rasterize, Fragment, Vertex};
+use super::shadow::ShadowMap;
+use super::stats::RenderStats;
+use super::utils::{cross3, dot3, normalize3, sub3, xyz};
+use super::{PPMImg, RGB};
+
+/// Texture coordinates and tangents at the corners of a triangle, for textures
+/// and normal maps
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TriangleUv {
+    pub uv: [[f64; 2]; 3],
+    /// (x, y, z, w) as returned by `PolygonMatrix::tangents`
+    pub tangent: [[f64; 4]; 3],
+}
+
+#[allow(dead_code)]
+impl TriangleUv {
+    /// Those of every triangle of `mesh`, in order, with `uvs` the texture
+    /// coordinates of every point
+    pub fn from_mesh(mesh: &PolygonMatrix, uvs: &[[f64; 2]]) -> Vec<TriangleUv> {
+        let tangents = mesh.tangents(uvs);
+        let tangents: Vec<&[f64]> = tangents.iter_by_row().collect();
+        uvs.chunks(3)
+            .zip(tangents.chunks(3))
+            .map(|(uv, t)| TriangleUv {
+                uv: [uv[0], uv[1], uv[2]],
+                tangent: [0, 1, 2].map(|i| [t[i][0], t[i][1], t[i][2], t[i][3]]),
+            })
+            .collect()
+    }
+}
+
+/// The closest surface at every pixel: its depth, position, normal and material
+///
+/// Filled by a geometry pass (`rasterize`) and turned into colors later by a
+/// lighting pass (`shade`), so lighting is only worked out once per pixel,
+/// and post effects and debug views can look at the surfaces themselves.
+#[derive(Clone)]
+pub struct GBuffer {
+    width: u32,
+    height: u32,
+    depth: ZBuffer,
+    position: Vec<[f64; 3]>,
+    normal: Vec<[f64; 3]>,
+    /// Texture coordinates, for surfaces rasterized with them
+    uv: Vec<Option<[f64; 2]>>,
+    tangent: Vec<[f64; 4]>,
+    /// How much uv changes one pixel to the right, and one down, for mipmaps
+    duv: Vec<[[f64; 2]; 2]>,
+    material: Vec<Option<Material>>,
+    face: Vec<usize>,
+}
+
+#[allow(dead_code)]
+impl GBuffer {
+    /// Empty buffer: no surface anywhere
+    pub fn new(height: u32, width: u32) -> GBuffer {
+        let n = (width * height) as usize;
+        GBuffer {
+            width,
+            height,
+            depth: ZBuffer::new(height, width),
+            position: vec![[0.0; 3]; n],
+            normal: vec![[0.0; 3]; n],
+            uv: vec![None; n],
+            tangent: vec![[0.0; 4]; n],
+            duv: vec![[[0.0; 2]; 2]; n],
+            material: vec![None; n],
+            face: vec![0; n],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "Index out of bound");
+        (y * self.width + x) as usize
+    }
+
+    /// Camera space depth of the surface at (x, y), infinity if there is none
+    pub fn depth(&self, x: u32, y: u32) -> f64 {
+        self.depth.depth(x, y)
+    }
+
+    /// World space position of the surface at (x, y)
+    pub fn position(&self, x: u32, y: u32) -> Option<[f64; 3]> {
+        let i = self.index(x, y);
+        self.material[i].as_ref().map(|_| self.position[i])
+    }
+
+    /// Unit world space normal of the surface at (x, y), on the side facing the camera
+    pub fn normal(&self, x: u32, y: u32) -> Option<[f64; 3]> {
+        let i = self.index(x, y);
+        self.material[i].as_ref().map(|_| self.normal[i])
+    }
+
+    /// Texture coordinates of the surface at (x, y), if it has any, see `rasterize_textured`
+    pub fn uv(&self, x: u32, y: u32) -> Option<[f64; 2]> {
+        self.uv[self.index(x, y)]
+    }
+
+    /// Color of the surface at pixel `i` as fractions of `depth`, with its
+    /// material's texture, if any, over the pixel's area, see `Texture::sample_area`
+    fn base_color(&self, i: usize, depth: u16) -> [f64; 3] {
+        let material = self.material[i].as_ref().unwrap();
+        let base = material.base_color(depth);
+        match (&material.texture, self.uv[i]) {
+            (Some(texture), Some(uv)) => {
+                let [dx, dy] = self.duv[i];
+                let t = texture.sample_area(uv, dx, dy);
+                [0, 1, 2].map(|c| base[c] * t[c])
+            }
+            _ => base,
+        }
+    }
+
+    /// Normal to light the surface at pixel `i` with, bent by its material's normal map
+    fn shading_normal(&self, i: usize) -> [f64; 3] {
+        match (&self.material[i], self.uv[i]) {
+            (Some(m), Some(uv)) => m.shading_normal(self.normal[i], self.tangent[i], uv),
+            _ => self.normal[i],
+        }
+    }
+
+    pub fn material(&self, x: u32, y: u32) -> Option<&Material> {
+        self.material[self.index(x, y)].as_ref()
+    }
+
+    /// Triangle covering (x, y): its index in the slice given to `rasterize`
+    pub fn face(&self, x: u32, y: u32) -> Option<FaceId> {
+        let i = self.index(x, y);
+        self.material[i].as_ref().map(|_| FaceId(self.face[i]))
+    }
+
+    /// Closest and farthest depth of any surface, None if there are none
+    pub fn depth_range(&self) -> Option<(f64, f64)> {
+        let mut range: Option<(f64, f64)> = None;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let d = self.depth(x, y);
+                if d.is_finite() {
+                    range = Some(range.map_or((d, d), |(lo, hi)| (lo.min(d), hi.max(d))));
+                }
+            }
+        }
+        range
+    }
+
+    /// Geometry pass: stores the closest of `triangles` at every pixel, as seen by `camera`
+    ///
+    /// Triangles are in world space. Those crossing the camera's near plane are left out.
+    pub fn rasterize(
+        &mut self,
+        triangles: &[([[f64; 3]; 3], Material)],
+        camera: &Camera,
+    ) -> RenderStats {
+        self.rasterize_faces(triangles, 0..triangles.len(), camera)
+    }
+
+    /// Like `rasterize`, keeping texture coordinates and tangents from `uvs`,
+    /// one per triangle, for materials with a texture or normal map
+    pub fn rasterize_textured(
+        &mut self,
+        triangles: &[([[f64; 3]; 3], Material)],
+        uvs: &[TriangleUv],
+        camera: &Camera,
+    ) -> RenderStats {
+        assert_eq!(
+            triangles.len(),
+            uvs.len(),
+            "Every triangle must have texture coordinates"
+        );
+        self.rasterize_with(triangles, Some(uvs), 0..triangles.len(), camera)
+    }
+
+    /// Geometry pass over only the triangles at indices `faces` of `triangles`
+    ///
+    /// Face ids still count from the start of `triangles`, so the others can be
+    /// skipped, like those of objects out of view, without renumbering.
+    pub fn rasterize_faces<I>(
+        &mut self,
+        triangles: &[([[f64; 3]; 3], Material)],
+        faces: I,
+        camera: &Camera,
+    ) -> RenderStats
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        self.rasterize_with(triangles, None, faces, camera)
+    }
+
+    fn rasterize_with<I>(
+        &mut self,
+        triangles: &[([[f64; 3]; 3], Material)],
+        uvs: Option<&[TriangleUv]>,
+        faces: I,
+        camera: &Camera,
+    ) -> RenderStats
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        let mut stats = RenderStats::default();
+        let (width, height) = (self.width, self.height);
+        let (w, h) = (f64::from(width), f64::from(height));
+        let view = camera.view_matrix();
+        for face in faces {
+            let (tri, material) = &triangles[face];
+            let cam = tri.map(|p| xyz(&view.transform_point(&[p[0], p[1], p[2], 1.0])));
+            let screen = [
+                camera.project_camera_point(cam[0], w, h),
+                camera.project_camera_point(cam[1], w, h),
+                camera.project_camera_point(cam[2], w, h),
+            ];
+            let screen = match screen {
+                [Some(a), Some(b), Some(c)] => [a, b, c],
+                _ => {
+                    stats.triangles_culled += 1;
+                    continue;
+                }
+            };
+            stats.triangles_filled += 1;
+            let normal = normalize3(cross3(sub3(tri[1], tri[0]), sub3(tri[2], tri[0])));
+            let mut store = |x: u32, y: u32, z: f64, wt: [f64; 3], frag: Option<&Fragment>| {
+                if !self.depth.test_and_set(x, y, z) {
+                    stats.zbuffer_rejections += 1;
+                    return;
+                }
+                let mut p = [0.0; 3];
+                for (i, c) in p.iter_mut().enumerate() {
+                    *c = wt[0] * tri[0][i] + wt[1] * tri[1][i] + wt[2] * tri[2][i];
+                }
+                // keep whichever side faces the camera
+                let n = if dot3(normal, sub3(camera.eye, p)) < 0.0 {
+                    [-normal[0], -normal[1], -normal[2]]
+                } else {
+                    normal
+                };
+                let i = (y * width + x) as usize;
+                self.position[i] = p;
+                self.normal[i] = n;
+                self.uv[i] = frag.map(|f| f.uv);
+                if let Some(f) = frag {
+                    self.tangent[i] = f.tangent;
+                    self.duv[i] = [f.duv_dx, f.duv_dy];
+                }
+                self.material[i] = Some(material.clone());
+                self.face[i] = face;
+            };
+            match uvs.map(|uvs| uvs[face]) {
+                Some(corners) => {
+                    let vertex = |k: usize| Vertex {
+                        screen: screen[k],
+                        normal,
+                        uv: corners.uv[k],
+                        tangent: corners.tangent[k],
+                    };
+                    rasterize(width, height, &[vertex(0), vertex(1), vertex(2)], |f| {
+                        store(f.x, f.y, f.depth, f.weights, Some(f))
+                    });
+                }
+                None => fill_triangle(width, height, screen, |x, y, z, wt| {
+                    store(x, y, z, wt, None)
+                }),
+            }
+        }
+        stats
+    }
+
+    /// Lighting pass: Phong shades every pixel with a surface into `img`, seen from `eye`
+    ///
+    /// `shadow_maps`, if not empty, has one map per light, and keeps each light
+    /// from reaching points it doesn't see. With an `environment`, reflective
+    /// materials mirror it, see `Sky::reflection`. Pixels without a surface are
+    /// left alone.
+    pub fn shade(
+        &self,
+        img: &mut PPMImg,
+        eye: [f64; 3],
+        ambient: [f64; 3],
+        lights: &[Light],
+        shadow_maps: &[ShadowMap],
+        environment: Option<&Sky>,
+    ) {
+        assert!(
+            img.width() == self.width && img.height() == self.height,
+            "Image and G-buffer sizes must match"
+        );
+        let depth = img.depth();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let i = self.index(x, y);
+                let material = match &self.material[i] {
+                    Some(m) => m,
+                    None => continue,
+                };
+                let (p, n) = (self.position[i], self.shading_normal(i));
+                let lit = lights
+                    .iter()
+                    .enumerate()
+                    .filter(|(l, _)| shadow_maps.get(*l).is_none_or(|map| map.is_lit(p)))
+                    .map(|(_, light)| light);
+                let base = self.base_color(i, depth);
+                let mut color = phong(material, base, p, n, eye, ambient, lit);
+                if let Some(env) = environment.filter(|_| material.reflectivity > 0.0) {
+                    let mirrored = env.reflection(normalize3(sub3(p, eye)), n);
+                    for (c, m) in color.iter_mut().zip(mirrored.iter()) {
+                        *c = *c * (1.0 - material.reflectivity) + m * material.reflectivity;
+                    }
+                }
+                img.set_pixel(x, y, RGB::from_fractions(color, depth));
+            }
+        }
+    }
+
+    /// Lighting pass for cel shading: like `shade` without shadows or
+    /// reflections, with the light cut into `bands` flat steps, see `Light::shade_bands`
+    pub fn shade_toon(
+        &self,
+        img: &mut PPMImg,
+        eye: [f64; 3],
+        ambient: [f64; 3],
+        lights: &[Light],
+        bands: u32,
+    ) {
+        let depth = img.depth();
+        self.draw(img, |g, x, y| {
+            let i = g.index(x, y);
+            let material = g.material[i].as_ref().unwrap();
+            let (p, n) = (g.position[i], g.shading_normal(i));
+            let base = g.base_color(i, depth);
+            let mut color = [0, 1, 2].map(|c| base[c] * material.ambient * ambient[c]);
+            for light in lights {
+                let lit = light.shade_bands(material, base, p, n, eye, bands);
+                for (c, l) in color.iter_mut().zip(lit.iter()) {
+                    *c += l;
+                }
+            }
+            color
+        });
+    }
+
+    /// Whether each pixel is on an outline, row by row from the top
+    ///
+    /// Outlines are where a surface ends, against the background or a surface
+    /// farther away, and sharp creases, where the normal turns more than 60
+    /// degrees from one pixel to the next. Only the closer side of an edge is on it.
+    pub fn outlines(&self) -> Vec<bool> {
+        // depth jumps by more than this fraction between neighbors
+        const DEPTH_JUMP: f64 = 0.05;
+        const CREASE_COS: f64 = 0.5;
+        let mut out = vec![false; (self.width * self.height) as usize];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let i = self.index(x, y);
+                let n = match self.normal(x, y) {
+                    Some(n) => n,
+                    None => continue,
+                };
+                let d = self.depth(x, y);
+                let neighbors = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+                out[i] = neighbors.iter().enumerate().any(|(k, &(dx, dy))| {
+                    let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                    if nx < 0
+                        || ny < 0
+                        || nx >= i64::from(self.width)
+                        || ny >= i64::from(self.height)
+                    {
+                        return false;
+                    }
+                    let (nx, ny) = (nx as u32, ny as u32);
+                    match self.normal(nx, ny) {
+                        None => true,
+                        // creases are on both sides: only mark the left and top one
+                        Some(m) => {
+                            self.depth(nx, ny) > d * (1.0 + DEPTH_JUMP)
+                                || (k < 2 && dot3(n, m) < CREASE_COS)
+                        }
+                    }
+                });
+            }
+        }
+        out
+    }
+
+    /// Draws `outlines` into `img` in `color`
+    pub fn draw_outlines(&self, img: &mut PPMImg, color: RGB) {
+        assert!(
+            img.width() == self.width && img.height() == self.height,
+            "Image and G-buffer sizes must match"
+        );
+        for (i, edge) in self.outlines().into_iter().enumerate() {
+            if edge {
+                let i = i as u32;
+                img.set_pixel(i % self.width, i / self.width, color);
+            }
+        }
+    }
+
+    /// Depth as gray: white at `near`, black at `far` and beyond, and where there is no surface
+    pub fn depth_image(&self, near: f64, far: f64, depth: u16) -> PPMImg {
+        let mut img = PPMImg::new(self.height, self.width, depth);
+        self.draw_depth(&mut img, near, far);
+        img
+    }
+
+    /// Normals as colors, black where there is no surface; see `draw_normals`
+    pub fn normal_image(&self, depth: u16) -> PPMImg {
+        let mut img = PPMImg::new(self.height, self.width, depth);
+        self.draw_normals(&mut img);
+        img
+    }
+
+    /// Draws depth as gray into `img`, white at `near` and black at `far` and beyond
+    pub fn draw_depth(&self, img: &mut PPMImg, near: f64, far: f64) {
+        self.draw(img, |g, x, y| {
+            let v = (1.0 - (g.depth(x, y) - near) / (far - near)).clamp(0.0, 1.0);
+            [v, v, v]
+        });
+    }
+
+    /// Draws normals as colors into `img`
+    ///
+    /// x, y and z from -1 to 1 are mapped to red, green and blue from 0 to full.
+    pub fn draw_normals(&self, img: &mut PPMImg) {
+        self.draw(img, |g, x, y| {
+            g.normal[g.index(x, y)].map(|c| (c + 1.0) / 2.0)
+        });
+    }
+
+    /// Draws every triangle into `img` in a flat color of its own, picked from its index
+    pub fn draw_faces(&self, img: &mut PPMImg) {
+        let depth = img.depth();
+        self.draw(img, |g, x, y| {
+            let face = g.face[g.index(x, y)] as u64;
+            Rng::new(face).random_color(depth).to_fractions(depth)
+        });
+    }
+
+    /// Draws every triangle into `img` in the color `FaceId::to_color` gives it
+    ///
+    /// Pixels without a surface are set to black, so `pick` can tell them apart.
+    /// So are faces past the last one img's depth has a color for, which can't
+    /// be picked; use a deeper image for meshes that big.
+    pub fn draw_face_ids(&self, img: &mut PPMImg) {
+        assert!(
+            img.width() == self.width && img.height() == self.height,
+            "Image and G-buffer sizes must match"
+        );
+        let depth = img.depth();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let color = self
+                    .face(x, y)
+                    .and_then(|face| face.to_color(depth))
+                    .unwrap_or_else(|| RGB::from_fractions([0.0; 3], depth));
+                img.set_pixel(x, y, color);
+            }
+        }
+    }
+
+    /// Sets every pixel of `img` with a surface to `color(self, x, y)`, leaving the others alone
+    fn draw<F>(&self, img: &mut PPMImg, color: F)
+    where
+        F: Fn(&GBuffer, u32, u32) -> [f64; 3],
+    {
+        assert!(
+            img.width() == self.width && img.height() == self.height,
+            "Image and G-buffer sizes must match"
+        );
+        let depth = img.depth();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.material[self.index(x, y)].is_some() {
+                    img.set_pixel(x, y, RGB::from_fractions(color(self, x, y), depth));
+                }
+            }
+        }
+    }
+}
+
+// ambient occlusion
+#[allow(dead_code)]
+impl GBuffer {
+    /// How open the surface at every pixel is to light from around it, from 0
+    /// (enclosed) to 1, row by row from the top; 1 where there is no surface
+    ///
+    /// Screen space ambient occlusion: `samples` surfaces within `radius` pixels
+    /// are looked at, and each one above the tangent plane and closer than
+    /// `range` in world space blocks some light. Cheap and rough, since only
+    /// what the camera sees can block anything, but it darkens creases and
+    /// where objects touch.
+    pub fn occlusion(&self, radius: u32, range: f64, samples: usize) -> Vec<f64> {
+        // neighbors just off the tangent plane don't count, or flat surfaces
+        // made of many triangles would darken themselves
+        const BIAS: f64 = 0.1;
+        let mut out = vec![1.0; (self.width * self.height) as usize];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let i = self.index(x, y);
+                if self.material[i].is_none() {
+                    continue;
+                }
+                let (p, n) = (self.position[i], self.normal[i]);
+                // a different pattern at every pixel turns banding into noise
+                let mut rng = Rng::new(i as u64);
+                let mut blocked = 0.0;
+                for _ in 0..samples {
+                    let [dx, dy] = rng.random_in_unit_disk().map(|d| d * f64::from(radius));
+                    let (sx, sy) = (f64::from(x) + dx, f64::from(y) + dy);
+                    if sx < 0.0 || sy < 0.0 {
+                        continue;
+                    }
+                    let q = match (sx as u32, sy as u32) {
+                        (qx, qy) if qx < self.width && qy < self.height => self.position(qx, qy),
+                        _ => None,
+                    };
+                    let v = match q {
+                        Some(q) => sub3(q, p),
+                        None => continue,
+                    };
+                    let d = dot3(v, v).sqrt();
+                    if d > 0.0 && d < range {
+                        let up = dot3(n, v) / d;
+                        blocked += (up - BIAS).max(0.0) * (1.0 - d / range);
+                    }
+                }
+                out[i] = 1.0 - blocked / samples.max(1) as f64;
+            }
+        }
+        out
+    }
+
+    /// Darkens every pixel of `img` by how occluded it is, as returned by
+    /// `occlusion`, times `strength`, from 0 (no change) to 1
+    pub fn apply_occlusion(&self, img: &mut PPMImg, occlusion: &[f64], strength: f64) {
+        assert_eq!(
+            occlusion.len(),
+            (self.width * self.height) as usize,
+            "Occlusion must have one value per pixel"
+        );
+        assert!(
+            img.width() == self.width && img.height() == self.height,
+            "Image and G-buffer sizes must match"
+        );
+        let depth = img.depth();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let open = 1.0 - (1.0 - occlusion[self.index(x, y)]) * strength;
+                let c = img.pixel(x, y).to_fractions(depth);
+                img.set_pixel(x, y, RGB::from_fractions(c.map(|c| c * open), depth));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::matrix::Matrix;
+    use crate::graphics::scene::{Geometry, Node, SceneGraph};
+    use crate::graphics::texture::Texture;
+
+    #[test]
+    fn surfaces_are_stored_then_lit() {
+        let mut tri = Matrix::new(0, 4, vec![]);
+        tri.append_edge([-4.0, -4.0, 0.0]);
+        tri.append_edge([4.0, -4.0, 0.0]);
+        tri.append_edge([0.0, 4.0, 0.0]);
+        let mut scene = SceneGraph::new();
+        scene
+            .root
+            .add_child(Node::new("tri").with_geometry(Geometry::Polygons(tri)));
+        scene.lights.push(Light::white([0.0, 0.0, -5.0]));
+        let camera = Camera::new([0.0, 0.0, -5.0], [0.0, 0.0, 0.0]);
+        let white = RGB::from_fractions([1.0, 1.0, 1.0], 255);
+
+        let gbuf = scene.render_gbuffer(&camera, 20, 20, Material::new(white));
+        assert!((gbuf.depth(10, 10) - 5.0).abs() < 1e-9);
+        let n = gbuf.normal(10, 10).unwrap();
+        assert!(
+            (n[2] + 1.0).abs() < 1e-9,
+            "normal faces the camera: {:?}",
+            n
+        );
+        assert!(gbuf.depth(0, 0).is_infinite() && gbuf.material(0, 0).is_none());
+
+        let mut img = PPMImg::new(20, 20, 255);
+        gbuf.shade(&mut img, camera.eye, [0.0; 3], &scene.lights, &[], None);
+        assert!(img.pixel(10, 10).red > 0 && img.pixel(0, 0).red == 0);
+        assert!(gbuf.normal_image(255).pixel(10, 10).blue == 0);
+    }
+
+    /// Lit square facing the camera, drawn in `material` with the texture
+    /// stretched over it once
+    fn render_square(material: Material) -> PPMImg {
+        let corners = [
+            [[-4.0, -4.0, 0.0], [4.0, -4.0, 0.0], [4.0, 4.0, 0.0]],
+            [[-4.0, -4.0, 0.0], [4.0, 4.0, 0.0], [-4.0, 4.0, 0.0]],
+        ];
+        let mut square = Matrix::new(0, 4, vec![]);
+        for [a, b, c] in corners {
+            square.add_polygon(a, b, c);
+        }
+        let uvs = [
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [1.0, 1.0],
+            [0.0, 0.0],
+            [1.0, 1.0],
+            [0.0, 1.0],
+        ];
+        let uvs = TriangleUv::from_mesh(&square, &uvs);
+        let camera = Camera::new([0.0, 0.0, -5.0], [0.0; 3]);
+        let lights = [Light::white([0.0, 0.0, -5.0])];
+        let triangles = corners.map(|t| (t, material.clone()));
+        let mut gbuf = GBuffer::new(20, 20);
+        gbuf.rasterize_textured(&triangles, &uvs, &camera);
+        let mut img = PPMImg::new(20, 20, 255);
+        gbuf.shade(&mut img, camera.eye, [0.0; 3], &lights, &[], None);
+        img
+    }
+
+    #[test]
+    fn normal_maps_change_the_lighting() {
+        let white = Material::new(RGB::from_fractions([1.0; 3], 255));
+        let center = |m: Material| render_square(m).pixel(10, 10).red;
+        let flat = center(white.clone());
+        let unchanged = white
+            .clone()
+            .with_normal_map(Texture::Solid([0.5, 0.5, 1.0]));
+        assert_eq!(center(unchanged), flat);
+        // tilted away from the light
+        let tilted = white.with_normal_map(Texture::Solid([1.0, 0.5, 0.6]));
+        assert!(center(tilted) < flat);
+    }
+
+    #[test]
+    fn textures_color_the_surface() {
+        let white = Material::new(RGB::from_fractions([1.0; 3], 255));
+        let checker = Texture::checkerboard([1.0, 0.0, 0.0], [0.0, 0.0, 1.0], 2.0);
+        let img = render_square(white.with_texture(checker));
+        // neighboring squares of the checkerboard differ, the ones across from each other don't
+        let red = |x, y| img.pixel(x, y).red > img.pixel(x, y).blue;
+        assert_ne!(red(7, 7), red(12, 7));
+        assert_eq!(red(7, 7), red(12, 12));
+    }
+
+    #[test]
+    fn mipmaps_smooth_small_texels() {
+        // black and white pixels, far smaller on screen than a pixel
+        let mut fine = PPMImg::new(64, 64, 255);
+        for y in 0..64 {
+            for x in (y % 2..64).step_by(2) {
+                fine.plot(x, y);
+            }
+        }
+        let white = Material::new(RGB::from_fractions([1.0; 3], 255));
+        let spread = |texture: Texture| {
+            let img = render_square(white.clone().with_texture(texture));
+            let reds: Vec<u16> = (8..12)
+                .flat_map(|y| (8..12).map(move |x| (x, y)))
+                .map(|(x, y)| img.pixel(x, y).red)
+                .collect();
+            reds.iter().max().unwrap() - reds.iter().min().unwrap()
+        };
+        let mut plain = Texture::image(fine.clone());
+        if let Texture::Image { mipmaps, .. } = &mut plain {
+            mipmaps.clear();
+        }
+        assert!(spread(Texture::image(fine)) < 20);
+        assert!(spread(plain) > 100);
+    }
+
+    #[test]
+    fn creases_are_occluded() {
+        let white = Material::new(RGB::from_fractions([1.0; 3], 255));
+        let quad = |a: [f64; 3], b: [f64; 3], c: [f64; 3], d: [f64; 3]| {
+            [([a, b, c], white.clone()), ([a, c, d], white.clone())]
+        };
+        // a wall standing at the back of a floor
+        let mut triangles = vec![];
+        triangles.extend(quad(
+            [-4.0, 0.0, -4.0],
+            [4.0, 0.0, -4.0],
+            [4.0, 0.0, 1.0],
+            [-4.0, 0.0, 1.0],
+        ));
+        triangles.extend(quad(
+            [-4.0, 0.0, 1.0],
+            [4.0, 0.0, 1.0],
+            [4.0, 4.0, 1.0],
+            [-4.0, 4.0, 1.0],
+        ));
+        let camera = Camera::new([0.0, 3.0, -5.0], [0.0, 0.5, 1.0]);
+        let mut gbuf = GBuffer::new(60, 60);
+        gbuf.rasterize(&triangles, &camera);
+
+        let ao = gbuf.occlusion(6, 1.0, 16);
+        let floor_average = |near: f64, far: f64| {
+            let values: Vec<f64> = (0..60 * 60)
+                .filter_map(|i| {
+                    let p = gbuf.position(i % 60, i / 60)?;
+                    (p[1] == 0.0 && p[2] > near && p[2] < far).then(|| ao[i as usize])
+                })
+                .collect();
+            values.iter().sum::<f64>() / values.len() as f64
+        };
+        assert!(floor_average(0.5, 1.0) < 0.95);
+        assert!(floor_average(-2.0, -1.0) > 0.99);
+
+        let mut img = PPMImg::new(60, 60, 255);
+        img.bg_color = img.fg_color;
+        img.clear();
+        gbuf.apply_occlusion(&mut img, &ao, 1.0);
+        let darkest = (0..ao.len())
+            .min_by(|&a, &b| ao[a].total_cmp(&ao[b]))
+            .unwrap();
+        let pixel = |i: usize| img.pixel(i as u32 % 60, i as u32 / 60).red;
+        assert!(pixel(darkest) < 230);
+        assert_eq!(pixel(ao.iter().position(|&a| a == 1.0).unwrap()), 255);
+    }
+}
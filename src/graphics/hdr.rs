@@ -0,0 +1,285 @@
+#[cfg(feature = "fs")]
+use super::utils::create_file;
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+use super::{PPMImg, RGB};
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Write};
+
+/// How `HdrImg::to_ppm` squeezes unbounded intensities into colors
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ToneMap {
+    /// Intensities above 1 are cut off
+    Clamp,
+    /// ln(1 + v), scaled so the brightest channel of the image becomes 1; shows
+    /// detail across huge ranges, like the density of fractals
+    Log,
+    /// v / (1 + v): dim parts stay about the same, and bright ones never clip
+    Reinhard,
+}
+
+/// Image of unbounded floating point intensities, where drawing adds light
+///
+/// Every hit on a pixel adds up, so the brightness of a pixel is how much landed
+/// on it: for the density of fractals and particle trails, or long exposures.
+/// Turned into a `PPMImg` with a `ToneMap` when done. (0, 0) is the top left.
+#[derive(Clone, Debug)]
+pub struct HdrImg {
+    height: u32,
+    width: u32,
+    data: Vec<[f64; 3]>,
+}
+
+#[allow(dead_code)]
+impl HdrImg {
+    /// Black image
+    pub fn new(height: u32, width: u32) -> HdrImg {
+        HdrImg {
+            height,
+            width,
+            data: vec![[0.0; 3]; (width * height) as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(
+            x < self.width && y < self.height,
+            "Pixel ({}, {}) out of bounds",
+            x,
+            y
+        );
+        (y * self.width + x) as usize
+    }
+
+    /// Red, green and blue intensities of pixel (x, y)
+    pub fn pixel(&self, x: u32, y: u32) -> [f64; 3] {
+        self.data[self.index(x, y)]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: [f64; 3]) {
+        let i = self.index(x, y);
+        self.data[i] = color;
+    }
+
+    /// Adds `color` to pixel (x, y); points off the image are ignored
+    pub fn add(&mut self, x: i32, y: i32, color: [f64; 3]) {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return;
+        }
+        let i = self.index(x as u32, y as u32);
+        for (sum, c) in self.data[i].iter_mut().zip(color.iter()) {
+            *sum += c;
+        }
+    }
+
+    /// Adds `color` at the point (x, y), shared between the 4 pixels around it by
+    /// how close they are, so moving points leave smooth trails
+    pub fn splat(&mut self, x: f64, y: f64, color: [f64; 3]) {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        for (dx, dy, weight) in [
+            (0, 0, (1.0 - fx) * (1.0 - fy)),
+            (1, 0, fx * (1.0 - fy)),
+            (0, 1, (1.0 - fx) * fy),
+            (1, 1, fx * fy),
+        ] {
+            self.add(x0 + dx, y0 + dy, color.map(|c| c * weight));
+        }
+    }
+
+    /// Adds every pixel of `img`, as fractions of its depth, times `weight`
+    pub fn add_image(&mut self, img: &PPMImg, weight: f64) {
+        assert!(
+            self.width == img.width() && self.height == img.height(),
+            "Image and buffer must have the same size"
+        );
+        for (sum, p) in self.data.iter_mut().zip(img.data.iter()) {
+            let c = p.to_fractions(img.depth());
+            for (s, c) in sum.iter_mut().zip(c.iter()) {
+                *s += c * weight;
+            }
+        }
+    }
+
+    /// Brightest channel of any pixel
+    pub fn max(&self) -> f64 {
+        self.data.iter().flatten().fold(0.0, |m, &c| c.max(m))
+    }
+
+    /// Multiplies every intensity by `factor`
+    pub fn scale(&mut self, factor: f64) {
+        for c in self.data.iter_mut().flatten() {
+            *c *= factor;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.data.fill([0.0; 3]);
+    }
+
+    /// Tone maps the image into a new `PPMImg` of `depth`, multiplying
+    /// intensities by `exposure` first
+    pub fn to_ppm(&self, tone: ToneMap, exposure: f64, depth: u16) -> PPMImg {
+        assert!(exposure > 0.0, "Exposure must be positive");
+        let log_scale = 1.0 / (1.0 + self.max() * exposure).ln();
+        let map = |v: f64| {
+            let v = v.max(0.0) * exposure;
+            match tone {
+                ToneMap::Clamp => v.min(1.0),
+                // a black image has no brightest channel to scale by
+                ToneMap::Log if log_scale.is_finite() => (1.0 + v).ln() * log_scale,
+                ToneMap::Log => 0.0,
+                ToneMap::Reinhard => v / (1.0 + v),
+            }
+        };
+        let mut img = PPMImg::new(self.height, self.width, depth);
+        for (p, c) in img.data.iter_mut().zip(self.data.iter()) {
+            *p = RGB::from_fractions(c.map(map), depth);
+        }
+        img
+    }
+}
+
+/// Red, green and blue sharing one exponent, like Radiance hdr files store them
+#[cfg(feature = "std")]
+fn rgbe(c: [f64; 3]) -> [u8; 4] {
+    let c = c.map(|v| v.max(0.0));
+    let v = c[0].max(c[1]).max(c[2]);
+    if v < 1e-32 {
+        return [0; 4];
+    }
+    // v = m * 2^e with m in [0.5, 1)
+    let mut e = v.log2().floor() as i32 + 1;
+    if v / 2f64.powi(e) >= 1.0 {
+        e += 1;
+    }
+    let scale = 256.0 / 2f64.powi(e);
+    let [r, g, b] = c.map(|v| (v * scale).min(255.0) as u8);
+    [r, g, b, (e + 128).clamp(0, 255) as u8]
+}
+
+// export
+#[allow(dead_code)]
+impl HdrImg {
+    /// Writes the image as a Radiance hdr (.hdr) file to `out`, keeping its full range
+    ///
+    /// Scanlines are stored flat, without run length encoding.
+    #[cfg(feature = "std")]
+    pub fn encode_hdr<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "#?RADIANCE")?;
+        writeln!(out, "FORMAT=32-bit_rle_rgbe")?;
+        writeln!(out)?;
+        writeln!(out, "-Y {} +X {}", self.height, self.width)?;
+        for c in self.data.iter() {
+            out.write_all(&rgbe(*c))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the image as a color pfm file to `out`: 32 bit floats, little endian
+    ///
+    /// Pfm stores rows from the bottom up.
+    #[cfg(feature = "std")]
+    pub fn encode_pfm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "PF")?;
+        writeln!(out, "{} {}", self.width, self.height)?;
+        // a negative scale means little endian
+        writeln!(out, "-1.0")?;
+        for row in self.data.chunks(self.width.max(1) as usize).rev() {
+            for v in row.iter().flatten() {
+                out.write_all(&(*v as f32).to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn write_hdr(&self, filepath: &str) -> io::Result<()> {
+        let mut file = create_file(filepath);
+        self.encode_hdr(&mut file)?;
+        file.flush()
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn write_pfm(&self, filepath: &str) -> io::Result<()> {
+        let mut file = create_file(filepath);
+        self.encode_pfm(&mut file)?;
+        file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_add_up_and_tone_map() {
+        let mut hdr = HdrImg::new(2, 3);
+        for _ in 0..9 {
+            hdr.add(0, 0, [1.0, 0.0, 0.0]);
+        }
+        hdr.add(2, 1, [1.0, 0.0, 0.0]);
+        hdr.add(-1, 5, [1.0; 3]);
+        hdr.splat(1.25, 0.0, [0.0, 4.0, 0.0]);
+        assert_eq!(hdr.pixel(0, 0), [9.0, 0.0, 0.0]);
+        assert_eq!(hdr.pixel(1, 0), [0.0, 3.0, 0.0]);
+        assert_eq!(hdr.pixel(2, 0), [0.0, 1.0, 0.0]);
+        assert_eq!(hdr.max(), 9.0);
+
+        let clamp = hdr.to_ppm(ToneMap::Clamp, 1.0, 255);
+        assert_eq!(clamp.pixel(0, 0).red, clamp.pixel(2, 1).red);
+        // the brightest pixel is white, and a single hit still shows
+        let log = hdr.to_ppm(ToneMap::Log, 1.0, 255);
+        assert_eq!(log.pixel(0, 0).red, 255);
+        assert_eq!(log.pixel(2, 1).red, 77);
+        let reinhard = hdr.to_ppm(ToneMap::Reinhard, 3.0, 255);
+        assert_eq!(reinhard.pixel(2, 1).red, 191);
+        assert_eq!(reinhard.pixel(0, 0).red, 246);
+        assert_eq!(
+            HdrImg::new(1, 1)
+                .to_ppm(ToneMap::Log, 1.0, 255)
+                .pixel(0, 0)
+                .red,
+            0
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn exports_keep_the_range() {
+        let mut hdr = HdrImg::new(2, 1);
+        hdr.set_pixel(0, 0, [1.0, 0.5, 0.0]);
+        hdr.set_pixel(0, 1, [1000.0, 0.0, 0.25]);
+
+        let mut out = vec![];
+        hdr.encode_hdr(&mut out).unwrap();
+        let header = "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 1\n";
+        assert!(out.starts_with(header.as_bytes()));
+        let pixels = &out[header.len()..];
+        assert_eq!(&pixels[..4], &[128, 64, 0, 129]);
+        // 1000 = 0.977 * 2^10
+        assert_eq!(&pixels[4..], &[250, 0, 0, 138]);
+
+        let mut out = vec![];
+        hdr.encode_pfm(&mut out).unwrap();
+        let header = "PF\n1 2\n-1.0\n";
+        assert!(out.starts_with(header.as_bytes()));
+        let floats: Vec<f32> = out[header.len()..]
+            .chunks(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        // bottom row first
+        assert_eq!(floats, vec![1000.0, 0.0, 0.25, 1.0, 0.5, 0.0]);
+    }
+}
@@ -0,0 +1,186 @@
+use super::hdr::HdrImg;
+use super::matrix::{EdgeMatrix, Matrix};
+use super::plot::{Palette, Viewport};
+use super::rng::Rng;
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+use super::{PPMImg, RGB};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Points thrown away at the start of the chaos game, before they land on the attractor
+const SETTLE_ITERATIONS: usize = 20;
+
+/// Iterated function system: affine maps, each picked with its own probability
+///
+/// Its attractor, the shape every map together maps onto itself, is drawn with
+/// the chaos game: a point jumps around by randomly picked maps, and lands on
+/// the attractor after a few jumps.
+#[derive(Clone, Debug)]
+pub struct Ifs {
+    /// 4 by 4 transforms, with their weights
+    maps: Vec<(Matrix, f64)>,
+}
+
+#[allow(dead_code)]
+impl Ifs {
+    pub fn new() -> Ifs {
+        Ifs { maps: vec![] }
+    }
+
+    /// Adds the transform `map`, picked `weight` times as often as a map of weight 1
+    ///
+    /// Maps should shrink, or the points fly off.
+    pub fn add_map(&mut self, map: Matrix, weight: f64) {
+        assert!(
+            map.rows() == 4 && map.cols() == 4,
+            "Transform must be 4 by 4"
+        );
+        assert!(weight > 0.0, "Weight must be positive");
+        self.maps.push((map, weight));
+    }
+
+    /// Barnsley's fern, in about x in [-2.2, 2.7] and y in [0, 10]
+    pub fn barnsley_fern() -> Ifs {
+        let mut ifs = Ifs::new();
+        ifs.add_map(Matrix::affine_2d(0.0, 0.0, 0.0, 0.16, 0.0, 0.0), 0.01);
+        ifs.add_map(Matrix::affine_2d(0.85, 0.04, -0.04, 0.85, 0.0, 1.6), 0.85);
+        ifs.add_map(Matrix::affine_2d(0.2, -0.26, 0.23, 0.22, 0.0, 1.6), 0.07);
+        ifs.add_map(Matrix::affine_2d(-0.15, 0.28, 0.26, 0.24, 0.0, 0.44), 0.07);
+        ifs
+    }
+
+    /// Sierpinski triangle with corners (0, 0), (1, 0) and (0.5, 1)
+    pub fn sierpinski() -> Ifs {
+        let mut ifs = Ifs::new();
+        for (e, f) in [(0.0, 0.0), (0.5, 0.0), (0.25, 0.5)] {
+            ifs.add_map(Matrix::affine_2d(0.5, 0.0, 0.0, 0.5, e, f), 1.0);
+        }
+        ifs
+    }
+
+    /// `n` points of the attractor, one (x, y, z, 1) row each, starting at the origin
+    pub fn points(&self, n: usize, seed: u64) -> EdgeMatrix {
+        let mut m = Matrix::new(0, 4, vec![]);
+        self.chaos_game(n, seed, |p| m.append_edge(p));
+        m
+    }
+
+    /// How many of `n` points of the attractor land on every pixel of `vp`'s
+    /// screen, row major from the top
+    pub fn density(&self, vp: &Viewport, n: usize, seed: u64) -> Vec<u32> {
+        let r = vp.screen;
+        let mut counts = vec![0; (r.w * r.h) as usize];
+        self.chaos_game(n, seed, |p| {
+            let (x, y) = vp.to_screen(p[0], p[1]);
+            let (x, y) = ((x - f64::from(r.x)).round(), (y - f64::from(r.y)).round());
+            if x >= 0.0 && y >= 0.0 && x < f64::from(r.w) && y < f64::from(r.h) {
+                counts[y as usize * r.w as usize + x as usize] += 1;
+            }
+        });
+        counts
+    }
+
+    /// Adds `color` to `hdr` for each of `n` points of the attractor landing on
+    /// a pixel of `vp`'s screen, which must fit in `hdr`
+    pub fn accumulate(
+        &self,
+        hdr: &mut HdrImg,
+        vp: &Viewport,
+        n: usize,
+        seed: u64,
+        color: [f64; 3],
+    ) {
+        let r = vp.screen;
+        for (i, count) in self.density(vp, n, seed).into_iter().enumerate() {
+            let (x, y) = (r.x + (i as u32 % r.w) as i32, r.y + (i as u32 / r.w) as i32);
+            hdr.add(x, y, color.map(|c| c * f64::from(count)));
+        }
+    }
+
+    /// Calls `plot` with `n` points of the attractor
+    fn chaos_game<F>(&self, n: usize, seed: u64, mut plot: F)
+    where
+        F: FnMut([f64; 3]),
+    {
+        assert!(!self.maps.is_empty(), "Ifs needs at least 1 map");
+        let total: f64 = self.maps.iter().map(|(_, w)| w).sum();
+        let mut rng = Rng::new(seed);
+        let mut p = [0.0, 0.0, 0.0, 1.0];
+        for i in 0..n + SETTLE_ITERATIONS {
+            let mut pick = rng.next_f64() * total;
+            let map = self
+                .maps
+                .iter()
+                .find(|(_, w)| {
+                    pick -= w;
+                    pick < 0.0
+                })
+                .map_or(&self.maps[self.maps.len() - 1].0, |(m, _)| m);
+            p = map.transform_point(&p);
+            if i >= SETTLE_ITERATIONS {
+                plot([p[0], p[1], p[2]]);
+            }
+        }
+    }
+}
+
+impl Default for Ifs {
+    fn default() -> Self {
+        Ifs::new()
+    }
+}
+
+// iterated function systems
+#[allow(dead_code)]
+impl PPMImg {
+    /// Draws `n` points of `ifs`'s attractor over `vp`, colored by how many land
+    /// on every pixel
+    ///
+    /// Colors come from `palette` on a log scale, since a few pixels get far more
+    /// points than the rest; pixels no point lands on are left alone.
+    pub fn draw_ifs(&mut self, vp: &Viewport, ifs: &Ifs, n: usize, seed: u64, palette: &Palette) {
+        let counts = ifs.density(vp, n, seed);
+        let max = counts.iter().copied().max().unwrap_or(0);
+        let scale = 1.0 / (f64::from(max) + 1.0).ln();
+        let r = vp.screen;
+        let fg = self.fg_color;
+        for (i, &count) in counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let t = (f64::from(count) + 1.0).ln() * scale;
+            self.fg_color = RGB::from_fractions(palette.color(t), self.depth());
+            let (x, y) = (r.x + (i as u32 % r.w) as i32, r.y + (i as u32 / r.w) as i32);
+            self.in_pixels(|img| img.plot(x, y));
+        }
+        self.fg_color = fg;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fern_stays_in_its_box() {
+        let fern = Ifs::barnsley_fern();
+        let m = fern.points(2000, 1);
+        assert_eq!(m.rows(), 2000);
+        let b = m.bounds().unwrap();
+        assert!(b.min[0] > -2.3 && b.max[0] < 2.8 && b.min[1] >= 0.0 && b.max[1] < 10.0);
+        // the same seed draws the same points
+        let (a, b) = (fern.points(50, 1), fern.points(50, 1));
+        assert!(a.iter_by_row().eq(b.iter_by_row()));
+
+        let mut img = PPMImg::new(40, 40, 255);
+        let vp = Viewport::full(&img, (-0.1, 1.1), (-0.1, 1.1));
+        let counts = Ifs::sierpinski().density(&vp, 20000, 3);
+        assert_eq!(counts.iter().sum::<u32>(), 20000);
+        // the hole in the middle stays empty
+        let (x, y) = vp.to_screen(0.5, 0.3);
+        assert_eq!(counts[y.round() as usize * 40 + x.round() as usize], 0);
+        img.draw_ifs(&vp, &Ifs::sierpinski(), 20000, 3, &Palette::heat());
+        assert_eq!(img.pixel(x as u32, y as u32).red, 0);
+    }
+}
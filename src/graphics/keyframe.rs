@@ -0,0 +1,280 @@
+use super::matrix::Matrix;
+
+/// Values that can be blended between keyframes
+pub trait Interpolate {
+    /// Value `t` of the way from self to other, with t in [0, 1]
+    fn interpolate(&self, other: &Self, t: f64) -> Self;
+}
+
+impl Interpolate for f64 {
+    fn interpolate(&self, other: &f64, t: f64) -> f64 {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for [f64; 3] {
+    fn interpolate(&self, other: &[f64; 3], t: f64) -> [f64; 3] {
+        [
+            self[0].interpolate(&other[0], t),
+            self[1].interpolate(&other[1], t),
+            self[2].interpolate(&other[2], t),
+        ]
+    }
+}
+
+/// Unit quaternion representing a rotation
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quat {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+#[allow(dead_code)]
+impl Quat {
+    pub fn identity() -> Quat {
+        Quat {
+            w: 1.0,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    }
+
+    /// Rotation of `angle_deg` about `axis`, same direction as `Matrix::rotate_z` for +z
+    pub fn from_axis_angle(axis: [f64; 3], angle_deg: f64) -> Quat {
+        let len = (axis[0] * axis[0] + axis[1] * axis[1] + axis[2] * axis[2]).sqrt();
+        assert!(len > 0.0, "Rotation axis can't be 0");
+        let (s, c) = (angle_deg.to_radians() / 2.0).sin_cos();
+        Quat {
+            w: c,
+            x: axis[0] / len * s,
+            y: axis[1] / len * s,
+            z: axis[2] / len * s,
+        }
+    }
+
+    fn dot(&self, o: &Quat) -> f64 {
+        self.w * o.w + self.x * o.x + self.y * o.y + self.z * o.z
+    }
+
+    fn normalize(&self) -> Quat {
+        let len = self.dot(self).sqrt();
+        Quat {
+            w: self.w / len,
+            x: self.x / len,
+            y: self.y / len,
+            z: self.z / len,
+        }
+    }
+
+    /// Rotation that does self first, then other
+    pub fn then(&self, other: &Quat) -> Quat {
+        let (a, b) = (other, self);
+        Quat {
+            w: a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+            x: a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+            y: a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+            z: a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+        }
+    }
+
+    /// Spherical linear interpolation, taking the shortest path
+    pub fn slerp(&self, other: &Quat, t: f64) -> Quat {
+        let mut dot = self.dot(other);
+        // q and -q are the same rotation; flip to go the short way around
+        let other = if dot < 0.0 {
+            dot = -dot;
+            Quat {
+                w: -other.w,
+                x: -other.x,
+                y: -other.y,
+                z: -other.z,
+            }
+        } else {
+            *other
+        };
+
+        let (s0, s1) = if dot > 0.9995 {
+            // nearly parallel: lerp is accurate and avoids dividing by ~0
+            (1.0 - t, t)
+        } else {
+            let theta = dot.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+        Quat {
+            w: s0 * self.w + s1 * other.w,
+            x: s0 * self.x + s1 * other.x,
+            y: s0 * self.y + s1 * other.y,
+            z: s0 * self.z + s1 * other.z,
+        }
+        .normalize()
+    }
+
+    /// 4 by 4 rotation matrix, for row vector points (see `Matrix`)
+    pub fn to_matrix(self) -> Matrix {
+        let Quat { w, x, y, z } = self.normalize();
+        Matrix::new(
+            4,
+            4,
+            vec![
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y + w * z),
+                2.0 * (x * z - w * y),
+                0.0,
+                2.0 * (x * y - w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z + w * x),
+                0.0,
+                2.0 * (x * z + w * y),
+                2.0 * (y * z - w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                1.0,
+            ],
+        )
+    }
+}
+
+impl Interpolate for Quat {
+    fn interpolate(&self, other: &Quat, t: f64) -> Quat {
+        self.slerp(other, t)
+    }
+}
+
+/// Scale, then rotate, then translate
+///
+/// Interpolates translation and scale linearly, and rotation with slerp
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub translate: [f64; 3],
+    pub rotate: Quat,
+    pub scale: [f64; 3],
+}
+
+#[allow(dead_code)]
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            translate: [0.0; 3],
+            rotate: Quat::identity(),
+            scale: [1.0; 3],
+        }
+    }
+
+    pub fn to_matrix(self) -> Matrix {
+        let [sx, sy, sz] = self.scale;
+        let [tx, ty, tz] = self.translate;
+        Matrix::scale(sx, sy, sz)
+            .compose(&self.rotate.to_matrix())
+            .compose(&Matrix::translate(tx, ty, tz))
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+impl Interpolate for Transform {
+    fn interpolate(&self, other: &Transform, t: f64) -> Transform {
+        Transform {
+            translate: self.translate.interpolate(&other.translate, t),
+            rotate: self.rotate.interpolate(&other.rotate, t),
+            scale: self.scale.interpolate(&other.scale, t),
+        }
+    }
+}
+
+/// Keyframes of a value over time
+///
+/// Before the first key the value is the first key's, after the last key it is the last key's
+#[derive(Clone, Debug)]
+pub struct Track<T> {
+    /// (time, value), sorted by time
+    keys: Vec<(f64, T)>,
+}
+
+#[allow(dead_code)]
+impl<T: Interpolate + Clone> Track<T> {
+    pub fn new() -> Track<T> {
+        Track { keys: vec![] }
+    }
+
+    /// Adds a keyframe, builder style
+    pub fn key(mut self, time: f64, value: T) -> Track<T> {
+        self.insert(time, value);
+        self
+    }
+
+    /// Adds a keyframe, replacing any existing one at the same time
+    pub fn insert(&mut self, time: f64, value: T) {
+        match self
+            .keys
+            .binary_search_by(|(t, _)| t.partial_cmp(&time).expect("Time can't be NaN"))
+        {
+            Ok(i) => self.keys[i].1 = value,
+            Err(i) => self.keys.insert(i, (time, value)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Value of the track at `time`
+    pub fn value_at(&self, time: f64) -> T {
+        assert!(!self.keys.is_empty(), "Track has no keyframes");
+        let i = self.keys.partition_point(|(t, _)| *t <= time);
+        if i == 0 {
+            return self.keys[0].1.clone();
+        }
+        if i == self.keys.len() {
+            return self.keys[i - 1].1.clone();
+        }
+        let ((t0, v0), (t1, v1)) = (&self.keys[i - 1], &self.keys[i]);
+        v0.interpolate(v1, (time - t0) / (t1 - t0))
+    }
+}
+
+impl<T: Interpolate + Clone> Default for Track<T> {
+    fn default() -> Self {
+        Track::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scalar_track_lerps_and_clamps() {
+        let t = Track::new().key(2.0, 10.0).key(0.0, 0.0).key(3.0, 0.0);
+        assert_eq!(t.value_at(-1.0), 0.0);
+        assert_eq!(t.value_at(1.0), 5.0);
+        assert_eq!(t.value_at(2.5), 5.0);
+        assert_eq!(t.value_at(9.0), 0.0);
+    }
+
+    #[test]
+    fn rotation_track_slerps() {
+        let z = [0.0, 0.0, 1.0];
+        let t = Track::new()
+            .key(0.0, Quat::from_axis_angle(z, 0.0))
+            .key(1.0, Quat::from_axis_angle(z, 90.0));
+        let halfway = t.value_at(0.5).to_matrix();
+        let expected = Matrix::rotate_z(45.0);
+        for r in 0..4 {
+            for c in 0..4 {
+                let (a, b) = (halfway.get(r, c).unwrap(), expected.get(r, c).unwrap());
+                assert!((a - b).abs() < 1e-9, "({}, {}): {} != {}", r, c, a, b);
+            }
+        }
+    }
+}
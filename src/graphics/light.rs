@@ -0,0 +1,114 @@
+use super::material::Material;
+use super::utils::{dot3, normalize3, sub3};
+
+/// Point light
+#[derive(Copy, Clone, Debug)]
+pub struct Light {
+    pub pos: [f64; 3],
+    /// Intensity of each channel, 1 is full brightness
+    pub color: [f64; 3],
+}
+
+#[allow(dead_code)]
+impl Light {
+    pub fn new(pos: [f64; 3], color: [f64; 3]) -> Light {
+        Light { pos, color }
+    }
+
+    /// White light of intensity 1 at `pos`
+    pub fn white(pos: [f64; 3]) -> Light {
+        Light::new(pos, [1.0; 3])
+    }
+
+    /// Diffuse and specular light reflected towards `eye` by point `p` with unit normal `n`
+    ///
+    /// `base` is the surface color as fractions of full brightness. Doesn't check
+    /// whether anything is in the way of the light.
+    pub fn shade(
+        &self,
+        material: &Material,
+        base: [f64; 3],
+        p: [f64; 3],
+        n: [f64; 3],
+        eye: [f64; 3],
+    ) -> [f64; 3] {
+        let l = normalize3(sub3(self.pos, p));
+        let ndotl = dot3(n, l);
+        if ndotl <= 0.0 {
+            return [0.0; 3];
+        }
+        let v = normalize3(sub3(eye, p));
+        let r = [
+            2.0 * ndotl * n[0] - l[0],
+            2.0 * ndotl * n[1] - l[1],
+            2.0 * ndotl * n[2] - l[2],
+        ];
+        let spec = material.specular * dot3(r, v).max(0.0).powf(material.shininess);
+        let mut out = [0.0; 3];
+        for (i, o) in out.iter_mut().enumerate() {
+            *o = self.color[i] * (base[i] * material.diffuse * ndotl + spec);
+        }
+        out
+    }
+
+    /// Like `shade`, for cel shading: the diffuse light is cut into `bands`
+    /// flat steps, and highlights are all or nothing
+    pub fn shade_bands(
+        &self,
+        material: &Material,
+        base: [f64; 3],
+        p: [f64; 3],
+        n: [f64; 3],
+        eye: [f64; 3],
+        bands: u32,
+    ) -> [f64; 3] {
+        assert!(bands > 0, "There must be at least one band");
+        let l = normalize3(sub3(self.pos, p));
+        let ndotl = dot3(n, l);
+        if ndotl <= 0.0 {
+            return [0.0; 3];
+        }
+        let diffuse = (ndotl * f64::from(bands)).ceil() / f64::from(bands);
+        let v = normalize3(sub3(eye, p));
+        let r = [0, 1, 2].map(|i| 2.0 * ndotl * n[i] - l[i]);
+        let spec = if dot3(r, v).max(0.0).powf(material.shininess) > 0.5 {
+            material.specular
+        } else {
+            0.0
+        };
+        let mut out = [0.0; 3];
+        for (i, o) in out.iter_mut().enumerate() {
+            *o = self.color[i] * (base[i] * material.diffuse * diffuse + spec);
+        }
+        out
+    }
+}
+
+/// Phong shaded color of point `p` with unit normal `n`, seen from `eye`
+///
+/// Adds the `ambient` light and every light in `lights`; leave out lights that
+/// are blocked. `base` is the surface color as fractions of full brightness.
+pub fn phong<'a, I>(
+    material: &Material,
+    base: [f64; 3],
+    p: [f64; 3],
+    n: [f64; 3],
+    eye: [f64; 3],
+    ambient: [f64; 3],
+    lights: I,
+) -> [f64; 3]
+where
+    I: IntoIterator<Item = &'a Light>,
+{
+    let mut color = [0.0; 3];
+    for (i, c) in color.iter_mut().enumerate() {
+        *c = base[i] * material.ambient * ambient[i];
+    }
+    for light in lights {
+        let lit = light.shade(material, base, p, n, eye);
+        for (c, l) in color.iter_mut().zip(lit.iter()) {
+            *c += l;
+        }
+    }
+    color
+}
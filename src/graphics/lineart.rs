@@ -0,0 +1,146 @@
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+use super::{PPMImg, RGB};
+use alloc::vec::Vec;
+
+/// Kernel `edge_strength` finds edges with
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EdgeFilter {
+    /// Length of the gradient from the 3x3 Sobel kernels: smooth lines, about
+    /// two pixels wide
+    Sobel,
+    /// Size of the 4 neighbor Laplacian: thin lines, but picks up noise too
+    Laplacian,
+}
+
+/// How sharply `values`, a grid `width` wide stored row by row, changes at
+/// every point
+///
+/// Points past the border count as copies of the nearest one on it. Values
+/// that aren't finite, like the depth where there is no surface, count as far
+/// past the largest finite one, so shapes against the background have edges.
+pub fn edge_strength(values: &[f64], width: u32, filter: EdgeFilter) -> Vec<f64> {
+    let w = width as usize;
+    assert!(
+        w > 0 && values.len() % w == 0,
+        "Values must fill whole rows"
+    );
+    let h = values.len() / w;
+    let (lo, hi) = values
+        .iter()
+        .filter(|v| v.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| {
+            (lo.min(v), hi.max(v))
+        });
+    let background = if hi >= lo {
+        hi + (hi - lo).max(1.0)
+    } else {
+        0.0
+    };
+    let at = |x: isize, y: isize| {
+        let x = x.clamp(0, w as isize - 1) as usize;
+        let y = y.clamp(0, h as isize - 1) as usize;
+        let v = values[y * w + x];
+        if v.is_finite() {
+            v
+        } else {
+            background
+        }
+    };
+    let mut out = Vec::with_capacity(values.len());
+    for y in 0..h as isize {
+        for x in 0..w as isize {
+            let strength = match filter {
+                EdgeFilter::Sobel => {
+                    let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                        - at(x - 1, y - 1)
+                        - 2.0 * at(x - 1, y)
+                        - at(x - 1, y + 1);
+                    let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                        - at(x - 1, y - 1)
+                        - 2.0 * at(x, y - 1)
+                        - at(x + 1, y - 1);
+                    // a step of 1 gives 4 with the kernels as they are
+                    (gx * gx + gy * gy).sqrt() / 4.0
+                }
+                EdgeFilter::Laplacian => {
+                    let sum = at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1);
+                    (sum - 4.0 * at(x, y)).abs()
+                }
+            };
+            out.push(strength);
+        }
+    }
+    out
+}
+
+// line art
+#[allow(dead_code)]
+impl PPMImg {
+    /// Brightness of every pixel from 0 to 1, row by row from the top, weighting
+    /// the channels by how bright they look
+    pub fn luminance(&self) -> Vec<f64> {
+        let depth = self.depth();
+        self.data
+            .iter()
+            .map(|p| {
+                let [r, g, b] = p.to_fractions(depth);
+                0.2126 * r + 0.7152 * g + 0.0722 * b
+            })
+            .collect()
+    }
+
+    /// Black lines on white where the image's brightness changes by more than
+    /// `threshold`, from 0 to 1, like a coloring page
+    pub fn line_art(&self, filter: EdgeFilter, threshold: f64) -> PPMImg {
+        let mut out = PPMImg::new(self.height(), self.width(), self.depth());
+        out.bg_color = RGB::from_fractions([1.0; 3], self.depth());
+        out.fg_color = RGB::from_fractions([0.0; 3], self.depth());
+        out.clear();
+        out.draw_edges(&self.luminance(), filter, threshold);
+        out
+    }
+
+    /// Draws, in fg_color, every pixel where `values`, one per pixel row by row,
+    /// change by more than `threshold`; see `edge_strength`
+    ///
+    /// Draws the edges of the image itself over it with values from `luminance`,
+    /// or those of the shapes in a render with its depth buffer.
+    pub fn draw_edges(&mut self, values: &[f64], filter: EdgeFilter, threshold: f64) {
+        assert_eq!(
+            values.len(),
+            self.data.len(),
+            "There must be one value per pixel"
+        );
+        let width = self.width();
+        let strength = edge_strength(values, width, filter);
+        for (i, s) in strength.iter().enumerate() {
+            if *s > threshold {
+                let (x, y) = (i as i32 % width as i32, i as i32 / width as i32);
+                self.in_pixels(|img| img.plot(x, y));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edges_follow_the_shapes() {
+        let mut img = PPMImg::new(10, 10, 255);
+        img.fill_rect(3, 3, 4, 4);
+        for filter in [EdgeFilter::Sobel, EdgeFilter::Laplacian] {
+            let art = img.line_art(filter, 0.5);
+            let dark = |x, y| art.pixel(x, y).red == 0;
+            assert!(dark(3, 5) && dark(5, 3), "{:?}", filter);
+            assert!(!dark(0, 0) && !dark(5, 5), "{:?}", filter);
+        }
+
+        // the background is past the farthest surface
+        let depth = [f64::INFINITY, 2.0, 2.0, 2.0];
+        let strength = edge_strength(&depth, 4, EdgeFilter::Laplacian);
+        assert_eq!(strength, vec![1.0, 1.0, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,130 @@
+use super::rect::Rect;
+use super::PPMImg;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Which pixels drawing is allowed to change
+///
+/// Set as `PPMImg::clip` to keep every drawing call, `clear` included, from
+/// modifying pixels outside the mask. Pixels past the mask's own size are outside.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClipMask {
+    width: u32,
+    height: u32,
+    inside: Vec<bool>,
+}
+
+#[allow(dead_code)]
+impl ClipMask {
+    /// Mask with every pixel inside
+    pub fn all(width: u32, height: u32) -> ClipMask {
+        ClipMask {
+            width,
+            height,
+            inside: vec![true; (width * height) as usize],
+        }
+    }
+
+    /// Mask with only the pixels of `r` inside
+    pub fn from_rect(width: u32, height: u32, r: Rect) -> ClipMask {
+        let mut mask = ClipMask::all(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                mask.inside[(y * width + x) as usize] = r.contains(x as i32, y as i32);
+            }
+        }
+        mask
+    }
+
+    /// Mask with the pixels of `img` that aren't black inside
+    pub fn from_image(img: &PPMImg) -> ClipMask {
+        let (width, height) = (img.width(), img.height());
+        let mut mask = ClipMask::all(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let p = img.pixel(x, y);
+                mask.inside[(y * width + x) as usize] = p.red > 0 || p.green > 0 || p.blue > 0;
+            }
+        }
+        mask
+    }
+
+    /// Mask with the pixels inside the polygon through `points` inside, by the even-odd rule
+    pub fn from_polygon(width: u32, height: u32, points: &[(f64, f64)]) -> ClipMask {
+        let mut mask = ClipMask::all(width, height);
+        let mut crossings = vec![];
+        for y in 0..height {
+            let fy = f64::from(y);
+            crossings.clear();
+            for (i, &(x0, y0)) in points.iter().enumerate() {
+                let (x1, y1) = points[(i + 1) % points.len()];
+                // half open, so a vertex on the row is only counted once
+                if (y0 <= fy) != (y1 <= fy) {
+                    crossings.push(x0 + (fy - y0) / (y1 - y0) * (x1 - x0));
+                }
+            }
+            crossings.sort_unstable_by(f64::total_cmp);
+            for x in 0..width {
+                let fx = f64::from(x);
+                let left = crossings.iter().filter(|&&c| c <= fx).count();
+                mask.inside[(y * width + x) as usize] = left % 2 == 1;
+            }
+        }
+        mask
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Whether drawing may change pixel (x, y)
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0
+            && y >= 0
+            && (x as u32) < self.width
+            && (y as u32) < self.height
+            && self.inside[(y as u32 * self.width + x as u32) as usize]
+    }
+
+    /// Swaps inside and outside
+    pub fn invert(&mut self) {
+        for p in self.inside.iter_mut() {
+            *p = !*p;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drawing_stays_inside() {
+        let mut img = PPMImg::new(8, 8, 255);
+        let triangle = [(0.0, 0.0), (8.0, 0.0), (0.0, 8.0)];
+        img.clip = Some(ClipMask::from_polygon(8, 8, &triangle));
+        img.fill_rect(0, 0, 8, 8);
+        img.draw_line(0.0, 7.0, 7.0, 7.0);
+        for y in 0..8 {
+            for x in 0..8 {
+                let lit = img.pixel(x, y).red > 0;
+                assert_eq!(lit, x + y < 8, "pixel {:?}", (x, y));
+            }
+        }
+
+        let mut mask = ClipMask::from_rect(8, 8, Rect::new(0, 0, 4, 8));
+        mask.invert();
+        img.clip = Some(mask);
+        img.clear();
+        assert!(img.pixel(1, 1).red > 0 && img.pixel(5, 1).red == 0);
+
+        // a NaN corner's edges cross nowhere
+        let broken = [(0.0, 0.0), (f64::NAN, 4.0), (0.0, 8.0), (8.0, 4.0)];
+        let mask = ClipMask::from_polygon(8, 8, &broken);
+        assert!(!mask.contains(7, 4));
+    }
+}
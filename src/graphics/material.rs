@@ -0,0 +1,123 @@
+use super::texture::Texture;
+use super::utils::{cross3, normalize3};
+use super::RGB;
+use std::sync::Arc;
+
+/// Surface properties used when rendering geometry
+///
+/// The coefficients are for Phong shading; wireframe rendering only uses `color`
+#[derive(Clone)]
+pub struct Material {
+    pub color: RGB,
+    /// Fraction of the scene's ambient light reflected
+    pub ambient: f64,
+    /// Fraction of direct light scattered evenly in all directions
+    pub diffuse: f64,
+    /// Strength of the highlights
+    pub specular: f64,
+    /// Higher is a smaller, sharper highlight
+    pub shininess: f64,
+    /// Fraction of the color that comes from what the surface mirrors, from 0 to 1
+    pub reflectivity: f64,
+    /// How much of what is behind the surface it hides, from 0 (invisible) to 1
+    /// (opaque); see `SceneGraph::render_shaded`
+    pub opacity: f64,
+    /// Tangent space normals: red, green and blue are x along the tangent, y along
+    /// the bitangent and z out of the surface, mapped from [-1, 1] to [0, 1], so
+    /// (0.5, 0.5, 1) leaves the normal alone. See `shading_normal`.
+    pub normal_map: Option<Arc<Texture>>,
+    /// Multiplies `color` at every point of surfaces with texture coordinates,
+    /// see `GBuffer::rasterize_textured`
+    pub texture: Option<Arc<Texture>>,
+}
+
+#[allow(dead_code)]
+impl Material {
+    /// Matte material of `color`
+    pub fn new(color: RGB) -> Material {
+        Material {
+            color,
+            ambient: 0.1,
+            diffuse: 0.8,
+            specular: 0.2,
+            shininess: 16.0,
+            reflectivity: 0.0,
+            opacity: 1.0,
+            normal_map: None,
+            texture: None,
+        }
+    }
+
+    pub fn with_reflectivity(mut self, reflectivity: f64) -> Material {
+        self.reflectivity = reflectivity;
+        self
+    }
+
+    pub fn with_opacity(mut self, opacity: f64) -> Material {
+        assert!(
+            (0.0..=1.0).contains(&opacity),
+            "Opacity must be between 0 and 1"
+        );
+        self.opacity = opacity;
+        self
+    }
+
+    /// Whether the surface lets some of what is behind it show through
+    pub fn is_transparent(&self) -> bool {
+        self.opacity < 1.0
+    }
+
+    pub fn with_normal_map(mut self, normal_map: Texture) -> Material {
+        self.normal_map = Some(Arc::new(normal_map));
+        self
+    }
+
+    pub fn with_texture(mut self, texture: Texture) -> Material {
+        self.texture = Some(Arc::new(texture));
+        self
+    }
+
+    /// Normal to shade with at texture coordinates `uv`, bent by the normal map
+    ///
+    /// `n` is the unit normal of the surface, and `tangent` its (x, y, z, w)
+    /// tangent from `PolygonMatrix::tangents`, w saying which way the bitangent
+    /// goes. Without a normal map, `n` is returned as is.
+    pub fn shading_normal(&self, n: [f64; 3], tangent: [f64; 4], uv: [f64; 2]) -> [f64; 3] {
+        let map = match &self.normal_map {
+            Some(map) => map,
+            None => return n,
+        };
+        let c = map.sample(uv).map(|c| c * 2.0 - 1.0);
+        let t = [tangent[0], tangent[1], tangent[2]];
+        let b = cross3(n, t).map(|v| v * tangent[3]);
+        normalize3([0, 1, 2].map(|i| t[i] * c[0] + b[i] * c[1] + n[i] * c[2]))
+    }
+
+    /// `color` as fractions of `depth`, the max value of a channel
+    pub fn base_color(&self, depth: u16) -> [f64; 3] {
+        self.color.to_fractions(depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normal_map_bends_the_normal() {
+        let plain = Material::new(RGB::from_fractions([1.0; 3], 255));
+        let (n, tangent) = ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(plain.shading_normal(n, tangent, [0.5; 2]), n);
+        let flat = plain
+            .clone()
+            .with_normal_map(Texture::Solid([0.5, 0.5, 1.0]));
+        assert_eq!(flat.shading_normal(n, tangent, [0.5; 2]), n);
+        // leaning towards the bitangent, which flips with w
+        let tilted = plain.with_normal_map(Texture::Solid([0.5, 1.0, 1.0]));
+        let s = 0.5f64.sqrt();
+        let bent = tilted.shading_normal(n, tangent, [0.5; 2]);
+        assert!((bent[1] - s).abs() < 1e-9 && (bent[2] - s).abs() < 1e-9);
+        let mirrored = tilted.shading_normal(n, [1.0, 0.0, 0.0, -1.0], [0.5; 2]);
+        assert!((mirrored[1] + s).abs() < 1e-9);
+    }
+}
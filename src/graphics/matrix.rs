@@ -1,14 +1,46 @@
-use std::fmt;
+pub mod gen;
+pub mod invariants;
+pub mod linalg;
+pub mod projection;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::iter::FromIterator;
+use core::ops::Range;
+
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+use super::utils::{dot3, normalize3};
 
 #[derive(Clone, Debug)]
 /// Row major rectangular matrix
 /// Each row represents a new point
+///
+/// ## Convention
+/// Points are row vectors, so a point `p` is transformed by `t` as `p * t`,
+/// and translation lives in the last row of a transformation matrix.
+/// Use `apply` and `compose` to avoid getting the order wrong, or `apply_with`
+/// and `compose_with` for column vectors.
 pub struct Matrix {
     rows: usize,
     cols: usize,
     data: Vec<f64>,
 }
 
+/// How points and transforms are laid out, for `Matrix::apply_with` and
+/// `Matrix::compose_with`
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Convention {
+    /// Points are rows, transformed as `p * t`, like everywhere else in the crate
+    RowVectors,
+    /// Points are columns, transformed as `t * p`, like in most math texts
+    ///
+    /// Transforms are the transpose of the row vector ones, see `Matrix::transpose`.
+    ColumnVectors,
+}
+
 // constructor, get, set
 impl Matrix {
     /// Row major index
@@ -18,13 +50,13 @@ impl Matrix {
     }
 
     #[allow(dead_code)]
-    pub fn new_clone_vec(rows: usize, cols: usize, data: &Vec<f64>) -> Matrix {
+    pub fn new_clone_vec(rows: usize, cols: usize, data: &[f64]) -> Matrix {
         assert_eq!(rows * cols, data.len(), "rows * cols must == data.len()");
 
         Matrix {
             rows,
             cols,
-            data: data.clone(),
+            data: data.to_vec(),
         }
     }
 
@@ -34,47 +66,203 @@ impl Matrix {
         Matrix { rows, cols, data }
     }
 
+    /// Make a new matrix with `cols` columns, one row per item of `rows`
+    #[allow(dead_code)]
+    pub fn from_rows<I, R>(cols: usize, rows: I) -> Matrix
+    where
+        I: IntoIterator<Item = R>,
+        R: AsRef<[f64]>,
+    {
+        let mut m = Matrix::new(0, cols, vec![]);
+        for row in rows {
+            let row = row.as_ref();
+            assert_eq!(
+                cols,
+                row.len(),
+                "Length of row and matrix column size don't match"
+            );
+            m.data.extend_from_slice(row);
+            m.rows += 1;
+        }
+        m
+    }
+
+    #[allow(dead_code)]
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    #[allow(dead_code)]
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
     #[allow(dead_code)]
     pub fn get(&self, row: usize, col: usize) -> Option<f64> {
-        if row > self.rows || col > self.cols {
+        if row >= self.rows || col >= self.cols {
             None
         } else {
             Some(self.data[self.index(row, col)])
         }
     }
 
+    /// Like `get`, with an error saying which index was out of bound
+    #[allow(dead_code)]
+    pub fn get_checked(&self, row: usize, col: usize) -> Result<f64, IndexError> {
+        self.checked_index(row, col).map(|i| self.data[i])
+    }
+
+    /// Like `get`, without any bounds check
+    ///
+    /// # Safety
+    ///
+    /// `row` must be less than `rows()` and `col` less than `cols()`
+    #[allow(dead_code)]
+    pub unsafe fn get_unchecked(&self, row: usize, col: usize) -> f64 {
+        debug_assert!(row < self.rows && col < self.cols, "Index out of bound");
+        *self.data.get_unchecked(self.index(row, col))
+    }
+
     #[allow(dead_code)]
     pub fn set(&mut self, row: usize, col: usize, data: f64) {
         assert!(row < self.rows && col < self.cols, "Index out of bound");
         let i = self.index(row, col);
         self.data[i] = data;
     }
+
+    /// Like `set`, returning an error instead of panicking if (row, col) is out of bound
+    #[allow(dead_code)]
+    pub fn try_set(&mut self, row: usize, col: usize, data: f64) -> Result<(), IndexError> {
+        let i = self.checked_index(row, col)?;
+        self.data[i] = data;
+        Ok(())
+    }
+
+    /// Index of (row, col) in data, if it is in bound
+    fn checked_index(&self, row: usize, col: usize) -> Result<usize, IndexError> {
+        if row >= self.rows || col >= self.cols {
+            return Err(IndexError {
+                row,
+                col,
+                rows: self.rows,
+                cols: self.cols,
+            });
+        }
+        Ok(self.index(row, col))
+    }
+
+    /// Like `set`, without any bounds check
+    ///
+    /// # Safety
+    ///
+    /// `row` must be less than `rows()` and `col` less than `cols()`
+    #[allow(dead_code)]
+    pub unsafe fn set_unchecked(&mut self, row: usize, col: usize, data: f64) {
+        debug_assert!(row < self.rows && col < self.cols, "Index out of bound");
+        let i = self.index(row, col);
+        *self.data.get_unchecked_mut(i) = data;
+    }
+}
+
+/// (row, col) outside of a `rows` by `cols` matrix
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct IndexError {
+    pub row: usize,
+    pub col: usize,
+    pub rows: usize,
+    pub cols: usize,
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Index ({}, {}) out of bound for a {} x {} matrix",
+            self.row, self.col, self.rows, self.cols
+        )
+    }
 }
 
 #[allow(dead_code)]
 // add edge (row)
 impl Matrix {
-    pub fn append_row(&mut self, row: &mut Vec<f64>) {
+    /// Adds a copy of `row`, which can be an array, a slice or a Vec
+    pub fn append_row<R: AsRef<[f64]>>(&mut self, row: R) {
+        let row = row.as_ref();
         assert_eq!(
             self.cols,
             row.len(),
             "Length of edge and matrix column size don't match"
         );
-        self.data.append(row);
+        self.data.extend_from_slice(row);
         self.rows += 1;
     }
 
+    /// Adds point `p` as a row (x, y, z, 1)
+    ///
+    /// `p` can be an `[x, y, z]` array or an `(x, y, z)` tuple
     #[allow(dead_code)]
-    pub fn append_edge(&mut self, edge: &mut Vec<f64>) {
+    pub fn append_edge<P: Into<[f64; 3]>>(&mut self, p: P) {
         assert_eq!(
-            self.cols,
-            edge.len() + 1,
+            self.cols, 4,
             "Length of edge and matrix column size don't match"
         );
-        edge.push(1.0);
-        self.data.append(edge);
+        let [x, y, z] = p.into();
+        self.data.extend_from_slice(&[x, y, z, 1.0]);
         self.rows += 1;
     }
+
+    /// Edge matrix with one edge per ((x0, y0, z0), (x1, y1, z1)) pair
+    pub fn from_segments<I>(segments: I) -> EdgeMatrix
+    where
+        I: IntoIterator<Item = ((f64, f64, f64), (f64, f64, f64))>,
+    {
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.extend_segments(segments);
+        m
+    }
+
+    /// Adds one edge per ((x0, y0, z0), (x1, y1, z1)) pair
+    pub fn extend_segments<I>(&mut self, segments: I)
+    where
+        I: IntoIterator<Item = ((f64, f64, f64), (f64, f64, f64))>,
+    {
+        assert_eq!(self.cols, 4, "Edge matrix must have 4 columns");
+        for (p0, p1) in segments {
+            self.data
+                .extend_from_slice(&[p0.0, p0.1, p0.2, 1.0, p1.0, p1.1, p1.2, 1.0]);
+            self.rows += 2;
+        }
+    }
+}
+
+/// Collects segments into an edge matrix, see `Matrix::from_segments`
+impl FromIterator<((f64, f64, f64), (f64, f64, f64))> for Matrix {
+    fn from_iter<I>(segments: I) -> Matrix
+    where
+        I: IntoIterator<Item = ((f64, f64, f64), (f64, f64, f64))>,
+    {
+        Matrix::from_segments(segments)
+    }
+}
+
+impl Extend<((f64, f64, f64), (f64, f64, f64))> for Matrix {
+    fn extend<I>(&mut self, segments: I)
+    where
+        I: IntoIterator<Item = ((f64, f64, f64), (f64, f64, f64))>,
+    {
+        self.extend_segments(segments);
+    }
+}
+
+/// Collects (x, y, z) points into a matrix of 4 columns, one row per point
+impl FromIterator<(f64, f64, f64)> for Matrix {
+    fn from_iter<I>(points: I) -> Matrix
+    where
+        I: IntoIterator<Item = (f64, f64, f64)>,
+    {
+        Matrix::from_rows(4, points.into_iter().map(|(x, y, z)| [x, y, z, 1.0]))
+    }
 }
 
 #[allow(dead_code)]
@@ -82,22 +270,82 @@ impl Matrix {
 impl Matrix {
 
     /// Iterate over a certain row
-    pub fn row_iter<'a>(&'a self, r: usize) -> impl Iterator<Item = &f64> {
+    pub fn row_iter(&self, r: usize) -> impl Iterator<Item = &f64> {
         let start = r * self.cols;
         self.data[start..start + self.cols].iter()
     }
 
     /// Iterate over a certain column
-    pub fn col_iter<'a>(&'a self, c: usize) -> impl Iterator<Item = &f64> {
+    pub fn col_iter(&self, c: usize) -> impl Iterator<Item = &f64> {
         self.data.iter().skip(c).step_by(self.cols)
     }
 
     /// Interate over the matrix by row, one row at a time
     /// 
     /// Returns an iterator for the row
-    pub fn iter_by_row(&self) -> std::slice::Chunks<'_, f64> {
+    pub fn iter_by_row(&self) -> core::slice::Chunks<'_, f64> {
         self.data.as_slice().chunks(self.cols)
     }
+
+    /// Mutably iterate over a certain row
+    pub fn row_iter_mut(&mut self, r: usize) -> impl Iterator<Item = &mut f64> {
+        let start = r * self.cols;
+        self.data[start..start + self.cols].iter_mut()
+    }
+
+    /// Mutably iterate over a certain column
+    pub fn col_iter_mut(&mut self, c: usize) -> impl Iterator<Item = &mut f64> {
+        self.data.iter_mut().skip(c).step_by(self.cols)
+    }
+
+    /// Mutably interate over the matrix by row, one row at a time
+    pub fn iter_by_row_mut(&mut self) -> core::slice::ChunksMut<'_, f64> {
+        self.data.as_mut_slice().chunks_mut(self.cols)
+    }
+}
+
+#[allow(dead_code)]
+// row editing and slicing
+impl Matrix {
+    pub fn swap_rows(&mut self, r0: usize, r1: usize) {
+        assert!(r0 < self.rows && r1 < self.rows, "Index out of bound");
+        if r0 == r1 {
+            return;
+        }
+        let (r0, r1) = if r0 < r1 { (r0, r1) } else { (r1, r0) };
+        let (head, tail) = self.data.split_at_mut(r1 * self.cols);
+        head[r0 * self.cols..(r0 + 1) * self.cols].swap_with_slice(&mut tail[..self.cols]);
+    }
+
+    /// Removes row `r`, returning its values
+    pub fn remove_row(&mut self, r: usize) -> Vec<f64> {
+        assert!(r < self.rows, "Index out of bound");
+        let start = r * self.cols;
+        self.rows -= 1;
+        self.data.drain(start..start + self.cols).collect()
+    }
+
+    /// Copy out the part of the matrix in the given row and column ranges
+    pub fn submatrix(&self, rows: Range<usize>, cols: Range<usize>) -> Matrix {
+        assert!(
+            rows.start <= rows.end && rows.end <= self.rows,
+            "Row range out of bound"
+        );
+        assert!(
+            cols.start <= cols.end && cols.end <= self.cols,
+            "Column range out of bound"
+        );
+        let data = rows
+            .clone()
+            .flat_map(|r| {
+                let start = self.index(r, 0);
+                self.data[start + cols.start..start + cols.end]
+                    .iter()
+                    .copied()
+            })
+            .collect();
+        Matrix::new(rows.len(), cols.len(), data)
+    }
 }
 
 #[allow(dead_code)]
@@ -107,9 +355,8 @@ impl Matrix {
         (i / cols, i % cols)
     }
 
-    /// Multiplies self matrix by other matrix
+    /// Multiplies self matrix by other matrix: `self * other`
     pub fn mul(&self, other: &Self) -> Self {
-        // other * self
         assert_eq!(self.cols, other.rows, "cols of m1 must == rows of m2");
         let (frows, fcols) = (self.rows, other.cols);
         let mut fdata = vec![0.0; frows * fcols];
@@ -120,15 +367,321 @@ impl Matrix {
                 .zip(other.col_iter(c))
                 .fold(0.0, |sum, (a, b)| sum + a * b);
         }
-        Matrix::new(frows, fcols, fdata)
+        let m = Matrix::new(frows, fcols, fdata);
+        invariants::debug_check(&m);
+        m
     }
 
+    /// Replaces b with `a * b`
     pub fn mul_mut_b(a: &Matrix, b: &mut Matrix) {
         *b = a.mul(b);
         // println!("result: {}", b);
     }
 }
 
+/// Matrix used as a list of edges: every two rows (points) make a line
+pub type EdgeMatrix = Matrix;
+
+/// Matrix used as a list of triangles: every three rows (points) make a triangle
+#[allow(dead_code)]
+pub type PolygonMatrix = Matrix;
+
+/// Axis aligned bounding box, as min and max of (x, y, z)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+}
+
+#[allow(dead_code)]
+impl Bounds {
+    /// Empty box around a single point
+    pub fn point(p: [f64; 3]) -> Bounds {
+        Bounds { min: p, max: p }
+    }
+
+    /// Grows the box to contain `p`
+    pub fn include(&mut self, p: [f64; 3]) {
+        for (axis, d) in p.iter().enumerate() {
+            self.min[axis] = self.min[axis].min(*d);
+            self.max[axis] = self.max[axis].max(*d);
+        }
+    }
+
+    /// Smallest box containing both boxes
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        let mut b = *self;
+        b.include(other.min);
+        b.include(other.max);
+        b
+    }
+
+    pub fn size(&self) -> [f64; 3] {
+        [
+            self.max[0] - self.min[0],
+            self.max[1] - self.min[1],
+            self.max[2] - self.min[2],
+        ]
+    }
+
+    pub fn center(&self) -> [f64; 3] {
+        [
+            (self.max[0] + self.min[0]) / 2.0,
+            (self.max[1] + self.min[1]) / 2.0,
+            (self.max[2] + self.min[2]) / 2.0,
+        ]
+    }
+
+    /// The 8 corners of the box
+    pub fn corners(&self) -> [[f64; 3]; 8] {
+        let mut out = [[0.0; 3]; 8];
+        for (i, c) in out.iter_mut().enumerate() {
+            for (axis, d) in c.iter_mut().enumerate() {
+                *d = if i >> axis & 1 == 0 {
+                    self.min[axis]
+                } else {
+                    self.max[axis]
+                };
+            }
+        }
+        out
+    }
+
+    /// Smallest box containing this one transformed by `m`
+    pub fn transformed(&self, m: &Matrix) -> Bounds {
+        let corners = self.corners().map(|[x, y, z]| {
+            let p = m.transform_point(&[x, y, z, 1.0]);
+            [p[0], p[1], p[2]]
+        });
+        let mut b = Bounds::point(corners[0]);
+        for p in corners.iter().skip(1) {
+            b.include(*p);
+        }
+        b
+    }
+}
+
+/// Plane of the points p with `dot(normal, p) == offset`; `normal` has length 1
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Plane {
+    pub normal: [f64; 3],
+    pub offset: f64,
+}
+
+#[allow(dead_code)]
+impl Plane {
+    /// Plane through `point`, facing `normal`
+    pub fn new(point: [f64; 3], normal: [f64; 3]) -> Plane {
+        let normal = normalize3(normal);
+        Plane {
+            normal,
+            offset: dot3(normal, point),
+        }
+    }
+
+    /// Signed distance from the plane to `p`, positive on the side `normal` points to
+    pub fn distance(&self, p: [f64; 3]) -> f64 {
+        dot3(self.normal, p) - self.offset
+    }
+
+    /// Same plane facing the other way
+    pub fn flipped(&self) -> Plane {
+        Plane {
+            normal: [-self.normal[0], -self.normal[1], -self.normal[2]],
+            offset: -self.offset,
+        }
+    }
+}
+
+#[allow(dead_code)]
+// bounds
+impl Matrix {
+    /// Min and max of x, y, z over all points, or None if there are no points
+    pub fn bounds(&self) -> Option<Bounds> {
+        assert!(self.cols >= 3, "Points must have at least 3 columns");
+        let mut rows = self.iter_by_row();
+        let first = rows.next()?;
+        let mut b = Bounds {
+            min: [first[0], first[1], first[2]],
+            max: [first[0], first[1], first[2]],
+        };
+        for p in rows {
+            for (axis, d) in p.iter().take(3).enumerate() {
+                b.min[axis] = b.min[axis].min(*d);
+                b.max[axis] = b.max[axis].max(*d);
+            }
+        }
+        Some(b)
+    }
+}
+
+#[allow(dead_code)]
+// transformation constructors
+impl Matrix {
+    pub fn translate(x: f64, y: f64, z: f64) -> Matrix {
+        let mut m = Matrix::ident(4);
+        m.set(3, 0, x);
+        m.set(3, 1, y);
+        m.set(3, 2, z);
+        m
+    }
+
+    pub fn scale(x: f64, y: f64, z: f64) -> Matrix {
+        let mut m = Matrix::ident(4);
+        m.set(0, 0, x);
+        m.set(1, 1, y);
+        m.set(2, 2, z);
+        m
+    }
+
+    /// Rotation about the x axis, from y towards z
+    pub fn rotate_x(angle_deg: f64) -> Matrix {
+        let (s, c) = angle_deg.to_radians().sin_cos();
+        let mut m = Matrix::ident(4);
+        m.set(1, 1, c);
+        m.set(1, 2, s);
+        m.set(2, 1, -s);
+        m.set(2, 2, c);
+        m
+    }
+
+    /// Rotation about the y axis, from z towards x
+    pub fn rotate_y(angle_deg: f64) -> Matrix {
+        let (s, c) = angle_deg.to_radians().sin_cos();
+        let mut m = Matrix::ident(4);
+        m.set(0, 0, c);
+        m.set(0, 2, -s);
+        m.set(2, 0, s);
+        m.set(2, 2, c);
+        m
+    }
+
+    /// Rotation about the z axis, from x towards y
+    pub fn rotate_z(angle_deg: f64) -> Matrix {
+        let (s, c) = angle_deg.to_radians().sin_cos();
+        let mut m = Matrix::ident(4);
+        m.set(0, 0, c);
+        m.set(0, 1, s);
+        m.set(1, 0, -s);
+        m.set(1, 1, c);
+        m
+    }
+
+    /// Affine map of the xy plane: (x, y) to (a x + b y + e, c x + d y + f),
+    /// leaving z alone
+    pub fn affine_2d(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Matrix {
+        let mut m = Matrix::ident(4);
+        m.set(0, 0, a);
+        m.set(1, 0, b);
+        m.set(0, 1, c);
+        m.set(1, 1, d);
+        m.set(3, 0, e);
+        m.set(3, 1, f);
+        m
+    }
+}
+
+#[allow(dead_code)]
+// transformation
+impl Matrix {
+    /// Transforms every point (row) in `points` by self, returning the new points
+    ///
+    /// Same as `points.mul(self)`
+    pub fn apply(&self, points: &EdgeMatrix) -> EdgeMatrix {
+        points.mul(self)
+    }
+
+    /// Transforms a single point (x, y, z, w) by self
+    ///
+    /// Self must be 4 by 4
+    pub fn transform_point(&self, p: &[f64]) -> [f64; 4] {
+        assert!(self.rows == 4 && self.cols == 4, "Transform must be 4 by 4");
+        let mut out = [0.0; 4];
+        for (c, o) in out.iter_mut().enumerate() {
+            *o = p
+                .iter()
+                .take(4)
+                .zip(self.col_iter(c))
+                .fold(0.0, |sum, (a, b)| sum + a * b);
+        }
+        out
+    }
+
+    /// Transforms every point in `points` by self, in place
+    pub fn apply_mut(&self, points: &mut EdgeMatrix) {
+        *points = points.mul(self);
+    }
+
+    /// Makes a transform that does self first, then other
+    ///
+    /// Same as `self.mul(other)`
+    pub fn compose(&self, other: &Matrix) -> Matrix {
+        self.mul(other)
+    }
+
+    /// Transforms `points` by self: its rows as `apply` does, or its columns under
+    /// `Convention::ColumnVectors`
+    pub fn apply_with(&self, points: &Matrix, convention: Convention) -> Matrix {
+        match convention {
+            Convention::RowVectors => points.mul(self),
+            Convention::ColumnVectors => self.mul(points),
+        }
+    }
+
+    /// Makes a transform that does self first, then other, both laid out by `convention`
+    pub fn compose_with(&self, other: &Matrix, convention: Convention) -> Matrix {
+        match convention {
+            Convention::RowVectors => self.mul(other),
+            Convention::ColumnVectors => other.mul(self),
+        }
+    }
+
+    /// Rows turned into columns; switches a transform or points between conventions
+    pub fn transpose(&self) -> Matrix {
+        let mut data = Vec::with_capacity(self.data.len());
+        for c in 0..self.cols {
+            data.extend(self.col_iter(c).copied());
+        }
+        Matrix::new(self.cols, self.rows, data)
+    }
+
+    /// Re-squares the rotation part (upper 3x3) of a transform with Gram-Schmidt
+    ///
+    /// Use after many multiplications to get rid of floating point drift.
+    /// Rows become unit length and perpendicular, keeping their handedness,
+    /// so any scaling in the upper 3x3 is removed as well. Translation is untouched.
+    pub fn orthonormalize(&mut self) {
+        assert!(
+            self.rows >= 3 && self.cols >= 3,
+            "Matrix must be at least 3 by 3"
+        );
+        let mut basis: [[f64; 3]; 3] = [[0.0; 3]; 3];
+        for r in 0..3 {
+            let mut v = [
+                self.data[self.index(r, 0)],
+                self.data[self.index(r, 1)],
+                self.data[self.index(r, 2)],
+            ];
+            for b in basis.iter().take(r) {
+                let dot = v[0] * b[0] + v[1] * b[1] + v[2] * b[2];
+                for (vi, bi) in v.iter_mut().zip(b.iter()) {
+                    *vi -= dot * bi;
+                }
+            }
+            let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+            assert!(len > 0.0, "Rotation part of the matrix is degenerate");
+            for (c, vi) in v.iter().enumerate() {
+                basis[r][c] = vi / len;
+            }
+        }
+        for (r, b) in basis.iter().enumerate() {
+            for (c, bi) in b.iter().enumerate() {
+                self.set(r, c, *bi);
+            }
+        }
+    }
+}
+
 // identity
 impl Matrix {
 
@@ -142,19 +695,13 @@ impl Matrix {
         m
     }
 
-    #[allow(dead_code)]
+    #[allow(dead_code, clippy::wrong_self_convention)]
     /// Transforms self into an identity matrix
     pub fn to_ident(&mut self) {
         let cols = self.cols;
         for (i, d) in self.data.iter_mut().enumerate() {
-            *d = if {
-                let (r, c) = Matrix::index_to_rc(i, cols);
-                r == c
-            } {
-                1.0
-            } else {
-                0.0
-            }
+            let (r, c) = Matrix::index_to_rc(i, cols);
+            *d = if r == c { 1.0 } else { 0.0 }
         }
     }
 }
@@ -167,9 +714,9 @@ impl fmt::Display for Matrix {
         } else {
             writeln!(f, "Matrix ({} by {}) {{", self.rows, self.cols)?;
 
-            for col_offset in 0..self.cols {
+            for r in 0..self.rows {
                 write!(f, "  ")?; // indentation
-                for d in self.data.iter().skip(col_offset).step_by(self.cols) {
+                for d in self.row_iter(r) {
                     write!(f, "{arg:.prec$} ", arg = d, prec = 2)?;
                 }
                 writeln!(f)?; // line change
@@ -211,8 +758,8 @@ mod tests {
         let mut m = Matrix::new(0, 4, vec![]);
         println!("m: {}", m);
         println!("Adding (1, 2, 4) and (5, 6, 7) to empty matrix",);
-        m.append_edge(&mut vec![1.0, 2.0, 4.0]);
-        m.append_edge(&mut vec![5.0, 6.0, 7.0]);
+        m.append_edge([1.0, 2.0, 4.0]);
+        m.append_edge([5.0, 6.0, 7.0]);
         println!("m: {}", m);
         assert!(
             matrix_equal(
@@ -254,6 +801,42 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn get_and_set_bounds() {
+        let mut m = Matrix::new(2, 3, vec![0.0; 6]);
+        assert_eq!(m.get(2, 0), None);
+        assert_eq!(m.get(0, 3), None);
+        assert_eq!(m.try_set(1, 2, 5.0), Ok(()));
+        assert_eq!(m.get_checked(1, 2), Ok(5.0));
+        assert!(m.get_checked(0, 3).is_err());
+        assert_eq!(unsafe { m.get_unchecked(1, 2) }, 5.0);
+        let err = m.try_set(2, 0, 1.0).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Index (2, 0) out of bound for a 2 x 3 matrix"
+        );
+    }
+
+    #[test]
+    fn collect_segments() {
+        let m: EdgeMatrix = (0..3)
+            .map(|i| {
+                let x = f64::from(i);
+                ((x, 0.0, 0.0), (x, 1.0, 0.0))
+            })
+            .collect();
+        assert_eq!((m.rows(), m.cols()), (6, 4));
+        assert_eq!(m.iter_by_row().nth(3), Some(&[1.0, 1.0, 0.0, 1.0][..]));
+
+        let mut m = m;
+        m.extend(vec![((5.0, 5.0, 5.0), (6.0, 6.0, 6.0))]);
+        assert_eq!(m.rows(), 8);
+        let mut points: Matrix = vec![(1.0, 2.0, 3.0)].into_iter().collect();
+        points.append_edge((4.0, 5.0, 6.0));
+        assert_eq!(points.iter_by_row().next(), Some(&[1.0, 2.0, 3.0, 1.0][..]));
+        assert_eq!(points.iter_by_row().nth(1), Some(&[4.0, 5.0, 6.0, 1.0][..]));
+    }
+
     #[test]
     fn test_new_ident()
     {
@@ -282,4 +865,120 @@ mod tests {
         assert!(matrix_equal(&m, &Matrix::ident(1)), "1 x 1 matrix edge case");
     }
 
+    #[test]
+    fn display_prints_rows() {
+        let m = Matrix::new(2, 3, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(
+            format!("{}", m),
+            "Matrix (2 by 3) {\n  1.00 2.00 3.00 \n  4.00 5.00 6.00 \n}"
+        );
+    }
+
+    #[test]
+    fn apply_and_compose_order() {
+        let mut points = Matrix::new(0, 4, vec![]);
+        points.append_edge([1.0, 0.0, 0.0]);
+
+        let mut translate = Matrix::ident(4);
+        translate.set(3, 0, 5.0);
+        let mut scale = Matrix::ident(4);
+        scale.set(0, 0, 2.0);
+
+        // translate then scale: (1 + 5) * 2
+        let t = translate.compose(&scale);
+        assert_eq!(t.apply(&points).get(0, 0), Some(12.0));
+        // scale then translate: 1 * 2 + 5
+        let t = scale.compose(&translate);
+        assert_eq!(t.apply(&points).get(0, 0), Some(7.0));
+
+        // the same with points as columns and transposed transforms
+        let columns = Convention::ColumnVectors;
+        let (translate, scale) = (translate.transpose(), scale.transpose());
+        let t = translate.compose_with(&scale, columns);
+        let moved = t.apply_with(&points.transpose(), columns);
+        assert_eq!((moved.rows, moved.get(0, 0)), (4, Some(12.0)));
+        let t = scale.compose_with(&translate, columns);
+        let moved = t.apply_with(&points.transpose(), columns);
+        assert_eq!(moved.get(0, 0), Some(7.0));
+    }
+
+    #[test]
+    fn edit_rows() {
+        let mut m = Matrix::from_rows(2, vec![[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]]);
+        m.swap_rows(2, 0);
+        assert!(matrix_equal(
+            &m,
+            &Matrix::new(3, 2, vec![5.0, 6.0, 3.0, 4.0, 1.0, 2.0])
+        ));
+        assert_eq!(m.remove_row(1), vec![3.0, 4.0]);
+        for d in m.col_iter_mut(1) {
+            *d *= 10.0;
+        }
+        assert!(matrix_equal(
+            &m,
+            &Matrix::new(2, 2, vec![5.0, 60.0, 1.0, 20.0])
+        ));
+    }
+
+    #[test]
+    fn submatrix() {
+        let m = Matrix::new(3, 3, (1..=9).map(f64::from).collect());
+        assert!(matrix_equal(
+            &m.submatrix(1..3, 0..2),
+            &Matrix::new(2, 2, vec![4.0, 5.0, 7.0, 8.0])
+        ));
+        assert_eq!(m.submatrix(0..0, 0..3).rows(), 0);
+    }
+
+    #[test]
+    fn orthonormalize_drifted_rotation() {
+        let (s, c) = 30f64.to_radians().sin_cos();
+        // slightly scaled and sheared rotation about z
+        let (c0, c1, ns) = (c * 1.01, c * 0.98, -s);
+        let mut m = Matrix::new(
+            4,
+            4,
+            vec![
+                c0, s, 0.0, 0.0, //
+                ns, c1, 0.02, 0.0, //
+                0.0, 0.0, 1.03, 0.0, //
+                5.0, 6.0, 7.0, 1.0, //
+            ],
+        );
+        m.orthonormalize();
+        let rot = m.submatrix(0..3, 0..3);
+        let should_be_ident = rot.mul(&Matrix::new(
+            3,
+            3,
+            (0..9).map(|i| rot.get(i % 3, i / 3).unwrap()).collect(),
+        ));
+        assert!(should_be_ident
+            .data
+            .iter()
+            .zip(Matrix::ident(3).data.iter())
+            .all(|(a, b)| (a - b).abs() < 1e-9));
+        assert!((rot.det() - 1.0).abs() < 1e-9);
+        assert_eq!(m.get(3, 2), Some(7.0));
+    }
+
+    #[test]
+    fn rotate_z_quarter_turn() {
+        let mut p = Matrix::new(0, 4, vec![]);
+        p.append_edge([1.0, 0.0, 0.0]);
+        let p = Matrix::rotate_z(90.0).apply(&p);
+        assert!((p.get(0, 0).unwrap()).abs() < 1e-9);
+        assert!((p.get(0, 1).unwrap() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn bounds() {
+        let mut m = Matrix::new(0, 4, vec![]);
+        assert_eq!(m.bounds(), None);
+        m.append_edge([1.0, -2.0, 4.0]);
+        m.append_edge([-5.0, 6.0, 0.0]);
+        let b = m.bounds().unwrap();
+        assert_eq!(b.min, [-5.0, -2.0, 0.0]);
+        assert_eq!(b.max, [1.0, 6.0, 4.0]);
+        assert_eq!(b.center(), [-2.0, 2.0, 2.0]);
+    }
 }
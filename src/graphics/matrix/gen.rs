@@ -0,0 +1,86 @@
+use super::Matrix;
+use crate::graphics::rng::Rng;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+/// Random size in [1, max]
+pub fn size(rng: &mut Rng, max: usize) -> usize {
+    assert!(max > 0, "Max size must be at least 1");
+    1 + rng.below(max as u64) as usize
+}
+
+/// `rows` by `cols` matrix with every element uniform in [-10, 10)
+pub fn any(rng: &mut Rng, rows: usize, cols: usize) -> Matrix {
+    let data: Vec<f64> = (0..rows * cols).map(|_| rng.range(-10.0, 10.0)).collect();
+    Matrix::new(rows, cols, data)
+}
+
+/// Square `n` by `n` matrix that is always invertible
+///
+/// Each diagonal element is bigger than the rest of its row put together.
+pub fn invertible(rng: &mut Rng, n: usize) -> Matrix {
+    let mut m = any(rng, n, n);
+    for r in 0..n {
+        let rest: f64 = (0..n)
+            .filter(|&c| c != r)
+            .map(|c| m.data[m.index(r, c)].abs())
+            .sum();
+        let sign = if rng.below(2) == 0 { 1.0 } else { -1.0 };
+        m.set(r, r, sign * (rest + rng.range(1.0, 10.0)));
+    }
+    m
+}
+
+/// 4 by 4 transform made of up to 6 random translations, non-zero scales and rotations
+pub fn transform(rng: &mut Rng) -> Matrix {
+    let mut t = Matrix::ident(4);
+    for _ in 0..size(rng, 6) {
+        let step = match rng.below(5) {
+            0 => Matrix::translate(
+                rng.range(-10.0, 10.0),
+                rng.range(-10.0, 10.0),
+                rng.range(-10.0, 10.0),
+            ),
+            1 => Matrix::scale(scale(rng), scale(rng), scale(rng)),
+            2 => Matrix::rotate_x(rng.range(-180.0, 180.0)),
+            3 => Matrix::rotate_y(rng.range(-180.0, 180.0)),
+            _ => Matrix::rotate_z(rng.range(-180.0, 180.0)),
+        };
+        t = t.compose(&step);
+    }
+    t
+}
+
+/// Scale factor in ±[0.1, 4), never close to 0
+fn scale(rng: &mut Rng) -> f64 {
+    let s = rng.range(0.1, 4.0);
+    if rng.below(2) == 0 {
+        s
+    } else {
+        -s
+    }
+}
+
+/// Checks `prop` on `cases` values made by `gen`, starting from `seed`
+///
+/// Panics on the first value `prop` rejects, showing it along with the seed
+/// and case number; the same seed always makes the same values, so failures
+/// can be reproduced.
+pub fn for_all<T, G, P>(seed: u64, cases: usize, mut gen: G, mut prop: P)
+where
+    T: Debug,
+    G: FnMut(&mut Rng) -> T,
+    P: FnMut(&T) -> bool,
+{
+    let mut rng = Rng::new(seed);
+    for case in 0..cases {
+        let value = gen(&mut rng);
+        assert!(
+            prop(&value),
+            "Property failed for case {} of seed {}: {:?}",
+            case,
+            seed,
+            value
+        );
+    }
+}
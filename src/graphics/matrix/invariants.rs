@@ -0,0 +1,137 @@
+use super::Matrix;
+
+/// Largest absolute difference between two matrices of the same shape
+pub fn max_diff(m1: &Matrix, m2: &Matrix) -> f64 {
+    assert!(
+        m1.rows == m2.rows && m1.cols == m2.cols,
+        "Matrices must have the same shape"
+    );
+    m1.data
+        .iter()
+        .zip(m2.data.iter())
+        .fold(0.0, |d, (a, b)| d.max((a - b).abs()))
+}
+
+/// Whether two matrices have the same shape and every element within `tolerance`
+pub fn approx_eq(m1: &Matrix, m2: &Matrix, tolerance: f64) -> bool {
+    m1.rows == m2.rows && m1.cols == m2.cols && max_diff(m1, m2) <= tolerance
+}
+
+/// Largest absolute element, 0 for an empty matrix
+pub fn max_abs(m: &Matrix) -> f64 {
+    m.data.iter().fold(0.0, |d, a| d.max(a.abs()))
+}
+
+/// Panics if `m` is malformed: its data doesn't fill rows * cols, or it holds NaN
+pub fn check(m: &Matrix) {
+    assert_eq!(
+        m.rows * m.cols,
+        m.data.len(),
+        "Matrix data doesn't match its {} x {} shape",
+        m.rows,
+        m.cols
+    );
+    assert!(
+        m.data.iter().all(|d| !d.is_nan()),
+        "Matrix holds NaN: {}",
+        m
+    );
+}
+
+/// Panics unless `inv` really is the inverse of `m`, up to rounding
+pub fn check_inverse(m: &Matrix, inv: &Matrix) {
+    let n = m.rows;
+    let product = m.mul(inv);
+    // rounding errors grow with the size of the elements
+    let tolerance = 1e-9 * (n as f64) * max_abs(m).max(1.0) * max_abs(inv).max(1.0);
+    assert!(
+        approx_eq(&product, &Matrix::ident(n), tolerance),
+        "Inverse doesn't round-trip, m * inv = {}",
+        product
+    );
+}
+
+/// Runs `check` with the debug-checks feature, and does nothing without it
+#[inline]
+pub(crate) fn debug_check(m: &Matrix) {
+    #[cfg(feature = "debug-checks")]
+    check(m);
+    #[cfg(not(feature = "debug-checks"))]
+    let _ = m;
+}
+
+/// Runs `check_inverse` with the debug-checks feature, and does nothing without it
+#[inline]
+pub(crate) fn debug_check_inverse(m: &Matrix, inv: &Matrix) {
+    #[cfg(feature = "debug-checks")]
+    check_inverse(m, inv);
+    #[cfg(not(feature = "debug-checks"))]
+    let _ = (m, inv);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::gen;
+    use super::*;
+
+    #[test]
+    fn multiplication_is_associative() {
+        gen::for_all(
+            1,
+            200,
+            |rng| {
+                let (r, k, l, c) = (
+                    gen::size(rng, 4),
+                    gen::size(rng, 4),
+                    gen::size(rng, 4),
+                    gen::size(rng, 4),
+                );
+                (
+                    gen::any(rng, r, k),
+                    gen::any(rng, k, l),
+                    gen::any(rng, l, c),
+                )
+            },
+            |(a, b, c)| approx_eq(&a.mul(b).mul(c), &a.mul(&b.mul(c)), 1e-9),
+        );
+    }
+
+    #[test]
+    fn identity_changes_nothing() {
+        gen::for_all(
+            2,
+            200,
+            |rng| {
+                let (r, c) = (gen::size(rng, 5), gen::size(rng, 5));
+                gen::any(rng, r, c)
+            },
+            |m| {
+                approx_eq(&Matrix::ident(m.rows()).mul(m), m, 0.0)
+                    && approx_eq(&m.mul(&Matrix::ident(m.cols())), m, 0.0)
+            },
+        );
+    }
+
+    #[test]
+    fn inverse_round_trips() {
+        gen::for_all(3, 200, gen::transform, |t| {
+            let inv = t.inverse().expect("transforms are invertible");
+            check_inverse(t, &inv);
+            approx_eq(&t.compose(&inv), &Matrix::ident(4), 1e-9)
+        });
+        gen::for_all(
+            4,
+            200,
+            |rng| {
+                let n = gen::size(rng, 6);
+                gen::invertible(rng, n)
+            },
+            |m| {
+                let inv = m
+                    .inverse()
+                    .expect("diagonally dominant matrices are invertible");
+                approx_eq(&inv.inverse().unwrap(), m, 1e-9)
+            },
+        );
+    }
+}
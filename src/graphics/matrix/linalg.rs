@@ -0,0 +1,202 @@
+use super::{invariants, Matrix};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Pivots smaller than this times the largest entry are treated as 0, making
+/// the matrix singular
+const EPSILON: f64 = 1e-12;
+
+/// LU decomposition with partial pivoting: `P * A = L * U`
+///
+/// L (unit lower triangular) and U are packed into one matrix.
+#[derive(Clone, Debug)]
+pub struct LU {
+    lu: Matrix,
+    /// Row `i` of `P * A` is row `perm[i]` of A
+    perm: Vec<usize>,
+    /// +1.0 or -1.0 depending on the number of row swaps
+    sign: f64,
+}
+
+#[allow(dead_code)]
+impl LU {
+    /// Lower triangular factor, with ones on the diagonal
+    pub fn l(&self) -> Matrix {
+        let n = self.lu.rows;
+        let mut l = Matrix::ident(n);
+        for r in 0..n {
+            for c in 0..r {
+                l.set(r, c, self.lu.data[self.lu.index(r, c)]);
+            }
+        }
+        l
+    }
+
+    /// Upper triangular factor
+    pub fn u(&self) -> Matrix {
+        let n = self.lu.rows;
+        let mut u = Matrix::new(n, n, vec![0.0; n * n]);
+        for r in 0..n {
+            for c in r..n {
+                u.set(r, c, self.lu.data[self.lu.index(r, c)]);
+            }
+        }
+        u
+    }
+
+    /// Row permutation: row `i` of `P * A` is row `perm()[i]` of A
+    pub fn perm(&self) -> &[usize] {
+        &self.perm
+    }
+
+    pub fn det(&self) -> f64 {
+        (0..self.lu.rows).fold(self.sign, |d, i| d * self.lu.data[self.lu.index(i, i)])
+    }
+
+    /// Solve `A * x = b` for x, where each column of b is a right hand side
+    pub fn solve(&self, b: &Matrix) -> Matrix {
+        let n = self.lu.rows;
+        assert_eq!(n, b.rows, "rows of b must == size of the system");
+        let mut x = Matrix::new(n, b.cols, vec![0.0; n * b.cols]);
+        for c in 0..b.cols {
+            // forward substitution with L, applying the permutation on the way
+            for r in 0..n {
+                let mut sum = b.data[b.index(self.perm[r], c)];
+                for k in 0..r {
+                    sum -= self.lu.data[self.lu.index(r, k)] * x.data[x.index(k, c)];
+                }
+                x.set(r, c, sum);
+            }
+            // back substitution with U
+            for r in (0..n).rev() {
+                let mut sum = x.data[x.index(r, c)];
+                for k in r + 1..n {
+                    sum -= self.lu.data[self.lu.index(r, k)] * x.data[x.index(k, c)];
+                }
+                x.set(r, c, sum / self.lu.data[self.lu.index(r, r)]);
+            }
+        }
+        x
+    }
+}
+
+#[allow(dead_code)]
+// linear systems
+impl Matrix {
+    /// LU decomposition with partial pivoting
+    ///
+    /// Returns None if the matrix is singular or has entries that aren't finite.
+    /// Matrix must be square.
+    pub fn lu(&self) -> Option<LU> {
+        assert_eq!(self.rows, self.cols, "Matrix must be square");
+        let n = self.rows;
+        let tolerance = EPSILON * self.data.iter().fold(0.0, |m: f64, v| m.max(v.abs()));
+        let mut lu = self.clone();
+        let mut perm: Vec<usize> = (0..n).collect();
+        let mut sign = 1.0;
+
+        for k in 0..n {
+            // pick the largest pivot in column k
+            let p = (k..n)
+                .max_by(|&a, &b| {
+                    lu.data[lu.index(a, k)]
+                        .abs()
+                        .total_cmp(&lu.data[lu.index(b, k)].abs())
+                })
+                .unwrap();
+            let pivot = lu.data[lu.index(p, k)];
+            if !pivot.is_finite() || pivot.abs() <= tolerance {
+                return None;
+            }
+            if p != k {
+                lu.swap_rows(p, k);
+                perm.swap(p, k);
+                sign = -sign;
+            }
+
+            for r in k + 1..n {
+                let factor = lu.data[lu.index(r, k)] / pivot;
+                let i = lu.index(r, k);
+                lu.data[i] = factor;
+                for c in k + 1..n {
+                    let i = lu.index(r, c);
+                    lu.data[i] -= factor * lu.data[lu.index(k, c)];
+                }
+            }
+        }
+
+        Some(LU { lu, perm, sign })
+    }
+
+    /// Solve `self * x = b`, where each column of b is a right hand side
+    ///
+    /// Returns None if self is singular
+    pub fn solve(&self, b: &Matrix) -> Option<Matrix> {
+        self.lu().map(|lu| lu.solve(b))
+    }
+
+    /// Determinant of a square matrix
+    pub fn det(&self) -> f64 {
+        self.lu().map_or(0.0, |lu| lu.det())
+    }
+
+    /// Inverse of a square matrix, or None if it is singular
+    pub fn inverse(&self) -> Option<Matrix> {
+        let inv = self.solve(&Matrix::ident(self.rows))?;
+        invariants::debug_check_inverse(self, &inv);
+        Some(inv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(m1: &Matrix, m2: &Matrix) -> bool {
+        m1.rows == m2.rows
+            && m1.cols == m2.cols
+            && m1
+                .data
+                .iter()
+                .zip(m2.data.iter())
+                .all(|(a, b)| (a - b).abs() < 1e-9)
+    }
+
+    #[test]
+    fn solve_needs_pivoting() {
+        // first pivot is 0, so this only works with row swaps
+        let a = Matrix::new(3, 3, vec![0.0, 2.0, 1.0, 1.0, 1.0, 1.0, 2.0, 1.0, 3.0]);
+        let b = Matrix::new(3, 1, vec![7.0, 6.0, 13.0]);
+        let x = a.solve(&b).expect("system is not singular");
+        assert!(
+            close(&x, &Matrix::new(3, 1, vec![1.0, 2.0, 3.0])),
+            "x: {}",
+            x
+        );
+        assert!((a.det() - -3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn lu_factors_multiply_back() {
+        let a = Matrix::new(3, 3, vec![2.0, -1.0, 0.0, 4.0, 3.0, 1.0, -2.0, 5.0, 6.0]);
+        let lu = a.lu().unwrap();
+        let pa = Matrix::from_rows(
+            3,
+            lu.perm().iter().map(|&r| a.iter_by_row().nth(r).unwrap()),
+        );
+        assert!(close(&lu.l().mul(&lu.u()), &pa));
+    }
+
+    #[test]
+    fn singular() {
+        let a = Matrix::new(2, 2, vec![1.0, 2.0, 2.0, 4.0]);
+        assert!(a.solve(&Matrix::ident(2)).is_none());
+        assert_eq!(a.det(), 0.0);
+
+        // small entries are fine as long as the matrix is well conditioned
+        let small = Matrix::new(2, 2, vec![2e-13, 1e-13, 1e-13, 3e-13]);
+        assert!(small.inverse().is_some());
+        let nan = Matrix::new(2, 2, vec![1.0, f64::NAN, 0.0, 1.0]);
+        assert!(nan.lu().is_none());
+    }
+}
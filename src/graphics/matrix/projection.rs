@@ -0,0 +1,93 @@
+use super::Matrix;
+
+#[cfg(not(feature = "std"))]
+use crate::graphics::utils::Float;
+
+/// Parallel projections of classic technical drawings
+///
+/// `matrix` turns 3D points into drawing coordinates: x and y on the drawing
+/// (y up, like the world), and z as depth, growing away from the viewer. The
+/// axonometric ones look at the (+x, +y, +z) corner of things, with y up.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    /// All three axes equally foreshortened, 120 degrees apart on the drawing
+    Isometric,
+    /// x and y equally foreshortened, z half as long as them
+    Dimetric,
+    /// Front view with z drawn at the angle (in degrees, counter clockwise from
+    /// the x axis) at half its length
+    Cabinet(f64),
+    /// Same as `Cabinet`, with z drawn at its full length
+    Cavalier(f64),
+}
+
+#[allow(dead_code)]
+impl Projection {
+    /// Transform doing the projection; see `Projection`
+    pub fn matrix(&self) -> Matrix {
+        match *self {
+            // tilt so the axes come out foreshortened as wanted: asin(1 / sqrt(3)) for
+            // isometric, and asin(1 / 3) and asin(1 / sqrt(8)) for 1 : 1 : 1/2 dimetric
+            Projection::Isometric => Projection::axonometric(45.0, 35.264_389_682_754_654),
+            Projection::Dimetric => {
+                Projection::axonometric(20.704_811_054_635_47, 19.471_220_634_490_69)
+            }
+            Projection::Cabinet(angle_deg) => Projection::oblique(angle_deg, 0.5),
+            Projection::Cavalier(angle_deg) => Projection::oblique(angle_deg, 1.0),
+        }
+    }
+
+    /// Turns the (+x, +z) corner `turn_deg` towards the viewer, then the top `tilt_deg`
+    fn axonometric(turn_deg: f64, tilt_deg: f64) -> Matrix {
+        // flip depth: the world is right handed, drawing coordinates with depth away aren't
+        Matrix::rotate_y(-turn_deg)
+            .compose(&Matrix::rotate_x(tilt_deg))
+            .compose(&Matrix::scale(1.0, 1.0, -1.0))
+    }
+
+    /// Keeps x and y as they are, and draws z at `angle_deg` scaled by `depth_scale`
+    fn oblique(angle_deg: f64, depth_scale: f64) -> Matrix {
+        let (s, c) = angle_deg.to_radians().sin_cos();
+        let mut m = Matrix::ident(4);
+        m.set(2, 0, depth_scale * c);
+        m.set(2, 1, depth_scale * s);
+        m
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Length on the drawing and depth of each unit axis
+    fn axes(p: Projection) -> [(f64, f64); 3] {
+        let m = p.matrix();
+        let axis = |i: usize| {
+            let mut v = [0.0; 4];
+            v[i] = 1.0;
+            let q = m.transform_point(&v);
+            ((q[0] * q[0] + q[1] * q[1]).sqrt(), q[2])
+        };
+        [axis(0), axis(1), axis(2)]
+    }
+
+    #[test]
+    fn axes_are_foreshortened_like_the_standards() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        let [x, y, z] = axes(Projection::Isometric);
+        let len = (2.0f64 / 3.0).sqrt();
+        assert!(close(x.0, len) && close(y.0, len) && close(z.0, len));
+        // every axis points towards the viewer
+        assert!(x.1 < 0.0 && y.1 < 0.0 && z.1 < 0.0);
+
+        let [x, y, z] = axes(Projection::Dimetric);
+        assert!(close(x.0, y.0) && close(z.0, x.0 / 2.0));
+
+        let [x, y, z] = axes(Projection::Cabinet(45.0));
+        assert!(close(x.0, 1.0) && close(y.0, 1.0) && close(z.0, 0.5) && close(z.1, 1.0));
+        let tip = Projection::Cavalier(90.0)
+            .matrix()
+            .transform_point(&[0.0, 0.0, 1.0, 1.0]);
+        assert!(close(tip[0], 0.0) && close(tip[1], 1.0));
+    }
+}
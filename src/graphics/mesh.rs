@@ -0,0 +1,916 @@
+use super::matrix::{Bounds, Matrix, Plane, PolygonMatrix};
+use super::utils::{cross3, dot3, normalize3, sub3, xyz};
+use super::PPMImg;
+use std::collections::{HashMap, HashSet};
+
+/// Finest grid `PolygonMatrix::simplified` tries, in cells across the mesh
+const MAX_CLUSTER_CELLS: usize = 1 << 12;
+
+/// Triangles with twice their area below this are degenerate
+const DEGENERATE_AREA: f64 = 1e-12;
+
+/// Points closer than this to a plane are on it, for `PolygonMatrix::slice`
+const PLANE_EPSILON: f64 = 1e-9;
+
+/// A mesh cut in two by `PolygonMatrix::slice`
+#[derive(Clone, Debug)]
+pub struct MeshSlice {
+    /// Triangles on the side the plane's normal points to
+    pub above: PolygonMatrix,
+    pub below: PolygonMatrix,
+    /// Outlines of the cut, where the mesh crosses the plane, as polygons: each
+    /// point joins the next and the last joins the first
+    ///
+    /// Outlines go counter clockwise seen from above. Those of meshes with holes
+    /// may not close, and then run from one edge of the mesh to another.
+    pub section: Vec<Vec<[f64; 3]>>,
+}
+
+/// Problems `PolygonMatrix::validate` found in a mesh
+///
+/// Points at the same position count as the same vertex.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MeshReport {
+    /// Triangles with no area, by index
+    pub degenerate: Vec<usize>,
+    /// Edges shared by more than 2 triangles
+    pub non_manifold_edges: usize,
+    /// Edges whose 2 triangles go the same way along them, so one of them is flipped
+    pub inconsistent_edges: usize,
+    /// Edges of a single triangle: holes, or the border of an open surface
+    pub boundary_edges: usize,
+}
+
+impl MeshReport {
+    /// Whether nothing breaks culling and lighting; boundaries are fine
+    pub fn is_valid(&self) -> bool {
+        self.degenerate.is_empty() && self.non_manifold_edges == 0 && self.inconsistent_edges == 0
+    }
+}
+
+#[allow(dead_code)]
+// add polygons
+impl PolygonMatrix {
+    /// Adds triangle (p0, p1, p2)
+    ///
+    /// Points should go counter clockwise when looking at the front of the triangle
+    pub fn add_polygon(&mut self, p0: [f64; 3], p1: [f64; 3], p2: [f64; 3]) {
+        for p in [p0, p1, p2].iter() {
+            self.append_edge(*p);
+        }
+    }
+
+    /// Adds a terrain mesh over x in [0, width] and z in [0, depth], with height y = f(x, z)
+    ///
+    /// The grid has `steps` by `steps` cells, two triangles each, facing +y
+    pub fn add_heightmap<F>(&mut self, width: f64, depth: f64, f: F, steps: usize)
+    where
+        F: Fn(f64, f64) -> f64,
+    {
+        assert!(steps > 0, "Heightmap needs at least 1 step");
+        let (dx, dz) = (width / steps as f64, depth / steps as f64);
+        self.add_grid(steps, steps, |i, j| {
+            let (x, z) = (i as f64 * dx, j as f64 * dz);
+            [x, f(x, z), z]
+        });
+    }
+
+    /// Adds a terrain mesh using the brightness of each pixel in `img` as height
+    ///
+    /// The terrain covers x in [0, width] and z in [0, depth], with one grid point per pixel.
+    /// White maps to a height of `max_height`, black to 0.
+    pub fn add_heightmap_from_img(
+        &mut self,
+        img: &PPMImg,
+        width: f64,
+        depth: f64,
+        max_height: f64,
+    ) {
+        let (cols, rows) = (img.width() as usize, img.height() as usize);
+        assert!(cols > 1 && rows > 1, "Image must be at least 2 by 2");
+        let (dx, dz) = (width / (cols - 1) as f64, depth / (rows - 1) as f64);
+        let scale = max_height / (3.0 * f64::from(img.depth()));
+        self.add_grid(cols - 1, rows - 1, |i, j| {
+            let p = img.pixel(i as u32, j as u32);
+            let brightness = f64::from(p.red) + f64::from(p.green) + f64::from(p.blue);
+            [i as f64 * dx, brightness * scale, j as f64 * dz]
+        });
+    }
+
+    /// Adds a sphere of `radius` around `center`, with `steps` bands of latitude
+    /// and twice as many of longitude
+    ///
+    /// Triangles face outwards
+    pub fn add_sphere(&mut self, center: [f64; 3], radius: f64, steps: usize) {
+        assert!(steps > 1, "Sphere needs at least 2 steps");
+        let [cx, cy, cz] = center;
+        let point = |i: usize, j: usize| {
+            let phi = std::f64::consts::PI * i as f64 / steps as f64;
+            let theta = std::f64::consts::PI * j as f64 / steps as f64;
+            let (sp, cp) = phi.sin_cos();
+            let (st, ct) = theta.sin_cos();
+            [
+                cx + radius * st * cp,
+                cy + radius * ct,
+                cz - radius * st * sp,
+            ]
+        };
+        for j in 0..steps {
+            for i in 0..2 * steps {
+                let (p00, p10) = (point(i, j), point(i + 1, j));
+                let (p01, p11) = (point(i, j + 1), point(i + 1, j + 1));
+                // the quads touching the poles only have one triangle
+                if j != 0 {
+                    self.add_polygon(p00, p11, p10);
+                }
+                if j != steps - 1 {
+                    self.add_polygon(p00, p01, p11);
+                }
+            }
+        }
+    }
+
+    /// Adds a torus around `center` in the xz plane, with a ring of `radius` and a
+    /// tube of `tube_radius`, with `steps` bands around the tube and twice as many
+    /// around the ring
+    ///
+    /// Triangles face outwards
+    pub fn add_torus(&mut self, center: [f64; 3], radius: f64, tube_radius: f64, steps: usize) {
+        assert!(steps > 2, "Torus needs at least 3 steps");
+        let [cx, cy, cz] = center;
+        // around the tube first, so the triangles wind outwards
+        self.add_grid(steps, 2 * steps, |j, i| {
+            let theta = std::f64::consts::PI * i as f64 / steps as f64;
+            let phi = 2.0 * std::f64::consts::PI * j as f64 / steps as f64;
+            let (st, ct) = theta.sin_cos();
+            let (sp, cp) = phi.sin_cos();
+            let r = radius + tube_radius * cp;
+            [cx + r * ct, cy + tube_radius * sp, cz - r * st]
+        });
+    }
+
+    /// Adds the box with opposite corners `min` and `max`, 2 triangles per side
+    ///
+    /// Triangles face outwards
+    pub fn add_box(&mut self, min: [f64; 3], max: [f64; 3]) {
+        let corner = |i: usize| {
+            [
+                if i & 1 == 0 { min[0] } else { max[0] },
+                if i & 2 == 0 { min[1] } else { max[1] },
+                if i & 4 == 0 { min[2] } else { max[2] },
+            ]
+        };
+        // corners of each side, counter clockwise from outside, by bits of x, y, z
+        let sides = [
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+        ];
+        for s in sides.iter() {
+            let [a, b, c, d] = s.map(corner);
+            self.add_polygon(a, b, c);
+            self.add_polygon(a, c, d);
+        }
+    }
+
+    /// Adds a grid of `cols` by `rows` quads, where `point(i, j)` gives the corner
+    /// at column i, row j
+    fn add_grid<F>(&mut self, cols: usize, rows: usize, point: F)
+    where
+        F: Fn(usize, usize) -> [f64; 3],
+    {
+        for j in 0..rows {
+            for i in 0..cols {
+                let (p00, p10) = (point(i, j), point(i + 1, j));
+                let (p01, p11) = (point(i, j + 1), point(i + 1, j + 1));
+                self.add_polygon(p00, p01, p11);
+                self.add_polygon(p00, p11, p10);
+            }
+        }
+    }
+}
+
+#[allow(dead_code)]
+// normals
+impl PolygonMatrix {
+    /// Unit normal of every triangle, one (x, y, z, 0) row per triangle
+    pub fn face_normals(&self) -> Matrix {
+        assert_eq!(
+            self.rows() % 3,
+            0,
+            "Number of points must be a multiple of 3"
+        );
+        let points: Vec<&[f64]> = self.iter_by_row().collect();
+        Matrix::from_rows(
+            4,
+            points.chunks(3).map(|t| {
+                let n = normalize3(cross3(
+                    sub3(xyz(t[1]), xyz(t[0])),
+                    sub3(xyz(t[2]), xyz(t[0])),
+                ));
+                [n[0], n[1], n[2], 0.0]
+            }),
+        )
+    }
+
+    /// Smooth normal of every point, one (x, y, z, 0) row per point
+    ///
+    /// Points at the same position share a normal: the average of the normals of
+    /// every triangle they belong to, weighted by triangle area
+    pub fn vertex_normals(&self) -> Matrix {
+        assert_eq!(
+            self.rows() % 3,
+            0,
+            "Number of points must be a multiple of 3"
+        );
+        let key = |p: &[f64]| (p[0].to_bits(), p[1].to_bits(), p[2].to_bits());
+        let points: Vec<&[f64]> = self.iter_by_row().collect();
+
+        // the unnormalized cross product is proportional to the triangle area
+        let mut sums: HashMap<(u64, u64, u64), [f64; 3]> = HashMap::new();
+        for t in points.chunks(3) {
+            let n = cross3(sub3(xyz(t[1]), xyz(t[0])), sub3(xyz(t[2]), xyz(t[0])));
+            for p in t {
+                let sum = sums.entry(key(p)).or_insert([0.0; 3]);
+                for (s, c) in sum.iter_mut().zip(n.iter()) {
+                    *s += c;
+                }
+            }
+        }
+
+        Matrix::from_rows(
+            4,
+            points.iter().map(|p| {
+                let n = normalize3(sums[&key(p)]);
+                [n[0], n[1], n[2], 0.0]
+            }),
+        )
+    }
+
+    /// Unit tangent of every point, pointing where the texture coordinate u
+    /// grows, one (x, y, z, w) row per point, for normal maps
+    ///
+    /// `uvs` has the texture coordinates of every point. Tangents are averaged
+    /// like `vertex_normals`, over points sharing both position and uv, then made
+    /// perpendicular to the vertex normal. w is 1 if the bitangent, the normal
+    /// crossed with the tangent, points where v grows, and -1 if the texture is
+    /// mirrored there.
+    pub fn tangents(&self, uvs: &[[f64; 2]]) -> Matrix {
+        assert_eq!(
+            self.rows(),
+            uvs.len(),
+            "Every point must have texture coordinates"
+        );
+        let normals = self.vertex_normals();
+        let key = |p: &[f64], uv: [f64; 2]| {
+            (
+                (p[0].to_bits(), p[1].to_bits(), p[2].to_bits()),
+                (uv[0].to_bits(), uv[1].to_bits()),
+            )
+        };
+        let points: Vec<&[f64]> = self.iter_by_row().collect();
+
+        // sums of the tangent and bitangent of every triangle around a point
+        let mut sums = HashMap::new();
+        for (t, uv) in points.chunks(3).zip(uvs.chunks(3)) {
+            let (e1, e2) = (sub3(xyz(t[1]), xyz(t[0])), sub3(xyz(t[2]), xyz(t[0])));
+            let (du1, dv1) = (uv[1][0] - uv[0][0], uv[1][1] - uv[0][1]);
+            let (du2, dv2) = (uv[2][0] - uv[0][0], uv[2][1] - uv[0][1]);
+            let det = du1 * dv2 - du2 * dv1;
+            // the texture is squashed to a line on this triangle
+            if det == 0.0 {
+                continue;
+            }
+            let tangent = [0, 1, 2].map(|i| (e1[i] * dv2 - e2[i] * dv1) / det);
+            let bitangent = [0, 1, 2].map(|i| (e2[i] * du1 - e1[i] * du2) / det);
+            for (p, &uv) in t.iter().zip(uv) {
+                let sum = sums.entry(key(p, uv)).or_insert([[0.0; 3]; 2]);
+                for i in 0..3 {
+                    sum[0][i] += tangent[i];
+                    sum[1][i] += bitangent[i];
+                }
+            }
+        }
+
+        Matrix::from_rows(
+            4,
+            points
+                .iter()
+                .zip(uvs)
+                .zip(normals.iter_by_row())
+                .map(|((p, &uv), n)| {
+                    let n = xyz(n);
+                    let [t, b] = sums.get(&key(p, uv)).copied().unwrap_or([[0.0; 3]; 2]);
+                    // Gram-Schmidt: drop the part along the normal
+                    let along = dot3(n, t);
+                    let t = normalize3([0, 1, 2].map(|i| t[i] - n[i] * along));
+                    let w = if dot3(cross3(n, t), b) < 0.0 {
+                        -1.0
+                    } else {
+                        1.0
+                    };
+                    [t[0], t[1], t[2], w]
+                }),
+        )
+    }
+}
+
+#[allow(dead_code)]
+// simplification
+impl PolygonMatrix {
+    /// Copy of the mesh with at most `target_triangles` triangles, keeping its overall shape
+    ///
+    /// Vertex clustering: points are snapped to the finest grid over the mesh
+    /// that leaves few enough triangles, each cell becoming the average of its
+    /// points. Triangles collapsing to a line or a point, and duplicates, are
+    /// dropped; the rest keep their winding.
+    pub fn simplified(&self, target_triangles: usize) -> PolygonMatrix {
+        assert_eq!(
+            self.rows() % 3,
+            0,
+            "Number of points must be a multiple of 3"
+        );
+        if self.rows() / 3 <= target_triangles {
+            return self.clone();
+        }
+        let bounds = match self.bounds() {
+            Some(b) => b,
+            None => return self.clone(),
+        };
+        // one cell leaves no triangles at all; find the most cells that fit
+        let (mut lo, mut hi) = (1, 2);
+        let mut best = self.clustered(&bounds, 1);
+        loop {
+            let m = self.clustered(&bounds, hi);
+            if m.rows() / 3 > target_triangles {
+                break;
+            }
+            best = m;
+            if hi >= MAX_CLUSTER_CELLS {
+                return best;
+            }
+            lo = hi;
+            hi *= 2;
+        }
+        while hi - lo > 1 {
+            let mid = (lo + hi) / 2;
+            let m = self.clustered(&bounds, mid);
+            if m.rows() / 3 <= target_triangles {
+                best = m;
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        best
+    }
+
+    /// The mesh with its points snapped to a grid of `cells` cells across the
+    /// longest side of `bounds`; see `simplified`
+    fn clustered(&self, bounds: &Bounds, cells: usize) -> PolygonMatrix {
+        let size = bounds.size();
+        let cell = size[0].max(size[1]).max(size[2]) / cells as f64;
+        let key = |p: &[f64]| {
+            let mut k = [0; 3];
+            for (axis, k) in k.iter_mut().enumerate() {
+                let i = if cell > 0.0 {
+                    ((p[axis] - bounds.min[axis]) / cell) as usize
+                } else {
+                    0
+                };
+                *k = i.min(cells - 1);
+            }
+            k
+        };
+
+        let mut sums: HashMap<[usize; 3], ([f64; 3], f64)> = HashMap::new();
+        for p in self.iter_by_row() {
+            let (sum, n) = sums.entry(key(p)).or_insert(([0.0; 3], 0.0));
+            for (s, c) in sum.iter_mut().zip(p.iter()) {
+                *s += c;
+            }
+            *n += 1.0;
+        }
+        let center = |k: &[usize; 3]| {
+            let (sum, n) = sums[k];
+            [sum[0] / n, sum[1] / n, sum[2] / n]
+        };
+
+        let mut seen = HashSet::new();
+        let mut m = Matrix::new(0, 4, vec![]);
+        let points: Vec<&[f64]> = self.iter_by_row().collect();
+        for t in points.chunks(3) {
+            let k = [key(t[0]), key(t[1]), key(t[2])];
+            if k[0] == k[1] || k[1] == k[2] || k[2] == k[0] {
+                continue;
+            }
+            // the same triangle starting from any corner, as long as it winds the same way
+            let first = (0..3).min_by_key(|&i| k[i]).unwrap();
+            let k = [k[first], k[(first + 1) % 3], k[(first + 2) % 3]];
+            if seen.insert(k) {
+                m.add_polygon(center(&k[0]), center(&k[1]), center(&k[2]));
+            }
+        }
+        m
+    }
+}
+
+#[allow(dead_code)]
+// slicing
+impl PolygonMatrix {
+    /// Cuts the mesh along `plane`, splitting triangles that cross it
+    ///
+    /// Triangles keep their winding. Triangles lying in the plane go above.
+    pub fn slice(&self, plane: &Plane) -> MeshSlice {
+        assert_eq!(
+            self.rows() % 3,
+            0,
+            "Number of points must be a multiple of 3"
+        );
+        let mut slice = MeshSlice {
+            above: Matrix::new(0, 4, vec![]),
+            below: Matrix::new(0, 4, vec![]),
+            section: vec![],
+        };
+        let mut segments = vec![];
+        let points: Vec<[f64; 3]> = self.iter_by_row().map(xyz).collect();
+        for tri in points.chunks(3) {
+            let d = [0, 1, 2].map(|i| {
+                let d = plane.distance(tri[i]);
+                if d.abs() < PLANE_EPSILON {
+                    0.0
+                } else {
+                    d
+                }
+            });
+            let on_side = |sign: f64| d.iter().all(|&d| d * sign >= 0.0);
+            if on_side(1.0) || on_side(-1.0) {
+                let flat = d.iter().all(|&d| d == 0.0);
+                let above = flat || on_side(1.0);
+                let out = if above {
+                    &mut slice.above
+                } else {
+                    &mut slice.below
+                };
+                out.add_polygon(tri[0], tri[1], tri[2]);
+                // an edge in the plane belongs to the outline; take it from the side above only
+                for i in 0..3 {
+                    let j = (i + 1) % 3;
+                    if above && !flat && d[i] == 0.0 && d[j] == 0.0 {
+                        segments.push([tri[i], tri[j]]);
+                    }
+                }
+                continue;
+            }
+            for (sign, out) in [(1.0, &mut slice.above), (-1.0, &mut slice.below)] {
+                let part = clip_triangle(tri, d, sign);
+                for i in 1..part.len() - 1 {
+                    out.add_polygon(part[0], part[i], part[i + 1]);
+                }
+            }
+            // the points of the triangle in the plane: a vertex and a crossing, or two crossings
+            let mut cut = vec![];
+            for i in 0..3 {
+                let j = (i + 1) % 3;
+                if d[i] == 0.0 {
+                    cut.push(tri[i]);
+                } else if d[i] * d[j] < 0.0 {
+                    cut.push(crossing(tri[i], tri[j], d[i], d[j]));
+                }
+            }
+            segments.push([cut[0], cut[1]]);
+        }
+        slice.section = outlines(&segments, plane.normal);
+        slice
+    }
+}
+
+/// Chains `segments` that share end points into outlines, turned to go counter
+/// clockwise around `normal`
+fn outlines(segments: &[[[f64; 3]; 2]], normal: [f64; 3]) -> Vec<Vec<[f64; 3]>> {
+    // adding 0 turns -0 into 0
+    let key = |p: [f64; 3]| p.map(|c| (c + 0.0).to_bits());
+    let mut ends: HashMap<[u64; 3], Vec<usize>> = HashMap::new();
+    for (i, s) in segments.iter().enumerate() {
+        for &p in s {
+            ends.entry(key(p)).or_default().push(i);
+        }
+    }
+    let near = |p: [f64; 3], q: [f64; 3]| {
+        let d = sub3(p, q);
+        dot3(d, d) < PLANE_EPSILON * PLANE_EPSILON
+    };
+    let mut used = vec![false; segments.len()];
+    let mut outlines = vec![];
+    for first in 0..segments.len() {
+        if used[first] {
+            continue;
+        }
+        used[first] = true;
+        let mut outline = segments[first].to_vec();
+        // follow the chain one way, then, if it didn't close, the other way
+        for _ in 0..2 {
+            loop {
+                let last = *outline.last().unwrap();
+                // seams where the points are only nearly the same are crossed too
+                let next = ends[&key(last)]
+                    .iter()
+                    .copied()
+                    .find(|&i| !used[i])
+                    .or_else(|| {
+                        (0..segments.len())
+                            .find(|&i| !used[i] && segments[i].iter().any(|&p| near(p, last)))
+                    });
+                let [a, b] = match next {
+                    Some(i) => {
+                        used[i] = true;
+                        segments[i]
+                    }
+                    None => break,
+                };
+                outline.push(if near(a, last) { b } else { a });
+            }
+            if near(*outline.last().unwrap(), outline[0]) {
+                outline.pop();
+                break;
+            }
+            outline.reverse();
+        }
+        // Newell's method: the sum points along the polygon's normal
+        let mut area = [0.0; 3];
+        for (i, &p) in outline.iter().enumerate() {
+            let q = outline[(i + 1) % outline.len()];
+            let c = cross3(p, q);
+            area = [area[0] + c[0], area[1] + c[1], area[2] + c[2]];
+        }
+        if dot3(area, normal) < 0.0 {
+            outline.reverse();
+        }
+        outlines.push(outline);
+    }
+    outlines
+}
+
+/// Point where the segment (a, b) crosses a plane, `da` and `db` being their distances to it
+///
+/// The same either way along the segment, so triangles sharing it share the point.
+fn crossing(a: [f64; 3], b: [f64; 3], da: f64, db: f64) -> [f64; 3] {
+    if da < db {
+        return crossing(b, a, db, da);
+    }
+    let t = da / (da - db);
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+    ]
+}
+
+/// Part of the triangle `tri` on the `sign` side of a plane, its points being
+/// `d` away from it; a polygon of 3 or 4 points, in the triangle's winding
+fn clip_triangle(tri: &[[f64; 3]], d: [f64; 3], sign: f64) -> Vec<[f64; 3]> {
+    let mut out = vec![];
+    for i in 0..3 {
+        let j = (i + 1) % 3;
+        if d[i] * sign >= 0.0 {
+            out.push(tri[i]);
+        }
+        if d[i] * d[j] < 0.0 {
+            out.push(crossing(tri[i], tri[j], d[i], d[j]));
+        }
+    }
+    out
+}
+
+#[allow(dead_code)]
+// validation and repair
+impl PolygonMatrix {
+    /// Looks for degenerate triangles, non manifold edges and inconsistent winding
+    pub fn validate(&self) -> MeshReport {
+        let triangles = self.welded();
+        let mut report = MeshReport {
+            degenerate: (0..triangles.len())
+                .filter(|&t| self.is_degenerate(t))
+                .collect(),
+            ..MeshReport::default()
+        };
+        for uses in self.edges(&triangles).values() {
+            match uses.as_slice() {
+                [_] => report.boundary_edges += 1,
+                [(_, a), (_, b)] if a == b => report.inconsistent_edges += 1,
+                [_, _] => (),
+                _ => report.non_manifold_edges += 1,
+            }
+        }
+        report
+    }
+
+    /// Removes triangles with no area, returning how many there were
+    pub fn remove_degenerates(&mut self) -> usize {
+        let keep: Vec<usize> = (0..self.rows() / 3)
+            .filter(|&t| !self.is_degenerate(t))
+            .collect();
+        let removed = self.rows() / 3 - keep.len();
+        let points: Vec<&[f64]> = self.iter_by_row().collect();
+        let m = Matrix::from_rows(
+            self.cols(),
+            keep.iter().flat_map(|&t| points[3 * t..3 * t + 3].iter()),
+        );
+        *self = m;
+        removed
+    }
+
+    /// Flips triangles so neighbors wind the same way, returning how many were flipped
+    ///
+    /// Every connected piece follows its first triangle, and closed pieces are then
+    /// turned to face outwards. Neighbors across non manifold edges are left alone.
+    pub fn fix_winding(&mut self) -> usize {
+        let triangles = self.welded();
+        let edges = self.edges(&triangles);
+        let points: Vec<[f64; 3]> = self.iter_by_row().map(xyz).collect();
+        // whether each triangle gets flipped, once it has been reached
+        let mut flip: Vec<Option<bool>> = vec![None; triangles.len()];
+        for seed in 0..triangles.len() {
+            if flip[seed].is_some() {
+                continue;
+            }
+            flip[seed] = Some(false);
+            let (mut piece, mut stack, mut closed) = (vec![seed], vec![seed], true);
+            while let Some(t) = stack.pop() {
+                for i in 0..3 {
+                    let (a, b) = (triangles[t][i], triangles[t][(i + 1) % 3]);
+                    let uses = &edges[&(a.min(b), a.max(b))];
+                    if uses.len() == 1 {
+                        closed = false;
+                    }
+                    if uses.len() != 2 {
+                        continue;
+                    }
+                    let (here, there) = if uses[0].0 == t {
+                        (uses[0], uses[1])
+                    } else {
+                        (uses[1], uses[0])
+                    };
+                    if flip[there.0].is_none() {
+                        // same direction along the edge means opposite winding
+                        flip[there.0] = Some(flip[t].unwrap() ^ (here.1 == there.1));
+                        piece.push(there.0);
+                        stack.push(there.0);
+                    }
+                }
+            }
+            // a closed surface facing outwards encloses a positive volume
+            let volume: f64 = piece
+                .iter()
+                .map(|&t| {
+                    let [a, b, c] = [points[3 * t], points[3 * t + 1], points[3 * t + 2]];
+                    let v = dot3(a, cross3(b, c));
+                    if flip[t].unwrap() {
+                        -v
+                    } else {
+                        v
+                    }
+                })
+                .sum();
+            if closed && volume < 0.0 {
+                for &t in piece.iter() {
+                    flip[t] = flip[t].map(|f| !f);
+                }
+            }
+        }
+        let mut flipped = 0;
+        for (t, f) in flip.iter().enumerate() {
+            if *f == Some(true) {
+                self.swap_rows(3 * t + 1, 3 * t + 2);
+                flipped += 1;
+            }
+        }
+        flipped
+    }
+
+    /// Vertex ids of every triangle, the same for points at the same position
+    fn welded(&self) -> Vec<[usize; 3]> {
+        assert_eq!(
+            self.rows() % 3,
+            0,
+            "Number of points must be a multiple of 3"
+        );
+        let mut ids: HashMap<(u64, u64, u64), usize> = HashMap::new();
+        let vertices: Vec<usize> = self
+            .iter_by_row()
+            .map(|p| {
+                let next = ids.len();
+                // adding 0 turns -0 into 0
+                let bits = |c: f64| (c + 0.0).to_bits();
+                *ids.entry((bits(p[0]), bits(p[1]), bits(p[2])))
+                    .or_insert(next)
+            })
+            .collect();
+        vertices.chunks(3).map(|t| [t[0], t[1], t[2]]).collect()
+    }
+
+    /// Triangles using each edge, keyed by its vertex ids in order, with whether
+    /// they go along it from the lower id to the higher one
+    fn edges(&self, triangles: &[[usize; 3]]) -> HashMap<(usize, usize), Vec<(usize, bool)>> {
+        let mut edges: HashMap<(usize, usize), Vec<(usize, bool)>> = HashMap::new();
+        for (t, v) in triangles.iter().enumerate() {
+            for i in 0..3 {
+                let (a, b) = (v[i], v[(i + 1) % 3]);
+                if a != b {
+                    edges
+                        .entry((a.min(b), a.max(b)))
+                        .or_default()
+                        .push((t, a < b));
+                }
+            }
+        }
+        edges
+    }
+
+    fn is_degenerate(&self, t: usize) -> bool {
+        let p = |i: usize| xyz(self.iter_by_row().nth(3 * t + i).unwrap());
+        let n = cross3(sub3(p(1), p(0)), sub3(p(2), p(0)));
+        dot3(n, n).sqrt() < DEGENERATE_AREA
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::matrix::Plane;
+
+    #[test]
+    fn flat_heightmap_faces_up() {
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_heightmap(2.0, 2.0, |_, _| 1.0, 2);
+        assert_eq!(m.rows(), 2 * 2 * 2 * 3);
+        let b = m.bounds().unwrap();
+        assert_eq!((b.min, b.max), ([0.0, 1.0, 0.0], [2.0, 1.0, 2.0]));
+        for n in m.face_normals().iter_by_row() {
+            assert_eq!(n, &[0.0, 1.0, 0.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn sphere_faces_outwards() {
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_sphere([1.0, 2.0, 3.0], 2.0, 6);
+        // 2 * 6 quads per band, with only one triangle in each of the 2 polar bands
+        assert_eq!(m.rows(), (2 * 6 * 6 * 2 - 2 * 6 * 2) * 3);
+        let normals = m.face_normals();
+        for (i, n) in normals.iter_by_row().enumerate() {
+            let p = m.iter_by_row().nth(i * 3).unwrap();
+            let out = sub3(xyz(p), [1.0, 2.0, 3.0]);
+            assert!(out[0] * n[0] + out[1] * n[1] + out[2] * n[2] > 0.0);
+        }
+    }
+
+    #[test]
+    fn torus_faces_outwards() {
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_torus([0.0; 3], 3.0, 1.0, 8);
+        assert_eq!(m.rows(), 2 * 8 * 8 * 2 * 3);
+        let normals = m.face_normals();
+        let points: Vec<&[f64]> = m.iter_by_row().collect();
+        for (t, n) in points.chunks(3).zip(normals.iter_by_row()) {
+            let mut c = [0.0; 3];
+            for p in t {
+                for (ci, pi) in c.iter_mut().zip(p.iter()) {
+                    *ci += pi / 3.0;
+                }
+            }
+            // away from the nearest point of the ring
+            let ring = normalize3([c[0], 0.0, c[2]]);
+            let out = sub3(c, [ring[0] * 3.0, 0.0, ring[2] * 3.0]);
+            assert!(out[0] * n[0] + out[1] * n[1] + out[2] * n[2] > 0.0);
+        }
+    }
+
+    #[test]
+    fn simplified_sphere_keeps_its_shape() {
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_sphere([1.0, 2.0, 3.0], 2.0, 16);
+        let simple = m.simplified(100);
+        let triangles = simple.rows() / 3;
+        assert!(
+            triangles <= 100 && triangles > 30,
+            "{} triangles",
+            triangles
+        );
+        // still round, and still facing outwards
+        let normals = simple.face_normals();
+        let points: Vec<&[f64]> = simple.iter_by_row().collect();
+        for (t, n) in points.chunks(3).zip(normals.iter_by_row()) {
+            let out = sub3(xyz(t[0]), [1.0, 2.0, 3.0]);
+            let r = (out[0] * out[0] + out[1] * out[1] + out[2] * out[2]).sqrt();
+            assert!(r > 1.5 && r < 2.01);
+            assert!(out[0] * n[0] + out[1] * n[1] + out[2] * n[2] > 0.0);
+        }
+        assert_eq!(m.simplified(10_000).rows(), m.rows());
+    }
+
+    #[test]
+    fn slices_split_along_the_plane() {
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_sphere([0.0; 3], 2.0, 6);
+        // through the ring of points at the equator
+        let slice = m.slice(&Plane::new([0.0; 3], [0.0, 1.0, 0.0]));
+        assert_eq!(slice.above.rows() + slice.below.rows(), m.rows());
+        assert!(slice.above.iter_by_row().all(|p| p[1] > -1e-9));
+        assert_eq!(slice.section.len(), 1);
+        assert_eq!(slice.section[0].len(), 12);
+
+        // off the points, triangles get split
+        let plane = Plane::new([0.0, 0.5, 0.0], [0.0, -1.0, 0.0]);
+        let slice = m.slice(&plane);
+        assert!(slice.above.rows() + slice.below.rows() > m.rows());
+        assert!(slice.above.iter_by_row().all(|p| p[1] < 0.5 + 1e-9));
+        assert!(slice.below.iter_by_row().all(|p| p[1] > 0.5 - 1e-9));
+        // both triangles of the 12 quads in the band cross the plane, in one loop
+        // going clockwise seen from +y, the plane facing down
+        assert_eq!(slice.section.len(), 1);
+        let outline = &slice.section[0];
+        assert_eq!(outline.len(), 24);
+        for (i, &p) in outline.iter().enumerate() {
+            let r = (p[0] * p[0] + p[2] * p[2]).sqrt();
+            assert!(plane.distance(p).abs() < 1e-9 && r > 1.7 && r < 1.95);
+            let q = outline[(i + 1) % outline.len()];
+            assert!(p[2] * q[0] - p[0] * q[2] < 0.0);
+        }
+        // splitting leaves no slivers or flipped triangles
+        assert!(slice.above.validate().is_valid());
+    }
+
+    #[test]
+    fn broken_meshes_are_found_and_fixed() {
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_heightmap(3.0, 3.0, |_, _| 0.0, 3);
+        let report = m.validate();
+        assert!(report.is_valid() && report.boundary_edges == 12);
+
+        // flip two triangles, and add a sliver
+        m.swap_rows(10, 11);
+        m.swap_rows(31, 32);
+        m.add_polygon([0.0; 3], [1.0, 0.0, 0.0], [2.0, 0.0, 0.0]);
+        let report = m.validate();
+        assert_eq!(report.degenerate, vec![m.rows() / 3 - 1]);
+        assert!(report.inconsistent_edges >= 4 && report.non_manifold_edges == 0);
+        assert_eq!(m.remove_degenerates(), 1);
+        assert_eq!(m.fix_winding(), 2);
+        assert!(m.validate().is_valid());
+        for n in m.face_normals().iter_by_row() {
+            assert_eq!(n, &[0.0, 1.0, 0.0, 0.0]);
+        }
+
+        // a closed tetrahedron wound inside out is turned around
+        let mut m = Matrix::new(0, 4, vec![]);
+        let [a, b, c, d] = [[0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        m.add_polygon(a, b, c);
+        m.add_polygon(a, d, b);
+        m.add_polygon(a, c, d);
+        m.add_polygon(b, d, c);
+        assert!(m.validate().is_valid());
+        assert_eq!(m.fix_winding(), 4);
+        // the face on the z = 0 plane now points down, away from d
+        assert_eq!(m.face_normals().iter_by_row().next().unwrap()[2], -1.0);
+    }
+
+    #[test]
+    fn shared_vertices_average_normals() {
+        // ridge along z: two slopes meeting at x = 1
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_heightmap(2.0, 2.0, |x, _| 1.0 - (x - 1.0).abs(), 2);
+        let normals = m.vertex_normals();
+        for (p, n) in m.iter_by_row().zip(normals.iter_by_row()) {
+            if p[0] == 1.0 && p[2] == 1.0 {
+                // the only point with a full ring of triangles around it
+                assert!(n[0].abs() < 1e-9 && (n[1] - 1.0).abs() < 1e-9, "n: {:?}", n);
+            } else if p[0] != 1.0 {
+                assert!(n[0].abs() > 0.5, "n: {:?}", n);
+            }
+        }
+    }
+
+    #[test]
+    fn tangents_follow_u() {
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_polygon([0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 2.0, 0.0]);
+        m.add_polygon([4.0, 0.0, 0.0], [6.0, 0.0, 0.0], [4.0, 2.0, 0.0]);
+        // the second triangle has its texture mirrored along x
+        let uvs = [
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [0.0, 1.0],
+            [1.0, 0.0],
+            [0.0, 0.0],
+            [1.0, 1.0],
+        ];
+        let tangents = m.tangents(&uvs);
+        let tangents: Vec<&[f64]> = tangents.iter_by_row().collect();
+        assert_eq!(tangents[0], &[1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(tangents[5], &[-1.0, 0.0, 0.0, -1.0]);
+    }
+}
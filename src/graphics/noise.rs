@@ -0,0 +1,179 @@
+use super::rng::Rng;
+
+/// Seeded gradient (Perlin) noise in 2D and 3D
+///
+/// The same seed always gives the same noise, on every platform.
+/// Noise is 0 at every integer lattice point and stays roughly within [-1, 1].
+#[derive(Clone)]
+pub struct Noise {
+    perm: [u8; 512],
+}
+
+/// Gradients pointing at the edges of a cube, as in improved Perlin noise
+const GRAD3: [[f64; 3]; 16] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+    [1.0, 1.0, 0.0],
+    [0.0, -1.0, 1.0],
+    [-1.0, 1.0, 0.0],
+    [0.0, -1.0, -1.0],
+];
+
+/// 6t^5 - 15t^4 + 10t^3, so the noise has continuous second derivatives
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+#[allow(dead_code)]
+impl Noise {
+    pub fn new(seed: u64) -> Noise {
+        let mut rng = Rng::new(seed);
+        let mut p = [0u8; 256];
+        for (i, v) in p.iter_mut().enumerate() {
+            *v = i as u8;
+        }
+        for i in (1..256).rev() {
+            let j = rng.below(i as u64 + 1) as usize;
+            p.swap(i, j);
+        }
+
+        let mut perm = [0u8; 512];
+        for (i, v) in perm.iter_mut().enumerate() {
+            *v = p[i & 255];
+        }
+        Noise { perm }
+    }
+
+    fn hash(&self, x: usize, y: usize, z: usize) -> usize {
+        let h = self.perm[self.perm[x] as usize + y] as usize;
+        self.perm[h + z] as usize
+    }
+
+    fn grad(&self, hash: usize, x: f64, y: f64, z: f64) -> f64 {
+        let g = GRAD3[hash & 15];
+        g[0] * x + g[1] * y + g[2] * z
+    }
+
+    /// 2D noise, a slice of the 3D noise at z = 0
+    pub fn noise2(&self, x: f64, y: f64) -> f64 {
+        self.noise3(x, y, 0.0)
+    }
+
+    pub fn noise3(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (fx, fy, fz) = (x.floor(), y.floor(), z.floor());
+        // lattice cell, wrapped to the permutation table
+        let (xi, yi, zi) = (
+            (fx as i64 & 255) as usize,
+            (fy as i64 & 255) as usize,
+            (fz as i64 & 255) as usize,
+        );
+        // position inside the cell
+        let (x, y, z) = (x - fx, y - fy, z - fz);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let corner = |dx: usize, dy: usize, dz: usize| {
+            let h = self.hash(xi + dx, yi + dy, zi + dz);
+            self.grad(h, x - dx as f64, y - dy as f64, z - dz as f64)
+        };
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+            ),
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+            ),
+        )
+    }
+
+    /// Fractal noise: `octaves` layers of noise, each at double the frequency
+    /// and half the amplitude of the previous one
+    ///
+    /// Normalized to stay roughly within [-1, 1]
+    pub fn fbm3(&self, x: f64, y: f64, z: f64, octaves: u32) -> f64 {
+        let (mut sum, mut amp, mut freq, mut total) = (0.0, 1.0, 1.0, 0.0);
+        for _ in 0..octaves {
+            sum += amp * self.noise3(x * freq, y * freq, z * freq);
+            total += amp;
+            amp /= 2.0;
+            freq *= 2.0;
+        }
+        if total > 0.0 {
+            sum / total
+        } else {
+            0.0
+        }
+    }
+
+    pub fn fbm2(&self, x: f64, y: f64, octaves: u32) -> f64 {
+        self.fbm3(x, y, 0.0, octaves)
+    }
+
+    /// Like `fbm3`, but summing the absolute value of each octave
+    ///
+    /// Gives billowy, always positive values in [0, 1], good for smoke and marble
+    pub fn turbulence3(&self, x: f64, y: f64, z: f64, octaves: u32) -> f64 {
+        let (mut sum, mut amp, mut freq, mut total) = (0.0, 1.0, 1.0, 0.0);
+        for _ in 0..octaves {
+            sum += amp * self.noise3(x * freq, y * freq, z * freq).abs();
+            total += amp;
+            amp /= 2.0;
+            freq *= 2.0;
+        }
+        if total > 0.0 {
+            sum / total
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_noise() {
+        let (a, b, c) = (Noise::new(42), Noise::new(42), Noise::new(43));
+        let samples = |n: &Noise| -> Vec<f64> {
+            (0..50)
+                .map(|i| n.noise3(i as f64 * 0.37, i as f64 * 0.11, -1.3))
+                .collect()
+        };
+        assert_eq!(samples(&a), samples(&b));
+        assert_ne!(samples(&a), samples(&c));
+    }
+
+    #[test]
+    fn zero_on_lattice_and_bounded() {
+        let n = Noise::new(7);
+        for i in -5..5 {
+            assert_eq!(n.noise2(i as f64, (i * 3) as f64), 0.0);
+        }
+        for i in 0..1000 {
+            let t = i as f64 * 0.173;
+            let v = n.noise3(t, t * 0.7, t * 1.3);
+            assert!((-1.5..=1.5).contains(&v), "v: {}", v);
+            assert!((0.0..=1.0).contains(&n.turbulence3(t, 0.5, 0.25, 4)));
+        }
+    }
+}
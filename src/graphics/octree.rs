@@ -0,0 +1,329 @@
+use super::camera::Camera;
+use super::matrix::{Bounds, Matrix};
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+use super::utils::{sub3, xyz};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Max number of points in a leaf
+const LEAF_SIZE: usize = 16;
+
+/// Cells stop splitting this deep, so many copies of one point can't recurse forever
+const MAX_DEPTH: usize = 20;
+
+struct OctreeNode {
+    /// Cube this node covers
+    cell: Bounds,
+    /// Points `order[start..start + count]`
+    start: usize,
+    count: usize,
+    /// Indices into `Octree::nodes` of the non empty octants, none for leaves
+    children: Vec<usize>,
+}
+
+/// Octree over the points of a matrix, for fast queries on big point clouds
+///
+/// Queries return row indices into the matrix the tree was built from. The tree
+/// is built once; rebuild it if the points move.
+pub struct Octree {
+    points: Vec<[f64; 3]>,
+    /// Point indices, grouped so every node is a contiguous range
+    order: Vec<usize>,
+    nodes: Vec<OctreeNode>,
+}
+
+/// Squared distance from `p` to the closest point of `b`, 0 inside
+fn distance2_to(b: &Bounds, p: [f64; 3]) -> f64 {
+    (0..3)
+        .map(|axis| {
+            let d = (b.min[axis] - p[axis]).max(p[axis] - b.max[axis]).max(0.0);
+            d * d
+        })
+        .sum()
+}
+
+fn distance2(a: [f64; 3], b: [f64; 3]) -> f64 {
+    let d = sub3(a, b);
+    d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+}
+
+#[allow(dead_code)]
+impl Octree {
+    /// Builds a tree over the rows of `m`, using their first 3 columns
+    pub fn new(m: &Matrix) -> Octree {
+        let points: Vec<[f64; 3]> = m.iter_by_row().map(xyz).collect();
+        let mut tree = Octree {
+            order: (0..points.len()).collect(),
+            points,
+            nodes: vec![],
+        };
+        if let Some(b) = m.bounds() {
+            // cubic cells, so octants are cubes too
+            let size = b.size();
+            let half = size[0].max(size[1]).max(size[2]) / 2.0;
+            let c = b.center();
+            let cell = Bounds {
+                min: [c[0] - half, c[1] - half, c[2] - half],
+                max: [c[0] + half, c[1] + half, c[2] + half],
+            };
+            tree.build(cell, 0, tree.points.len(), 0);
+        }
+        tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Builds the subtree over `order[start..end]` inside `cell`, returning its node index
+    fn build(&mut self, cell: Bounds, start: usize, end: usize, depth: usize) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(OctreeNode {
+            cell,
+            start,
+            count: end - start,
+            children: vec![],
+        });
+        if end - start <= LEAF_SIZE || depth == MAX_DEPTH {
+            return index;
+        }
+
+        let c = cell.center();
+        let octant =
+            |p: [f64; 3]| (0..3).fold(0, |o, axis| o | ((p[axis] >= c[axis]) as usize) << axis);
+        let mut order = core::mem::take(&mut self.order);
+        order[start..end].sort_unstable_by_key(|&i| octant(self.points[i]));
+        self.order = order;
+
+        let mut children = vec![];
+        let mut first = start;
+        for o in 0..8 {
+            let mut last = first;
+            while last < end && octant(self.points[self.order[last]]) == o {
+                last += 1;
+            }
+            if last > first {
+                let mut child = cell;
+                for (axis, &mid) in c.iter().enumerate() {
+                    if o >> axis & 1 == 0 {
+                        child.max[axis] = mid;
+                    } else {
+                        child.min[axis] = mid;
+                    }
+                }
+                children.push(self.build(child, first, last, depth + 1));
+            }
+            first = last;
+        }
+        self.nodes[index].children = children;
+        index
+    }
+
+    /// Points inside `b`, edges included
+    pub fn within(&self, b: &Bounds) -> Vec<usize> {
+        self.collect(
+            |cell| {
+                (0..3).all(|axis| cell.min[axis] <= b.max[axis] && b.min[axis] <= cell.max[axis])
+            },
+            |p| (0..3).all(|axis| b.min[axis] <= p[axis] && p[axis] <= b.max[axis]),
+        )
+    }
+
+    /// Points at most `radius` away from `center`
+    pub fn within_radius(&self, center: [f64; 3], radius: f64) -> Vec<usize> {
+        let r2 = radius * radius;
+        self.collect(
+            |cell| distance2_to(cell, center) <= r2,
+            |p| distance2(p, center) <= r2,
+        )
+    }
+
+    /// Points in nodes `visit` accepts the cell of, that `keep` accepts
+    fn collect<V, K>(&self, visit: V, keep: K) -> Vec<usize>
+    where
+        V: Fn(&Bounds) -> bool,
+        K: Fn([f64; 3]) -> bool,
+    {
+        let mut out = vec![];
+        let mut stack = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        };
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            if !visit(&node.cell) {
+                continue;
+            }
+            if node.children.is_empty() {
+                let points = &self.order[node.start..node.start + node.count];
+                out.extend(points.iter().filter(|&&i| keep(self.points[i])));
+            } else {
+                stack.extend(node.children.iter());
+            }
+        }
+        out
+    }
+
+    /// Point closest to `p`, or None if the tree is empty
+    pub fn nearest(&self, p: [f64; 3]) -> Option<usize> {
+        let mut best: Option<(usize, f64)> = None;
+        let mut stack = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        };
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            if best.is_some_and(|(_, d)| distance2_to(&node.cell, p) >= d) {
+                continue;
+            }
+            if node.children.is_empty() {
+                for &i in &self.order[node.start..node.start + node.count] {
+                    let d = distance2(self.points[i], p);
+                    if best.is_none_or(|(_, bd)| d < bd) {
+                        best = Some((i, d));
+                    }
+                }
+            } else {
+                // closest octant last, so it is searched first
+                let mut children = node.children.clone();
+                children.sort_by(|&a, &b| {
+                    let da = distance2_to(&self.nodes[a].cell, p);
+                    let db = distance2_to(&self.nodes[b].cell, p);
+                    db.total_cmp(&da)
+                });
+                stack.extend(children);
+            }
+        }
+        best.map(|(i, _)| i)
+    }
+
+    /// One point per cell at most `cell_size` wide, and every point of bigger leaves
+    ///
+    /// Thins out a cloud evenly: dense areas lose the most points.
+    pub fn subsample(&self, cell_size: f64) -> Vec<usize> {
+        self.thin(|cell| Some(cell.size()[0] <= cell_size))
+    }
+
+    /// Points worth drawing of the tree transformed by `world`, as seen by `camera`
+    /// on a `width` by `height` screen
+    ///
+    /// Cells out of view are left out, and cells smaller than `pixels` pixels
+    /// on screen are drawn as a single point.
+    pub fn visible_points(
+        &self,
+        camera: &Camera,
+        world: &Matrix,
+        width: f64,
+        height: f64,
+        pixels: f64,
+    ) -> Vec<usize> {
+        self.thin(|cell| {
+            let cell = cell.transformed(world);
+            if !camera.can_see(&cell, width, height) {
+                return None;
+            }
+            let [dx, dy, dz] = cell.size();
+            let radius = (dx * dx + dy * dy + dz * dz).sqrt() / 2.0;
+            Some(camera.projected_size(cell.center(), radius, height) <= pixels)
+        })
+    }
+
+    /// First point of every node whose cell `small` says is small enough, and
+    /// every point of other leaves; None leaves the whole cell out
+    fn thin<F>(&self, small: F) -> Vec<usize>
+    where
+        F: Fn(&Bounds) -> Option<bool>,
+    {
+        let mut out = vec![];
+        let mut stack = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        };
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            let small = match small(&node.cell) {
+                Some(small) => small,
+                None => continue,
+            };
+            if small {
+                out.push(self.order[node.start]);
+            } else if node.children.is_empty() {
+                out.extend_from_slice(&self.order[node.start..node.start + node.count]);
+            } else {
+                stack.extend(node.children.iter());
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::rng::Rng;
+    use crate::graphics::PPMImg;
+
+    #[test]
+    fn queries_match_brute_force() {
+        let mut rng = Rng::new(7);
+        let mut m = Matrix::new(0, 4, vec![]);
+        for _ in 0..500 {
+            m.append_edge([
+                rng.range(-5.0, 5.0),
+                rng.range(0.0, 1.0),
+                rng.range(-5.0, 5.0),
+            ]);
+        }
+        // lots of copies of one point
+        for _ in 0..100 {
+            m.append_edge([1.0, 0.5, 1.0]);
+        }
+        let tree = Octree::new(&m);
+        let points: Vec<[f64; 3]> = m.iter_by_row().map(xyz).collect();
+
+        let b = Bounds {
+            min: [-1.0, 0.2, -3.0],
+            max: [2.0, 0.8, 1.0],
+        };
+        let mut found = tree.within(&b);
+        found.sort_unstable();
+        let expected: Vec<usize> = (0..points.len())
+            .filter(|&i| (0..3).all(|a| b.min[a] <= points[i][a] && points[i][a] <= b.max[a]))
+            .collect();
+        assert_eq!(found, expected);
+
+        let mut found = tree.within_radius([-2.0, 0.0, 2.0], 1.5);
+        found.sort_unstable();
+        let expected: Vec<usize> = (0..points.len())
+            .filter(|&i| distance2(points[i], [-2.0, 0.0, 2.0]) <= 1.5 * 1.5)
+            .collect();
+        assert_eq!(found, expected);
+
+        for q in [[0.3, 0.1, -4.0], [9.0, 9.0, 9.0], [1.0, 0.5, 1.01]].iter() {
+            let best = tree.nearest(*q).unwrap();
+            assert!(points
+                .iter()
+                .all(|&p| distance2(p, *q) >= distance2(points[best], *q)));
+        }
+
+        // one point per 2.5 wide cell: the slab crosses 4 * 2 * 4 of them
+        let thinned = tree.subsample(3.0);
+        assert!(thinned.len() <= 32 && thinned.len() >= 16);
+
+        // far away, the whole cloud is a few pixels
+        let cam = Camera::new([0.0, 100.0, -400.0], [0.0; 3]);
+        let world = Matrix::ident(4);
+        let mut img = PPMImg::new(20, 20, 255);
+        let all = cam.render_points(&mut img, &m, &world, 0.0);
+        let lod = cam.render_octree_points(&mut img, &m, &tree, &world, 0.0);
+        assert!(lod.pixels_plotted > 0 && lod.pixels_plotted * 4 < all.pixels_plotted);
+    }
+}
@@ -0,0 +1,128 @@
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+use super::{PPMImg, RGB};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+/// `a` moved towards `b` by `t`, from 0 (a) to 1 (b)
+fn mix(a: RGB, b: RGB, t: f64) -> RGB {
+    let mix = |a: u16, b: u16| (f64::from(a) + (f64::from(b) - f64::from(a)) * t).round() as u16;
+    RGB {
+        red: mix(a.red, b.red),
+        green: mix(a.green, b.green),
+        blue: mix(a.blue, b.blue),
+    }
+}
+
+/// Remembers the last few frames of an animation, to show them faded out under
+/// the current one
+///
+/// Handy to see how things move while debugging an animation, and for trails.
+/// Pixels of bg_color count as empty in every frame.
+#[derive(Clone)]
+pub struct OnionSkin {
+    /// Past frames, the most recent first
+    frames: VecDeque<PPMImg>,
+    layers: usize,
+    /// Opacity of the frame right before the current one; every frame before
+    /// that is this much fainter again
+    pub opacity: f64,
+}
+
+#[allow(dead_code)]
+impl OnionSkin {
+    /// Onion skin showing up to `layers` past frames
+    pub fn new(layers: usize, opacity: f64) -> OnionSkin {
+        assert!(
+            (0.0..=1.0).contains(&opacity),
+            "Opacity must be between 0 and 1"
+        );
+        OnionSkin {
+            frames: VecDeque::with_capacity(layers + 1),
+            layers,
+            opacity,
+        }
+    }
+
+    /// Number of past frames remembered so far
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Remembers `frame` as the most recent one, forgetting the oldest if there
+    /// are too many
+    pub fn push(&mut self, frame: &PPMImg) {
+        self.frames.push_front(frame.clone());
+        self.frames.truncate(self.layers);
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    /// `current` drawn over the remembered frames, which fade out the older they
+    /// are; all frames must be the size of `current`
+    pub fn composite(&self, current: &PPMImg) -> PPMImg {
+        for frame in self.frames.iter() {
+            assert!(
+                frame.width == current.width && frame.height == current.height,
+                "Frames must have the same size"
+            );
+        }
+        let fades: Vec<f64> = self
+            .frames
+            .iter()
+            .scan(1.0, |fade, _| {
+                *fade *= self.opacity;
+                Some(*fade)
+            })
+            .collect();
+        let mut out = current.clone();
+        let bg = current.bg_color;
+        for (i, p) in out.data.iter_mut().enumerate() {
+            if *p != bg {
+                continue;
+            }
+            // oldest first, so newer frames end up on top
+            for (frame, &fade) in self.frames.iter().zip(fades.iter()).rev() {
+                let c = frame.data[i];
+                if c != frame.bg_color {
+                    *p = mix(*p, c, fade);
+                }
+            }
+        }
+        out
+    }
+
+    /// Composites `current` over the past frames with `composite`, then remembers it
+    pub fn advance(&mut self, current: &PPMImg) -> PPMImg {
+        let out = self.composite(current);
+        self.push(current);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn older_frames_fade_out() {
+        let mut onion = OnionSkin::new(2, 0.5);
+        let mut img = PPMImg::new(1, 4, 255);
+        let mut last = img.clone();
+        for frame in 0..4 {
+            img.clear();
+            img.plot(frame, 0);
+            last = onion.advance(&img);
+        }
+        assert_eq!(onion.len(), 2);
+        let red: Vec<u16> = (0..4).map(|x| last.pixel(x, 0).red).collect();
+        // the frame 3 back is forgotten
+        assert_eq!(red, vec![0, 64, 128, 255]);
+    }
+}
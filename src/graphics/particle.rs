@@ -0,0 +1,186 @@
+use super::rng::Rng;
+use super::{PPMImg, RGB};
+
+/// A single simulated particle
+#[derive(Copy, Clone, Debug)]
+pub struct Particle {
+    pub pos: [f64; 3],
+    pub vel: [f64; 3],
+    /// Seconds since the particle was emitted
+    pub age: f64,
+    /// Seconds the particle lives for
+    pub lifetime: f64,
+}
+
+/// Spawns particles at a fixed point
+#[derive(Clone, Debug)]
+pub struct Emitter {
+    pub pos: [f64; 3],
+    /// Particles emitted per second
+    pub rate: f64,
+    /// Initial velocity of emitted particles
+    pub velocity: [f64; 3],
+    /// Each velocity component is jittered by up to ± `spread`
+    pub spread: f64,
+    pub lifetime: f64,
+    /// Fraction of a particle carried over between steps
+    pending: f64,
+}
+
+#[allow(dead_code)]
+impl Emitter {
+    pub fn new(
+        pos: [f64; 3],
+        rate: f64,
+        velocity: [f64; 3],
+        spread: f64,
+        lifetime: f64,
+    ) -> Emitter {
+        Emitter {
+            pos,
+            rate,
+            velocity,
+            spread,
+            lifetime,
+            pending: 0.0,
+        }
+    }
+
+    /// Emits `count` particles at once, like a firework burst
+    pub fn burst(&self, system: &mut ParticleSystem, count: usize) {
+        for _ in 0..count {
+            let p = self.spawn(&mut system.rng);
+            system.particles.push(p);
+        }
+    }
+
+    fn spawn(&self, rng: &mut Rng) -> Particle {
+        let mut vel = self.velocity;
+        for v in vel.iter_mut() {
+            *v += rng.range(-self.spread, self.spread);
+        }
+        Particle {
+            pos: self.pos,
+            vel,
+            age: 0.0,
+            lifetime: self.lifetime,
+        }
+    }
+}
+
+fn lerp_color(a: RGB, b: RGB, t: f64) -> RGB {
+    let mix = |a: u16, b: u16| (f64::from(a) + (f64::from(b) - f64::from(a)) * t).round() as u16;
+    RGB {
+        red: mix(a.red, b.red),
+        green: mix(a.green, b.green),
+        blue: mix(a.blue, b.blue),
+    }
+}
+
+/// Particles with emitters, a shared acceleration (e.g. gravity), and color over life
+///
+/// Call `step` once per frame, then `render` into the frame's image.
+pub struct ParticleSystem {
+    pub emitters: Vec<Emitter>,
+    pub particles: Vec<Particle>,
+    pub acceleration: [f64; 3],
+    /// Color of a newly emitted particle
+    pub start_color: RGB,
+    /// Color of a particle at the end of its life
+    pub end_color: RGB,
+    /// When > 0, particles are drawn as lines covering the last `trail` seconds of motion
+    pub trail: f64,
+    rng: Rng,
+}
+
+#[allow(dead_code)]
+impl ParticleSystem {
+    /// Same seed gives the same simulation
+    pub fn new(seed: u64, start_color: RGB, end_color: RGB) -> ParticleSystem {
+        ParticleSystem {
+            emitters: vec![],
+            particles: vec![],
+            acceleration: [0.0; 3],
+            start_color,
+            end_color,
+            trail: 0.0,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Advance the simulation by `dt` seconds
+    pub fn step(&mut self, dt: f64) {
+        for p in self.particles.iter_mut() {
+            for i in 0..3 {
+                p.vel[i] += self.acceleration[i] * dt;
+                p.pos[i] += p.vel[i] * dt;
+            }
+            p.age += dt;
+        }
+        self.particles.retain(|p| p.age < p.lifetime);
+
+        for e in self.emitters.iter_mut() {
+            e.pending += e.rate * dt;
+            while e.pending >= 1.0 {
+                e.pending -= 1.0;
+                let p = e.spawn(&mut self.rng);
+                self.particles.push(p);
+            }
+        }
+    }
+
+    /// Draws every particle as a point (or a short line, see `trail`)
+    ///
+    /// Uses x and y of each particle; img's fg_color is left unchanged
+    pub fn render(&self, img: &mut PPMImg) {
+        let fg = img.fg_color;
+        for p in self.particles.iter() {
+            let life = (p.age / p.lifetime).clamp(0.0, 1.0);
+            img.fg_color = lerp_color(self.start_color, self.end_color, life);
+            if self.trail > 0.0 {
+                let (x0, y0) = (
+                    p.pos[0] - p.vel[0] * self.trail,
+                    p.pos[1] - p.vel[1] * self.trail,
+                );
+                img.draw_line(x0, y0, p.pos[0], p.pos[1]);
+            } else {
+                img.plot(p.pos[0].round() as i32, p.pos[1].round() as i32);
+            }
+        }
+        img.fg_color = fg;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emit_move_and_expire() {
+        let black = RGB {
+            red: 0,
+            green: 0,
+            blue: 0,
+        };
+        let mut ps = ParticleSystem::new(1, black, black);
+        ps.acceleration = [0.0, 10.0, 0.0];
+        ps.emitters.push(Emitter::new(
+            [0.0, 0.0, 0.0],
+            4.0,
+            [1.0, 0.0, 0.0],
+            0.0,
+            1.0,
+        ));
+
+        ps.step(0.5);
+        assert_eq!(ps.particles.len(), 2);
+        ps.step(0.5);
+        assert_eq!(ps.particles.len(), 4);
+        // the first two particles have moved for 0.5s
+        assert_eq!(ps.particles[0].pos, [0.5, 2.5, 0.0]);
+        ps.step(0.5);
+        // first batch expired after 1s
+        assert_eq!(ps.particles.len(), 4);
+        assert!(ps.particles.iter().all(|p| p.age < 1.0));
+    }
+}
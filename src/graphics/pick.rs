@@ -0,0 +1,66 @@
+use super::{PPMImg, RGB};
+
+/// A triangle of a scene: its index in the list `SceneGraph::triangles` returns
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FaceId(pub usize);
+
+#[allow(dead_code)]
+impl FaceId {
+    /// Color standing for this face in an image of max value `depth`, None if
+    /// there are not enough colors left for it
+    ///
+    /// Every face gets a different color, and black stands for no face at all,
+    /// so `depth` 255 tells 2^24 - 1 faces apart.
+    pub fn to_color(self, depth: u16) -> Option<RGB> {
+        let base = u64::from(depth) + 1;
+        let n = self.0 as u64 + 1;
+        if n >= base * base * base {
+            return None;
+        }
+        Some(RGB {
+            red: (n / (base * base)) as u16,
+            green: (n / base % base) as u16,
+            blue: (n % base) as u16,
+        })
+    }
+
+    /// Face standing for `color` in an image of max value `depth`, None for black
+    pub fn from_color(color: RGB, depth: u16) -> Option<FaceId> {
+        let base = u64::from(depth) + 1;
+        let n =
+            (u64::from(color.red) * base + u64::from(color.green)) * base + u64::from(color.blue);
+        n.checked_sub(1).map(|i| FaceId(i as usize))
+    }
+}
+
+/// Face under pixel (x, y) of an image rendered with `RenderMode::Picking`
+///
+/// Lets a front end find the triangle under the cursor; render the picking
+/// image at the same size and from the same camera as the one on screen.
+pub fn pick(img: &PPMImg, x: u32, y: u32) -> Option<FaceId> {
+    if x >= img.width() || y >= img.height() {
+        return None;
+    }
+    FaceId::from_color(img.pixel(x, y), img.depth())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_round_trip() {
+        for &depth in [1, 255, 65535].iter() {
+            for &i in [0, 1, 6, 1000].iter() {
+                let face = FaceId(i);
+                match face.to_color(depth) {
+                    Some(c) => assert_eq!(FaceId::from_color(c, depth), Some(face)),
+                    None => assert!((i as u64) + 1 >= (u64::from(depth) + 1).pow(3)),
+                }
+            }
+        }
+        let black = RGB::from_fractions([0.0; 3], 255);
+        assert_eq!(FaceId::from_color(black, 255), None);
+        assert!(FaceId(1 << 24).to_color(255).is_none());
+    }
+}
@@ -0,0 +1,574 @@
+use super::cap::LineCaps;
+use super::rect::Rect;
+use super::{PPMImg, RGB};
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+
+/// Maps a rectangle of data coordinates onto a rectangle of pixels, for charts
+///
+/// Data y points up, so bigger values are drawn higher in the image. The
+/// corners of the ranges land on the centers of the corner pixels of `screen`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Viewport {
+    /// (min, max) of x shown
+    pub x_range: (f64, f64),
+    /// (min, max) of y shown
+    pub y_range: (f64, f64),
+    /// Pixels the ranges are drawn in
+    pub screen: Rect,
+}
+
+#[allow(dead_code)]
+impl Viewport {
+    pub fn new(x_range: (f64, f64), y_range: (f64, f64), screen: Rect) -> Viewport {
+        assert!(
+            x_range.0 < x_range.1 && y_range.0 < y_range.1,
+            "Ranges must go from min to max"
+        );
+        assert!(
+            screen.w > 1 && screen.h > 1,
+            "Screen must be at least 2 by 2"
+        );
+        Viewport {
+            x_range,
+            y_range,
+            screen,
+        }
+    }
+
+    /// Viewport covering the whole of `img`
+    pub fn full(img: &PPMImg, x_range: (f64, f64), y_range: (f64, f64)) -> Viewport {
+        Viewport::new(x_range, y_range, Rect::new(0, 0, img.width(), img.height()))
+    }
+
+    /// Pixels per unit along x and y
+    pub fn scale(&self) -> (f64, f64) {
+        (
+            f64::from(self.screen.w - 1) / (self.x_range.1 - self.x_range.0),
+            f64::from(self.screen.h - 1) / (self.y_range.1 - self.y_range.0),
+        )
+    }
+
+    /// Pixel coordinates of the data point (x, y)
+    pub fn to_screen(&self, x: f64, y: f64) -> (f64, f64) {
+        let (sx, sy) = self.scale();
+        (
+            f64::from(self.screen.x) + (x - self.x_range.0) * sx,
+            f64::from(self.screen.bottom()) - (y - self.y_range.0) * sy,
+        )
+    }
+
+    /// Data point drawn at pixel coordinates (x, y); inverse of `to_screen`
+    pub fn to_data(&self, x: f64, y: f64) -> (f64, f64) {
+        let (sx, sy) = self.scale();
+        (
+            self.x_range.0 + (x - f64::from(self.screen.x)) / sx,
+            self.y_range.0 + (f64::from(self.screen.bottom()) - y) / sy,
+        )
+    }
+
+    /// Whether the data point (x, y) is inside the ranges
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x_range.0 && x <= self.x_range.1 && y >= self.y_range.0 && y <= self.y_range.1
+    }
+}
+
+/// Data axis, for ticks and rulers
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+}
+
+/// Multiples of `spacing` in [range.0, range.1], in increasing order
+fn multiples(range: (f64, f64), spacing: f64) -> impl Iterator<Item = f64> {
+    assert!(spacing > 0.0, "Spacing must be positive");
+    let first = (range.0 / spacing).ceil() as i64;
+    let last = (range.1 / spacing).floor() as i64;
+    (first..=last).map(move |k| k as f64 * spacing)
+}
+
+// grid and axes
+#[allow(dead_code)]
+impl PPMImg {
+    /// Draws grid lines at every multiple of `spacing` along both axes of `vp`
+    pub fn draw_grid(&mut self, vp: &Viewport, spacing: f64) {
+        let ((x0, x1), (y0, y1)) = (vp.x_range, vp.y_range);
+        for x in multiples(vp.x_range, spacing) {
+            self.draw_data_line(vp, (x, y0), (x, y1));
+        }
+        for y in multiples(vp.y_range, spacing) {
+            self.draw_data_line(vp, (x0, y), (x1, y));
+        }
+    }
+
+    /// Draws the x and y axes through the data point `origin`, with a tick every `scale` units
+    ///
+    /// An axis whose line falls outside the viewport isn't drawn.
+    pub fn draw_axes(&mut self, vp: &Viewport, origin: (f64, f64), scale: f64) {
+        let ((x0, x1), (y0, y1)) = (vp.x_range, vp.y_range);
+        let (ox, oy) = origin;
+        if oy >= y0 && oy <= y1 {
+            self.draw_data_line(vp, (x0, oy), (x1, oy));
+            self.draw_ticks(vp, Axis::X, oy, scale, 3.0);
+        }
+        if ox >= x0 && ox <= x1 {
+            self.draw_data_line(vp, (ox, y0), (ox, y1));
+            self.draw_ticks(vp, Axis::Y, ox, scale, 3.0);
+        }
+    }
+
+    /// Draws ticks `length` pixels long at every multiple of `spacing` along `axis`
+    ///
+    /// Ticks along X are centered on the line y = `at`, those along Y on x = `at`.
+    pub fn draw_ticks(&mut self, vp: &Viewport, axis: Axis, at: f64, spacing: f64, length: f64) {
+        let half = length / 2.0;
+        match axis {
+            Axis::X => {
+                for x in multiples(vp.x_range, spacing) {
+                    let (sx, sy) = vp.to_screen(x, at);
+                    self.in_pixels(|img| img.draw_line(sx, sy - half, sx, sy + half));
+                }
+            }
+            Axis::Y => {
+                for y in multiples(vp.y_range, spacing) {
+                    let (sx, sy) = vp.to_screen(at, y);
+                    self.in_pixels(|img| img.draw_line(sx - half, sy, sx + half, sy));
+                }
+            }
+        }
+    }
+
+    /// Draws a ruler along the bottom (X) or left (Y) edge of `vp`
+    ///
+    /// Ticks go inwards every `spacing` units, with every fifth one twice as long.
+    pub fn draw_ruler(&mut self, vp: &Viewport, axis: Axis, spacing: f64) {
+        let (x0, y0) = (vp.x_range.0, vp.y_range.0);
+        let range = match axis {
+            Axis::X => vp.x_range,
+            Axis::Y => vp.y_range,
+        };
+        let (x0_px, y0_px) = vp.to_screen(x0, y0);
+        for v in multiples(range, spacing) {
+            let major = (v / spacing).round() as i64 % 5 == 0;
+            let length = if major { 8.0 } else { 4.0 };
+            match axis {
+                Axis::X => {
+                    let (px, _) = vp.to_screen(v, y0);
+                    self.in_pixels(|img| img.draw_line(px, y0_px, px, y0_px - length));
+                }
+                Axis::Y => {
+                    let (_, py) = vp.to_screen(x0, v);
+                    self.in_pixels(|img| img.draw_line(x0_px, py, x0_px + length, py));
+                }
+            }
+        }
+        // the edge itself
+        match axis {
+            Axis::X => self.draw_data_line(vp, (x0, y0), (vp.x_range.1, y0)),
+            Axis::Y => self.draw_data_line(vp, (x0, y0), (x0, vp.y_range.1)),
+        }
+    }
+
+    /// Draws the line between data points `p0` and `p1`
+    fn draw_data_line(&mut self, vp: &Viewport, p0: (f64, f64), p1: (f64, f64)) {
+        let (sx0, sy0) = vp.to_screen(p0.0, p0.1);
+        let (sx1, sy1) = vp.to_screen(p1.0, p1.1);
+        self.in_pixels(|img| img.draw_line(sx0, sy0, sx1, sy1));
+    }
+}
+
+/// Shape drawn at each point of a scatter plot
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Marker {
+    /// Filled disc
+    Dot,
+    /// Disc outline
+    Circle,
+    /// Filled square
+    Square,
+    /// x shaped cross
+    Cross,
+    /// + shaped cross
+    Plus,
+}
+
+/// Colors values in [0, 1] are mapped to, for heatmaps
+///
+/// Colors are channel intensities in [0, 1], spread evenly from 0 to 1 and
+/// blended in between.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    stops: Vec<[f64; 3]>,
+}
+
+#[allow(dead_code)]
+impl Palette {
+    pub fn new(stops: Vec<[f64; 3]>) -> Palette {
+        assert!(!stops.is_empty(), "Palette needs at least 1 color");
+        Palette { stops }
+    }
+
+    /// Black to white
+    pub fn grayscale() -> Palette {
+        Palette::new(vec![[0.0; 3], [1.0; 3]])
+    }
+
+    /// Black through red and yellow to white
+    pub fn heat() -> Palette {
+        Palette::new(vec![
+            [0.0, 0.0, 0.0],
+            [0.8, 0.0, 0.0],
+            [1.0, 0.8, 0.0],
+            [1.0, 1.0, 1.0],
+        ])
+    }
+
+    /// Dark blue through teal and green to yellow, evenly bright throughout
+    pub fn viridis() -> Palette {
+        Palette::new(vec![
+            [0.267, 0.005, 0.329],
+            [0.231, 0.322, 0.545],
+            [0.129, 0.569, 0.549],
+            [0.369, 0.788, 0.384],
+            [0.993, 0.906, 0.144],
+        ])
+    }
+
+    /// Color for `t`, clamped to [0, 1]
+    pub fn color(&self, t: f64) -> [f64; 3] {
+        let last = self.stops.len() - 1;
+        let pos = t.clamp(0.0, 1.0) * last as f64;
+        let i = (pos.floor() as usize).min(last);
+        let (a, b) = (self.stops[i], self.stops[(i + 1).min(last)]);
+        let f = pos - i as f64;
+        [
+            a[0] + (b[0] - a[0]) * f,
+            a[1] + (b[1] - a[1]) * f,
+            a[2] + (b[2] - a[2]) * f,
+        ]
+    }
+}
+
+// charts
+#[allow(dead_code)]
+impl PPMImg {
+    /// Draws `marker` `size` pixels across, centered on the pixel coordinates (x, y)
+    pub fn draw_marker(&mut self, x: f64, y: f64, marker: Marker, size: f64) {
+        let r = size / 2.0;
+        match marker {
+            Marker::Dot | Marker::Circle => {
+                let (x0, x1) = ((x - r).floor() as i32, (x + r).ceil() as i32);
+                let (y0, y1) = ((y - r).floor() as i32, (y + r).ceil() as i32);
+                for py in y0..=y1 {
+                    for px in x0..=x1 {
+                        let (dx, dy) = (f64::from(px) - x, f64::from(py) - y);
+                        let d = (dx * dx + dy * dy).sqrt();
+                        let inside = match marker {
+                            Marker::Dot => d <= r + 0.25,
+                            _ => (d - r).abs() <= 0.5,
+                        };
+                        if inside {
+                            self.plot(px, py);
+                        }
+                    }
+                }
+            }
+            Marker::Square => {
+                // fill_rect always counts rows from the top
+                let y = self.raster_y(y);
+                let side = size.round().max(1.0) as u32;
+                let (left, top) = ((x - r + 0.5).floor(), (y - r + 0.5).floor());
+                self.fill_rect(left as i32, top as i32, side, side);
+            }
+            Marker::Cross => {
+                self.draw_line(x - r, y - r, x + r, y + r);
+                self.draw_line(x - r, y + r, x + r, y - r);
+            }
+            Marker::Plus => {
+                self.draw_line(x - r, y, x + r, y);
+                self.draw_line(x, y - r, x, y + r);
+            }
+        }
+    }
+
+    /// Draws an arrow from pixel coordinates (x0, y0) to (x1, y1), with a head `head` pixels long
+    pub fn draw_arrow(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, head: f64) {
+        self.draw_capped_line(x0, y0, x1, y1, &LineCaps::arrow(head));
+    }
+
+    /// Draws `marker` at every data point of `points` inside `vp`
+    pub fn draw_scatter(
+        &mut self,
+        vp: &Viewport,
+        points: &[(f64, f64)],
+        marker: Marker,
+        size: f64,
+    ) {
+        for &(x, y) in points {
+            if vp.contains(x, y) {
+                let (sx, sy) = vp.to_screen(x, y);
+                self.in_pixels(|img| img.draw_marker(sx, sy, marker, size));
+            }
+        }
+    }
+
+    /// Draws the vector field `f(x, y)` as arrows from every multiple of `grid_spacing` in `vp`
+    ///
+    /// Arrows are scaled together so the longest is 0.9 `grid_spacing` long and
+    /// they don't overlap; their lengths still compare like those of the vectors.
+    /// Zero and non finite vectors are left out.
+    pub fn draw_vector_field<F>(&mut self, vp: &Viewport, f: F, grid_spacing: f64)
+    where
+        F: Fn(f64, f64) -> (f64, f64),
+    {
+        let mut arrows = vec![];
+        for y in multiples(vp.y_range, grid_spacing) {
+            for x in multiples(vp.x_range, grid_spacing) {
+                let (vx, vy) = f(x, y);
+                if vx.is_finite() && vy.is_finite() && (vx != 0.0 || vy != 0.0) {
+                    arrows.push((x, y, vx, vy));
+                }
+            }
+        }
+        let longest = arrows
+            .iter()
+            .map(|&(_, _, vx, vy)| (vx * vx + vy * vy).sqrt())
+            .fold(0.0, f64::max);
+        let scale = 0.9 * grid_spacing / longest;
+        for (x, y, vx, vy) in arrows {
+            let (x0, y0) = vp.to_screen(x, y);
+            let (x1, y1) = vp.to_screen(x + vx * scale, y + vy * scale);
+            let len = ((x1 - x0) * (x1 - x0) + (y1 - y0) * (y1 - y0)).sqrt();
+            self.in_pixels(|img| img.draw_arrow(x0, y0, x1, y1, (len * 0.35).min(6.0)));
+        }
+    }
+
+    /// Fills `vp`'s screen with a `rows` by `cols` grid of cells colored by `values`
+    ///
+    /// `values` are row major, with row 0 at the top like an image. They are
+    /// scaled so the smallest maps to the start of `palette` and the biggest to its
+    /// end. Cells that aren't finite are left alone.
+    pub fn draw_heatmap(
+        &mut self,
+        vp: &Viewport,
+        rows: usize,
+        cols: usize,
+        values: &[f64],
+        palette: &Palette,
+    ) {
+        assert_eq!(
+            rows * cols,
+            values.len(),
+            "rows * cols must == values.len()"
+        );
+        let finite = values.iter().copied().filter(|v| v.is_finite());
+        let (lo, hi) = finite.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+            (lo.min(v), hi.max(v))
+        });
+        let scale = if hi > lo { 1.0 / (hi - lo) } else { 0.0 };
+        let r = vp.screen;
+        // pixel edges of cell i of n, across `len` pixels
+        let edge = |i: usize, n: usize, len: u32| (i as u64 * u64::from(len) / n as u64) as i32;
+        let fg = self.fg_color;
+        for row in 0..rows {
+            let (y0, y1) = (edge(row, rows, r.h), edge(row + 1, rows, r.h));
+            for col in 0..cols {
+                let v = values[row * cols + col];
+                if !v.is_finite() {
+                    continue;
+                }
+                let (x0, x1) = (edge(col, cols, r.w), edge(col + 1, cols, r.w));
+                let color = palette.color((v - lo) * scale);
+                self.fg_color = RGB::from_fractions(color, self.depth());
+                self.fill_rect(r.x + x0, r.y + y0, (x1 - x0) as u32, (y1 - y0) as u32);
+            }
+        }
+        self.fg_color = fg;
+    }
+}
+
+/// Segments evenly spaced in the parameter, before refining
+const CURVE_SEGMENTS: usize = 64;
+/// Most times a segment is split in half while refining
+const CURVE_MAX_SPLITS: u32 = 8;
+/// How far, in pixels, a curve may bend away from a straight segment before it is split
+const CURVE_TOLERANCE: f64 = 0.25;
+
+/// Pushes screen space segments following the curve `point(t)` for t in `range` onto `out`
+///
+/// Segments are split where the curve bends, so it stays smooth where it turns
+/// fast without sampling straight parts more than needed. Points that aren't
+/// finite leave a gap.
+fn sample_curve<F>(vp: &Viewport, point: F, range: (f64, f64), out: &mut Vec<(f64, f64, f64, f64)>)
+where
+    F: Fn(f64) -> (f64, f64),
+{
+    let screen = |t: f64| {
+        let (x, y) = point(t);
+        vp.to_screen(x, y)
+    };
+    let finite = |p: (f64, f64)| p.0.is_finite() && p.1.is_finite();
+    // (t0, p0, t1, p1, splits left)
+    let mut stack = vec![];
+    let step = (range.1 - range.0) / CURVE_SEGMENTS as f64;
+    for i in (0..CURVE_SEGMENTS).rev() {
+        let (t0, t1) = (range.0 + i as f64 * step, range.0 + (i + 1) as f64 * step);
+        stack.push((t0, screen(t0), t1, screen(t1), CURVE_MAX_SPLITS));
+    }
+    while let Some((t0, p0, t1, p1, splits)) = stack.pop() {
+        let tm = (t0 + t1) / 2.0;
+        let pm = screen(tm);
+        let (dx, dy) = (pm.0 - (p0.0 + p1.0) / 2.0, pm.1 - (p0.1 + p1.1) / 2.0);
+        let straight = finite(pm) && (dx * dx + dy * dy).sqrt() <= CURVE_TOLERANCE;
+        let ends_ok = finite(p0) && finite(p1);
+        if splits > 0 && !(ends_ok && straight) {
+            // second half below the first, so segments come out in order
+            stack.push((tm, pm, t1, p1, splits - 1));
+            stack.push((t0, p0, tm, pm, splits - 1));
+        } else if ends_ok {
+            out.push((p0.0, p0.1, p1.0, p1.1));
+        }
+    }
+}
+
+// function plots
+#[allow(dead_code)]
+impl PPMImg {
+    /// Plots y = f(x) for x in `domain`, through `vp`
+    pub fn plot_function<F>(&mut self, vp: &Viewport, f: F, domain: (f64, f64))
+    where
+        F: Fn(f64) -> f64,
+    {
+        self.plot_parametric(vp, |x| x, f, domain);
+    }
+
+    /// Plots the curve (fx(t), fy(t)) for t in `t_range`, through `vp`
+    pub fn plot_parametric<FX, FY>(&mut self, vp: &Viewport, fx: FX, fy: FY, t_range: (f64, f64))
+    where
+        FX: Fn(f64) -> f64,
+        FY: Fn(f64) -> f64,
+    {
+        let mut segments = vec![];
+        sample_curve(vp, |t| (fx(t), fy(t)), t_range, &mut segments);
+        self.in_pixels(|img| img.draw_lines(&segments));
+    }
+
+    /// Plots the polar curve r = fr(theta) for theta (in radians) in `theta_range`, through `vp`
+    pub fn plot_polar<F>(&mut self, vp: &Viewport, fr: F, theta_range: (f64, f64))
+    where
+        F: Fn(f64) -> f64,
+    {
+        let mut segments = vec![];
+        let point = |theta: f64| {
+            let r = fr(theta);
+            let (sin, cos) = theta.sin_cos();
+            (r * cos, r * sin)
+        };
+        sample_curve(vp, point, theta_range, &mut segments);
+        self.in_pixels(|img| img.draw_lines(&segments));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_and_axes_land_on_data_coordinates() {
+        let mut img = PPMImg::new(21, 41, 255);
+        let vp = Viewport::full(&img, (-2.0, 2.0), (-1.0, 1.0));
+        assert_eq!(vp.to_screen(0.0, 0.0), (20.0, 10.0));
+        assert_eq!(vp.to_data(40.0, 0.0), (2.0, 1.0));
+
+        img.draw_grid(&vp, 1.0);
+        let lit = |img: &PPMImg, x: u32, y: u32| img.pixel(x, y).red > 0;
+        // vertical lines at x = -2, -1, 0, 1, 2
+        assert!([0, 10, 20, 30, 40].iter().all(|&x| lit(&img, x, 5)));
+        assert!(!lit(&img, 5, 5) && lit(&img, 5, 0) && lit(&img, 5, 10));
+
+        let mut img = PPMImg::new(21, 41, 255);
+        img.draw_axes(&vp, (0.0, 0.0), 0.5);
+        assert!(lit(&img, 3, 10) && lit(&img, 20, 3));
+        // tick at x = 0.5 crosses the x axis
+        assert!(lit(&img, 25, 9) && !lit(&img, 24, 9));
+    }
+
+    #[test]
+    fn curves_pass_through_their_points() {
+        let mut img = PPMImg::new(101, 101, 255);
+        let vp = Viewport::full(&img, (-2.0, 2.0), (-2.0, 2.0));
+        let lit_at = |img: &PPMImg, x: f64, y: f64| {
+            let (sx, sy) = vp.to_screen(x, y);
+            img.pixel(sx.round() as u32, sy.round() as u32).red > 0
+        };
+        img.plot_function(&vp, |x| x * x, (-1.4, 1.4));
+        assert!(lit_at(&img, 1.0, 1.0) && lit_at(&img, -0.5, 0.25));
+        assert!(!lit_at(&img, 0.0, 1.0));
+
+        let mut img = PPMImg::new(101, 101, 255);
+        img.plot_polar(&vp, |_| 1.5, (0.0, core::f64::consts::PI));
+        assert!(lit_at(&img, 0.0, 1.5) && lit_at(&img, -1.5, 0.0));
+        assert!(!lit_at(&img, 0.0, -1.5));
+
+        // 1 / x leaves a gap where it isn't finite instead of a vertical line
+        let mut img = PPMImg::new(101, 101, 255);
+        img.plot_function(
+            &vp,
+            |x| if x == 0.0 { f64::NAN } else { 1.0 / x },
+            (-2.0, 2.0),
+        );
+        assert!(lit_at(&img, 1.0, 1.0) && !lit_at(&img, 0.0, 0.0));
+    }
+
+    #[test]
+    fn scatter_and_heatmap() {
+        let mut img = PPMImg::new(11, 11, 255);
+        let vp = Viewport::full(&img, (0.0, 10.0), (0.0, 10.0));
+        img.draw_scatter(
+            &vp,
+            &[(2.0, 2.0), (8.0, 8.0), (20.0, 0.0)],
+            Marker::Dot,
+            3.0,
+        );
+        let lit = |img: &PPMImg, x: u32, y: u32| img.pixel(x, y).red > 0;
+        assert!(lit(&img, 2, 8) && lit(&img, 3, 8) && lit(&img, 8, 2));
+        assert!(!lit(&img, 4, 8) && !lit(&img, 5, 5));
+
+        let mut img = PPMImg::new(4, 6, 255);
+        let vp = Viewport::full(&img, (0.0, 1.0), (0.0, 1.0));
+        img.draw_heatmap(
+            &vp,
+            2,
+            3,
+            &[0.0, 1.0, 2.0, 2.0, f64::NAN, 4.0],
+            &Palette::grayscale(),
+        );
+        let gray = |img: &PPMImg, x: u32, y: u32| img.pixel(x, y).red;
+        assert_eq!(
+            [gray(&img, 0, 0), gray(&img, 3, 1), gray(&img, 5, 3)],
+            [0, 64, 255]
+        );
+        // the NaN cell is left alone
+        assert_eq!(gray(&img, 2, 2), 0);
+    }
+
+    #[test]
+    fn vector_field_arrows_point_along_the_field() {
+        let mut img = PPMImg::new(21, 21, 255);
+        let vp = Viewport::full(&img, (-1.0, 1.0), (-1.0, 1.0));
+        // everything flows right, faster higher up
+        img.draw_vector_field(&vp, |_, y| (y + 2.0, 0.0), 1.0);
+        let lit = |img: &PPMImg, x: u32, y: u32| img.pixel(x, y).red > 0;
+        // the top arrows are the longest: 9 pixels, the middle ones 6
+        assert!((10..=19).all(|x| lit(&img, x, 0)));
+        assert!(lit(&img, 16, 10) && !lit(&img, 17, 10));
+        // heads trail back from the tips
+        assert!(lit(&img, 17, 1) && !lit(&img, 19, 1));
+        // the bottom ones are a third of the top ones
+        assert!(lit(&img, 13, 20) && !lit(&img, 14, 20));
+    }
+}
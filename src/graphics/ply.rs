@@ -0,0 +1,380 @@
+use super::matrix::{Matrix, PolygonMatrix};
+use super::utils::xyz;
+#[cfg(feature = "fs")]
+use std::fs;
+use std::io;
+
+/// Points and faces of a PLY file, the usual format of 3D scans
+///
+/// Every vertex is a row of `vertices`: x, y, z, 1, followed by its red, green
+/// and blue as fractions when the file has colors, like `Camera::render_points`
+/// expects. Faces list the indices of their vertices.
+#[derive(Clone, Debug)]
+pub struct Ply {
+    pub vertices: Matrix,
+    pub faces: Vec<Vec<usize>>,
+}
+
+/// Scalar types of PLY properties
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Scalar> {
+        Some(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    /// What a color stored as this type is divided by to get a fraction
+    fn color_scale(self) -> f64 {
+        match self {
+            Scalar::U8 => 255.0,
+            Scalar::U16 => 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Property {
+    Scalar(String, Scalar),
+    /// name, type of the count, type of the items
+    List(String, Scalar, Scalar),
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Property::Scalar(name, _) | Property::List(name, _, _) => name,
+        }
+    }
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Reads the values of the body, one at a time
+enum Body<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary(&'a [u8]),
+}
+
+impl<'a> Body<'a> {
+    fn next(&mut self, ty: Scalar) -> io::Result<f64> {
+        match self {
+            Body::Ascii(words) => words
+                .next()
+                .ok_or_else(|| invalid("Unexpected end of ply file"))?
+                .parse()
+                .map_err(|_| invalid("Invalid number in ply file")),
+            Body::Binary(bytes) => {
+                if bytes.len() < ty.size() {
+                    return Err(invalid("Unexpected end of ply file"));
+                }
+                let (b, rest) = bytes.split_at(ty.size());
+                *bytes = rest;
+                Ok(match ty {
+                    Scalar::I8 => f64::from(b[0] as i8),
+                    Scalar::U8 => f64::from(b[0]),
+                    Scalar::I16 => f64::from(i16::from_le_bytes([b[0], b[1]])),
+                    Scalar::U16 => f64::from(u16::from_le_bytes([b[0], b[1]])),
+                    Scalar::I32 => f64::from(i32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                    Scalar::U32 => f64::from(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                    Scalar::F32 => f64::from(f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                    Scalar::F64 => {
+                        f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]])
+                    }
+                })
+            }
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// `v` as a count or index, if it is a whole number from 0 to `max`
+fn whole(v: f64, max: usize) -> Option<usize> {
+    (v >= 0.0 && v.fract() == 0.0 && v <= max as f64).then_some(v as usize)
+}
+
+#[allow(dead_code)]
+impl Ply {
+    /// Parses an ascii or binary little endian ply file
+    ///
+    /// Only vertex positions and colors (red, green, blue) and face vertex
+    /// indices are kept; other elements and properties are skipped.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Ply> {
+        // the header ends with a line of its own saying end_header, and the body
+        // starts after it
+        let mut line_start = 0;
+        let (header_end, body_start) = loop {
+            if line_start >= bytes.len() {
+                return Err(invalid("Missing end_header in ply file"));
+            }
+            let line_end = bytes[line_start..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(bytes.len(), |i| line_start + i);
+            let line = std::str::from_utf8(&bytes[line_start..line_end]);
+            if line.map(str::trim) == Ok("end_header") {
+                break (line_start, (line_end + 1).min(bytes.len()));
+            }
+            line_start = line_end + 1;
+        };
+        let header = std::str::from_utf8(&bytes[..header_end])
+            .map_err(|_| invalid("Ply header must be ascii"))?;
+
+        let mut lines = header.lines();
+        if lines.next().map(str::trim) != Some("ply") {
+            return Err(invalid("Not a ply file"));
+        }
+        let mut binary = None;
+        let mut elements: Vec<Element> = vec![];
+        for line in lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["format", "ascii", _] => binary = Some(false),
+                ["format", "binary_little_endian", _] => binary = Some(true),
+                ["format", ..] => {
+                    return Err(invalid(
+                        "Only ascii and binary little endian ply files are supported",
+                    ))
+                }
+                ["element", name, count] => elements.push(Element {
+                    name: name.to_string(),
+                    count: count
+                        .parse()
+                        .map_err(|_| invalid("Invalid element count in ply file"))?,
+                    properties: vec![],
+                }),
+                ["property", "list", count, item, name] => {
+                    let property = match (Scalar::parse(count), Scalar::parse(item)) {
+                        (Some(c), Some(i)) => Property::List(name.to_string(), c, i),
+                        _ => return Err(invalid("Unknown property type in ply file")),
+                    };
+                    elements
+                        .last_mut()
+                        .ok_or_else(|| invalid("Property before any element in ply file"))?
+                        .properties
+                        .push(property);
+                }
+                ["property", ty, name] => {
+                    let ty = Scalar::parse(ty)
+                        .ok_or_else(|| invalid("Unknown property type in ply file"))?;
+                    elements
+                        .last_mut()
+                        .ok_or_else(|| invalid("Property before any element in ply file"))?
+                        .properties
+                        .push(Property::Scalar(name.to_string(), ty));
+                }
+                [] | ["comment", ..] | ["obj_info", ..] => (),
+                _ => return Err(invalid("Invalid line in ply header")),
+            }
+        }
+
+        let body = &bytes[body_start..];
+        // every element and list item takes at least a byte
+        for element in &elements {
+            if element.count > 0 && element.properties.is_empty() {
+                return Err(invalid("Element without properties in ply file"));
+            }
+            if element.count > body.len() {
+                return Err(invalid("Unexpected end of ply file"));
+            }
+        }
+        let max_count = body.len();
+        let mut body = match binary {
+            Some(true) => Body::Binary(body),
+            Some(false) => Body::Ascii(
+                std::str::from_utf8(body)
+                    .map_err(|_| invalid("Ascii ply body must be text"))?
+                    .split_ascii_whitespace(),
+            ),
+            None => return Err(invalid("Missing format in ply file")),
+        };
+
+        let mut vertices: Option<Matrix> = None;
+        let mut faces = vec![];
+        for element in elements.iter() {
+            let find = |name: &str| element.properties.iter().position(|p| p.name() == name);
+            let position = [find("x"), find("y"), find("z")];
+            let color = [find("red"), find("green"), find("blue")];
+            let has_color = color.iter().all(Option::is_some);
+            let indices = find("vertex_indices").or_else(|| find("vertex_index"));
+            if element.name == "vertex" {
+                vertices = Some(Matrix::new(0, if has_color { 7 } else { 4 }, vec![]));
+            }
+            for _ in 0..element.count {
+                let mut scalars = vec![0.0; element.properties.len()];
+                let mut lists = vec![vec![]; element.properties.len()];
+                for (i, p) in element.properties.iter().enumerate() {
+                    match *p {
+                        Property::Scalar(_, ty) if color.contains(&Some(i)) => {
+                            scalars[i] = body.next(ty)? / ty.color_scale();
+                        }
+                        Property::Scalar(_, ty) => scalars[i] = body.next(ty)?,
+                        Property::List(_, count, item) => {
+                            let n = whole(body.next(count)?, max_count)
+                                .ok_or_else(|| invalid("Invalid list length in ply file"))?;
+                            for _ in 0..n {
+                                lists[i].push(body.next(item)?);
+                            }
+                        }
+                    }
+                }
+                match element.name.as_str() {
+                    "vertex" => {
+                        let value = |p: Option<usize>| p.map_or(0.0, |i| scalars[i]);
+                        let mut row = vec![
+                            value(position[0]),
+                            value(position[1]),
+                            value(position[2]),
+                            1.0,
+                        ];
+                        if has_color {
+                            row.extend(color.iter().map(|&c| value(c)));
+                        }
+                        vertices.as_mut().unwrap().append_row(&row);
+                    }
+                    "face" => {
+                        let list = indices
+                            .ok_or_else(|| invalid("Face without vertex indices in ply file"))?;
+                        let face = lists[list]
+                            .iter()
+                            .map(|&i| whole(i, u32::MAX as usize))
+                            .collect::<Option<_>>()
+                            .ok_or_else(|| invalid("Invalid face vertex index in ply file"))?;
+                        faces.push(face);
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        let vertices = vertices.unwrap_or_else(|| Matrix::new(0, 4, vec![]));
+        if faces.iter().flatten().any(|&i| i >= vertices.rows()) {
+            return Err(invalid("Face vertex index out of range in ply file"));
+        }
+        Ok(Ply { vertices, faces })
+    }
+
+    /// Reads an ascii or binary little endian ply file
+    #[cfg(feature = "fs")]
+    pub fn load(filepath: &str) -> io::Result<Ply> {
+        Ply::from_bytes(&fs::read(filepath)?)
+    }
+
+    /// Whether vertices have colors, in columns 4 to 6
+    pub fn has_colors(&self) -> bool {
+        self.vertices.cols() == 7
+    }
+
+    /// Faces as triangles, fanning out from the first vertex of each face
+    ///
+    /// Faces with less than 3 vertices are left out.
+    pub fn polygons(&self) -> PolygonMatrix {
+        let mut m = Matrix::new(0, 4, vec![]);
+        let rows: Vec<&[f64]> = self.vertices.iter_by_row().collect();
+        let point = |i: usize| xyz(rows[i]);
+        for face in self.faces.iter().filter(|f| f.len() >= 3) {
+            for i in 1..face.len() - 1 {
+                m.add_polygon(point(face[0]), point(face[i]), point(face[i + 1]));
+            }
+        }
+        m
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_mesh_with_colors() {
+        let file = "ply\nformat ascii 1.0\ncomment a colored square\n\
+            element vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+            property uchar red\nproperty uchar green\nproperty uchar blue\n\
+            element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+            0 0 0 255 0 0\n1 0 0 0 255 0\n1 1 0 0 0 255\n0 1 0 255 255 255\n4 0 1 2 3\n";
+        let ply = Ply::from_bytes(file.as_bytes()).unwrap();
+        assert!(ply.has_colors());
+        assert_eq!(ply.vertices.rows(), 4);
+        let row: Vec<&[f64]> = ply.vertices.iter_by_row().collect();
+        assert_eq!(row[1], &[1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0]);
+        assert_eq!(ply.faces, vec![vec![0, 1, 2, 3]]);
+        // the quad becomes 2 triangles
+        assert_eq!(ply.polygons().rows(), 6);
+
+        let bad = file.replace("4 0 1 2 3", "3 0 1 7");
+        assert!(Ply::from_bytes(bad.as_bytes()).is_err());
+        for bad in ["4 0 1 -2 3", "4 0 1 2.5 3", "-4 0 1 2 3", "900000 0 1 2 3"] {
+            let bad = file.replace("4 0 1 2 3", bad);
+            let err = Ply::from_bytes(bad.as_bytes()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        // a comment mentioning end_header doesn't end the header
+        let commented = file.replace("a colored square", "no end_header here");
+        assert_eq!(
+            Ply::from_bytes(commented.as_bytes()).unwrap().faces.len(),
+            1
+        );
+        let empty = "ply\nformat ascii 1.0\nelement nothing 99999999999\nend_header\n";
+        assert!(Ply::from_bytes(empty.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn binary_points() {
+        let mut file = b"ply\nformat binary_little_endian 1.0\nelement vertex 2\n\
+            property double x\nproperty double y\nproperty double z\n\
+            property int intensity\nend_header\n"
+            .to_vec();
+        for (p, intensity) in [([1.0f64, 2.0, 3.0], 7i32), ([-4.0, 0.5, 0.0], 9)].iter() {
+            for c in p.iter() {
+                file.extend_from_slice(&c.to_le_bytes());
+            }
+            file.extend_from_slice(&intensity.to_le_bytes());
+        }
+        let ply = Ply::from_bytes(&file).unwrap();
+        assert!(!ply.has_colors() && ply.faces.is_empty());
+        let rows: Vec<&[f64]> = ply.vertices.iter_by_row().collect();
+        assert_eq!(
+            rows,
+            vec![&[1.0, 2.0, 3.0, 1.0][..], &[-4.0, 0.5, 0.0, 1.0][..]]
+        );
+
+        file.truncate(file.len() - 1);
+        assert!(Ply::from_bytes(&file).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How far along a long render is
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Progress {
+    /// Units of work (frames, rows) done so far
+    pub done: usize,
+    pub total: usize,
+    /// Time since the render started
+    pub elapsed: Duration,
+}
+
+/// Called by renders every time a unit of work is done
+pub type ProgressFn = Box<dyn Fn(Progress) + Send + Sync>;
+
+#[allow(dead_code)]
+impl Progress {
+    /// Fraction done, in [0, 1]
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.done as f64 / self.total as f64
+        }
+    }
+
+    /// Estimated time left, assuming the rest goes as fast as what's done so far
+    pub fn eta(&self) -> Option<Duration> {
+        if self.done == 0 {
+            return None;
+        }
+        let left = self.total.saturating_sub(self.done) as f64;
+        Some(self.elapsed.mul_f64(left / self.done as f64))
+    }
+
+    /// One line progress bar `width` characters wide, like `[=====     ] 3/6 eta 2s`
+    pub fn bar(&self, width: usize) -> String {
+        let filled = ((self.fraction() * width as f64) as usize).min(width);
+        let eta = match self.eta() {
+            Some(eta) => format!(" eta {}s", eta.as_secs()),
+            None => String::new(),
+        };
+        format!(
+            "[{}{}] {}/{}{}",
+            "=".repeat(filled),
+            " ".repeat(width - filled),
+            self.done,
+            self.total,
+            eta
+        )
+    }
+}
+
+/// Counts work done and reports it to an optional `ProgressFn`
+pub struct ProgressTracker<'a> {
+    callback: Option<&'a ProgressFn>,
+    done: usize,
+    total: usize,
+    started: Instant,
+}
+
+impl<'a> ProgressTracker<'a> {
+    /// Starts the clock for `total` units of work
+    pub fn new(callback: Option<&'a ProgressFn>, total: usize) -> ProgressTracker<'a> {
+        ProgressTracker {
+            callback,
+            done: 0,
+            total,
+            started: Instant::now(),
+        }
+    }
+
+    /// Marks one more unit of work as done
+    pub fn step(&mut self) {
+        self.done += 1;
+        if let Some(callback) = self.callback {
+            callback(Progress {
+                done: self.done,
+                total: self.total,
+                elapsed: self.started.elapsed(),
+            });
+        }
+    }
+}
+
+/// Lets another thread stop a render early
+///
+/// Clones share the same flag: keep one, give the other to the render, and
+/// call `cancel` when its result isn't needed anymore. Renders check it
+/// between rows or frames, and stop with what they have drawn so far.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+#[allow(dead_code)]
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Whether an optional token was cancelled
+pub fn is_cancelled(token: &Option<CancelToken>) -> bool {
+    token.as_ref().is_some_and(|t| t.is_cancelled())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eta_and_bar() {
+        let p = Progress {
+            done: 3,
+            total: 6,
+            elapsed: Duration::from_secs(2),
+        };
+        assert_eq!(p.eta(), Some(Duration::from_secs(2)));
+        assert_eq!(p.bar(10), "[=====     ] 3/6 eta 2s");
+        let start = Progress { done: 0, ..p };
+        assert_eq!(start.eta(), None);
+    }
+}
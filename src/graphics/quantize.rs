@@ -0,0 +1,204 @@
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+use super::{PPMImg, RGB};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Image of at most 256 colors: a palette, and the palette index of every pixel,
+/// row by row from the top, like GIF frames store them
+#[derive(Clone)]
+pub struct IndexedImg {
+    pub width: u32,
+    pub height: u32,
+    pub depth: u16,
+    pub palette: Vec<RGB>,
+    pub indices: Vec<u8>,
+}
+
+#[allow(dead_code)]
+impl IndexedImg {
+    /// The image with every index replaced by its color
+    pub fn to_ppm(&self) -> PPMImg {
+        let mut img = PPMImg::new(self.height, self.width, self.depth);
+        for (p, &i) in img.data.iter_mut().zip(self.indices.iter()) {
+            *p = self.palette[i as usize];
+        }
+        img
+    }
+}
+
+fn channels(c: RGB) -> [f64; 3] {
+    [f64::from(c.red), f64::from(c.green), f64::from(c.blue)]
+}
+
+/// Index of the color of `palette` closest to `c`
+fn nearest(palette: &[[f64; 3]], c: [f64; 3]) -> usize {
+    let distance = |p: &[f64; 3]| (0..3).map(|i| (p[i] - c[i]) * (p[i] - c[i])).sum::<f64>();
+    (0..palette.len())
+        .min_by(|&a, &b| distance(&palette[a]).total_cmp(&distance(&palette[b])))
+        .unwrap()
+}
+
+/// Up to `max_colors` colors standing in for `colors`, by median cut
+///
+/// The box of colors spanning the widest range of a channel is split in two at
+/// its median along that channel, until there are enough boxes; every box then
+/// gives the average of its colors.
+fn median_cut(mut colors: Vec<[f64; 3]>, max_colors: usize) -> Vec<[f64; 3]> {
+    // (start, end) of every box, in `colors`
+    let mut boxes = vec![(0, colors.len())];
+    let widest = |colors: &[[f64; 3]]| {
+        (0..3)
+            .map(|ch| {
+                let (lo, hi) = colors
+                    .iter()
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), c| {
+                        (lo.min(c[ch]), hi.max(c[ch]))
+                    });
+                (ch, hi - lo)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap()
+    };
+    while boxes.len() < max_colors {
+        let best = boxes
+            .iter()
+            .enumerate()
+            .map(|(i, &(start, end))| (i, widest(&colors[start..end])))
+            .max_by(|a, b| (a.1).1.total_cmp(&(b.1).1));
+        let (i, (ch, range)) = match best {
+            Some(best) => best,
+            None => break,
+        };
+        // every box holds a single color
+        if range <= 0.0 {
+            break;
+        }
+        let (start, end) = boxes[i];
+        colors[start..end].sort_unstable_by(|a, b| a[ch].total_cmp(&b[ch]));
+        // split between different values, so neither half is empty
+        let mut mid = (start + end) / 2;
+        while mid > start && colors[mid - 1][ch] == colors[mid][ch] {
+            mid -= 1;
+        }
+        if mid == start {
+            while colors[mid][ch] == colors[start][ch] {
+                mid += 1;
+            }
+        }
+        boxes[i] = (start, mid);
+        boxes.push((mid, end));
+    }
+    boxes
+        .iter()
+        .map(|&(start, end)| {
+            let n = (end - start) as f64;
+            let sum = colors[start..end]
+                .iter()
+                .fold([0.0; 3], |s, c| [s[0] + c[0], s[1] + c[1], s[2] + c[2]]);
+            sum.map(|v| (v / n).round())
+        })
+        .collect()
+}
+
+// color quantization
+#[allow(dead_code)]
+impl PPMImg {
+    /// Reduces the image to at most `max_colors` colors, picked by median cut
+    ///
+    /// With `dither` on, the error of every pixel is spread to the pixels after it
+    /// (Floyd-Steinberg), so smooth gradients come out as a mix of nearby colors
+    /// instead of bands.
+    pub fn quantize(&self, max_colors: usize, dither: bool) -> IndexedImg {
+        assert!(
+            (1..=256).contains(&max_colors),
+            "Max colors must be between 1 and 256"
+        );
+        let pixels: Vec<[f64; 3]> = self.data.iter().map(|&c| channels(c)).collect();
+        let palette = if pixels.is_empty() {
+            vec![[0.0; 3]]
+        } else {
+            median_cut(pixels.clone(), max_colors)
+        };
+
+        let (w, h) = (self.width as usize, self.height as usize);
+        let max = f64::from(self.depth);
+        let mut error = vec![[0.0; 3]; if dither { w * h } else { 0 }];
+        let mut indices = Vec::with_capacity(w * h);
+        for (i, &c) in pixels.iter().enumerate() {
+            let c = if dither {
+                [0, 1, 2].map(|ch| (c[ch] + error[i][ch]).clamp(0.0, max))
+            } else {
+                c
+            };
+            let index = nearest(&palette, c);
+            indices.push(index as u8);
+            if dither {
+                let (x, y) = (i % w, i / w);
+                let e = [0, 1, 2].map(|ch| c[ch] - palette[index][ch]);
+                let mut spread = |dx: isize, dy: usize, weight: f64| {
+                    let nx = x as isize + dx;
+                    if nx >= 0 && (nx as usize) < w && y + dy < h {
+                        let j = (y + dy) * w + nx as usize;
+                        for ch in 0..3 {
+                            error[j][ch] += e[ch] * weight;
+                        }
+                    }
+                };
+                spread(1, 0, 7.0 / 16.0);
+                spread(-1, 1, 3.0 / 16.0);
+                spread(0, 1, 5.0 / 16.0);
+                spread(1, 1, 1.0 / 16.0);
+            }
+        }
+        IndexedImg {
+            width: self.width,
+            height: self.height,
+            depth: self.depth,
+            palette: palette
+                .iter()
+                .map(|c| RGB {
+                    red: c[0] as u16,
+                    green: c[1] as u16,
+                    blue: c[2] as u16,
+                })
+                .collect(),
+            indices,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gradient_fits_in_few_colors() {
+        let mut img = PPMImg::new(8, 64, 255);
+        for x in 0..64 {
+            let v = x as f64 / 63.0;
+            img.fg_color = RGB::from_fractions([v, 0.5, 1.0 - v], 255);
+            img.draw_line(x as f64, 0.0, x as f64, 7.0);
+        }
+        // few enough colors are kept exactly
+        let exact = img.quantize(256, false);
+        assert_eq!(exact.palette.len(), 64);
+        let back = exact.to_ppm();
+        assert!((0..64).all(|x| back.pixel(x, 3).red == img.pixel(x, 3).red));
+
+        let banded = img.quantize(4, false);
+        assert_eq!(banded.palette.len(), 4);
+        let dithered = img.quantize(4, true);
+        // dithering mixes colors, so a column averages out closer to the original
+        let column_error = |q: &IndexedImg| {
+            let p = q.to_ppm();
+            (0..64)
+                .map(|x| {
+                    let sum: f64 = (0..8).map(|y| f64::from(p.pixel(x, y).red)).sum();
+                    (sum / 8.0 - f64::from(img.pixel(x, 0).red)).abs()
+                })
+                .sum::<f64>()
+        };
+        assert!(column_error(&dithered) < column_error(&banded));
+    }
+}
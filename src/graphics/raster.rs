@@ -0,0 +1,290 @@
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Depth of the closest surface drawn so far at every pixel
+///
+/// Depths are camera space z: smaller is closer. Starts out at infinity.
+#[derive(Clone)]
+pub struct ZBuffer {
+    width: u32,
+    height: u32,
+    data: Vec<f64>,
+}
+
+#[allow(dead_code)]
+impl ZBuffer {
+    pub fn new(height: u32, width: u32) -> ZBuffer {
+        ZBuffer {
+            width,
+            height,
+            data: vec![f64::INFINITY; (width * height) as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn clear(&mut self) {
+        self.data.fill(f64::INFINITY);
+    }
+
+    pub fn depth(&self, x: u32, y: u32) -> f64 {
+        assert!(x < self.width && y < self.height, "Index out of bound");
+        self.data[(y * self.width + x) as usize]
+    }
+
+    /// Stores `depth` at (x, y) if it is closer than what is there, returning whether it was
+    pub fn test_and_set(&mut self, x: u32, y: u32, depth: f64) -> bool {
+        assert!(x < self.width && y < self.height, "Index out of bound");
+        let d = &mut self.data[(y * self.width + x) as usize];
+        if depth < *d {
+            *d = depth;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Calls `f(x, y, depth, weights)` for every pixel of a `width` by `height` screen
+/// whose center is inside triangle `tri`
+///
+/// Points of `tri` are (x, y, depth) as returned by `Camera::project`, with depth > 0.
+/// `weights` are the perspective correct barycentric coordinates of the pixel, so
+/// any per-point value can be interpolated as `w[0] * v0 + w[1] * v1 + w[2] * v2`.
+/// Pixels on an edge shared by two triangles are drawn by only one of them.
+pub fn fill_triangle<F>(width: u32, height: u32, tri: [[f64; 3]; 3], mut f: F)
+where
+    F: FnMut(u32, u32, f64, [f64; 3]),
+{
+    let [a, b, c] = tri;
+    let area = edge(a, b, c);
+    if area == 0.0 {
+        return;
+    }
+    let min_x = a[0].min(b[0]).min(c[0]).floor().max(0.0) as u32;
+    let min_y = a[1].min(b[1]).min(c[1]).floor().max(0.0) as u32;
+    let max_x = a[0].max(b[0]).max(c[0]).ceil().min(f64::from(width)) as u32;
+    let max_y = a[1].max(b[1]).max(c[1]).ceil().min(f64::from(height)) as u32;
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let p = [f64::from(x) + 0.5, f64::from(y) + 0.5];
+            // screen space barycentrics, positive inside for either winding
+            let w = [
+                edge(b, c, [p[0], p[1], 0.0]) / area,
+                edge(c, a, [p[0], p[1], 0.0]) / area,
+                edge(a, b, [p[0], p[1], 0.0]) / area,
+            ];
+            let edges = [(b, c), (c, a), (a, b)];
+            let inside = w
+                .iter()
+                .zip(edges.iter())
+                .all(|(wi, (e0, e1))| *wi > 0.0 || (*wi == 0.0 && is_top_left(*e0, *e1, area)));
+            if !inside {
+                continue;
+            }
+            // 1 / depth is linear in screen space
+            let inv = [w[0] / a[2], w[1] / b[2], w[2] / c[2]];
+            let sum = inv[0] + inv[1] + inv[2];
+            f(x, y, 1.0 / sum, [inv[0] / sum, inv[1] / sum, inv[2] / sum]);
+        }
+    }
+}
+
+/// Twice the signed area of (a, b, p), using x and y only
+fn edge(a: [f64; 3], b: [f64; 3], p: [f64; 3]) -> f64 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+/// Top-left fill rule, so pixels exactly on a shared edge are drawn once
+fn is_top_left(a: [f64; 3], b: [f64; 3], area: f64) -> bool {
+    // make the edge direction independent of winding
+    let (dx, dy) = if area > 0.0 {
+        (b[0] - a[0], b[1] - a[1])
+    } else {
+        (a[0] - b[0], a[1] - b[1])
+    };
+    (dy == 0.0 && dx > 0.0) || dy < 0.0
+}
+
+/// Fractional bits of the fixed-point (26.6) coordinates used by `subpixel_line_pixels`
+pub const SUBPIXEL_BITS: u32 = 6;
+
+/// 1.0 in fixed point
+const FIXED_ONE: i64 = 1 << SUBPIXEL_BITS;
+
+/// Converts to fixed point, rounding to the nearest 1/64 of a pixel
+pub fn to_fixed(v: f64) -> i64 {
+    (v * FIXED_ONE as f64).round() as i64
+}
+
+/// Pixel whose center (at integer coordinates) is closest to fixed-point `v`,
+/// saturating at the ends of i32
+pub fn fixed_to_pixel(v: i64) -> i32 {
+    let p = v.saturating_add(FIXED_ONE / 2).div_euclid(FIXED_ONE);
+    p.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32
+}
+
+/// Lines are cut to a box this many pixels from the origin on every side before
+/// they go to fixed point, so far off endpoints can't overflow
+const GUARD_BAND: f64 = (1 << 20) as f64;
+
+/// Part of the line from (x0, y0) to (x1, y1) that can reach pixels of a
+/// `width` by `height` image, or None if it misses it
+///
+/// Keeps a pixel to spare on every side, so the pixels of the part that is
+/// drawn don't change; far off endpoints then don't have to be walked.
+pub fn clip_to_image(line: [f64; 4], width: u32, height: u32) -> Option<[f64; 4]> {
+    let (w, h) = (f64::from(width), f64::from(height));
+    clip_line(line, -2.0, w + 1.0, -2.0, h + 1.0)
+}
+
+/// Part of the line inside the box from (xlo, ylo) to (xhi, yhi), with Liang-Barsky
+///
+/// Endpoints already inside are returned as they are.
+pub fn clip_line(line: [f64; 4], xlo: f64, xhi: f64, ylo: f64, yhi: f64) -> Option<[f64; 4]> {
+    let [x0, y0, x1, y1] = line;
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let (mut t0, mut t1) = (0.0_f64, 1.0_f64);
+    for &(p, q) in [
+        (-dx, x0 - xlo),
+        (dx, xhi - x0),
+        (-dy, y0 - ylo),
+        (dy, yhi - y0),
+    ]
+    .iter()
+    {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    let (x0, y0) = if t0 > 0.0 {
+        (x0 + t0 * dx, y0 + t0 * dy)
+    } else {
+        (x0, y0)
+    };
+    let (x1, y1) = if t1 < 1.0 {
+        (line[0] + t1 * dx, line[1] + t1 * dy)
+    } else {
+        (x1, y1)
+    };
+    Some([x0, y0, x1, y1])
+}
+
+/// Calls `plot` for every pixel of the line from (x0, y0) to (x1, y1), without
+/// rounding the endpoints to pixels first
+///
+/// Endpoints are kept in 26.6 fixed point. The line steps one pixel at a time
+/// along its longer axis, and at each step the other coordinate is worked out
+/// from the true line, so a line moved by a fraction of a pixel moves some of
+/// its pixels instead of jumping all at once.
+pub fn subpixel_line_pixels<F: FnMut(i32, i32)>(x0: f64, y0: f64, x1: f64, y1: f64, mut plot: F) {
+    let g = GUARD_BAND;
+    let [x0, y0, x1, y1] = match clip_line([x0, y0, x1, y1], -g, g, -g, g) {
+        Some(line) => line,
+        None => return,
+    };
+    let (x0, y0, x1, y1) = (to_fixed(x0), to_fixed(y0), to_fixed(x1), to_fixed(y1));
+    if (x1 - x0).abs() >= (y1 - y0).abs() {
+        walk_major_axis(x0, y0, x1, y1, &mut plot);
+    } else {
+        walk_major_axis(y0, x0, y1, x1, |y, x| plot(x, y));
+    }
+}
+
+/// Steps `a` over every pixel from a0 to a1, calling `plot(a, b)` with `b` on the line
+fn walk_major_axis<F: FnMut(i32, i32)>(a0: i64, b0: i64, a1: i64, b1: i64, mut plot: F) {
+    let (a0, b0, a1, b1) = if a0 > a1 {
+        (a1, b1, a0, b0)
+    } else {
+        (a0, b0, a1, b1)
+    };
+    let (da, db) = (a1 - a0, b1 - b0);
+    for a in fixed_to_pixel(a0)..=fixed_to_pixel(a1) {
+        let b = if da == 0 {
+            b0
+        } else {
+            // end pixels can have their centers just past the endpoints
+            let t = (i64::from(a) * FIXED_ONE).clamp(a0, a1) - a0;
+            // t * db / da, rounded to nearest
+            let (t, db, da) = (i128::from(t), i128::from(db), i128::from(da));
+            b0 + (2 * t * db + da).div_euclid(2 * da) as i64
+        };
+        plot(a, fixed_to_pixel(b));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_edge_drawn_once() {
+        let (a, b, c, d) = (
+            [0.0, 0.0, 1.0],
+            [4.0, 0.0, 1.0],
+            [4.0, 4.0, 1.0],
+            [0.0, 4.0, 1.0],
+        );
+        let mut count = vec![0; 16];
+        for tri in [[a, b, c], [a, c, d]].iter() {
+            fill_triangle(4, 4, *tri, |x, y, depth, _| {
+                assert!((depth - 1.0).abs() < 1e-12);
+                count[(y * 4 + x) as usize] += 1;
+            });
+        }
+        assert_eq!(count, vec![1; 16]);
+    }
+
+    #[test]
+    fn subpixel_line_follows_true_line() {
+        let mut pixels = vec![];
+        subpixel_line_pixels(0.0, 0.375, 20.0, 1.375, |x, y| pixels.push((x, y)));
+        assert_eq!(pixels.len(), 21);
+        for &(x, y) in pixels.iter() {
+            let true_y = 0.375 + f64::from(x) / 20.0;
+            assert_eq!(y, true_y.round() as i32, "pixel {:?}", (x, y));
+        }
+        // both endpoints round to (0, 0) and (20, 1), but the line is already past
+        // halfway to row 1 at x = 5
+        assert!(pixels.contains(&(5, 1)));
+    }
+
+    #[test]
+    fn depth_is_perspective_correct() {
+        // edge from depth 1 to depth 3: linear interpolation in screen space
+        // would give 1.875 at pixel (3, 0), the correct depth is about 1.41
+        let tri = [[0.0, 0.0, 1.0], [8.0, 0.0, 3.0], [0.0, 8.0, 1.0]];
+        let mut zbuf = ZBuffer::new(8, 8);
+        fill_triangle(8, 8, tri, |x, y, depth, w| {
+            assert!((w.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+            zbuf.test_and_set(x, y, depth);
+        });
+        let d = zbuf.depth(3, 0);
+        assert!(
+            (d - 1.0 / (0.5 + 0.4375 / 3.0 + 0.0625)).abs() < 1e-12,
+            "{}",
+            d
+        );
+        assert_eq!(zbuf.depth(7, 7), f64::INFINITY);
+    }
+}
@@ -0,0 +1,432 @@
+use super::background::Sky;
+use super::bvh::Bvh;
+use super::camera::Camera;
+use super::light::{phong, Light};
+use super::material::Material;
+use super::matrix::Matrix;
+use super::progress::{is_cancelled, CancelToken, ProgressFn, ProgressTracker};
+use super::scene::{Geometry, SceneGraph};
+use super::utils::{cross3, dot3, normalize3, sub3, xyz, Span};
+use super::{PPMImg, RGB};
+
+/// Hits closer than this are ignored, so rays don't hit the surface they start on
+const EPSILON: f64 = 1e-9;
+
+/// Distance secondary rays start off the surface, along the normal
+const BIAS: f64 = 1e-6;
+
+/// Points `origin + t * dir` for t >= 0
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+    pub origin: [f64; 3],
+    pub dir: [f64; 3],
+}
+
+#[allow(dead_code)]
+impl Ray {
+    pub fn new(origin: [f64; 3], dir: [f64; 3]) -> Ray {
+        Ray { origin, dir }
+    }
+
+    pub fn at(&self, t: f64) -> [f64; 3] {
+        [
+            self.origin[0] + self.dir[0] * t,
+            self.origin[1] + self.dir[1] * t,
+            self.origin[2] + self.dir[2] * t,
+        ]
+    }
+}
+
+/// Closest intersection of a ray with a `RayScene`
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct Hit {
+    /// Ray parameter of the hit: the point is `ray.at(t)`
+    pub t: f64,
+    pub point: [f64; 3],
+    /// Unit normal, facing back towards the ray
+    pub normal: [f64; 3],
+    pub material: Material,
+}
+
+/// Sphere around the origin of its object space
+struct Sphere {
+    radius: f64,
+    /// Goes from world to object space
+    inverse: Matrix,
+    material: Material,
+}
+
+impl Sphere {
+    /// Ray parameter and unnormalized normal of the closest hit in (EPSILON, t_max)
+    fn intersect(&self, ray: &Ray, t_max: f64) -> Option<(f64, [f64; 3])> {
+        // intersect in object space; t is the same in both spaces
+        let [ox, oy, oz] = ray.origin;
+        let [dx, dy, dz] = ray.dir;
+        let o = xyz(&self.inverse.transform_point(&[ox, oy, oz, 1.0]));
+        let d = xyz(&self.inverse.transform_point(&[dx, dy, dz, 0.0]));
+        let a = dot3(d, d);
+        let b = 2.0 * dot3(o, d);
+        let c = dot3(o, o) - self.radius * self.radius;
+        let disc = b * b - 4.0 * a * c;
+        if disc < 0.0 {
+            return None;
+        }
+        let sqrt = disc.sqrt();
+        let t = [(-b - sqrt) / (2.0 * a), (-b + sqrt) / (2.0 * a)]
+            .iter()
+            .copied()
+            .find(|t| *t > EPSILON && *t < t_max)?;
+        // normals go through the inverse transpose
+        let n = [o[0] + d[0] * t, o[1] + d[1] * t, o[2] + d[2] * t];
+        let mut normal = [0.0; 3];
+        for (j, w) in normal.iter_mut().enumerate() {
+            *w = (0..3).map(|i| self.inverse.get(j, i).unwrap() * n[i]).sum();
+        }
+        Some((t, normal))
+    }
+}
+
+/// A `SceneGraph` flattened into world space spheres and triangles, for ray tracing
+///
+/// Triangles are kept in a `Bvh`, so big meshes stay fast
+pub struct RayScene {
+    spheres: Vec<Sphere>,
+    triangles: Bvh,
+    /// Material of each triangle in `triangles`
+    triangle_materials: Vec<Material>,
+    lights: Vec<Light>,
+    ambient: [f64; 3],
+    /// Color of rays that hit nothing, if there is no sky
+    pub background: [f64; 3],
+    pub sky: Option<Sky>,
+    /// Max number of times a ray bounces off reflective surfaces
+    pub max_depth: usize,
+    /// Called with the number of rows done after each one by `render`
+    pub on_progress: Option<ProgressFn>,
+    /// Checked before each row by `render`
+    pub cancel: Option<CancelToken>,
+}
+
+#[allow(dead_code)]
+impl RayScene {
+    /// Flattens `scene`; nodes without any material get a matte `default_material`
+    ///
+    /// Edges have no surface, so they are left out
+    pub fn new(scene: &SceneGraph, default_material: Material) -> RayScene {
+        let mut rs = RayScene {
+            spheres: vec![],
+            triangles: Bvh::from_triangles(vec![]),
+            triangle_materials: vec![],
+            lights: scene.lights.clone(),
+            ambient: scene.ambient,
+            background: [0.0; 3],
+            sky: scene.sky.clone(),
+            max_depth: 4,
+            on_progress: None,
+            cancel: None,
+        };
+        let mut triangles = vec![];
+        scene.walk(|node, world, material| {
+            if let Some(geometry) = &node.geometry {
+                let material = material.unwrap_or(&default_material).clone();
+                rs.add_geometry(geometry, world, material, &mut triangles);
+            }
+        });
+        #[cfg(feature = "logging")]
+        log::debug!(
+            "ray scene: {} triangles, {} spheres",
+            triangles.len(),
+            rs.spheres.len()
+        );
+        let _span = Span::new("build bvh");
+        rs.triangles = Bvh::from_triangles(triangles);
+        rs
+    }
+
+    fn add_geometry(
+        &mut self,
+        geometry: &Geometry,
+        world: &Matrix,
+        material: Material,
+        triangles: &mut Vec<[[f64; 3]; 3]>,
+    ) {
+        match geometry {
+            Geometry::Edges(_) => (),
+            Geometry::Polygons(m) => {
+                assert_eq!(m.rows() % 3, 0, "Number of points must be a multiple of 3");
+                let mut iter = m.iter_by_row().map(|p| xyz(&world.transform_point(p)));
+                while let (Some(p0), Some(p1), Some(p2)) = (iter.next(), iter.next(), iter.next()) {
+                    triangles.push([p0, p1, p2]);
+                    self.triangle_materials.push(material.clone());
+                }
+            }
+            Geometry::Sphere { radius } => {
+                if let Some(inverse) = world.inverse() {
+                    self.spheres.push(Sphere {
+                        radius: *radius,
+                        inverse,
+                        material: material.clone(),
+                    });
+                }
+            }
+            Geometry::Instanced { mesh, transforms } => {
+                for t in transforms {
+                    self.add_geometry(mesh, &t.compose(world), material.clone(), triangles);
+                }
+            }
+            Geometry::Lod { levels } => {
+                if let Some((_, level)) = levels.first() {
+                    self.add_geometry(level, world, material, triangles);
+                }
+            }
+        }
+    }
+
+    /// Closest hit along `ray` with t < t_max
+    pub fn intersect(&self, ray: &Ray, t_max: f64) -> Option<Hit> {
+        let mut closest = self
+            .triangles
+            .intersect_ray(ray.origin, ray.dir, t_max)
+            .map(|hit| {
+                let [p0, p1, p2] = self.triangles.triangle(hit.triangle);
+                let n = cross3(sub3(*p1, *p0), sub3(*p2, *p0));
+                (hit.t, n, &self.triangle_materials[hit.triangle])
+            });
+        for sphere in &self.spheres {
+            let t_max = closest.map_or(t_max, |(t, _, _)| t);
+            if let Some((t, n)) = sphere.intersect(ray, t_max) {
+                closest = Some((t, n, &sphere.material));
+            }
+        }
+        closest.map(|(t, n, material)| {
+            let n = normalize3(n);
+            let normal = if dot3(n, ray.dir) > 0.0 {
+                [-n[0], -n[1], -n[2]]
+            } else {
+                n
+            };
+            Hit {
+                t,
+                point: ray.at(t),
+                normal,
+                material: material.clone(),
+            }
+        })
+    }
+
+    /// Color seen along `ray`, as fractions of full brightness
+    ///
+    /// `depth` is the number of reflections so far
+    pub fn trace(&self, ray: &Ray, depth: usize, color_depth: u16) -> [f64; 3] {
+        let hit = match self.intersect(ray, f64::INFINITY) {
+            Some(hit) => hit,
+            None => {
+                return match &self.sky {
+                    Some(sky) => sky.sample(ray.dir),
+                    None => self.background,
+                }
+            }
+        };
+        let (m, n) = (&hit.material, hit.normal);
+        let base = m.base_color(color_depth);
+        let start = [
+            hit.point[0] + n[0] * BIAS,
+            hit.point[1] + n[1] * BIAS,
+            hit.point[2] + n[2] * BIAS,
+        ];
+
+        // shadow rays: anything between the point and a light blocks it
+        let visible = self.lights.iter().filter(|light| {
+            let shadow = Ray::new(start, sub3(light.pos, start));
+            self.intersect(&shadow, 1.0).is_none()
+        });
+        let mut color = phong(m, base, hit.point, n, ray.origin, self.ambient, visible);
+
+        if m.reflectivity > 0.0 && depth < self.max_depth {
+            let d = ray.dir;
+            let k = 2.0 * dot3(d, n);
+            let reflected = Ray::new(start, [d[0] - k * n[0], d[1] - k * n[1], d[2] - k * n[2]]);
+            let mirrored = self.trace(&reflected, depth + 1, color_depth);
+            for (c, r) in color.iter_mut().zip(mirrored.iter()) {
+                *c = *c * (1.0 - m.reflectivity) + r * m.reflectivity;
+            }
+        }
+        color
+    }
+
+    /// Color of pixel (x, y) of a `width` by `height` image seen by `camera`
+    ///
+    /// Pixels are independent, so this can render images of any size row by
+    /// row, see `stream::render_ppm`.
+    pub fn pixel(
+        &self,
+        camera: &Camera,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        depth: u16,
+    ) -> RGB {
+        let (w, h) = (f64::from(width), f64::from(height));
+        let (origin, dir) = camera.ray(f64::from(x) + 0.5, f64::from(y) + 0.5, w, h);
+        RGB::from_fractions(self.trace(&Ray::new(origin, dir), 0, depth), depth)
+    }
+
+    /// Traces one ray per pixel of `img`, as seen by `camera`
+    ///
+    /// Returns false if cancelled, leaving the rows below the last one done untouched
+    pub fn render(&self, camera: &Camera, img: &mut PPMImg) -> bool {
+        let _span = Span::new("ray trace");
+        let (w, h, depth) = (img.width(), img.height(), img.depth());
+        let mut progress = ProgressTracker::new(self.on_progress.as_ref(), h as usize);
+        for y in 0..h {
+            if is_cancelled(&self.cancel) {
+                return false;
+            }
+            for x in 0..w {
+                img.set_pixel(x, y, self.pixel(camera, x, y, w, h, depth));
+            }
+            progress.step();
+        }
+        true
+    }
+}
+
+/// Renders `scene` as seen by `camera` by tracing one ray per pixel
+///
+/// Unlike the wireframe renderers this shades surfaces with the scene's lights,
+/// with shadows and reflections. Nodes without any material use img's fg_color,
+/// and pixels that see nothing show the scene's sky, or img's bg_color if there is none.
+#[allow(dead_code)]
+pub fn render_raytraced(scene: &SceneGraph, camera: &Camera, img: &mut PPMImg) {
+    let depth = img.depth();
+    let mut rs = RayScene::new(scene, Material::new(img.fg_color));
+    rs.background = img.bg_color.to_fractions(depth);
+    rs.render(camera, img);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::scene::Node;
+
+    fn white() -> Material {
+        Material::new(RGB {
+            red: 255,
+            green: 255,
+            blue: 255,
+        })
+    }
+
+    #[test]
+    fn sphere_is_lit_from_the_light_side() {
+        let mut scene = SceneGraph::new();
+        scene.root.add_child(
+            Node::new("ball")
+                .with_geometry(Geometry::Sphere { radius: 1.0 })
+                .with_material(white()),
+        );
+        // +x is on the left, looking down +z
+        scene.lights.push(Light::white([10.0, 0.0, -10.0]));
+        let cam = Camera::new([0.0, 0.0, -5.0], [0.0, 0.0, 0.0]);
+        let mut img = PPMImg::new(21, 21, 255);
+        render_raytraced(&scene, &cam, &mut img);
+
+        assert_eq!(img.pixel(0, 0).red, 0, "background");
+        let (left, right) = (img.pixel(8, 10).red, img.pixel(12, 10).red);
+        assert!(left > right, "{} <= {}", left, right);
+        assert!(right > 0, "ambient light");
+    }
+
+    #[test]
+    fn render_reports_progress_and_stops_when_cancelled() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let mut scene = SceneGraph::new();
+        scene
+            .root
+            .add_child(Node::new("ball").with_geometry(Geometry::Sphere { radius: 1.0 }));
+        let cam = Camera::new([0.0, 0.0, -5.0], [0.0, 0.0, 0.0]);
+        let mut rs = RayScene::new(&scene, white());
+        rs.background = [0.0, 0.0, 1.0];
+
+        // cancel as soon as the third row is done
+        let token = CancelToken::new();
+        let rows = Arc::new(AtomicUsize::new(0));
+        let (t, r) = (token.clone(), rows.clone());
+        rs.on_progress = Some(Box::new(move |p| {
+            r.store(p.done, Ordering::Relaxed);
+            if p.done == 3 {
+                t.cancel();
+            }
+        }));
+        rs.cancel = Some(token);
+
+        let mut img = PPMImg::new(10, 10, 255);
+        assert!(!rs.render(&cam, &mut img));
+        assert_eq!(rows.load(Ordering::Relaxed), 3);
+        assert_eq!(img.pixel(0, 2).blue, 255);
+        assert_eq!(img.pixel(0, 3).blue, 0);
+    }
+
+    #[test]
+    fn sphere_casts_shadow_on_floor() {
+        let mut scene = SceneGraph::new();
+        let mut floor = Matrix::new(0, 4, vec![]);
+        let (a, b, c, d) = (
+            [-10.0, -1.0, -10.0],
+            [10.0, -1.0, -10.0],
+            [10.0, -1.0, 10.0],
+            [-10.0, -1.0, 10.0],
+        );
+        floor.add_polygon(a, c, b);
+        floor.add_polygon(a, d, c);
+        scene
+            .root
+            .add_child(Node::new("floor").with_geometry(Geometry::Polygons(floor)));
+        scene.root.add_child(
+            Node::new("ball")
+                .with_transform(Matrix::translate(0.0, 1.0, 0.0))
+                .with_geometry(Geometry::Sphere { radius: 1.0 }),
+        );
+        scene.lights.push(Light::white([0.0, 10.0, 0.0]));
+        let rs = RayScene::new(&scene, white());
+
+        // looking at the floor under the ball, and further along x
+        let floor_at = |x: f64| {
+            let ray = Ray::new([x, -0.5, -5.0], [0.0, -0.5, 5.0]);
+            let hit = rs.intersect(&ray, f64::INFINITY).unwrap();
+            assert_eq!((hit.t, hit.normal), (1.0, [0.0, 1.0, 0.0]));
+            rs.trace(&ray, 0, 255)
+        };
+        let (lit, shadowed) = (floor_at(5.0), floor_at(0.0));
+        assert!(lit[0] > shadowed[0] && shadowed[0] > 0.0);
+    }
+
+    #[test]
+    fn mirror_reflects_what_it_faces() {
+        let mut scene = SceneGraph::new();
+        let red = Material::new(RGB {
+            red: 255,
+            green: 0,
+            blue: 0,
+        });
+        scene.root.add_child(
+            Node::new("mirror")
+                .with_geometry(Geometry::Sphere { radius: 1.0 })
+                .with_material(white().with_reflectivity(1.0)),
+        );
+        scene.root.add_child(
+            Node::new("red")
+                .with_transform(Matrix::translate(0.0, 0.0, -4.0))
+                .with_geometry(Geometry::Sphere { radius: 1.0 })
+                .with_material(red),
+        );
+        let rs = RayScene::new(&scene, white());
+        // straight down the z axis, through the red sphere, bounces back into it
+        let c = rs.trace(&Ray::new([0.0, 0.0, -2.5], [0.0, 0.0, 1.0]), 0, 255);
+        assert!(c[0] > 0.0 && c[1] == 0.0, "{:?}", c);
+    }
+}
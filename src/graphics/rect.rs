@@ -0,0 +1,77 @@
+/// Axis aligned rectangle in pixel coordinates
+///
+/// (x, y) is the top left corner, and the rectangle covers `w` by `h` pixels
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub w: u32,
+    pub h: u32,
+}
+
+#[allow(dead_code)]
+impl Rect {
+    pub fn new(x: i32, y: i32, w: u32, h: u32) -> Rect {
+        Rect { x, y, w, h }
+    }
+
+    /// Rect covering a single pixel
+    pub fn point(x: i32, y: i32) -> Rect {
+        Rect::new(x, y, 1, 1)
+    }
+
+    /// Rect covering (x0, y0) to (x1, y1), both inclusive
+    pub fn from_corners(x0: i32, y0: i32, x1: i32, y1: i32) -> Rect {
+        let (x0, x1) = if x0 < x1 { (x0, x1) } else { (x1, x0) };
+        let (y0, y1) = if y0 < y1 { (y0, y1) } else { (y1, y0) };
+        Rect::new(x0, y0, (x1 - x0 + 1) as u32, (y1 - y0 + 1) as u32)
+    }
+
+    /// x of the right most column, inclusive
+    pub fn right(&self) -> i32 {
+        self.x + self.w as i32 - 1
+    }
+
+    /// y of the bottom row, inclusive
+    pub fn bottom(&self) -> i32 {
+        self.y + self.h as i32 - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.w == 0 || self.h == 0
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && x <= self.right() && y >= self.y && y <= self.bottom()
+    }
+
+    /// Smallest rect containing both self and other
+    pub fn union(&self, other: &Rect) -> Rect {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        Rect::from_corners(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.right().max(other.right()),
+            self.bottom().max(other.bottom()),
+        )
+    }
+
+    /// Overlapping part of self and other, or None if they don't overlap
+    pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let (x0, y0) = (self.x.max(other.x), self.y.max(other.y));
+        let (x1, y1) = (
+            self.right().min(other.right()),
+            self.bottom().min(other.bottom()),
+        );
+        if self.is_empty() || other.is_empty() || x0 > x1 || y0 > y1 {
+            None
+        } else {
+            Some(Rect::from_corners(x0, y0, x1, y1))
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use super::RGB;
+
+/// Small seeded random number generator (xorshift64*)
+///
+/// The same seed always gives the same numbers, on every platform, so
+/// procedural drawings can be reproduced exactly. Not for cryptography.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+#[allow(dead_code)]
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // xorshift gets stuck at 0, and small seeds start out poorly mixed
+        let state = seed ^ 0x9E37_79B9_7F4A_7C15;
+        Rng {
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in [lo, hi)
+    pub fn range(&mut self, lo: f64, hi: f64) -> f64 {
+        lo + (hi - lo) * self.next_f64()
+    }
+
+    /// Uniform integer in [0, n)
+    pub fn below(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "Range must not be empty");
+        self.next_u64() % n
+    }
+
+    /// Color with every channel uniform in [0, depth]
+    pub fn random_color(&mut self, depth: u16) -> RGB {
+        let n = u64::from(depth) + 1;
+        RGB {
+            red: self.below(n) as u16,
+            green: self.below(n) as u16,
+            blue: self.below(n) as u16,
+        }
+    }
+
+    /// Uniform point (x, y) inside the unit circle
+    pub fn random_in_unit_disk(&mut self) -> [f64; 2] {
+        loop {
+            let p = [self.range(-1.0, 1.0), self.range(-1.0, 1.0)];
+            if p[0] * p[0] + p[1] * p[1] < 1.0 {
+                return p;
+            }
+        }
+    }
+
+    /// Uniform point inside the unit sphere
+    pub fn random_in_unit_sphere(&mut self) -> [f64; 3] {
+        loop {
+            let p = [
+                self.range(-1.0, 1.0),
+                self.range(-1.0, 1.0),
+                self.range(-1.0, 1.0),
+            ];
+            if p[0] * p[0] + p[1] * p[1] + p[2] * p[2] < 1.0 {
+                return p;
+            }
+        }
+    }
+
+    /// Random point less than `radius` away from `point`
+    pub fn jitter(&mut self, point: [f64; 3], radius: f64) -> [f64; 3] {
+        let d = self.random_in_unit_sphere();
+        [
+            point[0] + d[0] * radius,
+            point[1] + d[1] * radius,
+            point[2] + d[2] * radius,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_numbers() {
+        let (mut a, mut b) = (Rng::new(42), Rng::new(42));
+        let xs: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+        let ys: Vec<u64> = (0..8).map(|_| b.next_u64()).collect();
+        assert_eq!(xs, ys);
+        let mut c = Rng::new(43);
+        assert_ne!(xs, (0..8).map(|_| c.next_u64()).collect::<Vec<_>>());
+
+        let mut rng = Rng::new(0);
+        for _ in 0..100 {
+            let f = rng.next_f64();
+            assert!((0.0..1.0).contains(&f));
+            let [x, y] = rng.random_in_unit_disk();
+            assert!(x * x + y * y < 1.0);
+        }
+    }
+}
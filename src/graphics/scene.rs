@@ -0,0 +1,952 @@
+use super::background::Sky;
+use super::camera::Camera;
+use super::gbuffer::GBuffer;
+use super::light::{phong, Light};
+use super::material::Material;
+use super::matrix::{Bounds, EdgeMatrix, Matrix, PolygonMatrix};
+use super::raster::fill_triangle;
+use super::shadow::ShadowMap;
+use super::stats::RenderStats;
+use super::utils::{cross3, dot3, normalize3, sub3, xyz, Span};
+use super::{PPMImg, RGB};
+use std::rc::Rc;
+
+/// Points owned by a scene node
+#[allow(dead_code)]
+pub enum Geometry {
+    Edges(EdgeMatrix),
+    Polygons(PolygonMatrix),
+    /// Sphere of `radius` around the node's origin
+    ///
+    /// The ray tracer intersects it exactly; other renderers draw a triangle mesh
+    Sphere {
+        radius: f64,
+    },
+    /// One mesh drawn once per transform, without copying its points
+    ///
+    /// Each transform places an instance inside the node, like a child node would.
+    /// The mesh can be shared by several nodes.
+    Instanced {
+        mesh: Rc<Geometry>,
+        transforms: Vec<Matrix>,
+    },
+    /// The same object at several levels of detail, picked by its size on screen
+    ///
+    /// Each level comes with the smallest size, in pixels across, it is drawn at.
+    /// Levels go from most to least detailed; renderers without a camera, like the
+    /// ray tracer, always use the first. Without any levels nothing is drawn.
+    Lod {
+        levels: Vec<(f64, Geometry)>,
+    },
+}
+
+#[allow(dead_code)]
+impl Geometry {
+    /// Instances of `mesh`, with no transforms added yet
+    pub fn instanced(mesh: Rc<Geometry>) -> Geometry {
+        Geometry::Instanced {
+            mesh,
+            transforms: vec![],
+        }
+    }
+
+    /// Levels of detail made by `generate(steps)` for each of `steps`
+    ///
+    /// A level is drawn once the object is `pixels_per_step` pixels across for each
+    /// of its steps, so its bands stay about that wide on screen; the one with the
+    /// fewest steps is drawn however small the object gets. `generate` would
+    /// typically call `add_sphere`, `add_torus` or `add_heightmap` with those steps.
+    pub fn lod<F>(steps: &[usize], pixels_per_step: f64, generate: F) -> Geometry
+    where
+        F: Fn(usize) -> PolygonMatrix,
+    {
+        assert!(!steps.is_empty(), "Need at least 1 level of detail");
+        let mut steps = steps.to_vec();
+        steps.sort_unstable_by(|a, b| b.cmp(a));
+        let coarsest = steps.len() - 1;
+        let levels = steps
+            .iter()
+            .enumerate()
+            .map(|(i, &s)| {
+                let min_size = if i == coarsest {
+                    0.0
+                } else {
+                    s as f64 * pixels_per_step
+                };
+                (min_size, Geometry::Polygons(generate(s)))
+            })
+            .collect();
+        Geometry::Lod { levels }
+    }
+
+    /// Level of detail to draw for the geometry transformed by `world`, as seen by
+    /// `camera` on a screen `height` pixels tall; self for anything but `Lod`, and
+    /// None for a `Lod` without levels
+    pub fn lod_level(&self, world: &Matrix, camera: &Camera, height: f64) -> Option<&Geometry> {
+        let levels = match self {
+            Geometry::Lod { levels } => levels,
+            _ => return Some(self),
+        };
+        let size = match levels.first().and_then(|(_, g)| g.bounds(world)) {
+            Some(b) => {
+                let [dx, dy, dz] = b.size();
+                let radius = (dx * dx + dy * dy + dz * dz).sqrt() / 2.0;
+                camera.projected_size(b.center(), radius, height)
+            }
+            None => f64::INFINITY,
+        };
+        levels
+            .iter()
+            .find(|(min_size, _)| size >= *min_size)
+            .or_else(|| levels.last())
+            .map(|(_, level)| level)
+    }
+
+    /// Steps used when drawing a `Sphere` as triangles
+    const SPHERE_STEPS: usize = 12;
+
+    /// Triangles approximating a sphere of `radius` around the origin
+    fn sphere_mesh(radius: f64) -> PolygonMatrix {
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_sphere([0.0; 3], radius, Geometry::SPHERE_STEPS);
+        m
+    }
+
+    /// Draws the geometry transformed by `world`
+    pub fn render(&self, img: &mut PPMImg, world: &Matrix) {
+        match self {
+            Geometry::Edges(m) => img.render_edge_matrix_transformed(m, world),
+            Geometry::Polygons(m) => img.render_polygon_matrix_transformed(m, world),
+            Geometry::Sphere { radius } => {
+                img.render_polygon_matrix_transformed(&Geometry::sphere_mesh(*radius), world)
+            }
+            Geometry::Instanced { mesh, transforms } => {
+                for t in transforms {
+                    mesh.render(img, &t.compose(world));
+                }
+            }
+            Geometry::Lod { levels } => {
+                if let Some((_, level)) = levels.first() {
+                    level.render(img, world);
+                }
+            }
+        }
+    }
+
+    /// Pushes every triangle of the geometry, transformed by `world`, onto `out`
+    ///
+    /// Spheres are turned into triangles; edges have no surface and are left out
+    pub fn triangles(&self, world: &Matrix, out: &mut Vec<[[f64; 3]; 3]>) {
+        self.push_triangles(world, None, out);
+    }
+
+    /// Same as `triangles`, with levels of detail picked for `camera` on a screen
+    /// `height` pixels tall
+    pub fn camera_triangles(
+        &self,
+        world: &Matrix,
+        camera: &Camera,
+        height: f64,
+        out: &mut Vec<[[f64; 3]; 3]>,
+    ) {
+        self.push_triangles(world, Some((camera, height)), out);
+    }
+
+    fn push_triangles(
+        &self,
+        world: &Matrix,
+        view: Option<(&Camera, f64)>,
+        out: &mut Vec<[[f64; 3]; 3]>,
+    ) {
+        let mut push = |m: &PolygonMatrix| {
+            let mut iter = m.iter_by_row().map(|p| xyz(&world.transform_point(p)));
+            while let (Some(p0), Some(p1), Some(p2)) = (iter.next(), iter.next(), iter.next()) {
+                out.push([p0, p1, p2]);
+            }
+        };
+        match self {
+            Geometry::Edges(_) => (),
+            Geometry::Polygons(m) => push(m),
+            Geometry::Sphere { radius } => push(&Geometry::sphere_mesh(*radius)),
+            Geometry::Instanced { mesh, transforms } => {
+                for t in transforms {
+                    mesh.push_triangles(&t.compose(world), view, out);
+                }
+            }
+            Geometry::Lod { levels } => {
+                let level = match view {
+                    Some((camera, height)) => self.lod_level(world, camera, height),
+                    None => levels.first().map(|(_, level)| level),
+                };
+                if let Some(level) = level {
+                    level.push_triangles(world, view, out);
+                }
+            }
+        }
+    }
+
+    /// World space box around the geometry transformed by `world`, None if it has no points
+    pub fn bounds(&self, world: &Matrix) -> Option<Bounds> {
+        match self {
+            Geometry::Edges(m) | Geometry::Polygons(m) => Some(m.bounds()?.transformed(world)),
+            Geometry::Sphere { radius } => {
+                let r = *radius;
+                let local = Bounds {
+                    min: [-r; 3],
+                    max: [r; 3],
+                };
+                Some(local.transformed(world))
+            }
+            Geometry::Instanced { mesh, transforms } => transforms
+                .iter()
+                .filter_map(|t| mesh.bounds(&t.compose(world)))
+                .reduce(|a, b| a.union(&b)),
+            Geometry::Lod { levels } => levels.first()?.1.bounds(world),
+        }
+    }
+
+    /// Whether the geometry transformed by `world` is entirely out of `camera`'s view on `img`
+    fn out_of_view(&self, img: &PPMImg, world: &Matrix, camera: &Camera) -> bool {
+        let (w, h) = (f64::from(img.width()), f64::from(img.height()));
+        self.bounds(world)
+            .is_some_and(|b| !camera.can_see(&b, w, h))
+    }
+
+    /// Draws the geometry transformed by `world`, as seen by `camera`
+    ///
+    /// Geometry entirely out of view is skipped, and counted as a culled object;
+    /// instances are culled one by one.
+    pub fn render_camera(&self, img: &mut PPMImg, world: &Matrix, camera: &Camera) -> RenderStats {
+        match self {
+            // culled instance by instance, or level by level
+            Geometry::Instanced { .. } | Geometry::Lod { .. } => (),
+            _ if self.out_of_view(img, world, camera) => {
+                img.stats_mut().objects_culled += 1;
+                return RenderStats {
+                    objects_culled: 1,
+                    ..RenderStats::default()
+                };
+            }
+            _ => (),
+        }
+        match self {
+            Geometry::Edges(m) => camera.render_edges(img, m, world),
+            Geometry::Polygons(m) => camera.render_polygons(img, m, world),
+            Geometry::Sphere { radius } => {
+                camera.render_polygons(img, &Geometry::sphere_mesh(*radius), world)
+            }
+            Geometry::Instanced { mesh, transforms } => {
+                let mut stats = RenderStats::default();
+                for t in transforms {
+                    stats += mesh.render_camera(img, &t.compose(world), camera);
+                }
+                stats
+            }
+            Geometry::Lod { .. } => {
+                let height = f64::from(img.height());
+                match self.lod_level(world, camera, height) {
+                    Some(level) => level.render_camera(img, world, camera),
+                    None => RenderStats::default(),
+                }
+            }
+        }
+    }
+}
+
+/// A named node in a `SceneGraph`
+///
+/// `transform` is local: it places the node inside its parent.
+/// Nodes without a material use the one of the closest ancestor that has one.
+pub struct Node {
+    pub name: String,
+    pub transform: Matrix,
+    pub geometry: Option<Geometry>,
+    pub material: Option<Material>,
+    pub children: Vec<Node>,
+}
+
+#[allow(dead_code)]
+impl Node {
+    /// New empty node with an identity transform
+    pub fn new(name: &str) -> Node {
+        Node {
+            name: name.to_string(),
+            transform: Matrix::ident(4),
+            geometry: None,
+            material: None,
+            children: vec![],
+        }
+    }
+
+    pub fn with_transform(mut self, transform: Matrix) -> Node {
+        self.transform = transform;
+        self
+    }
+
+    pub fn with_geometry(mut self, geometry: Geometry) -> Node {
+        self.geometry = Some(geometry);
+        self
+    }
+
+    pub fn with_material(mut self, material: Material) -> Node {
+        self.material = Some(material);
+        self
+    }
+
+    /// Adds a child node, returning a reference to it
+    pub fn add_child(&mut self, child: Node) -> &mut Node {
+        self.children.push(child);
+        self.children.last_mut().unwrap()
+    }
+
+    /// Depth first search for a node named `name`, including self
+    pub fn find(&self, name: &str) -> Option<&Node> {
+        if self.name == name {
+            return Some(self);
+        }
+        self.children.iter().find_map(|c| c.find(name))
+    }
+
+    /// Depth first search for a node named `name`, including self
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut Node> {
+        if self.name == name {
+            return Some(self);
+        }
+        self.children.iter_mut().find_map(|c| c.find_mut(name))
+    }
+}
+
+/// Tree of nodes, each transformed relative to its parent
+pub struct SceneGraph {
+    pub root: Node,
+    /// Lights used by shaded renderers, in world space
+    pub lights: Vec<Light>,
+    /// Light reaching every surface, per channel
+    pub ambient: [f64; 3],
+    /// Shown where there is no geometry by renderers that fill the whole image,
+    /// and mirrored by reflective materials
+    pub sky: Option<Sky>,
+}
+
+#[allow(dead_code)]
+impl SceneGraph {
+    pub fn new() -> SceneGraph {
+        SceneGraph {
+            root: Node::new("root"),
+            lights: vec![],
+            ambient: [1.0; 3],
+            sky: None,
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Node> {
+        self.root.find(name)
+    }
+
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut Node> {
+        self.root.find_mut(name)
+    }
+
+    /// Calls `f` on every node with its world transform and inherited material
+    ///
+    /// Nodes are visited depth first, parents before children
+    pub fn walk<F>(&self, mut f: F)
+    where
+        F: FnMut(&Node, &Matrix, Option<&Material>),
+    {
+        // transform stack: each entry is a node with its parent's world transform
+        let mut stack: Vec<(&Node, Matrix, Option<&Material>)> =
+            vec![(&self.root, Matrix::ident(4), None)];
+        while let Some((node, parent, parent_mat)) = stack.pop() {
+            let world = node.transform.compose(&parent);
+            let material = node.material.as_ref().or(parent_mat);
+            f(node, &world, material);
+            for child in node.children.iter().rev() {
+                stack.push((child, world.clone(), material));
+            }
+        }
+    }
+
+    /// Renders every node's geometry into `img`
+    ///
+    /// Nodes without any material are drawn with img's fg_color
+    pub fn render(&self, img: &mut PPMImg) {
+        let default_color = img.fg_color;
+        self.walk(|node, world, material| {
+            if let Some(geometry) = &node.geometry {
+                img.fg_color = material.map_or(default_color, |m| m.color);
+                geometry.render(img, world);
+            }
+        });
+        img.fg_color = default_color;
+    }
+
+    /// World space triangles of every node, with the node's material
+    ///
+    /// Nodes without any material get `default_material`
+    pub fn triangles(&self, default_material: Material) -> Vec<([[f64; 3]; 3], Material)> {
+        let mut out = vec![];
+        let mut tris = vec![];
+        self.walk(|node, world, material| {
+            if let Some(geometry) = &node.geometry {
+                geometry.triangles(world, &mut tris);
+                let material = material.unwrap_or(&default_material).clone();
+                out.extend(tris.drain(..).map(|t| (t, material.clone())));
+            }
+        });
+        out
+    }
+
+    /// Renders every node's geometry into `img` as seen by `camera`
+    pub fn render_camera(&self, img: &mut PPMImg, camera: &Camera) -> RenderStats {
+        let default_color = img.fg_color;
+        let mut stats = RenderStats::default();
+        self.walk(|node, world, material| {
+            if let Some(geometry) = &node.geometry {
+                img.fg_color = material.map_or(default_color, |m| m.color);
+                stats += geometry.render_camera(img, world, camera);
+            }
+        });
+        img.fg_color = default_color;
+        stats
+    }
+}
+
+/// What `SceneGraph::render_mode` draws
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Lit surfaces, as `render_shaded` draws them
+    #[default]
+    Shaded,
+    /// Closest surface depth as gray, from white at the nearest to dark gray at the farthest
+    Depth,
+    /// Surface normals as colors, see `GBuffer::draw_normals`
+    Normals,
+    /// Outlines only, as `render_camera` draws them
+    Wireframe,
+    /// Every triangle filled in a flat color of its own
+    FlatRandomColorPerFace,
+    /// Every triangle filled in a color encoding its `FaceId`, for `pick::pick`
+    Picking,
+    /// Cel shaded in 3 bands with outlines in img's fg_color, see `render_toon`
+    Toon,
+}
+
+// shaded rendering
+#[allow(dead_code)]
+impl SceneGraph {
+    /// Size of the depth map rendered from each light
+    const SHADOW_MAP_SIZE: u32 = 512;
+
+    /// Renders filled, Phong shaded surfaces as seen by `camera`, with shadows
+    ///
+    /// Each pixel is lit by the scene's lights that reach it, found with a shadow
+    /// map per light. Opaque surfaces are drawn first; transparent ones are then
+    /// blended over them from the farthest to the closest, and cast no shadows.
+    /// Nodes without any material use img's fg_color. Pixels no
+    /// surface covers show the sky, or are left alone if there is none, and
+    /// reflective materials mirror the sky. Triangles crossing the camera's near
+    /// plane are left out.
+    ///
+    /// Returns what was drawn, culled and hidden by the z-buffer.
+    pub fn render_shaded(&self, img: &mut PPMImg, camera: &Camera) -> RenderStats {
+        let before = img.stats();
+        if let Some(sky) = &self.sky {
+            sky.render(img, camera);
+        }
+        let (w, h) = (f64::from(img.width()), f64::from(img.height()));
+        let tris = self.shaded_triangles(camera, Material::new(img.fg_color), w, h);
+        img.stats_mut().objects_culled += tris.objects_culled;
+        img.stats_mut().triangles_culled += tris.triangles_culled;
+        // objects out of view still cast shadows into it
+        let points: Vec<[[f64; 3]; 3]> = tris.all.iter().map(|(t, _)| *t).collect();
+        let mut bounds = match points.first() {
+            Some(t) => Bounds::point(t[0]),
+            None => return img.stats() - before,
+        };
+        for p in points.iter().flatten() {
+            bounds.include(*p);
+        }
+        let casters: Vec<[[f64; 3]; 3]> = tris
+            .all
+            .iter()
+            .filter(|(_, m)| !m.is_transparent())
+            .map(|(t, _)| *t)
+            .collect();
+        let shadow_maps: Vec<ShadowMap> = {
+            let _span = Span::new("shadow maps");
+            self.lights
+                .iter()
+                .map(|l| ShadowMap::new(l, &bounds, &casters, SceneGraph::SHADOW_MAP_SIZE))
+                .collect()
+        };
+        let (transparent, opaque): (Vec<usize>, Vec<usize>) = tris
+            .visible
+            .iter()
+            .partition(|&&i| tris.all[i].1.is_transparent());
+        let gbuf = {
+            let _span = Span::new("rasterize");
+            let mut gbuf = GBuffer::new(img.height(), img.width());
+            *img.stats_mut() += gbuf.rasterize_faces(&tris.all, opaque, camera);
+            gbuf
+        };
+        {
+            let _span = Span::new("lighting");
+            gbuf.shade(
+                img,
+                camera.eye,
+                self.ambient,
+                &self.lights,
+                &shadow_maps,
+                self.sky.as_ref(),
+            );
+        }
+        if !transparent.is_empty() {
+            let _span = Span::new("transparency");
+            self.blend_transparent(img, camera, &gbuf, &tris.all, transparent, &shadow_maps);
+        }
+        let stats = img.stats() - before;
+        #[cfg(feature = "logging")]
+        log::debug!(
+            "render_shaded: {} triangles, {} out of view, {} culled or crossing the near plane, {} lights",
+            points.len(),
+            points.len() as u64 - stats.triangles_filled - stats.triangles_culled,
+            stats.triangles_culled,
+            self.lights.len()
+        );
+        stats
+    }
+
+    /// Blends the triangles at indices `faces` of `triangles` into `img`, from
+    /// the farthest to the closest, hiding the parts behind opaque surfaces in `gbuf`
+    ///
+    /// The pass after `GBuffer::shade` in `render_shaded`. Transparent surfaces
+    /// don't hide each other: those behind still show through the ones in front.
+    fn blend_transparent(
+        &self,
+        img: &mut PPMImg,
+        camera: &Camera,
+        gbuf: &GBuffer,
+        triangles: &[([[f64; 3]; 3], Material)],
+        mut faces: Vec<usize>,
+        shadow_maps: &[ShadowMap],
+    ) {
+        let (width, height, depth) = (img.width(), img.height(), img.depth());
+        let (w, h) = (f64::from(width), f64::from(height));
+        let view = camera.view_matrix();
+        let to_camera = |p: [f64; 3]| xyz(&view.transform_point(&[p[0], p[1], p[2], 1.0]));
+        // by the depth of their centers, the farthest first
+        let center_depth = |i: &usize| {
+            let t = &triangles[*i].0;
+            to_camera(t[0])[2] + to_camera(t[1])[2] + to_camera(t[2])[2]
+        };
+        faces.sort_by(|a, b| center_depth(b).total_cmp(&center_depth(a)));
+
+        for face in faces {
+            let (tri, material) = &triangles[face];
+            let screen = tri.map(|p| camera.project_camera_point(to_camera(p), w, h));
+            let screen = match screen {
+                [Some(a), Some(b), Some(c)] => [a, b, c],
+                _ => {
+                    img.stats_mut().triangles_culled += 1;
+                    continue;
+                }
+            };
+            img.stats_mut().triangles_filled += 1;
+            let normal = normalize3(cross3(sub3(tri[1], tri[0]), sub3(tri[2], tri[0])));
+            let base = material.base_color(depth);
+            let mut rejected = 0;
+            fill_triangle(width, height, screen, |x, y, z, wt| {
+                if z >= gbuf.depth(x, y) {
+                    rejected += 1;
+                    return;
+                }
+                let p =
+                    [0, 1, 2].map(|i| wt[0] * tri[0][i] + wt[1] * tri[1][i] + wt[2] * tri[2][i]);
+                // keep whichever side faces the camera
+                let n = if dot3(normal, sub3(camera.eye, p)) < 0.0 {
+                    normal.map(|c| -c)
+                } else {
+                    normal
+                };
+                let lit = self
+                    .lights
+                    .iter()
+                    .enumerate()
+                    .filter(|(l, _)| shadow_maps.get(*l).is_none_or(|map| map.is_lit(p)))
+                    .map(|(_, light)| light);
+                let color = phong(material, base, p, n, camera.eye, self.ambient, lit);
+                let behind = img.pixel(x, y).to_fractions(depth);
+                let a = material.opacity;
+                let blended = [0, 1, 2].map(|i| behind[i] * (1.0 - a) + color[i] * a);
+                img.set_pixel(x, y, RGB::from_fractions(blended, depth));
+            });
+            img.stats_mut().zbuffer_rejections += rejected;
+        }
+    }
+
+    /// Renders the scene as seen by `camera` cel shaded, like a cartoon
+    ///
+    /// Light is cut into `bands` flat steps, with no shadows, and the edges of
+    /// shapes are drawn in `outline`; see `GBuffer::outlines`. Nodes without
+    /// any material use img's fg_color, and pixels no surface covers show the
+    /// sky, or are left alone if there is none.
+    pub fn render_toon(
+        &self,
+        img: &mut PPMImg,
+        camera: &Camera,
+        bands: u32,
+        outline: RGB,
+    ) -> RenderStats {
+        let before = img.stats();
+        if let Some(sky) = &self.sky {
+            sky.render(img, camera);
+        }
+        let (w, h) = (f64::from(img.width()), f64::from(img.height()));
+        let tris = self.shaded_triangles(camera, Material::new(img.fg_color), w, h);
+        img.stats_mut().objects_culled += tris.objects_culled;
+        img.stats_mut().triangles_culled += tris.triangles_culled;
+        let mut gbuf = GBuffer::new(img.height(), img.width());
+        *img.stats_mut() += gbuf.rasterize_faces(&tris.all, tris.visible, camera);
+        gbuf.shade_toon(img, camera.eye, self.ambient, &self.lights, bands);
+        gbuf.draw_outlines(img, outline);
+        img.stats() - before
+    }
+
+    /// Renders the scene as seen by `camera` in `mode`
+    ///
+    /// Every mode but `Shaded` is for debugging: they show what the z-buffer,
+    /// normals and triangles look like without lighting in the way. Pixels no
+    /// surface covers are left alone in those.
+    pub fn render_mode(&self, img: &mut PPMImg, camera: &Camera, mode: RenderMode) -> RenderStats {
+        let before = img.stats();
+        match mode {
+            RenderMode::Shaded => return self.render_shaded(img, camera),
+            RenderMode::Wireframe => return self.render_camera(img, camera),
+            RenderMode::Toon => {
+                let outline = img.fg_color;
+                return self.render_toon(img, camera, 3, outline);
+            }
+            _ => {}
+        }
+        let (w, h) = (f64::from(img.width()), f64::from(img.height()));
+        let tris = self.shaded_triangles(camera, Material::new(img.fg_color), w, h);
+        img.stats_mut().objects_culled += tris.objects_culled;
+        img.stats_mut().triangles_culled += tris.triangles_culled;
+        let mut gbuf = GBuffer::new(img.height(), img.width());
+        *img.stats_mut() += gbuf.rasterize_faces(&tris.all, tris.visible, camera);
+        match mode {
+            RenderMode::Depth => {
+                if let Some((near, far)) = gbuf.depth_range() {
+                    // keep the farthest surface visible against an empty background
+                    let far = far + (far - near) / 4.0 + f64::EPSILON;
+                    gbuf.draw_depth(img, near, far);
+                }
+            }
+            RenderMode::Normals => gbuf.draw_normals(img),
+            RenderMode::Picking => gbuf.draw_face_ids(img),
+            _ => gbuf.draw_faces(img),
+        }
+        img.stats() - before
+    }
+
+    /// Surfaces `render_shaded` would light, without lighting them
+    ///
+    /// For post effects and debug views of a `height` by `width` image; see
+    /// `GBuffer::depth_image` and `GBuffer::normal_image`. Nodes without any
+    /// material get `default_material`.
+    pub fn render_gbuffer(
+        &self,
+        camera: &Camera,
+        height: u32,
+        width: u32,
+        default_material: Material,
+    ) -> GBuffer {
+        let (w, h) = (f64::from(width), f64::from(height));
+        let tris = self.shaded_triangles(camera, default_material, w, h);
+        let mut gbuf = GBuffer::new(height, width);
+        gbuf.rasterize_faces(&tris.all, tris.visible, camera);
+        gbuf
+    }
+
+    /// World space triangles with the camera's vertex shader applied, and which
+    /// of them belong to nodes in view of a `width` by `height` screen
+    fn shaded_triangles(
+        &self,
+        camera: &Camera,
+        default_material: Material,
+        width: f64,
+        height: f64,
+    ) -> CameraTriangles {
+        let mut tris = CameraTriangles::default();
+        let mut node_tris = vec![];
+        self.walk(|node, world, material| {
+            if let Some(geometry) = &node.geometry {
+                geometry.camera_triangles(world, camera, height, &mut node_tris);
+                let start = tris.all.len();
+                match geometry.bounds(world) {
+                    Some(b) if !camera.can_see(&b, width, height) => tris.objects_culled += 1,
+                    _ => tris.visible.extend(start..start + node_tris.len()),
+                }
+                let material = material.unwrap_or(&default_material).clone();
+                tris.all
+                    .extend(node_tris.drain(..).map(|t| (t, material.clone())));
+            }
+        });
+        if let Some(shader) = &camera.vertex_shader {
+            for (tri, _) in tris.all.iter_mut() {
+                *tri = tri.map(|p| shader.apply(p));
+            }
+        }
+        if camera.cull_backfaces {
+            let all = &tris.all;
+            let before = tris.visible.len();
+            tris.visible.retain(|&i| {
+                let [a, b, c] = all[i].0;
+                dot3(cross3(sub3(b, a), sub3(c, a)), sub3(camera.eye, a)) > 0.0
+            });
+            tris.triangles_culled = (before - tris.visible.len()) as u64;
+        }
+        tris
+    }
+}
+
+/// Triangles of a scene, in the order of `SceneGraph::triangles`, split by visibility
+#[derive(Default)]
+struct CameraTriangles {
+    all: Vec<([[f64; 3]; 3], Material)>,
+    /// Indices into `all` of the triangles of nodes in view
+    visible: Vec<usize>,
+    /// Nodes out of view
+    objects_culled: u64,
+    /// Triangles of nodes in view left out for facing away from the camera
+    triangles_culled: u64,
+}
+
+impl Default for SceneGraph {
+    fn default() -> Self {
+        SceneGraph::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::pick::{pick, FaceId};
+    use crate::graphics::RGB;
+
+    #[test]
+    fn walk_composes_parent_transforms() {
+        let mut scene = SceneGraph::new();
+        let red = RGB {
+            red: 255,
+            green: 0,
+            blue: 0,
+        };
+        scene
+            .root
+            .add_child(
+                Node::new("arm")
+                    .with_transform(Matrix::translate(10.0, 0.0, 0.0))
+                    .with_material(Material::new(red)),
+            )
+            .add_child(Node::new("hand").with_transform(Matrix::scale(2.0, 2.0, 2.0)));
+
+        let mut origin = Matrix::new(0, 4, vec![]);
+        origin.append_edge([1.0, 0.0, 0.0]);
+        let mut seen = vec![];
+        scene.walk(|node, world, material| {
+            seen.push((
+                node.name.clone(),
+                world.apply(&origin).get(0, 0).unwrap(),
+                material.map(|m| m.color.red),
+            ));
+        });
+        assert_eq!(
+            seen,
+            vec![
+                ("root".to_string(), 1.0, None),
+                ("arm".to_string(), 11.0, Some(255)),
+                // scaled by the hand first, then moved by the arm
+                ("hand".to_string(), 12.0, Some(255)),
+            ]
+        );
+    }
+
+    #[test]
+    fn instances_share_one_mesh() {
+        let mut edge = Matrix::new(0, 4, vec![]);
+        edge.append_edge([0.0, 0.0, 0.0]);
+        edge.append_edge([0.0, 1.0, 0.0]);
+        let mesh = Rc::new(Geometry::Edges(edge));
+
+        let mut trees = Geometry::instanced(Rc::clone(&mesh));
+        if let Geometry::Instanced { transforms, .. } = &mut trees {
+            transforms.push(Matrix::translate(1.0, 0.0, 0.0));
+            transforms.push(Matrix::translate(3.0, 0.0, 0.0));
+        }
+        let mut scene = SceneGraph::new();
+        scene.root.add_child(
+            Node::new("forest")
+                .with_transform(Matrix::translate(0.0, 2.0, 0.0))
+                .with_geometry(trees),
+        );
+
+        let mut img = PPMImg::new(4, 5, 255);
+        scene.render(&mut img);
+        assert_eq!(
+            img.dirty(),
+            Some(crate::graphics::rect::Rect::new(1, 2, 3, 2))
+        );
+        assert_eq!(Rc::strong_count(&mesh), 2);
+    }
+
+    #[test]
+    fn debug_modes_show_depth_and_faces() {
+        // a small triangle in front of a big one
+        let mut tris = Matrix::new(0, 4, vec![]);
+        for &(s, z) in [(4.0, 1.0), (1.0, -1.0)].iter() {
+            tris.append_edge([-s, -s, z]);
+            tris.append_edge([s, -s, z]);
+            tris.append_edge([0.0, s, z]);
+        }
+        let mut scene = SceneGraph::new();
+        scene
+            .root
+            .add_child(Node::new("tris").with_geometry(Geometry::Polygons(tris)));
+        let camera = Camera::new([0.0, 0.0, -5.0], [0.0, 0.0, 0.0]);
+
+        let mut img = PPMImg::new(20, 20, 255);
+        let stats = scene.render_mode(&mut img, &camera, RenderMode::Depth);
+        assert_eq!(stats.triangles_filled, 2);
+        assert_eq!(img.pixel(10, 10).red, 255);
+        let far = img.pixel(10, 16).red;
+        assert!(far > 0 && far < 255 && img.pixel(0, 0).red == 0);
+
+        let mut img = PPMImg::new(20, 20, 255);
+        scene.render_mode(&mut img, &camera, RenderMode::FlatRandomColorPerFace);
+        let colors = [img.pixel(10, 10), img.pixel(10, 16)].map(|p| (p.red, p.green, p.blue));
+        assert_ne!(colors[0], colors[1]);
+
+        let mut img = PPMImg::new(20, 20, 255);
+        scene.render_mode(&mut img, &camera, RenderMode::Picking);
+        assert_eq!(pick(&img, 10, 10), Some(FaceId(1)));
+        assert_eq!(pick(&img, 10, 16), Some(FaceId(0)));
+        assert_eq!(pick(&img, 0, 0), None);
+    }
+
+    #[test]
+    fn glass_blends_over_what_is_behind() {
+        let triangle = |s: f64, z: f64| {
+            let mut m = Matrix::new(0, 4, vec![]);
+            m.add_polygon([-s, -s, z], [s, -s, z], [0.0, s, z]);
+            Geometry::Polygons(m)
+        };
+        let lit = |color: [f64; 3]| {
+            let mut m = Material::new(RGB::from_fractions(color, 255));
+            m.ambient = 1.0;
+            m
+        };
+        let mut scene = SceneGraph::new();
+        scene.ambient = [1.0; 3];
+        // glass in front of a red wall, and more glass hidden behind it
+        scene.root.add_child(
+            Node::new("glass")
+                .with_geometry(triangle(1.0, -1.0))
+                .with_material(lit([0.0, 0.0, 1.0]).with_opacity(0.5)),
+        );
+        scene.root.add_child(
+            Node::new("wall")
+                .with_geometry(triangle(4.0, 1.0))
+                .with_material(lit([1.0, 0.0, 0.0])),
+        );
+        scene.root.add_child(
+            Node::new("hidden")
+                .with_geometry(triangle(4.0, 2.0))
+                .with_material(lit([0.0, 1.0, 0.0]).with_opacity(0.5)),
+        );
+        let camera = Camera::new([0.0, 0.0, -5.0], [0.0, 0.0, 0.0]);
+
+        let mut img = PPMImg::new(20, 20, 255);
+        scene.render_shaded(&mut img, &camera);
+        let p = img.pixel(10, 10);
+        assert_eq!((p.red, p.green, p.blue), (128, 0, 128));
+        let p = img.pixel(10, 16);
+        assert_eq!((p.red, p.green, p.blue), (255, 0, 0));
+    }
+
+    #[test]
+    fn toon_has_flat_bands_and_outlines() {
+        let mut scene = SceneGraph::new();
+        scene
+            .root
+            .add_child(Node::new("ball").with_geometry(Geometry::Sphere { radius: 1.0 }));
+        scene.lights.push(Light::white([-5.0, 5.0, -5.0]));
+        let camera = Camera::new([0.0, 0.0, -5.0], [0.0, 0.0, 0.0]);
+        let outline = RGB::from_fractions([1.0, 0.0, 0.0], 255);
+
+        let mut img = PPMImg::new(50, 50, 255);
+        scene.render_toon(&mut img, &camera, 2, outline);
+        let row: Vec<(u16, u16, u16)> = (0..50)
+            .map(|x| img.pixel(x, 25))
+            .map(|p| (p.red, p.green, p.blue))
+            .collect();
+        // the background, then the outline where the ball starts
+        let start = row.iter().position(|&p| p != (0, 0, 0)).unwrap();
+        assert_eq!(row[start], (255, 0, 0));
+        let mut inside: Vec<_> = row[start..]
+            .iter()
+            .filter(|&&p| p != (0, 0, 0) && p != (255, 0, 0))
+            .collect();
+        inside.dedup();
+        // unlit, 2 bands, and maybe a highlight
+        assert!(inside.len() <= 4, "bands: {:?}", inside);
+    }
+
+    #[test]
+    fn objects_out_of_view_are_culled() {
+        let mut scene = SceneGraph::new();
+        for i in 0..10 {
+            scene.root.add_child(
+                Node::new(&format!("ball {}", i))
+                    .with_transform(Matrix::translate(f64::from(i) * 10.0, 0.0, 0.0))
+                    .with_geometry(Geometry::Sphere { radius: 1.0 }),
+            );
+        }
+        let mut camera = Camera::new([0.0, 0.0, -5.0], [0.0, 0.0, 0.0]);
+        camera.fov_deg = 30.0;
+
+        let mut img = PPMImg::new(50, 50, 255);
+        let wire = scene.render_camera(&mut img, &camera);
+        assert_eq!(wire.objects_culled, 9);
+        let mut shaded = PPMImg::new(50, 50, 255);
+        let stats = scene.render_shaded(&mut shaded, &camera);
+        assert_eq!(stats.objects_culled, 9);
+        assert!(stats.triangles_filled > 0 && shaded.pixel(25, 25).red > 0);
+    }
+
+    #[test]
+    fn lod_follows_screen_size() {
+        let torus = Geometry::lod(&[4, 16], 2.0, |steps| {
+            let mut m = Matrix::new(0, 4, vec![]);
+            m.add_torus([0.0; 3], 1.0, 0.3, steps);
+            m
+        });
+        let count = |distance: f64| {
+            let camera = Camera::new([0.0, 0.0, -distance], [0.0; 3]);
+            let mut out = vec![];
+            torus.camera_triangles(&Matrix::ident(4), &camera, 100.0, &mut out);
+            out.len()
+        };
+        assert_eq!(count(3.0), 2 * 16 * 16 * 2);
+        assert_eq!(count(300.0), 2 * 4 * 4 * 2);
+        let mut all = vec![];
+        torus.triangles(&Matrix::ident(4), &mut all);
+        assert_eq!(all.len(), 2 * 16 * 16 * 2);
+
+        let empty = Geometry::Lod { levels: vec![] };
+        let camera = Camera::new([0.0, 0.0, -3.0], [0.0; 3]);
+        empty.camera_triangles(&Matrix::ident(4), &camera, 100.0, &mut all);
+        assert_eq!(all.len(), 2 * 16 * 16 * 2);
+    }
+}
@@ -0,0 +1,241 @@
+use super::camera::Camera;
+use super::light::{phong, Light};
+use super::material::Material;
+use super::utils::{dot3, normalize3, sub3, Span};
+use super::{PPMImg, RGB};
+
+/// Max number of steps along a ray before giving up
+const MAX_STEPS: usize = 256;
+
+/// Rays closer than this to a surface have hit it
+const HIT_DISTANCE: f64 = 1e-4;
+
+/// Rays going further than this hit nothing
+const MAX_DISTANCE: f64 = 1e3;
+
+/// Step used for the gradient, when working out normals
+const NORMAL_STEP: f64 = 1e-5;
+
+/// Shape given by its signed distance field: how far every point is from its
+/// surface, negative inside
+///
+/// Shapes combine like solids, without meshes; see `render_sdf`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Sdf {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+    },
+    /// Axis aligned box
+    Cuboid {
+        center: [f64; 3],
+        half_size: [f64; 3],
+    },
+    /// Ring in the xz plane, like `PolygonMatrix::add_torus`
+    Torus {
+        center: [f64; 3],
+        radius: f64,
+        tube_radius: f64,
+    },
+    Union(Box<Sdf>, Box<Sdf>),
+    /// Union blending the two shapes together where they are closer than the
+    /// last value
+    SmoothUnion(Box<Sdf>, Box<Sdf>, f64),
+    Intersection(Box<Sdf>, Box<Sdf>),
+    /// The first shape with the second one carved out
+    Difference(Box<Sdf>, Box<Sdf>),
+}
+
+#[allow(dead_code)]
+impl Sdf {
+    pub fn sphere(center: [f64; 3], radius: f64) -> Sdf {
+        Sdf::Sphere { center, radius }
+    }
+
+    pub fn cuboid(center: [f64; 3], half_size: [f64; 3]) -> Sdf {
+        Sdf::Cuboid { center, half_size }
+    }
+
+    pub fn torus(center: [f64; 3], radius: f64, tube_radius: f64) -> Sdf {
+        Sdf::Torus {
+            center,
+            radius,
+            tube_radius,
+        }
+    }
+
+    pub fn union(self, other: Sdf) -> Sdf {
+        Sdf::Union(Box::new(self), Box::new(other))
+    }
+
+    /// Union rounding off the seam, `blend` wide
+    pub fn smooth_union(self, other: Sdf, blend: f64) -> Sdf {
+        assert!(blend > 0.0, "Blend must be positive");
+        Sdf::SmoothUnion(Box::new(self), Box::new(other), blend)
+    }
+
+    pub fn intersection(self, other: Sdf) -> Sdf {
+        Sdf::Intersection(Box::new(self), Box::new(other))
+    }
+
+    pub fn difference(self, other: Sdf) -> Sdf {
+        Sdf::Difference(Box::new(self), Box::new(other))
+    }
+
+    /// Signed distance from `p` to the surface, negative inside
+    ///
+    /// Exact for the basic shapes; combinations may overestimate it a bit
+    /// inside, and underestimate it outside.
+    pub fn distance(&self, p: [f64; 3]) -> f64 {
+        let length = |v: [f64; 3]| dot3(v, v).sqrt();
+        match self {
+            Sdf::Sphere { center, radius } => length(sub3(p, *center)) - radius,
+            Sdf::Cuboid { center, half_size } => {
+                let d = sub3(p, *center);
+                let q = [0, 1, 2].map(|i| d[i].abs() - half_size[i]);
+                let outside = length(q.map(|c| c.max(0.0)));
+                outside + q[0].max(q[1]).max(q[2]).min(0.0)
+            }
+            Sdf::Torus {
+                center,
+                radius,
+                tube_radius,
+            } => {
+                let d = sub3(p, *center);
+                let ring = (d[0] * d[0] + d[2] * d[2]).sqrt() - radius;
+                (ring * ring + d[1] * d[1]).sqrt() - tube_radius
+            }
+            Sdf::Union(a, b) => a.distance(p).min(b.distance(p)),
+            Sdf::SmoothUnion(a, b, k) => {
+                // polynomial smooth minimum
+                let (da, db) = (a.distance(p), b.distance(p));
+                let h = (0.5 + 0.5 * (db - da) / k).clamp(0.0, 1.0);
+                db + (da - db) * h - k * h * (1.0 - h)
+            }
+            Sdf::Intersection(a, b) => a.distance(p).max(b.distance(p)),
+            Sdf::Difference(a, b) => a.distance(p).max(-b.distance(p)),
+        }
+    }
+
+    /// Unit normal of the surface near `p`, from the gradient of the distance
+    pub fn normal(&self, p: [f64; 3]) -> [f64; 3] {
+        let gradient = [0, 1, 2].map(|axis| {
+            let (mut a, mut b) = (p, p);
+            a[axis] += NORMAL_STEP;
+            b[axis] -= NORMAL_STEP;
+            self.distance(a) - self.distance(b)
+        });
+        normalize3(gradient)
+    }
+
+    /// Ray parameter t of the first surface hit by `origin + t * dir`, for t < t_max
+    ///
+    /// Sphere tracing: steps along the ray by the distance to the closest surface.
+    pub fn raymarch(&self, origin: [f64; 3], dir: [f64; 3], t_max: f64) -> Option<f64> {
+        let dir = normalize3(dir);
+        let mut t = 0.0;
+        for _ in 0..MAX_STEPS {
+            let p = [
+                origin[0] + dir[0] * t,
+                origin[1] + dir[1] * t,
+                origin[2] + dir[2] * t,
+            ];
+            let d = self.distance(p);
+            if d < HIT_DISTANCE {
+                return Some(t);
+            }
+            t += d;
+            if t >= t_max {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+/// Renders `sdf` as seen by `camera` by marching one ray per pixel
+///
+/// Shades it like `render_raytraced` does, with `material`, white ambient
+/// light, and shadows from `lights`. Pixels that see nothing get img's bg_color.
+#[allow(dead_code)]
+pub fn render_sdf(
+    sdf: &Sdf,
+    material: &Material,
+    lights: &[Light],
+    camera: &Camera,
+    img: &mut PPMImg,
+) {
+    let _span = Span::new("render sdf");
+    let (w, h, depth) = (img.width(), img.height(), img.depth());
+    let base = material.base_color(depth);
+    let bg = img.bg_color;
+    for y in 0..h {
+        for x in 0..w {
+            let (eye, dir) = camera.ray(f64::from(x), f64::from(y), f64::from(w), f64::from(h));
+            let dir = normalize3(dir);
+            let color = match sdf.raymarch(eye, dir, MAX_DISTANCE) {
+                Some(t) => {
+                    let p = [
+                        eye[0] + dir[0] * t,
+                        eye[1] + dir[1] * t,
+                        eye[2] + dir[2] * t,
+                    ];
+                    let n = sdf.normal(p);
+                    // step off the surface before marching towards the lights
+                    let start = [0, 1, 2].map(|i| p[i] + n[i] * HIT_DISTANCE * 10.0);
+                    let visible = lights.iter().filter(|l| {
+                        let to_light = sub3(l.pos, start);
+                        let dist = dot3(to_light, to_light).sqrt();
+                        sdf.raymarch(start, to_light, dist).is_none()
+                    });
+                    let c = phong(material, base, p, n, eye, [1.0; 3], visible);
+                    RGB::from_fractions(c, depth)
+                }
+                None => bg,
+            };
+            img.set_pixel(x, y, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances_and_blends() {
+        let ball = Sdf::sphere([0.0; 3], 1.0);
+        assert_eq!(ball.distance([3.0, 0.0, 0.0]), 2.0);
+        assert_eq!(ball.distance([0.0; 3]), -1.0);
+        let block = Sdf::cuboid([0.0; 3], [1.0, 2.0, 3.0]);
+        assert_eq!(block.distance([0.0, 5.0, 0.0]), 3.0);
+        assert_eq!(block.distance([0.5, 0.0, 0.0]), -0.5);
+        let ring = Sdf::torus([0.0; 3], 2.0, 0.5);
+        assert_eq!(ring.distance([0.0, 0.0, 2.0]), -0.5);
+
+        // the blend fills in the gap between two balls
+        let other = Sdf::sphere([2.5, 0.0, 0.0], 1.0);
+        let gap = [1.25, 0.0, 0.0];
+        assert!(ball.clone().union(other.clone()).distance(gap) > 0.0);
+        assert!(ball.clone().smooth_union(other, 2.0).distance(gap) < 0.0);
+        // a bite out of the ball, facing out into the hole
+        let bitten = ball.difference(Sdf::sphere([1.5, 0.0, 0.0], 1.0));
+        let n = bitten.normal([0.5, 0.0, 0.0]);
+        assert!((n[0] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sphere_is_lit_from_the_light_side() {
+        let sdf = Sdf::sphere([0.0; 3], 1.0).union(Sdf::cuboid([0.0, -2.0, 0.0], [5.0, 0.1, 5.0]));
+        let white = Material::new(RGB::from_fractions([1.0; 3], 255));
+        // +x is on the left, looking down +z
+        let lights = [Light::white([10.0, 0.0, -10.0])];
+        let cam = Camera::new([0.0, 0.0, -5.0], [0.0; 3]);
+        let mut img = PPMImg::new(21, 21, 255);
+        img.bg_color = RGB::from_fractions([0.0, 0.0, 1.0], 255);
+        render_sdf(&sdf, &white, &lights, &cam, &mut img);
+        assert_eq!(img.pixel(0, 0).blue, 255, "background");
+        let (left, right) = (img.pixel(8, 10).red, img.pixel(12, 10).red);
+        assert!(left > right && right > 0, "{} <= {}", left, right);
+    }
+}
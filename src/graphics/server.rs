@@ -0,0 +1,181 @@
+use super::{PPMImg, RGB};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+
+/// Renders frames for a client over a socket, one text command per line
+///
+/// Commands, with numbers separated by spaces:
+///
+/// - `size <height> <width> <depth>`: starts over with a new image, of at most
+///   `max_pixels` pixels
+/// - `color <r> <g> <b>` and `bg <r> <g> <b>`: set the fg and bg colors
+/// - `clear`
+/// - `plot <x> <y>`
+/// - `line <x0> <y0> <x1> <y1>`
+/// - `rect <x> <y> <w> <h>`: filled rectangle
+/// - `frame`: replies with the image as a binary ppm, header included
+/// - `quit`: closes the connection
+///
+/// Only `frame` replies on success; a bad command replies with a line
+/// starting with `error`, and the connection stays open.
+pub struct FrameServer {
+    pub img: PPMImg,
+    /// Most pixels a `size` command may ask for; 4096 by 4096 by default
+    pub max_pixels: u32,
+}
+
+#[allow(dead_code)]
+impl FrameServer {
+    pub fn new(img: PPMImg) -> FrameServer {
+        FrameServer {
+            img,
+            max_pixels: 4096 * 4096,
+        }
+    }
+
+    /// Runs commands from `input` until it ends or a `quit`, writing replies to `output`
+    pub fn handle<R: BufRead, W: Write>(&mut self, input: R, output: &mut W) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            let mut words = line.split_whitespace();
+            let cmd = match words.next() {
+                Some(cmd) => cmd,
+                None => continue,
+            };
+            let args: Vec<&str> = words.collect();
+            match self.run(cmd, &args) {
+                Ok(Reply::None) => (),
+                Ok(Reply::Frame) => self.img.encode_binary(output)?,
+                Ok(Reply::Quit) => break,
+                Err(e) => writeln!(output, "error {}", e)?,
+            }
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, cmd: &str, args: &[&str]) -> Result<Reply, String> {
+        let nums = |n: usize| -> Result<Vec<f64>, String> {
+            if args.len() != n {
+                return Err(format!("{} takes {} numbers, got {}", cmd, n, args.len()));
+            }
+            args.iter()
+                .map(|a| a.parse().map_err(|_| format!("Bad number '{}'", a)))
+                .collect()
+        };
+        let color = |v: &[f64]| RGB {
+            red: v[0] as u16,
+            green: v[1] as u16,
+            blue: v[2] as u16,
+        };
+        match cmd {
+            "size" => {
+                let v = nums(3)?;
+                if v[0] < 1.0 || v[1] < 1.0 || v[2] < 1.0 || v[2] > f64::from(u16::MAX) {
+                    return Err("Bad image size".to_string());
+                }
+                let (height, width) = (v[0] as u32, v[1] as u32);
+                match height.checked_mul(width) {
+                    Some(n) if n <= self.max_pixels => (),
+                    _ => return Err(format!("Image over {} pixels", self.max_pixels)),
+                }
+                let mut img = PPMImg::new(height, width, v[2] as u16);
+                img.fg_color = self.img.fg_color;
+                img.bg_color = self.img.bg_color;
+                img.clear();
+                self.img = img;
+            }
+            "color" => self.img.fg_color = color(&nums(3)?),
+            "bg" => self.img.bg_color = color(&nums(3)?),
+            "clear" => {
+                nums(0)?;
+                self.img.clear();
+            }
+            "plot" => {
+                let v = nums(2)?;
+                self.img.plot(v[0] as i32, v[1] as i32);
+            }
+            "line" => {
+                let v = nums(4)?;
+                self.img.draw_line(v[0], v[1], v[2], v[3]);
+            }
+            "rect" => {
+                let v = nums(4)?;
+                self.img
+                    .fill_rect(v[0] as i32, v[1] as i32, v[2] as u32, v[3] as u32);
+            }
+            "frame" => {
+                nums(0)?;
+                return Ok(Reply::Frame);
+            }
+            "quit" => return Ok(Reply::Quit),
+            _ => return Err(format!("Unknown command '{}'", cmd)),
+        }
+        Ok(Reply::None)
+    }
+
+    /// Listens on a TCP address like `127.0.0.1:4000`, serving one client at a time
+    ///
+    /// Clients share the image, so a new connection sees what the last one drew.
+    /// Only returns on an error accepting a connection.
+    pub fn serve_tcp(&mut self, addr: &str) -> io::Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let mut output = stream.try_clone()?;
+            // a client hanging up early isn't the server's problem
+            let _ = self.handle(BufReader::new(stream), &mut output);
+        }
+        Ok(())
+    }
+
+    /// Like `serve_tcp`, listening on a unix socket at `path`
+    #[cfg(unix)]
+    pub fn serve_unix(&mut self, path: &str) -> io::Result<()> {
+        let listener = std::os::unix::net::UnixListener::bind(path)?;
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let mut output = stream.try_clone()?;
+            let _ = self.handle(BufReader::new(stream), &mut output);
+        }
+        Ok(())
+    }
+}
+
+enum Reply {
+    None,
+    Frame,
+    Quit,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_commands_and_sends_frames() {
+        let mut server = FrameServer::new(PPMImg::new(1, 1, 255));
+        let script = "size 2 3 255\ncolor 0 9 7\nrect 1 0 1 2\nbogus\nframe\nquit\nframe\n";
+        let mut out = vec![];
+        server.handle(script.as_bytes(), &mut out).unwrap();
+
+        let error = b"error Unknown command 'bogus'\n";
+        assert_eq!(&out[..error.len()], error);
+        let frame = PPMImg::from_ppm_bytes(&out[error.len()..]).unwrap();
+        assert_eq!((frame.width(), frame.height()), (3, 2));
+        // only one frame: nothing runs after quit
+        assert_eq!(out.len(), error.len() + b"P6\n3 2 255\n".len() + 3 * 2 * 3);
+        assert_eq!(frame.pixel(1, 1).blue, 7);
+    }
+
+    #[test]
+    fn refuses_huge_images() {
+        let mut server = FrameServer::new(PPMImg::new(1, 1, 255));
+        server.max_pixels = 100;
+        let mut out = vec![];
+        let script = "size 1e10 1e10 255\nsize 11 10 255\nsize 10 10 255\n";
+        server.handle(script.as_bytes(), &mut out).unwrap();
+        assert_eq!(out, b"error Image over 100 pixels\n".repeat(2));
+        assert_eq!((server.img.width(), server.img.height()), (10, 10));
+    }
+}
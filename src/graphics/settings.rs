@@ -0,0 +1,166 @@
+use super::blit::Blit;
+use super::camera::Camera;
+use super::canvas::Canvas;
+use super::scene::{RenderMode, SceneGraph};
+use super::stats::RenderStats;
+use super::PPMImg;
+
+/// Most pixels `render_with` renders a supersampled image at
+pub const MAX_SUPERSAMPLED_PIXELS: u64 = 1 << 22;
+
+/// How `SceneGraph::render_with` draws a scene
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RenderSettings {
+    pub mode: RenderMode,
+    /// Leaves out triangles facing away from the camera, see `Camera::cull_backfaces`
+    pub cull_backfaces: bool,
+    /// Pixels rendered per side of every pixel of the image, then averaged, to
+    /// smooth jagged edges; 1 turns antialiasing off
+    ///
+    /// Cut down to keep the supersampled image under `MAX_SUPERSAMPLED_PIXELS`.
+    pub supersample: u32,
+}
+
+#[allow(dead_code)]
+impl RenderSettings {
+    /// Settings set up step by step, like
+    /// `RenderSettings::builder().mode(RenderMode::Toon).supersample(2).build()`
+    pub fn builder() -> RenderSettingsBuilder {
+        RenderSettingsBuilder {
+            settings: RenderSettings::default(),
+        }
+    }
+
+    /// `supersample`, cut down for a `width` by `height` image
+    fn samples_per_side(&self, width: u32, height: u32) -> u32 {
+        let pixels = u64::from(width) * u64::from(height);
+        let most = ((MAX_SUPERSAMPLED_PIXELS / pixels.max(1)) as f64).sqrt() as u32;
+        self.supersample.min(most.max(1))
+    }
+}
+
+/// Shaded, every triangle drawn, no antialiasing
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings {
+            mode: RenderMode::Shaded,
+            cull_backfaces: false,
+            supersample: 1,
+        }
+    }
+}
+
+/// Builds `RenderSettings`; anything left unset is as in the default
+#[derive(Copy, Clone, Debug)]
+pub struct RenderSettingsBuilder {
+    settings: RenderSettings,
+}
+
+#[allow(dead_code)]
+impl RenderSettingsBuilder {
+    pub fn mode(mut self, mode: RenderMode) -> RenderSettingsBuilder {
+        self.settings.mode = mode;
+        self
+    }
+
+    pub fn cull_backfaces(mut self, cull: bool) -> RenderSettingsBuilder {
+        self.settings.cull_backfaces = cull;
+        self
+    }
+
+    pub fn supersample(mut self, samples: u32) -> RenderSettingsBuilder {
+        assert!(samples > 0, "Must take at least one sample per pixel");
+        self.settings.supersample = samples;
+        self
+    }
+
+    pub fn build(self) -> RenderSettings {
+        self.settings
+    }
+}
+
+// render settings
+#[allow(dead_code)]
+impl SceneGraph {
+    /// Renders the scene as seen by `camera` with `settings`, see `render_mode`
+    ///
+    /// Supersampled renders are drawn on an image that many times bigger, which
+    /// starts out as img scaled up, with its colors, background, y_axis and
+    /// subpixel settings, then shrunk back into img.
+    pub fn render_with(
+        &self,
+        img: &mut PPMImg,
+        camera: &Camera,
+        settings: &RenderSettings,
+    ) -> RenderStats {
+        let mut camera = camera.clone();
+        camera.cull_backfaces |= settings.cull_backfaces;
+        let s = settings.samples_per_side(img.width(), img.height());
+        if s <= 1 {
+            return self.render_mode(img, &camera, settings.mode);
+        }
+        let mut big = PPMImg::new(img.height() * s, img.width() * s, img.depth());
+        big.fg_color = img.fg_color;
+        big.bg_color = img.bg_color;
+        big.background = img.background.clone();
+        big.y_axis = img.y_axis;
+        big.subpixel = img.subpixel;
+        big.blit_with(img, 0, 0, &Blit::new().with_scale(f64::from(s)));
+        big.take_stats();
+        let stats = self.render_mode(&mut big, &camera, settings.mode);
+        img.draw_scaled(&big);
+        *img.stats_mut() += stats;
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::scene::{Geometry, Node};
+
+    #[test]
+    fn settings_cull_and_smooth() {
+        let mut scene = SceneGraph::new();
+        scene
+            .root
+            .add_child(Node::new("ball").with_geometry(Geometry::Sphere { radius: 1.0 }));
+        let camera = Camera::new([0.0, 0.0, -4.0], [0.0; 3]);
+        let render = |settings: RenderSettings| {
+            let mut img = PPMImg::new(40, 40, 255);
+            let stats = scene.render_with(&mut img, &camera, &settings);
+            (img, stats)
+        };
+
+        let (plain, all) = render(RenderSettings::default());
+        let culling = RenderSettings::builder().cull_backfaces(true).build();
+        let (culled, some) = render(culling);
+        // the back of a closed mesh is hidden anyway
+        assert_eq!(plain.hash(), culled.hash());
+        assert_eq!(all.triangles_culled, 0);
+        assert!(some.triangles_culled > 0);
+        assert!(some.triangles_filled < all.triangles_filled);
+
+        let flat = RenderSettings::builder()
+            .mode(RenderMode::FlatRandomColorPerFace)
+            .build();
+        let smooth = RenderSettings {
+            supersample: 3,
+            ..flat
+        };
+        let shades = |img: &PPMImg| {
+            let mut shades: Vec<u16> = (0..40).map(|x| img.pixel(x, 20).green).collect();
+            shades.sort_unstable();
+            shades.dedup();
+            shades.len()
+        };
+        // blended edges between faces and the background
+        assert!(shades(&render(smooth).0) > shades(&render(flat).0));
+        let huge = RenderSettings {
+            supersample: u32::MAX,
+            ..flat
+        };
+        assert_eq!(huge.samples_per_side(40, 40), 51);
+        assert_eq!(huge.samples_per_side(u32::MAX, u32::MAX), 1);
+    }
+}
@@ -0,0 +1,169 @@
+use super::raster::{fill_triangle, ZBuffer};
+use super::utils::normalize3;
+use super::{PPMImg, RGB};
+
+/// Corner of a triangle for `rasterize` and `shade_triangle`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Vertex {
+    /// (x, y, depth) on screen, as returned by `Camera::project`
+    pub screen: [f64; 3],
+    pub normal: [f64; 3],
+    pub uv: [f64; 2],
+    /// (x, y, z, w) as returned by `PolygonMatrix::tangents`, for normal maps
+    pub tangent: [f64; 4],
+}
+
+/// Pixel covered by a triangle, with the values of its corners interpolated
+///
+/// Interpolation is perspective correct.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Fragment {
+    pub x: u32,
+    pub y: u32,
+    pub depth: f64,
+    /// Barycentric coordinates: how much each corner counts for this pixel
+    pub weights: [f64; 3],
+    /// Unit length
+    pub normal: [f64; 3],
+    pub uv: [f64; 2],
+    /// Unit length in x, y and z; w is the sign of the bitangent
+    pub tangent: [f64; 4],
+    /// How much uv changes one pixel to the right, and one pixel down, for
+    /// picking mipmap levels
+    pub duv_dx: [f64; 2],
+    pub duv_dy: [f64; 2],
+}
+
+/// Calls `f` with every pixel of triangle `tri` on a `width` by `height` screen
+///
+/// The same pixels as `fill_triangle`, with normals, uvs and tangents interpolated as well.
+pub fn rasterize<F>(width: u32, height: u32, tri: &[Vertex; 3], mut f: F)
+where
+    F: FnMut(&Fragment),
+{
+    let [a, b, c] = tri;
+    let [pa, pb, pc] = [a.screen, b.screen, c.screen];
+    let area = (pb[0] - pa[0]) * (pc[1] - pa[1]) - (pb[1] - pa[1]) * (pc[0] - pa[0]);
+    // perspective correct uv at any point of the screen, inside the triangle or not
+    let uv_at = |x: f64, y: f64| {
+        let edge =
+            |p: [f64; 3], q: [f64; 3]| (q[0] - p[0]) * (y - p[1]) - (q[1] - p[1]) * (x - p[0]);
+        let inv = [
+            edge(pb, pc) / area / pa[2],
+            edge(pc, pa) / area / pb[2],
+            edge(pa, pb) / area / pc[2],
+        ];
+        let sum = inv[0] + inv[1] + inv[2];
+        [0, 1].map(|i| (inv[0] * a.uv[i] + inv[1] * b.uv[i] + inv[2] * c.uv[i]) / sum)
+    };
+    fill_triangle(
+        width,
+        height,
+        [a.screen, b.screen, c.screen],
+        |x, y, depth, w| {
+            let mix = |va: f64, vb: f64, vc: f64| w[0] * va + w[1] * vb + w[2] * vc;
+            let mut normal = [0.0; 3];
+            for (i, n) in normal.iter_mut().enumerate() {
+                *n = mix(a.normal[i], b.normal[i], c.normal[i]);
+            }
+            let [tx, ty, tz, tw] =
+                [0, 1, 2, 3].map(|i| mix(a.tangent[i], b.tangent[i], c.tangent[i]));
+            let t = normalize3([tx, ty, tz]);
+            let (px, py) = (f64::from(x) + 0.5, f64::from(y) + 0.5);
+            let uv = uv_at(px, py);
+            let (right, below) = (uv_at(px + 1.0, py), uv_at(px, py + 1.0));
+            f(&Fragment {
+                x,
+                y,
+                depth,
+                weights: w,
+                normal: normalize3(normal),
+                uv,
+                tangent: [t[0], t[1], t[2], if tw < 0.0 { -1.0 } else { 1.0 }],
+                duv_dx: [right[0] - uv[0], right[1] - uv[1]],
+                duv_dy: [below[0] - uv[0], below[1] - uv[1]],
+            });
+        },
+    );
+}
+
+/// Draws triangle `tri` into `img` with a software fragment shader
+///
+/// `shader` is called for every pixel of the triangle closer than what `zbuf`
+/// already holds there, and returns the pixel's color, or None to leave it
+/// alone (for cutouts). Returns whether any pixel was drawn.
+pub fn shade_triangle<F>(
+    img: &mut PPMImg,
+    zbuf: &mut ZBuffer,
+    tri: &[Vertex; 3],
+    mut shader: F,
+) -> bool
+where
+    F: FnMut(&Fragment) -> Option<RGB>,
+{
+    let (width, height) = (img.width(), img.height());
+    let mut drawn = false;
+    img.stats_mut().triangles_filled += 1;
+    rasterize(width, height, tri, |frag| {
+        if frag.depth >= zbuf.depth(frag.x, frag.y) {
+            img.stats_mut().zbuffer_rejections += 1;
+            return;
+        }
+        if let Some(color) = shader(frag) {
+            zbuf.test_and_set(frag.x, frag.y, frag.depth);
+            img.set_pixel(frag.x, frag.y, color);
+            drawn = true;
+        }
+    });
+    drawn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(x: f64, y: f64, depth: f64, u: f64) -> Vertex {
+        Vertex {
+            screen: [x, y, depth],
+            normal: [0.0, 0.0, -1.0],
+            uv: [u, 0.0],
+            tangent: [1.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    #[test]
+    fn shader_sees_interpolated_values() {
+        let mut img = PPMImg::new(8, 8, 255);
+        let mut zbuf = ZBuffer::new(8, 8);
+        let near = [
+            vertex(0.0, 0.0, 1.0, 0.0),
+            vertex(8.0, 0.0, 1.0, 1.0),
+            vertex(0.0, 8.0, 1.0, 0.0),
+        ];
+        shade_triangle(&mut img, &mut zbuf, &near, |f| {
+            assert!((f.uv[0] - (f64::from(f.x) + 0.5) / 8.0).abs() < 1e-9);
+            assert!((f.duv_dx[0] - 1.0 / 8.0).abs() < 1e-9 && f.duv_dy[0].abs() < 1e-9);
+            assert_eq!(f.normal, [0.0, 0.0, -1.0]);
+            assert_eq!(f.tangent, [1.0, 0.0, 0.0, 1.0]);
+            // checkerboard cutout
+            if (f.x + f.y) % 2 == 0 {
+                Some(RGB::from_fractions([1.0, 0.0, 0.0], 255))
+            } else {
+                None
+            }
+        });
+        assert!(img.pixel(0, 0).red == 255 && img.pixel(1, 0).red == 0);
+
+        let far = [
+            vertex(0.0, 0.0, 2.0, 0.0),
+            vertex(8.0, 0.0, 2.0, 0.0),
+            vertex(0.0, 8.0, 2.0, 0.0),
+        ];
+        shade_triangle(&mut img, &mut zbuf, &far, |_| {
+            Some(RGB::from_fractions([0.0, 0.0, 1.0], 255))
+        });
+        // hidden where the near one was drawn, visible through the cutouts
+        assert!(img.pixel(0, 0).red == 255 && img.pixel(1, 0).blue == 255);
+        assert!(img.stats().zbuffer_rejections > 0);
+    }
+}
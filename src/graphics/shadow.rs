@@ -0,0 +1,135 @@
+use super::camera::Camera;
+use super::light::Light;
+use super::matrix::Bounds;
+use super::raster::{fill_triangle, ZBuffer};
+use super::utils::{cross3, dot3, normalize3, sub3, xyz};
+
+/// Depth of the scene as seen from a light, to tell which points the light reaches
+pub struct ShadowMap {
+    camera: Camera,
+    depth: ZBuffer,
+    /// Fraction of a point's depth it may be behind the map and still be lit,
+    /// so surfaces don't shadow themselves
+    pub bias: f64,
+}
+
+#[allow(dead_code)]
+impl ShadowMap {
+    /// Renders a `size` by `size` depth map of `triangles` from `light`
+    ///
+    /// The light looks at the center of `bounds`, with a field of view just wide
+    /// enough to see all of it. A light inside the bounds only sees part of them.
+    pub fn new(
+        light: &Light,
+        bounds: &Bounds,
+        triangles: &[[[f64; 3]; 3]],
+        size: u32,
+    ) -> ShadowMap {
+        let center = bounds.center();
+        let half = bounds.size();
+        let radius = dot3(half, half).sqrt() / 2.0;
+        let mut target = center;
+        if light.pos == center {
+            target[2] += 1.0;
+        }
+        let dist = dot3(sub3(target, light.pos), sub3(target, light.pos)).sqrt();
+
+        let mut camera = Camera::new(light.pos, target);
+        // looking straight up or down: any other up will do
+        let f = normalize3(sub3(target, light.pos));
+        if dot3(cross3(f, camera.up), cross3(f, camera.up)) < 1e-12 {
+            camera.up = [0.0, 0.0, 1.0];
+        }
+        camera.fov_deg = if dist > radius {
+            2.0 * (radius / dist).asin().to_degrees() * 1.05
+        } else {
+            150.0
+        };
+        camera.near = dist * 1e-4;
+
+        let mut depth = ZBuffer::new(size, size);
+        let view = camera.view_matrix();
+        let s = f64::from(size);
+        for tri in triangles {
+            let cam = tri.map(|p| xyz(&view.transform_point(&[p[0], p[1], p[2], 1.0])));
+            let screen = [
+                camera.project_camera_point(cam[0], s, s),
+                camera.project_camera_point(cam[1], s, s),
+                camera.project_camera_point(cam[2], s, s),
+            ];
+            // triangles crossing the near plane are left out
+            if let [Some(a), Some(b), Some(c)] = screen {
+                fill_triangle(size, size, [a, b, c], |x, y, d, _| {
+                    depth.test_and_set(x, y, d);
+                });
+            }
+        }
+        ShadowMap {
+            camera,
+            depth,
+            bias: 0.01,
+        }
+    }
+
+    /// The camera the map was rendered with
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /// Whether the light reaches `p`; points outside the map are lit
+    pub fn is_lit(&self, p: [f64; 3]) -> bool {
+        let s = f64::from(self.depth.width());
+        let c = match self.camera.project(&p, s, s) {
+            Some(c) => c,
+            None => return true,
+        };
+        if c[0] < 0.0 || c[1] < 0.0 || c[0] >= s || c[1] >= s {
+            return true;
+        }
+        c[2] <= self.depth.depth(c[0] as u32, c[1] as u32) * (1.0 + self.bias)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graphics::camera::Camera;
+    use crate::graphics::light::Light;
+    use crate::graphics::matrix::Matrix;
+    use crate::graphics::scene::{Geometry, Node, SceneGraph};
+    use crate::graphics::PPMImg;
+
+    #[test]
+    fn box_shadows_floor() {
+        let mut floor = Matrix::new(0, 4, vec![]);
+        floor.add_heightmap(20.0, 20.0, |_, _| 0.0, 1);
+        let mut block = Matrix::new(0, 4, vec![]);
+        block.add_heightmap(2.0, 2.0, |_, _| 0.0, 1);
+
+        let mut scene = SceneGraph::new();
+        scene.root.add_child(
+            Node::new("floor")
+                .with_transform(Matrix::translate(-10.0, 0.0, -10.0))
+                .with_geometry(Geometry::Polygons(floor)),
+        );
+        scene.root.add_child(
+            Node::new("block")
+                .with_transform(Matrix::translate(-1.0, 3.0, -1.0))
+                .with_geometry(Geometry::Polygons(block)),
+        );
+        scene.lights.push(Light::white([0.0, 20.0, 0.0]));
+
+        // looking down at the floor from the side, above the block
+        let cam = Camera::new([0.0, 30.0, -20.0], [0.0, 0.0, 0.0]);
+        let (shadow, lit) = (
+            cam.project(&[0.0, 0.0, 0.0], 100.0, 100.0).unwrap(),
+            cam.project(&[6.0, 0.0, 0.0], 100.0, 100.0).unwrap(),
+        );
+        let mut img = PPMImg::new(100, 100, 255);
+        scene.render_shaded(&mut img, &cam);
+        let (dark, bright) = (
+            img.pixel(shadow[0] as u32, shadow[1] as u32).green,
+            img.pixel(lit[0] as u32, lit[1] as u32).green,
+        );
+        assert!(dark > 0 && dark < bright / 2, "{} {}", dark, bright);
+    }
+}
@@ -0,0 +1,118 @@
+use super::blit::Blit;
+use super::rect::Rect;
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+use super::PPMImg;
+#[cfg(feature = "fs")]
+use std::io;
+
+/// Image cut into a grid of same sized cells, like the frames of a walk cycle
+///
+/// Cells are numbered row by row from the top left. Leftover pixels past the
+/// last whole column or row are ignored.
+#[derive(Clone)]
+pub struct SpriteSheet {
+    image: PPMImg,
+    cell_width: u32,
+    cell_height: u32,
+    columns: u32,
+    rows: u32,
+}
+
+#[allow(dead_code)]
+impl SpriteSheet {
+    pub fn new(image: PPMImg, cell_width: u32, cell_height: u32) -> SpriteSheet {
+        assert!(
+            cell_width > 0 && cell_height > 0,
+            "Cells must be at least a pixel"
+        );
+        let (columns, rows) = (image.width() / cell_width, image.height() / cell_height);
+        assert!(columns > 0 && rows > 0, "Cells must fit in the image");
+        SpriteSheet {
+            image,
+            cell_width,
+            cell_height,
+            columns,
+            rows,
+        }
+    }
+
+    /// Reads the sheet from a ppm file, see `PPMImg::read`
+    #[cfg(feature = "fs")]
+    pub fn load(filepath: &str, cell_width: u32, cell_height: u32) -> io::Result<SpriteSheet> {
+        Ok(SpriteSheet::new(
+            PPMImg::read(filepath)?,
+            cell_width,
+            cell_height,
+        ))
+    }
+
+    /// Number of cells
+    pub fn len(&self) -> usize {
+        (self.columns * self.rows) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn cell_size(&self) -> (u32, u32) {
+        (self.cell_width, self.cell_height)
+    }
+
+    /// Where cell `n` is in the sheet's image
+    pub fn cell(&self, n: usize) -> Rect {
+        assert!(n < self.len(), "Cell out of range");
+        let (column, row) = (n as u32 % self.columns, n as u32 / self.columns);
+        Rect::new(
+            (column * self.cell_width) as i32,
+            (row * self.cell_height) as i32,
+            self.cell_width,
+            self.cell_height,
+        )
+    }
+
+    /// Copy of cell `n`
+    pub fn frame(&self, n: usize) -> PPMImg {
+        let r = self.cell(n);
+        let mut out = PPMImg::new(r.h, r.w, self.image.depth());
+        out.blit(&self.image, -r.x, -r.y);
+        out
+    }
+
+    /// Cell showing at `time` seconds when the cells play in order at `fps`
+    /// cells per second, starting over after the last
+    pub fn frame_at(&self, time: f64, fps: f64) -> usize {
+        assert!(fps > 0.0, "Fps must be positive");
+        let n = (time * fps).floor() as i64;
+        n.rem_euclid(self.len() as i64) as usize
+    }
+
+    /// Pastes cell `n` onto `img` with its top left corner at (x, y), see
+    /// `PPMImg::blit_with`
+    pub fn draw(&self, img: &mut PPMImg, n: usize, x: i32, y: i32, how: &Blit) {
+        img.blit_with(&self.frame(n), x, y, how);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_go_row_by_row() {
+        let mut image = PPMImg::new(5, 7, 255);
+        // marks the top left pixel of cell 3, the second on the second row
+        image.fill_rect(3, 2, 1, 1);
+        let sheet = SpriteSheet::new(image, 3, 2);
+        assert_eq!(sheet.len(), 4);
+        assert_eq!(sheet.cell(3), Rect::new(3, 2, 3, 2));
+        let frame = sheet.frame(3);
+        assert_eq!((frame.width(), frame.height()), (3, 2));
+        assert!(frame.pixel(0, 0).red > 0 && frame.pixel(1, 0).red == 0);
+
+        assert_eq!(sheet.frame_at(0.9, 10.0), 1);
+        assert_eq!(sheet.frame_at(0.5, 10.0), 1);
+        assert_eq!(sheet.frame_at(0.3, 10.0), 3);
+    }
+}
@@ -0,0 +1,60 @@
+use core::ops::{Add, AddAssign, Sub};
+
+/// Counts of what the pipeline did, for performance tuning and tests
+///
+/// Every `PPMImg` keeps a running total in `stats()`; render calls return what
+/// they added to it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    /// Pixels written, counting a pixel again every time it is drawn over
+    pub pixels_plotted: u64,
+    pub lines_drawn: u64,
+    /// Lines left out because they were entirely behind the camera
+    pub lines_clipped: u64,
+    pub triangles_filled: u64,
+    /// Triangles left out entirely, like those crossing the camera's near plane
+    pub triangles_culled: u64,
+    /// Pixels of filled triangles hidden behind something already drawn
+    pub zbuffer_rejections: u64,
+    /// Whole objects left out because they were outside the camera's view
+    pub objects_culled: u64,
+}
+
+impl Add for RenderStats {
+    type Output = RenderStats;
+
+    fn add(self, other: RenderStats) -> RenderStats {
+        RenderStats {
+            pixels_plotted: self.pixels_plotted + other.pixels_plotted,
+            lines_drawn: self.lines_drawn + other.lines_drawn,
+            lines_clipped: self.lines_clipped + other.lines_clipped,
+            triangles_filled: self.triangles_filled + other.triangles_filled,
+            triangles_culled: self.triangles_culled + other.triangles_culled,
+            zbuffer_rejections: self.zbuffer_rejections + other.zbuffer_rejections,
+            objects_culled: self.objects_culled + other.objects_culled,
+        }
+    }
+}
+
+impl AddAssign for RenderStats {
+    fn add_assign(&mut self, other: RenderStats) {
+        *self = *self + other;
+    }
+}
+
+/// What happened between an earlier snapshot and this one
+impl Sub for RenderStats {
+    type Output = RenderStats;
+
+    fn sub(self, earlier: RenderStats) -> RenderStats {
+        RenderStats {
+            pixels_plotted: self.pixels_plotted - earlier.pixels_plotted,
+            lines_drawn: self.lines_drawn - earlier.lines_drawn,
+            lines_clipped: self.lines_clipped - earlier.lines_clipped,
+            triangles_filled: self.triangles_filled - earlier.triangles_filled,
+            triangles_culled: self.triangles_culled - earlier.triangles_culled,
+            zbuffer_rejections: self.zbuffer_rejections - earlier.zbuffer_rejections,
+            objects_culled: self.objects_culled - earlier.objects_culled,
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use super::camera::Camera;
+use super::PPMImg;
+
+/// How the two views of a stereo render are combined into one image
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StereoMode {
+    /// Red/cyan glasses: red from the left eye, green and blue from the right
+    Anaglyph,
+    /// Left eye on the left half of the image, right eye on the right half
+    SideBySide,
+}
+
+// stereo
+#[allow(dead_code)]
+impl Camera {
+    /// Left and right eye cameras, `separation` apart
+    ///
+    /// Both keep looking the same way and converge on the plane `focal_distance`
+    /// away: things on it line up in both views, nearer things pop out.
+    pub fn stereo_pair(&self, separation: f64) -> (Camera, Camera) {
+        (
+            self.jittered(-separation / 2.0, 0.0),
+            self.jittered(separation / 2.0, 0.0),
+        )
+    }
+
+    /// Renders the scene once per eye and combines both views into `img`
+    ///
+    /// `draw(img, camera)` should draw the scene as seen by `camera` into img,
+    /// which is cleared before each eye. Side by side, each eye gets an image
+    /// half as wide as img.
+    pub fn render_stereo<F>(&self, img: &mut PPMImg, separation: f64, mode: StereoMode, mut draw: F)
+    where
+        F: FnMut(&mut PPMImg, &Camera),
+    {
+        let (left_cam, right_cam) = self.stereo_pair(separation);
+        let (width, height) = (img.width(), img.height());
+        let mut view = |cam: &Camera, w: u32| {
+            let mut eye = PPMImg::new(height, w, img.depth());
+            eye.fg_color = img.fg_color;
+            eye.bg_color = img.bg_color;
+            eye.background = img.background.clone();
+            eye.clear();
+            draw(&mut eye, cam);
+            eye
+        };
+        match mode {
+            StereoMode::Anaglyph => {
+                let (left, right) = (view(&left_cam, width), view(&right_cam, width));
+                for y in 0..height {
+                    for x in 0..width {
+                        let mut c = right.pixel(x, y);
+                        c.red = left.pixel(x, y).red;
+                        img.set_pixel(x, y, c);
+                    }
+                }
+            }
+            StereoMode::SideBySide => {
+                let half = width / 2;
+                let (left, right) = (view(&left_cam, half), view(&right_cam, width - half));
+                for y in 0..height {
+                    for x in 0..half {
+                        img.set_pixel(x, y, left.pixel(x, y));
+                    }
+                    for x in 0..width - half {
+                        img.set_pixel(half + x, y, right.pixel(x, y));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anaglyph_splits_near_points() {
+        let cam = Camera::new([0.0, 0.0, -10.0], [0.0, 0.0, 0.0]);
+        let mut img = PPMImg::new(21, 21, 255);
+        let points = [[0.0, 2.0, 0.0], [0.0, -2.0, -5.0]];
+        cam.render_stereo(&mut img, 1.0, StereoMode::Anaglyph, |img, c| {
+            for p in points.iter() {
+                let s = c.project(p, 21.0, 21.0).unwrap();
+                img.plot(s[0] as i32, s[1] as i32);
+            }
+        });
+        // on the focal plane: both eyes agree, so the pixel is white
+        let p = img.pixel(10, 6);
+        assert_eq!((p.red, p.green, p.blue), (255, 255, 255));
+        // closer: the eyes see it in different places
+        let row: Vec<(u16, u16)> = (0..21)
+            .map(|x| img.pixel(x, 17))
+            .filter(|p| p.red > 0 || p.green > 0)
+            .map(|p| (p.red, p.green))
+            .collect();
+        assert_eq!(row, vec![(0, 255), (255, 0)]);
+    }
+}
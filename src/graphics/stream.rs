@@ -0,0 +1,116 @@
+use super::encoder::{ImageEncoder, PpmEncoder};
+use super::RGB;
+use std::io::{self, Write};
+
+/// Writes an image one row at a time, so the whole image never has to be in
+/// memory; a binary (P6) ppm unless given another encoder
+pub struct RowWriter<W: Write> {
+    out: W,
+    encoder: Box<dyn ImageEncoder>,
+    width: u32,
+    height: u32,
+    rows_written: u32,
+}
+
+#[allow(dead_code)]
+impl<W: Write> RowWriter<W> {
+    /// Writes the header right away
+    pub fn new(out: W, height: u32, width: u32, depth: u16) -> io::Result<RowWriter<W>> {
+        RowWriter::with_encoder(out, Box::new(PpmEncoder::binary()), height, width, depth)
+    }
+
+    /// Like `new`, writing with `encoder`
+    pub fn with_encoder(
+        mut out: W,
+        mut encoder: Box<dyn ImageEncoder>,
+        height: u32,
+        width: u32,
+        depth: u16,
+    ) -> io::Result<RowWriter<W>> {
+        encoder.write_header(&mut out, width, height, depth)?;
+        Ok(RowWriter {
+            out,
+            encoder,
+            width,
+            height,
+            rows_written: 0,
+        })
+    }
+
+    /// Writes the next row from the top, which must be exactly `width` pixels
+    pub fn write_row(&mut self, row: &[RGB]) -> io::Result<()> {
+        assert_eq!(
+            row.len(),
+            self.width as usize,
+            "Row must be width pixels long"
+        );
+        assert!(self.rows_written < self.height, "All rows already written");
+        self.encoder.write_row(&mut self.out, row)?;
+        self.rows_written += 1;
+        Ok(())
+    }
+
+    /// Flushes and returns the output; every row must have been written
+    pub fn finish(mut self) -> io::Result<W> {
+        assert_eq!(self.rows_written, self.height, "Not all rows written");
+        self.encoder.finish(&mut self.out)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Renders a `width` by `height` image one row at a time, top to bottom
+///
+/// `pixel(x, y)` gives the color of each pixel, and `row(y, pixels)` gets each
+/// row as soon as it is done. Only one row is held in memory, so this works
+/// for images far too big for a `PPMImg`.
+pub fn render_rows<F, G>(width: u32, height: u32, mut pixel: F, mut row: G) -> io::Result<()>
+where
+    F: FnMut(u32, u32) -> RGB,
+    G: FnMut(u32, &[RGB]) -> io::Result<()>,
+{
+    let mut buf = Vec::with_capacity(width as usize);
+    for y in 0..height {
+        buf.clear();
+        buf.extend((0..width).map(|x| pixel(x, y)));
+        row(y, &buf)?;
+    }
+    Ok(())
+}
+
+/// Renders straight into a binary ppm written to `out`, one row at a time
+pub fn render_ppm<W, F>(out: W, height: u32, width: u32, depth: u16, pixel: F) -> io::Result<W>
+where
+    W: Write,
+    F: FnMut(u32, u32) -> RGB,
+{
+    let mut writer = RowWriter::new(out, height, width, depth)?;
+    render_rows(width, height, pixel, |_, row| writer.write_row(row))?;
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::PPMImg;
+
+    #[test]
+    fn streamed_ppm_matches_image() {
+        let mut img = PPMImg::new(3, 4, 255);
+        let color = |x: u32, y: u32| RGB {
+            red: (x * 50) as u16,
+            green: (y * 80) as u16,
+            blue: 7,
+        };
+        for y in 0..3 {
+            for x in 0..4 {
+                img.set_pixel(x, y, color(x, y));
+            }
+        }
+        let mut expected = vec![];
+        img.encode_binary(&mut expected).unwrap();
+
+        let streamed = render_ppm(vec![], 3, 4, 255, color).unwrap();
+        assert_eq!(streamed, expected);
+    }
+}
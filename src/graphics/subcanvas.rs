@@ -0,0 +1,122 @@
+use super::rect::Rect;
+use super::PPMImg;
+use core::ops::{Deref, DerefMut};
+
+/// Region of an image drawn on as an image of its own, from `PPMImg::sub_canvas`
+///
+/// Derefs to an image the size of the region, so everything that draws on a
+/// `PPMImg`, cameras and plots included, fits its drawing to the region, with
+/// (0, 0) at the region's top left corner. Nothing drawn spills past the region.
+/// The drawing lands in the parent image when the view is dropped, minus the
+/// pixels the parent's clip mask leaves out.
+pub struct SubCanvas<'a> {
+    parent: &'a mut PPMImg,
+    rect: Rect,
+    view: PPMImg,
+}
+
+#[allow(dead_code)]
+impl SubCanvas<'_> {
+    /// Region of the parent image the view covers
+    pub fn rect(&self) -> Rect {
+        self.rect
+    }
+}
+
+impl Deref for SubCanvas<'_> {
+    type Target = PPMImg;
+
+    fn deref(&self) -> &PPMImg {
+        &self.view
+    }
+}
+
+impl DerefMut for SubCanvas<'_> {
+    fn deref_mut(&mut self) -> &mut PPMImg {
+        &mut self.view
+    }
+}
+
+impl Drop for SubCanvas<'_> {
+    fn drop(&mut self) {
+        let dirty = match self.view.take_dirty() {
+            Some(d) => d,
+            None => return,
+        };
+        let (x0, y0) = (self.rect.x, self.rect.y);
+        for y in dirty.y..=dirty.bottom() {
+            for x in dirty.x..=dirty.right() {
+                let (px, py) = (x + x0, y + y0);
+                if self.parent.is_clipped(px, py) {
+                    continue;
+                }
+                let i = self.parent.index(px as u32, py as u32);
+                self.parent.data[i] = self.view.pixel(x as u32, y as u32);
+            }
+        }
+        self.parent.stats += self.view.take_stats();
+        self.parent
+            .mark_dirty(Rect::new(x0 + dirty.x, y0 + dirty.y, dirty.w, dirty.h));
+    }
+}
+
+// sub canvas
+#[allow(dead_code)]
+impl PPMImg {
+    /// View that clips and offsets all drawing into `rect`, for laying out
+    /// several renders, like front, top, side and perspective views, in one image
+    ///
+    /// The view starts out with the region's pixels and the image's colors,
+    /// background, y_axis, subpixel and brush settings. `rect` is cut down to the
+    /// part inside the image, which mustn't be empty.
+    pub fn sub_canvas(&mut self, rect: Rect) -> SubCanvas<'_> {
+        let rect = rect
+            .intersect(&Rect::new(0, 0, self.width, self.height))
+            .expect("Sub canvas must overlap the image");
+        let mut view = PPMImg::new(rect.h, rect.w, self.depth);
+        view.y_axis = self.y_axis;
+        view.subpixel = self.subpixel;
+        view.brush = self.brush.clone();
+        view.fg_color = self.fg_color;
+        view.bg_color = self.bg_color;
+        view.background = self.background.clone();
+        for y in 0..rect.h {
+            let start = self.index(rect.x as u32, rect.y as u32 + y);
+            let end = start + rect.w as usize;
+            let row = view.index(0, y);
+            view.data[row..row + rect.w as usize].copy_from_slice(&self.data[start..end]);
+        }
+        SubCanvas {
+            parent: self,
+            rect,
+            view,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mask::ClipMask;
+    use super::*;
+
+    #[test]
+    fn views_are_offset_and_clipped() {
+        let mut img = PPMImg::new(10, 20, 255);
+        {
+            let mut view = img.sub_canvas(Rect::new(10, 5, 10, 5));
+            assert_eq!((view.width(), view.height()), (10, 5));
+            view.draw_line(0.0, 0.0, 30.0, 0.0);
+            view.fill_rect(-5, 4, 100, 100);
+        }
+        let lit = |x, y| img.pixel(x, y).red > 0;
+        assert!((10..20).all(|x| lit(x, 5) && lit(x, 9)));
+        assert!(!lit(9, 5) && !lit(15, 4) && !lit(15, 6));
+        assert_eq!(img.dirty(), Some(Rect::new(10, 5, 10, 5)));
+
+        // the parent's clip mask still holds
+        let mut img = PPMImg::new(4, 4, 255);
+        img.clip = Some(ClipMask::from_rect(4, 4, Rect::new(0, 0, 2, 4)));
+        img.sub_canvas(Rect::new(1, 0, 3, 4)).fill_rect(0, 0, 3, 4);
+        assert!(img.pixel(1, 0).red > 0 && img.pixel(2, 0).red == 0);
+    }
+}
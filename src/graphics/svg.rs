@@ -0,0 +1,516 @@
+use super::matrix::{EdgeMatrix, PolygonMatrix};
+#[cfg(feature = "fs")]
+use super::utils::create_file;
+use super::RGB;
+use std::io::{self, Write};
+
+/// A shape kept in an `Svg`, in image coordinates
+#[derive(Clone)]
+enum Element {
+    Line([f64; 2], [f64; 2], RGB),
+    Polygon(Vec<[f64; 2]>, RGB),
+    Circle([f64; 2], f64, RGB),
+    /// Filled rectangle: x, y, width, height
+    Rect([f64; 4], RGB),
+    /// Cubic bezier curve: start, two control points, end
+    Cubic([[f64; 2]; 4], RGB),
+    /// Quadratic bezier curve: start, control point, end
+    Quadratic([[f64; 2]; 3], RGB),
+}
+
+/// Vector image: records shapes and writes them out as SVG elements
+///
+/// Works like `PPMImg` with the same coordinates ((0, 0) top left, y down) and
+/// colors, but keeps the geometry instead of rasterizing it, so the output
+/// stays sharp at any size. Shapes are outlined with fg_color at the time they
+/// are added.
+#[derive(Clone)]
+pub struct Svg {
+    height: u32,
+    width: u32,
+    depth: u16,
+    pub fg_color: RGB,
+    /// Fills the whole image when set
+    pub bg_color: Option<RGB>,
+    pub stroke_width: f64,
+    elements: Vec<Element>,
+}
+
+#[allow(dead_code)]
+impl Svg {
+    /// Empty image with a transparent background and white shapes
+    pub fn new(height: u32, width: u32, depth: u16) -> Svg {
+        Svg {
+            height,
+            width,
+            depth,
+            fg_color: RGB {
+                red: depth,
+                green: depth,
+                blue: depth,
+            },
+            bg_color: None,
+            stroke_width: 1.0,
+            elements: vec![],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn depth(&self) -> u16 {
+        self.depth
+    }
+
+    /// Number of shapes added
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn draw_line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64) {
+        self.elements
+            .push(Element::Line([x0, y0], [x1, y1], self.fg_color));
+    }
+
+    /// Adds every edge of `m`, using x and y, like `PPMImg::render_edge_matrix`
+    pub fn render_edge_matrix(&mut self, m: &EdgeMatrix) {
+        assert_eq!(m.rows() % 2, 0, "Number of edges must be a multiple of 2");
+        let mut iter = m.iter_by_row();
+        while let (Some(p0), Some(p1)) = (iter.next(), iter.next()) {
+            self.draw_line(p0[0], p0[1], p1[0], p1[1]);
+        }
+    }
+
+    /// Adds the outline of every triangle of `m`, using x and y
+    pub fn render_polygon_matrix(&mut self, m: &PolygonMatrix) {
+        assert_eq!(m.rows() % 3, 0, "Number of points must be a multiple of 3");
+        let mut iter = m.iter_by_row();
+        while let (Some(p0), Some(p1), Some(p2)) = (iter.next(), iter.next(), iter.next()) {
+            self.draw_polygon(&[[p0[0], p0[1]], [p1[0], p1[1]], [p2[0], p2[1]]]);
+        }
+    }
+
+    /// Adds a closed outline through `points`
+    pub fn draw_polygon(&mut self, points: &[[f64; 2]]) {
+        self.elements
+            .push(Element::Polygon(points.to_vec(), self.fg_color));
+    }
+
+    /// Filled `w` by `h` rectangle with top left corner at (x, y), like `PPMImg::fill_rect`
+    pub fn fill_rect(&mut self, x: f64, y: f64, w: f64, h: f64) {
+        self.elements
+            .push(Element::Rect([x, y, w, h], self.fg_color));
+    }
+
+    /// Removes every shape
+    pub fn clear(&mut self) {
+        self.elements.clear();
+    }
+
+    pub fn draw_circle(&mut self, cx: f64, cy: f64, r: f64) {
+        self.elements
+            .push(Element::Circle([cx, cy], r, self.fg_color));
+    }
+
+    /// Cubic bezier curve from p0 to p3, with control points p1 and p2
+    pub fn draw_cubic(&mut self, p0: [f64; 2], p1: [f64; 2], p2: [f64; 2], p3: [f64; 2]) {
+        self.elements
+            .push(Element::Cubic([p0, p1, p2, p3], self.fg_color));
+    }
+
+    /// Quadratic bezier curve from p0 to p2, with control point p1
+    pub fn draw_quadratic(&mut self, p0: [f64; 2], p1: [f64; 2], p2: [f64; 2]) {
+        self.elements
+            .push(Element::Quadratic([p0, p1, p2], self.fg_color));
+    }
+
+    /// `#rrggbb` for a color of this image's depth
+    fn hex(&self, c: RGB) -> String {
+        let c = RGB::from_fractions(c.to_fractions(self.depth), 255);
+        format!("#{:02x}{:02x}{:02x}", c.red, c.green, c.blue)
+    }
+
+    /// Writes the svg document to `out`
+    pub fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(
+            out,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+            w = self.width,
+            h = self.height
+        )?;
+        if let Some(bg) = self.bg_color {
+            writeln!(
+                out,
+                "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>",
+                self.hex(bg)
+            )?;
+        }
+        writeln!(
+            out,
+            "<g fill=\"none\" stroke-width=\"{}\" stroke-linecap=\"round\">",
+            self.stroke_width
+        )?;
+        for e in &self.elements {
+            match e {
+                Element::Line(a, b, c) => writeln!(
+                    out,
+                    "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\"/>",
+                    a[0],
+                    a[1],
+                    b[0],
+                    b[1],
+                    self.hex(*c)
+                )?,
+                Element::Polygon(points, c) => {
+                    let points: Vec<String> = points
+                        .iter()
+                        .map(|p| format!("{},{}", p[0], p[1]))
+                        .collect();
+                    writeln!(
+                        out,
+                        "<polygon points=\"{}\" stroke=\"{}\"/>",
+                        points.join(" "),
+                        self.hex(*c)
+                    )?
+                }
+                Element::Circle(center, r, c) => writeln!(
+                    out,
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" stroke=\"{}\"/>",
+                    center[0],
+                    center[1],
+                    r,
+                    self.hex(*c)
+                )?,
+                Element::Rect([x, y, w, h], c) => writeln!(
+                    out,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"none\"/>",
+                    x,
+                    y,
+                    w,
+                    h,
+                    self.hex(*c)
+                )?,
+                Element::Cubic([p0, p1, p2, p3], c) => writeln!(
+                    out,
+                    "<path d=\"M {} {} C {} {}, {} {}, {} {}\" stroke=\"{}\"/>",
+                    p0[0],
+                    p0[1],
+                    p1[0],
+                    p1[1],
+                    p2[0],
+                    p2[1],
+                    p3[0],
+                    p3[1],
+                    self.hex(*c)
+                )?,
+                Element::Quadratic([p0, p1, p2], c) => writeln!(
+                    out,
+                    "<path d=\"M {} {} Q {} {}, {} {}\" stroke=\"{}\"/>",
+                    p0[0],
+                    p0[1],
+                    p1[0],
+                    p1[1],
+                    p2[0],
+                    p2[1],
+                    self.hex(*c)
+                )?,
+            }
+        }
+        writeln!(out, "</g>")?;
+        writeln!(out, "</svg>")
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn write_svg(&self, filepath: &str) -> io::Result<()> {
+        let mut file = create_file(filepath);
+        self.write(&mut file)?;
+        file.flush()
+    }
+}
+
+/// Tokens of svg path data
+struct PathTokens<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl PathTokens<'_> {
+    fn skip_separators(&mut self) {
+        while self.pos < self.s.len()
+            && (self.s[self.pos].is_ascii_whitespace() || self.s[self.pos] == b',')
+        {
+            self.pos += 1;
+        }
+    }
+
+    /// Next command letter, if the next token is one
+    fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        match self.s.get(self.pos) {
+            Some(c) if c.is_ascii_alphabetic() && *c != b'e' && *c != b'E' => {
+                self.pos += 1;
+                Some(*c)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether a number comes next
+    fn has_number(&mut self) -> bool {
+        self.skip_separators();
+        self.s
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_digit() || b"+-.".contains(c))
+    }
+
+    fn number(&mut self) -> io::Result<f64> {
+        self.skip_separators();
+        let start = self.pos;
+        let mut seen_dot = false;
+        while let Some(&c) = self.s.get(self.pos) {
+            let sign_ok = self.pos == start || b"eE".contains(&self.s[self.pos - 1]);
+            match c {
+                b'0'..=b'9' => (),
+                b'+' | b'-' if sign_ok => (),
+                // "1.5.5" is two numbers
+                b'.' if !seen_dot => seen_dot = true,
+                b'e' | b'E' => (),
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.s[start..self.pos])
+            .ok()
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Expected a number at byte {} of path data", start),
+                )
+            })
+    }
+
+    fn point(&mut self) -> io::Result<[f64; 2]> {
+        Ok([self.number()?, self.number()?])
+    }
+}
+
+#[allow(dead_code)]
+// curves and svg path import
+impl EdgeMatrix {
+    /// Adds a cubic bezier curve from p0 to p3 as `steps` line segments, in the z = 0 plane
+    pub fn add_cubic(
+        &mut self,
+        p0: [f64; 2],
+        p1: [f64; 2],
+        p2: [f64; 2],
+        p3: [f64; 2],
+        steps: usize,
+    ) {
+        self.add_curve(steps, |t| {
+            let s = 1.0 - t;
+            let (a, b, c, d) = (s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t);
+            [
+                a * p0[0] + b * p1[0] + c * p2[0] + d * p3[0],
+                a * p0[1] + b * p1[1] + c * p2[1] + d * p3[1],
+            ]
+        });
+    }
+
+    /// Adds a quadratic bezier curve from p0 to p2 as `steps` line segments, in the z = 0 plane
+    pub fn add_quadratic(&mut self, p0: [f64; 2], p1: [f64; 2], p2: [f64; 2], steps: usize) {
+        self.add_curve(steps, |t| {
+            let s = 1.0 - t;
+            let (a, b, c) = (s * s, 2.0 * s * t, t * t);
+            [
+                a * p0[0] + b * p1[0] + c * p2[0],
+                a * p0[1] + b * p1[1] + c * p2[1],
+            ]
+        });
+    }
+
+    /// Adds edges between `point(t)` for `steps + 1` values of t from 0 to 1
+    fn add_curve<F>(&mut self, steps: usize, point: F)
+    where
+        F: Fn(f64) -> [f64; 2],
+    {
+        assert!(steps > 0, "Curve needs at least 1 step");
+        let mut prev = point(0.0);
+        for i in 1..=steps {
+            let p = point(i as f64 / steps as f64);
+            self.append_edge([prev[0], prev[1], 0.0]);
+            self.append_edge([p[0], p[1], 0.0]);
+            prev = p;
+        }
+    }
+
+    /// Adds the outline described by svg path data `d`, in the z = 0 plane
+    ///
+    /// Supports the M, L, H, V, C, Q and Z commands, absolute (uppercase) and
+    /// relative (lowercase). Curves are flattened into `steps` segments each.
+    /// Returns an `InvalidData` error for anything else; the edges before the
+    /// error are kept.
+    pub fn add_svg_path(&mut self, d: &str, steps: usize) -> io::Result<()> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut tokens = PathTokens {
+            s: d.as_bytes(),
+            pos: 0,
+        };
+        let (mut cur, mut start) = ([0.0; 2], [0.0; 2]);
+        let line = |m: &mut EdgeMatrix, from: [f64; 2], to: [f64; 2]| {
+            m.append_edge([from[0], from[1], 0.0]);
+            m.append_edge([to[0], to[1], 0.0]);
+        };
+        let mut cmd = match tokens.command() {
+            Some(c) if c == b'M' || c == b'm' => c,
+            _ if tokens.pos >= tokens.s.len() => return Ok(()),
+            _ => return Err(invalid("Path data must start with a move".to_string())),
+        };
+        loop {
+            let rel = cmd.is_ascii_lowercase();
+            let offset = |p: [f64; 2], cur: [f64; 2]| {
+                if rel {
+                    [p[0] + cur[0], p[1] + cur[1]]
+                } else {
+                    p
+                }
+            };
+            match cmd.to_ascii_uppercase() {
+                b'M' => {
+                    cur = offset(tokens.point()?, cur);
+                    start = cur;
+                    // more points after a move are lines
+                    cmd = if rel { b'l' } else { b'L' };
+                }
+                b'L' => {
+                    let p = offset(tokens.point()?, cur);
+                    line(self, cur, p);
+                    cur = p;
+                }
+                b'H' => {
+                    let x = tokens.number()?;
+                    let p = [if rel { cur[0] + x } else { x }, cur[1]];
+                    line(self, cur, p);
+                    cur = p;
+                }
+                b'V' => {
+                    let y = tokens.number()?;
+                    let p = [cur[0], if rel { cur[1] + y } else { y }];
+                    line(self, cur, p);
+                    cur = p;
+                }
+                b'C' => {
+                    let p1 = offset(tokens.point()?, cur);
+                    let p2 = offset(tokens.point()?, cur);
+                    let p3 = offset(tokens.point()?, cur);
+                    self.add_cubic(cur, p1, p2, p3, steps);
+                    cur = p3;
+                }
+                b'Q' => {
+                    let p1 = offset(tokens.point()?, cur);
+                    let p2 = offset(tokens.point()?, cur);
+                    self.add_quadratic(cur, p1, p2, steps);
+                    cur = p2;
+                }
+                b'Z' => {
+                    if cur != start {
+                        line(self, cur, start);
+                    }
+                    cur = start;
+                }
+                _ => {
+                    return Err(invalid(format!(
+                        "Unsupported path command '{}'",
+                        cmd as char
+                    )))
+                }
+            }
+            // the same command repeats while numbers follow, except for Z
+            if cmd.eq_ignore_ascii_case(&b'Z') || !tokens.has_number() {
+                cmd = match tokens.command() {
+                    Some(c) => c,
+                    None if tokens.pos >= tokens.s.len() => return Ok(()),
+                    None => {
+                        return Err(invalid(format!(
+                            "Unexpected data at byte {} of path data",
+                            tokens.pos
+                        )))
+                    }
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::matrix::Matrix;
+
+    #[test]
+    fn writes_elements() {
+        let mut svg = Svg::new(10, 20, 100);
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.append_edge([0.0, 0.0, 0.0]);
+        m.append_edge([5.5, 2.0, 0.0]);
+        svg.render_edge_matrix(&m);
+        svg.fg_color = RGB {
+            red: 100,
+            green: 50,
+            blue: 0,
+        };
+        svg.draw_circle(10.0, 5.0, 3.0);
+        svg.draw_quadratic([0.0, 0.0], [1.0, 2.0], [3.0, 0.0]);
+
+        let mut out = vec![];
+        svg.write(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            &lines[2..5],
+            &[
+                "<line x1=\"0\" y1=\"0\" x2=\"5.5\" y2=\"2\" stroke=\"#ffffff\"/>",
+                "<circle cx=\"10\" cy=\"5\" r=\"3\" stroke=\"#ff8000\"/>",
+                "<path d=\"M 0 0 Q 1 2, 3 0\" stroke=\"#ff8000\"/>",
+            ]
+        );
+        assert_eq!(lines.last(), Some(&"</svg>"));
+    }
+
+    #[test]
+    fn imports_path_data() {
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_svg_path("M10,10 h5 v-5 L0 0z m1 1 Q2 3 3 1", 4)
+            .unwrap();
+        let edges: Vec<[f64; 2]> = m.iter_by_row().map(|p| [p[0], p[1]]).collect();
+        assert_eq!(
+            &edges[..8],
+            &[
+                [10.0, 10.0],
+                [15.0, 10.0],
+                [15.0, 10.0],
+                [15.0, 5.0],
+                [15.0, 5.0],
+                [0.0, 0.0],
+                [0.0, 0.0],
+                [10.0, 10.0],
+            ]
+        );
+        // the curve starts at the relative move from the closed path's start
+        assert_eq!(m.rows(), 8 + 4 * 2);
+        assert_eq!(edges[8], [11.0, 11.0]);
+        assert_eq!(edges[15], [3.0, 1.0]);
+
+        assert!(m.add_svg_path("M0 0 A 1 1 0 0 0 2 2", 4).is_err());
+        assert!(m.add_svg_path("L1 1", 4).is_err());
+    }
+}
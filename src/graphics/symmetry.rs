@@ -0,0 +1,91 @@
+use core::f64::consts::PI;
+
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+
+/// N-fold rotational symmetry about a point, optionally mirrored, like a kaleidoscope
+///
+/// Set as `PPMImg::symmetry` to have every point and line drawn repeated
+/// `folds` times, evenly rotated about `center`. With `mirror`, each of those
+/// is also drawn flipped left to right about `center`. Fills like `fill_rect`
+/// and `clear` are not repeated.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Symmetry {
+    pub center: (f64, f64),
+    folds: u32,
+    pub mirror: bool,
+}
+
+#[allow(dead_code)]
+impl Symmetry {
+    pub fn new(center: (f64, f64), folds: u32) -> Symmetry {
+        assert!(folds > 0, "Symmetry needs at least 1 fold");
+        Symmetry {
+            center,
+            folds,
+            mirror: false,
+        }
+    }
+
+    /// Same symmetry, with mirrored copies as well
+    pub fn mirrored(self) -> Symmetry {
+        Symmetry {
+            mirror: true,
+            ..self
+        }
+    }
+
+    pub fn folds(&self) -> u32 {
+        self.folds
+    }
+
+    /// Number of copies of everything drawn, the original included
+    pub fn copies(&self) -> u32 {
+        if self.mirror {
+            2 * self.folds
+        } else {
+            self.folds
+        }
+    }
+
+    /// Where copy `i` of point `p` goes; copy 0 is `p` itself
+    ///
+    /// Copies from `folds` on are the mirrored ones.
+    pub fn apply(&self, i: u32, p: (f64, f64)) -> (f64, f64) {
+        let (cx, cy) = self.center;
+        let (dx, dy) = (p.0 - cx, p.1 - cy);
+        let dx = if i >= self.folds { -dx } else { dx };
+        let angle = 2.0 * PI * f64::from(i % self.folds) / f64::from(self.folds);
+        let (sin, cos) = angle.sin_cos();
+        (cx + dx * cos - dy * sin, cy + dx * sin + dy * cos)
+    }
+
+    /// Copy `i` of the line (x0, y0, x1, y1)
+    pub fn apply_line(&self, i: u32, line: (f64, f64, f64, f64)) -> (f64, f64, f64, f64) {
+        let (x0, y0) = self.apply(i, (line.0, line.1));
+        let (x1, y1) = self.apply(i, (line.2, line.3));
+        (x0, y0, x1, y1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::PPMImg;
+
+    #[test]
+    fn lines_and_points_are_repeated() {
+        let mut img = PPMImg::new(11, 11, 255);
+        img.symmetry = Some(Symmetry::new((5.0, 5.0), 4));
+        img.draw_line(5.0, 5.0, 9.0, 5.0);
+        let lit = |img: &PPMImg, x: u32, y: u32| img.pixel(x, y).red > 0;
+        assert!(lit(&img, 9, 5) && lit(&img, 5, 9) && lit(&img, 1, 5) && lit(&img, 5, 1));
+        assert_eq!(img.stats().lines_drawn, 4);
+
+        let mut img = PPMImg::new(11, 11, 255);
+        img.symmetry = Some(Symmetry::new((5.0, 5.0), 3).mirrored());
+        img.plot(7, 2);
+        assert_eq!(img.data.iter().filter(|p| p.red > 0).count(), 6);
+        assert!(lit(&img, 7, 2) && lit(&img, 3, 2));
+    }
+}
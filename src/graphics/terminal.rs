@@ -0,0 +1,160 @@
+use super::canvas::Canvas;
+use super::{PPMImg, RGB};
+use std::env;
+use std::io::{self, Write};
+
+/// Characters from dark to bright, for terminals without color
+const RAMP: &[u8] = b" .:-=+*#%@";
+
+/// Canvas printed to a terminal, two pixels per character cell
+///
+/// Each cell is an upper half block colored with ANSI 24-bit escapes: the top
+/// pixel is the text color and the bottom pixel the background.
+#[derive(Clone)]
+pub struct TerminalCanvas {
+    width: u32,
+    height: u32,
+    data: Vec<RGB>,
+}
+
+#[allow(dead_code)]
+impl TerminalCanvas {
+    /// Canvas `cols` characters wide and `rows` tall, so `2 * rows` pixels high
+    pub fn new(rows: u32, cols: u32) -> TerminalCanvas {
+        let black = RGB {
+            red: 0,
+            green: 0,
+            blue: 0,
+        };
+        TerminalCanvas {
+            width: cols,
+            height: rows * 2,
+            data: vec![black; (cols * rows * 2) as usize],
+        }
+    }
+
+    /// Size of the terminal as (cols, rows), from the COLUMNS and LINES
+    /// environment variables, or 80 by 24
+    pub fn terminal_size() -> (u32, u32) {
+        let var = |name: &str, default: u32| {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|v| *v > 0)
+                .unwrap_or(default)
+        };
+        (var("COLUMNS", 80), var("LINES", 24))
+    }
+
+    /// `img` shrunk to fit in the terminal, keeping its aspect ratio
+    ///
+    /// Leaves a line free for the prompt. Images are never scaled up.
+    pub fn fit(img: &PPMImg) -> TerminalCanvas {
+        let (cols, rows) = TerminalCanvas::terminal_size();
+        let (max_w, max_h) = (f64::from(cols), f64::from(rows.max(2) - 1) * 2.0);
+        let (w, h) = (f64::from(img.width()), f64::from(img.height()));
+        let scale = (max_w / w).min(max_h / h).min(1.0);
+        let cols = ((w * scale).round() as u32).max(1);
+        let rows = ((h * scale / 2.0).round() as u32).max(1);
+        let mut canvas = TerminalCanvas::new(rows, cols);
+        canvas.draw_scaled(img);
+        canvas
+    }
+
+    /// Writes the canvas with 24-bit color escapes, one line per row of cells
+    pub fn write_ansi<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for row in 0..self.height / 2 {
+            for x in 0..self.width {
+                let (top, bottom) = (self.rgb8(x, row * 2), self.rgb8(x, row * 2 + 1));
+                write!(
+                    out,
+                    "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                    top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]
+                )?;
+            }
+            writeln!(out, "\x1b[0m")?;
+        }
+        Ok(())
+    }
+
+    /// Writes the canvas as plain characters by brightness, one per cell
+    pub fn write_ascii<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for row in 0..self.height / 2 {
+            let line: Vec<u8> = (0..self.width)
+                .map(|x| {
+                    let (top, bottom) = (self.rgb8(x, row * 2), self.rgb8(x, row * 2 + 1));
+                    let sum: u32 = top.iter().chain(bottom.iter()).map(|c| u32::from(*c)).sum();
+                    RAMP[(sum * (RAMP.len() as u32 - 1) / (6 * 255)) as usize]
+                })
+                .collect();
+            out.write_all(&line)?;
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// Prints the canvas to stdout with colors
+    pub fn print(&self) -> io::Result<()> {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        self.write_ansi(&mut out)?;
+        out.flush()
+    }
+
+    fn rgb8(&self, x: u32, y: u32) -> [u8; 3] {
+        let [r, g, b, _] = self.data[(y * self.width + x) as usize].to_rgba8(255);
+        [r, g, b]
+    }
+}
+
+impl Canvas for TerminalCanvas {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Terminal colors have 8 bits per channel
+    fn depth(&self) -> u16 {
+        255
+    }
+
+    fn pixel(&self, x: u32, y: u32) -> RGB {
+        assert!(x < self.width && y < self.height, "Index out of bound");
+        self.data[(y * self.width + x) as usize]
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, color: RGB) {
+        assert!(x < self.width && y < self.height, "Index out of bound");
+        self.data[(y * self.width + x) as usize] = color;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downscales_and_prints_half_blocks() {
+        let mut img = PPMImg::new(4, 4, 255);
+        img.fill_rect(0, 0, 2, 4);
+        let mut canvas = TerminalCanvas::new(1, 2);
+        canvas.draw_scaled(&img);
+        assert_eq!(canvas.pixel(0, 1).green, 255);
+        assert_eq!(canvas.pixel(1, 0).green, 0);
+
+        let mut out = vec![];
+        canvas.write_ansi(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(
+            out,
+            "\x1b[38;2;255;255;255m\x1b[48;2;255;255;255m\u{2580}\
+             \x1b[38;2;0;0;0m\x1b[48;2;0;0;0m\u{2580}\x1b[0m\n"
+        );
+        let mut out = vec![];
+        canvas.write_ascii(&mut out).unwrap();
+        assert_eq!(out, b"@ \n");
+    }
+}
@@ -0,0 +1,173 @@
+use super::camera::Camera;
+use super::stats::RenderStats;
+use super::PPMImg;
+use alloc::format;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// Room a character of `PPMImg::draw_text` takes at scale 1, spacing included
+pub const CHAR_WIDTH: u32 = 4;
+pub const CHAR_HEIGHT: u32 = 6;
+
+/// Rows of the 3 by 5 pixel glyph of `c`, from the top, leftmost pixel in the
+/// highest of the 3 bits
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ' ' => [0; 5],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        _ => [0b111, 0b001, 0b011, 0b000, 0b010],
+    }
+}
+
+/// Frame metadata `PPMImg::draw_hud` stamps on a frame
+#[derive(Clone, Debug)]
+pub struct Hud<'a> {
+    pub frame: usize,
+    /// Time in the animation, in seconds
+    pub time: f64,
+    /// How long drawing the frame took
+    pub render_time: Option<Duration>,
+    /// What was drawn in the frame
+    pub stats: RenderStats,
+    pub camera: Option<&'a Camera>,
+}
+
+// text
+#[allow(dead_code)]
+impl PPMImg {
+    /// Width and height `text` takes when drawn by `draw_text` at `scale`
+    pub fn text_size(text: &str, scale: u32) -> (u32, u32) {
+        let lines = text.split('\n');
+        let (columns, rows) = lines.fold((0, 0), |(w, h), line| {
+            (w.max(line.chars().count() as u32), h + 1)
+        });
+        (columns * CHAR_WIDTH * scale, rows * CHAR_HEIGHT * scale)
+    }
+
+    /// Draws `text` in fg_color with its top left corner at (x, y), in a small
+    /// built-in font with 3 by 5 pixel characters, each pixel `scale` pixels wide
+    ///
+    /// Lowercase letters are drawn as capitals, and characters the font lacks
+    /// as '?'. '\n' starts a new line.
+    pub fn draw_text(&mut self, x: i32, y: i32, text: &str, scale: u32) {
+        let s = scale as i32;
+        for (row, line) in text.split('\n').enumerate() {
+            let top = y + row as i32 * CHAR_HEIGHT as i32 * s;
+            for (column, c) in line.chars().enumerate() {
+                let left = x + column as i32 * CHAR_WIDTH as i32 * s;
+                for (gy, bits) in glyph(c).iter().enumerate() {
+                    for gx in 0..3 {
+                        if bits & (0b100 >> gx) != 0 {
+                            self.fill_rect(left + gx * s, top + gy as i32 * s, scale, scale);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Stamps the frame number, time, render time, triangle count and camera of
+    /// `hud` in the top left corner, in fg_color over a box of bg_color
+    pub fn draw_hud(&mut self, hud: &Hud) {
+        let mut lines = Vec::new();
+        lines.push(format!("FRAME {}  T {:.2}S", hud.frame, hud.time));
+        if let Some(t) = hud.render_time {
+            lines.push(format!("RENDER {:.1}MS", t.as_secs_f64() * 1000.0));
+        }
+        lines.push(format!(
+            "TRIS {}  LINES {}",
+            hud.stats.triangles_filled, hud.stats.lines_drawn
+        ));
+        if let Some(c) = hud.camera {
+            let [x, y, z] = c.eye;
+            lines.push(format!("EYE {:.1},{:.1},{:.1}", x, y, z));
+            let [x, y, z] = c.target;
+            lines.push(format!("AT {:.1},{:.1},{:.1}", x, y, z));
+            lines.push(format!("FOV {:.0}", c.fov_deg));
+        }
+        let text = lines.join("\n");
+        let (w, h) = PPMImg::text_size(&text, 1);
+        let fg = self.fg_color;
+        self.fg_color = self.bg_color;
+        self.fill_rect(0, 0, w + 3, h + 3);
+        self.fg_color = fg;
+        self.draw_text(2, 2, &text, 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_is_stamped_in_the_font() {
+        let mut img = PPMImg::new(12, 12, 255);
+        assert_eq!(PPMImg::text_size("ab\nc", 2), (16, 24));
+        img.draw_text(0, 0, "1", 2);
+        let lit = |img: &PPMImg, x, y| img.pixel(x, y).red > 0;
+        // the base of the 1 spans the whole glyph
+        assert!((0..6).all(|x| lit(&img, x, 8) && lit(&img, x, 9)));
+        assert!(!lit(&img, 0, 0) && lit(&img, 2, 0) && !lit(&img, 6, 0));
+
+        let mut img = PPMImg::new(40, 80, 255);
+        let camera = Camera::new([0.0, 0.0, -5.0], [0.0; 3]);
+        img.draw_hud(&Hud {
+            frame: 3,
+            time: 0.1,
+            render_time: None,
+            stats: RenderStats::default(),
+            camera: Some(&camera),
+        });
+        let lit_rows = (0..40)
+            .filter(|&y| (0..80).any(|x| lit(&img, x, y)))
+            .count();
+        // 5 lines of 5 pixel tall characters
+        assert_eq!(lit_rows, 25);
+    }
+}
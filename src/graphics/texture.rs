@@ -0,0 +1,350 @@
+use super::canvas::Canvas;
+use super::noise::Noise;
+use super::wrap::WrapMode;
+use super::{PPMImg, RGB};
+use std::f64::consts::PI;
+
+/// How an image texture picks colors between the centers of its pixels
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Filter {
+    /// The pixel the point falls in: blocky up close, and shimmers from afar
+    Nearest,
+    /// Blend of the 4 closest pixels by how close they are
+    Bilinear,
+}
+
+/// Color of a surface at every point (u, v) of its texture coordinates
+///
+/// Colors are channel intensities in [0, 1], like light colors. The unit square
+/// of uv space is one tile; procedural textures repeat past it.
+#[allow(dead_code)]
+#[derive(Clone)]
+pub enum Texture {
+    Solid([f64; 3]),
+    /// Picture covering the unit square, u going right and v up, so (0, 0) is its
+    /// bottom left corner
+    ///
+    /// `wrap` says what u and v outside [0, 1] see, like `PPMImg`'s wrap modes;
+    /// Discard gives black. `mipmaps` are smaller and smaller copies of img, each
+    /// half the size of the one before down to 1 by 1, for `sample_area`; empty
+    /// turns mipmapping off.
+    Image {
+        img: Box<PPMImg>,
+        filter: Filter,
+        wrap: [WrapMode; 2],
+        mipmaps: Vec<PPMImg>,
+    },
+    /// `squares` by `squares` squares of `a` and `b`, `a` at (0, 0)
+    Checkerboard {
+        a: [f64; 3],
+        b: [f64; 3],
+        squares: f64,
+    },
+    /// `stripes` pairs of stripes of `a` and `b` across u, `a` first
+    Stripes {
+        a: [f64; 3],
+        b: [f64; 3],
+        stripes: f64,
+    },
+    /// Veins of `b` through `a`, bent by noise; bigger `scale` is more veins
+    Marble {
+        a: [f64; 3],
+        b: [f64; 3],
+        scale: f64,
+        noise: Noise,
+    },
+    /// Growth rings around the center of the tile, `rings` of them from its
+    /// center to its edge, wobbled by noise
+    Wood {
+        light: [f64; 3],
+        dark: [f64; 3],
+        rings: f64,
+        noise: Noise,
+    },
+}
+
+fn mix(a: [f64; 3], b: [f64; 3], t: f64) -> [f64; 3] {
+    [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+/// Halves of `img`, then of that, until 1 by 1; every pixel is the average of
+/// the ones it covers
+pub fn mipmaps(img: &PPMImg) -> Vec<PPMImg> {
+    let mut levels: Vec<PPMImg> = vec![];
+    loop {
+        let last = levels.last().unwrap_or(img);
+        if last.width() == 1 && last.height() == 1 {
+            return levels;
+        }
+        let (w, h) = ((last.width() / 2).max(1), (last.height() / 2).max(1));
+        let mut next = PPMImg::new(h, w, last.depth());
+        next.draw_scaled(last);
+        levels.push(next);
+    }
+}
+
+/// Color of `img` at `uv`, see `Texture::Image`
+fn sample_image(img: &PPMImg, filter: Filter, wrap: [WrapMode; 2], uv: [f64; 2]) -> [f64; 3] {
+    let (w, h) = (img.width() as i32, img.height() as i32);
+    let texel = |x: i32, y: i32| match (wrap[0].apply(x, w), wrap[1].apply(y, h)) {
+        (Some(x), Some(y)) => img.pixel(x as u32, y as u32).to_fractions(img.depth()),
+        _ => [0.0; 3],
+    };
+    // pixel coordinates, y going down the picture
+    let (x, y) = (uv[0] * f64::from(w), (1.0 - uv[1]) * f64::from(h));
+    match filter {
+        Filter::Nearest => texel(x.floor() as i32, y.floor() as i32),
+        Filter::Bilinear => {
+            // between the centers of the 4 closest pixels
+            let (x, y) = (x - 0.5, y - 0.5);
+            let (x0, y0) = (x.floor(), y.floor());
+            let (fx, fy) = (x - x0, y - y0);
+            let (x0, y0) = (x0 as i32, y0 as i32);
+            let top = mix(texel(x0, y0), texel(x0 + 1, y0), fx);
+            let bottom = mix(texel(x0, y0 + 1), texel(x0 + 1, y0 + 1), fx);
+            mix(top, bottom, fy)
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Texture {
+    /// Bilinear filtered picture, repeating, with mipmaps
+    pub fn image(img: PPMImg) -> Texture {
+        Texture::Image {
+            mipmaps: mipmaps(&img),
+            img: Box::new(img),
+            filter: Filter::Bilinear,
+            wrap: [WrapMode::Wrap; 2],
+        }
+    }
+
+    pub fn checkerboard(a: [f64; 3], b: [f64; 3], squares: f64) -> Texture {
+        Texture::Checkerboard { a, b, squares }
+    }
+
+    pub fn stripes(a: [f64; 3], b: [f64; 3], stripes: f64) -> Texture {
+        Texture::Stripes { a, b, stripes }
+    }
+
+    /// Marble with noise of `seed`
+    pub fn marble(a: [f64; 3], b: [f64; 3], scale: f64, seed: u64) -> Texture {
+        Texture::Marble {
+            a,
+            b,
+            scale,
+            noise: Noise::new(seed),
+        }
+    }
+
+    /// Wood with noise of `seed`
+    pub fn wood(light: [f64; 3], dark: [f64; 3], rings: f64, seed: u64) -> Texture {
+        Texture::Wood {
+            light,
+            dark,
+            rings,
+            noise: Noise::new(seed),
+        }
+    }
+
+    /// Color at texture coordinates `uv`
+    pub fn sample(&self, uv: [f64; 2]) -> [f64; 3] {
+        let [u, v] = uv;
+        match self {
+            Texture::Solid(c) => *c,
+            Texture::Image {
+                img, filter, wrap, ..
+            } => sample_image(img, *filter, *wrap, uv),
+            Texture::Checkerboard { a, b, squares } => {
+                let (i, j) = ((u * squares).floor(), (v * squares).floor());
+                if (i + j).rem_euclid(2.0) < 1.0 {
+                    *a
+                } else {
+                    *b
+                }
+            }
+            Texture::Stripes { a, b, stripes } => {
+                if (u * stripes).rem_euclid(1.0) < 0.5 {
+                    *a
+                } else {
+                    *b
+                }
+            }
+            Texture::Marble { a, b, scale, noise } => {
+                let turbulence = noise.turbulence3(u * scale, v * scale, 0.0, 6);
+                let t = 0.5 + 0.5 * ((u + v + 2.0 * turbulence) * scale * PI).sin();
+                mix(*a, *b, t)
+            }
+            Texture::Wood {
+                light,
+                dark,
+                rings,
+                noise,
+            } => {
+                let (du, dv) = (u.rem_euclid(1.0) - 0.5, v.rem_euclid(1.0) - 0.5);
+                let wobble = 0.15 * noise.fbm2(u * 4.0, v * 4.0, 3);
+                let r = ((du * du + dv * dv).sqrt() * 2.0 + wobble) * rings;
+                // sharp dark edge at the end of every ring
+                mix(*light, *dark, r.rem_euclid(1.0).powi(3))
+            }
+        }
+    }
+}
+
+// mipmapping
+#[allow(dead_code)]
+impl Texture {
+    /// Average color around `uv` over a pixel whose sides go `duv_dx` and
+    /// `duv_dy` in uv space, like `Fragment`'s
+    ///
+    /// Image textures with mipmaps blend the two levels whose pixels are closest
+    /// to the size of that area, so far away surfaces don't shimmer; everything
+    /// else is sampled at `uv`.
+    pub fn sample_area(&self, uv: [f64; 2], duv_dx: [f64; 2], duv_dy: [f64; 2]) -> [f64; 3] {
+        let (img, filter, wrap, mipmaps) = match self {
+            Texture::Image {
+                img,
+                filter,
+                wrap,
+                mipmaps,
+            } if !mipmaps.is_empty() => (img, *filter, *wrap, mipmaps),
+            _ => return self.sample(uv),
+        };
+        // size of the area in pixels of the full picture
+        let (w, h) = (f64::from(img.width()), f64::from(img.height()));
+        let length = |d: [f64; 2]| ((d[0] * w).powi(2) + (d[1] * h).powi(2)).sqrt();
+        let size = length(duv_dx).max(length(duv_dy));
+        let level = size.max(1.0).log2().min(mipmaps.len() as f64);
+        let image = |l: usize| {
+            if l == 0 {
+                img.as_ref()
+            } else {
+                &mipmaps[l - 1]
+            }
+        };
+        let (l0, t) = (level.floor() as usize, level.fract());
+        let near = sample_image(image(l0), filter, wrap, uv);
+        if t == 0.0 {
+            return near;
+        }
+        mix(near, sample_image(image(l0 + 1), filter, wrap, uv), t)
+    }
+}
+
+// textures
+#[allow(dead_code)]
+impl PPMImg {
+    /// Covers the whole image with one tile of `texture`
+    pub fn fill_texture(&mut self, texture: &Texture) {
+        let (w, h, depth) = (self.width(), self.height(), self.depth());
+        for y in 0..h {
+            for x in 0..w {
+                let uv = [
+                    (f64::from(x) + 0.5) / f64::from(w),
+                    1.0 - (f64::from(y) + 0.5) / f64::from(h),
+                ];
+                let color = RGB::from_fractions(texture.sample(uv), depth);
+                self.set_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_repeat_and_vary() {
+        let (black, white) = ([0.0; 3], [1.0; 3]);
+        let checker = Texture::checkerboard(black, white, 4.0);
+        assert_eq!(checker.sample([0.1, 0.1]), black);
+        assert_eq!(checker.sample([0.3, 0.1]), white);
+        assert_eq!(checker.sample([-0.1, 0.1]), white);
+        let stripes = Texture::stripes(black, white, 2.0);
+        assert_eq!(stripes.sample([0.3, 0.9]), white);
+        assert_eq!(stripes.sample([1.1, 0.0]), black);
+
+        for texture in [
+            Texture::marble(black, white, 4.0, 1),
+            Texture::wood(white, black, 5.0, 1),
+        ] {
+            let values: Vec<f64> = (0..100)
+                .map(|i| texture.sample([f64::from(i) / 37.0, f64::from(i) / 53.0])[0])
+                .collect();
+            assert!(values.iter().all(|v| (0.0..=1.0).contains(v)));
+            let (lo, hi) = values
+                .iter()
+                .fold((1.0f64, 0.0f64), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+            assert!(hi - lo > 0.5, "{} to {}", lo, hi);
+        }
+
+        // v goes up the picture
+        let mut img = PPMImg::new(2, 1, 255);
+        img.plot(0, 1);
+        let picture = Texture::Image {
+            img: Box::new(img),
+            filter: Filter::Nearest,
+            wrap: [WrapMode::Wrap; 2],
+            mipmaps: vec![],
+        };
+        assert_eq!(picture.sample([0.5, 0.25]), white);
+        assert_eq!(picture.sample([0.5, 0.75]), black);
+        let mut out = PPMImg::new(4, 4, 255);
+        out.fill_texture(&picture);
+        assert!(out.pixel(0, 3).red == 255 && out.pixel(0, 0).red == 0);
+    }
+
+    #[test]
+    fn filters_and_wraps() {
+        // black, white from left to right
+        let mut img = PPMImg::new(1, 2, 255);
+        img.plot(1, 0);
+        let mut texture = Texture::image(img);
+        let red = |t: &Texture, u: f64| t.sample([u, 0.5])[0];
+        // halfway between the pixel centers, and at them
+        assert_eq!(red(&texture, 0.5), 0.5);
+        assert_eq!(red(&texture, 0.25), 0.0);
+        assert_eq!(red(&texture, 0.75), 1.0);
+        // repeating blends the right edge into the left one
+        assert_eq!(red(&texture, 1.0), 0.5);
+
+        let set = |t: &mut Texture, f: Filter, w: WrapMode| {
+            if let Texture::Image { filter, wrap, .. } = t {
+                *filter = f;
+                *wrap = [w; 2];
+            }
+        };
+        set(&mut texture, Filter::Bilinear, WrapMode::Clamp);
+        assert_eq!(red(&texture, 1.0), 1.0);
+        assert_eq!(red(&texture, 3.0), 1.0);
+        set(&mut texture, Filter::Nearest, WrapMode::Mirror);
+        assert_eq!(red(&texture, 0.4), 0.0);
+        assert_eq!(red(&texture, 1.25), 1.0);
+        assert_eq!(red(&texture, 1.75), 0.0);
+        set(&mut texture, Filter::Nearest, WrapMode::Discard);
+        assert_eq!(texture.sample([0.75, 1.5]), [0.0; 3]);
+    }
+
+    #[test]
+    fn far_away_pixels_average_out() {
+        // one pixel checkerboard
+        let mut img = PPMImg::new(8, 8, 255);
+        for i in 0..64 {
+            if (i % 8 + i / 8) % 2 == 0 {
+                img.plot(i % 8, i / 8);
+            }
+        }
+        let texture = Texture::image(img);
+        if let Texture::Image { mipmaps, .. } = &texture {
+            let sizes: Vec<u32> = mipmaps.iter().map(|m| m.width()).collect();
+            assert_eq!(sizes, vec![4, 2, 1]);
+        }
+        let uv = [0.5 / 8.0, 0.5 / 8.0];
+        // a pixel on screen covers one of the picture's, or 4 by 4 of them
+        let close = texture.sample_area(uv, [1.0 / 8.0, 0.0], [0.0, 1.0 / 8.0]);
+        assert!(close[0] == 0.0 || close[0] == 1.0);
+        let far = texture.sample_area(uv, [0.5, 0.0], [0.0, 0.5]);
+        assert!((far[0] - 0.5).abs() < 0.01, "{:?}", far);
+    }
+}
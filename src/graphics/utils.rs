@@ -1,17 +1,138 @@
+#[cfg(feature = "fs")]
 use std::fs::File;
+#[cfg(feature = "fs")]
 use std::io::BufWriter;
+#[cfg(feature = "fs")]
 use std::path::Path;
 
+#[cfg(feature = "fs")]
 pub fn create_file(filepath: &str) -> BufWriter<File> {
     let path = Path::new(filepath);
     let display = path.display();
-    match File::create(&path) {
+    match File::create(path) {
         Err(why) => panic!("Could not create {}: {}", display, why),
         Ok(file) => BufWriter::new(file),
     }
 }
 
+/// The float methods of std, backed by libm when there is no std
+#[cfg(not(feature = "std"))]
+pub trait Float {
+    fn sqrt(self) -> Self;
+    fn sin_cos(self) -> (Self, Self)
+    where
+        Self: Sized;
+    fn tan(self) -> Self;
+    fn atan2(self, other: Self) -> Self;
+    fn acos(self) -> Self;
+    fn round(self) -> Self;
+    fn floor(self) -> Self;
+    fn ceil(self) -> Self;
+    fn ln(self) -> Self;
+}
+
+#[cfg(not(feature = "std"))]
+impl Float for f64 {
+    fn sqrt(self) -> f64 {
+        libm::sqrt(self)
+    }
+
+    fn sin_cos(self) -> (f64, f64) {
+        libm::sincos(self)
+    }
+
+    fn tan(self) -> f64 {
+        libm::tan(self)
+    }
+
+    fn atan2(self, other: f64) -> f64 {
+        libm::atan2(self, other)
+    }
+
+    fn acos(self) -> f64 {
+        libm::acos(self)
+    }
+
+    fn round(self) -> f64 {
+        libm::round(self)
+    }
+
+    fn floor(self) -> f64 {
+        libm::floor(self)
+    }
+
+    fn ceil(self) -> f64 {
+        libm::ceil(self)
+    }
+
+    fn ln(self) -> f64 {
+        libm::log(self)
+    }
+}
+
+/// Stage of the pipeline, logging how long it took when dropped
+///
+/// Does nothing without the logging feature
+pub struct Span {
+    #[cfg(feature = "logging")]
+    name: &'static str,
+    #[cfg(feature = "logging")]
+    start: std::time::Instant,
+}
+
+impl Span {
+    pub fn new(name: &'static str) -> Span {
+        #[cfg(not(feature = "logging"))]
+        let _ = name;
+        Span {
+            #[cfg(feature = "logging")]
+            name,
+            #[cfg(feature = "logging")]
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "logging")]
+impl Drop for Span {
+    fn drop(&mut self) {
+        log::debug!("{} took {:?}", self.name, self.start.elapsed());
+    }
+}
+
 pub fn polar_to_xy(mag: f64, angle_degrees: f64) -> (f64, f64) {
     let (dy, dx) = angle_degrees.to_radians().sin_cos();
     (dx * mag, dy * mag)
-}
\ No newline at end of file
+}
+
+pub fn sub3(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub fn dot3(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn cross3(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// Unit vector in the direction of v; zero vectors are returned as is
+pub fn normalize3(v: [f64; 3]) -> [f64; 3] {
+    let len = dot3(v, v).sqrt();
+    if len == 0.0 {
+        v
+    } else {
+        [v[0] / len, v[1] / len, v[2] / len]
+    }
+}
+
+/// First three values of a point row as an array
+#[allow(dead_code)]
+pub fn xyz(p: &[f64]) -> [f64; 3] {
+    [p[0], p[1], p[2]]
+}
@@ -0,0 +1,162 @@
+use super::raster::clip_line;
+use core::ops::RangeInclusive;
+
+#[cfg(not(feature = "std"))]
+use super::utils::Float;
+
+/// What happens to pixels drawn past an edge of the image, on one axis
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum WrapMode {
+    /// Dropped
+    #[default]
+    Discard,
+    /// Come back in from the opposite edge
+    Wrap,
+    /// Stick to the closest edge
+    Clamp,
+    /// Reflected back in, as if the edge were a mirror
+    Mirror,
+}
+
+#[allow(dead_code)]
+impl WrapMode {
+    /// Where pixel coordinate `v` lands on an axis `size` pixels long, or None if it's dropped
+    pub fn apply(self, v: i32, size: i32) -> Option<i32> {
+        if (0..size).contains(&v) {
+            return Some(v);
+        }
+        match self {
+            WrapMode::Discard => None,
+            WrapMode::Wrap => Some(v.rem_euclid(size)),
+            WrapMode::Clamp => Some(v.clamp(0, size - 1)),
+            WrapMode::Mirror => {
+                let m = v.rem_euclid(2 * size);
+                Some(if m < size { m } else { 2 * size - 1 - m })
+            }
+        }
+    }
+
+    /// Tiles that coordinates from `lo` to `hi` go through
+    ///
+    /// Tile k covers pixels k * size to (k + 1) * size - 1; for Clamp, tiles
+    /// -1 and 1 are everything before and after the image.
+    fn tiles(self, lo: f64, hi: f64, size: i32) -> RangeInclusive<i64> {
+        let tile = |v: f64| ((v + 0.5) / f64::from(size)).floor() as i64;
+        match self {
+            WrapMode::Discard => 0..=0,
+            WrapMode::Clamp => tile(lo).clamp(-1, 1)..=tile(hi).clamp(-1, 1),
+            WrapMode::Wrap | WrapMode::Mirror => tile(lo)..=tile(hi),
+        }
+    }
+
+    /// Coordinates covered by tile `k`, edges of the end pixels included
+    ///
+    /// Discard has a single unbounded tile, left for the caller to clip.
+    fn tile_bounds(self, k: i64, size: i32) -> (f64, f64) {
+        let size = f64::from(size);
+        match (self, k) {
+            (WrapMode::Discard, _) => (f64::NEG_INFINITY, f64::INFINITY),
+            (WrapMode::Clamp, -1) => (f64::NEG_INFINITY, -0.5),
+            (WrapMode::Clamp, 1) => (size - 0.5, f64::INFINITY),
+            _ => {
+                let start = k as f64 * size - 0.5;
+                (start, start + size)
+            }
+        }
+    }
+
+    /// Moves coordinate `v` of tile `k` into the image
+    fn tile_map(self, k: i64, size: i32, v: f64) -> f64 {
+        let size = f64::from(size);
+        match (self, k) {
+            (WrapMode::Clamp, -1) => 0.0,
+            (WrapMode::Clamp, 1) => size - 1.0,
+            (WrapMode::Mirror, k) if k.rem_euclid(2) == 1 => (k + 1) as f64 * size - 1.0 - v,
+            (_, k) => v - k as f64 * size,
+        }
+    }
+}
+
+/// Splits the line from (x0, y0) to (x1, y1) at the edges of a `width` by `height`
+/// image, and moves every piece into the image following `x_mode` and `y_mode`
+///
+/// `piece(x0, y0, x1, y1)` is called with each moved piece. On `WrapMode::Discard`
+/// axes nothing is split or moved, so pieces can still stick out of the image
+/// there, for the caller to clip.
+pub fn split_line<F>(
+    x_mode: WrapMode,
+    y_mode: WrapMode,
+    width: u32,
+    height: u32,
+    line: [f64; 4],
+    mut piece: F,
+) where
+    F: FnMut(f64, f64, f64, f64),
+{
+    let [x0, y0, x1, y1] = line;
+    let (w, h) = (width as i32, height as i32);
+    if w == 0 || h == 0 {
+        return;
+    }
+    for kx in x_mode.tiles(x0.min(x1), x0.max(x1), w) {
+        for ky in y_mode.tiles(y0.min(y1), y0.max(y1), h) {
+            let (xlo, xhi) = x_mode.tile_bounds(kx, w);
+            let (ylo, yhi) = y_mode.tile_bounds(ky, h);
+            if let Some([a0, b0, a1, b1]) = clip_line(line, xlo, xhi, ylo, yhi) {
+                piece(
+                    x_mode.tile_map(kx, w, a0),
+                    y_mode.tile_map(ky, h, b0),
+                    x_mode.tile_map(kx, w, a1),
+                    y_mode.tile_map(ky, h, b1),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::PPMImg;
+
+    #[test]
+    fn apply_modes() {
+        let modes = [
+            WrapMode::Discard,
+            WrapMode::Wrap,
+            WrapMode::Clamp,
+            WrapMode::Mirror,
+        ];
+        let got: Vec<_> = modes
+            .iter()
+            .map(|m| (m.apply(-1, 4), m.apply(5, 4)))
+            .collect();
+        assert_eq!(
+            got,
+            vec![
+                (None, None),
+                (Some(3), Some(1)),
+                (Some(0), Some(3)),
+                (Some(0), Some(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn lines_split_at_edges() {
+        let lit = |img: &PPMImg, x: u32, y: u32| img.pixel(x, y).red > 0;
+
+        let mut img = PPMImg::new(4, 8, 255);
+        img.x_wrap = WrapMode::Wrap;
+        img.draw_line(5.0, 1.0, 10.0, 1.0);
+        assert!((5..8).all(|x| lit(&img, x, 1)));
+        assert!((0..3).all(|x| lit(&img, x, 1)));
+        assert!(!lit(&img, 3, 1) && !lit(&img, 4, 1));
+
+        let mut img = PPMImg::new(4, 8, 255);
+        img.x_wrap = WrapMode::Mirror;
+        img.draw_line(5.0, 2.0, 10.0, 2.0);
+        assert!((5..8).all(|x| lit(&img, x, 2)));
+        assert!(!lit(&img, 4, 2));
+    }
+}
@@ -1,6 +1,8 @@
-mod graphics;
+use w2_matrix::graphics;
 
+use graphics::animation::Animation;
 use graphics::matrix::Matrix;
+use graphics::wrap::WrapMode;
 use graphics::PPMImg;
 
 // credit: https://github.com/WilliamC07/graphics-matrix/blob/master/edges.json
@@ -78,23 +80,22 @@ const POINTS: [i32; 1324] = [
 
 fn main() {
     let mut img = PPMImg::new(500, 500, 255);
-    img.x_wrap = true;
+    img.x_wrap = WrapMode::Wrap;
     let mut m = Matrix::new(0, 4, Vec::with_capacity(1324 * 2));
     for point in POINTS.chunks(2) {
-        m.append_edge(&mut vec![point[0] as f64, point[1] as f64, 0.0]);
+        m.append_edge([point[0] as f64, point[1] as f64, 0.0]);
     }
 
     // credit: https://www.tutorialspoint.com/computer_graphics/3d_computer_graphics.htm
     let mut t = Matrix::ident(4);
     t.set(3, 0, 50.0);
 
-    for i in 0..10 {
-        img.clear();
-        m = m.mul(&t); // Can this step be sth that Mr. DW will talk about later?
-        img.render_edge_matrix(&m);
-        img.write_binary(format!("img{}.ppm", i).as_str())
-            .expect("Error writing to file");
-    }
+    Animation::new(10, 10.0, "img")
+        .render(&mut img, |img, _, _| {
+            t.apply_mut(&mut m); // Can this step be sth that Mr. DW will talk about later?
+            img.render_edge_matrix(&m);
+        })
+        .expect("Error writing to file");
 
     // NOTE: this is the code for image generation. For matrix tests, please see graphics/matrix.rs
 }